@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::Span;
+
+use crate::auth::{PermissionDenied, Role};
+use crate::metrics::{InFlight, Metrics, StoreMetrics};
+use crate::responder::Response;
+use crate::{OverloadedError, StoreError};
+
+/// The state a [`StoreServer`] owns: values of type `V` filed under keys of type `K`.
+pub trait KeyValueStore<K, V>: Default {
+    fn get(&self, key: &K) -> Option<&V>;
+    fn get_mut(&mut self, key: &K) -> Option<&mut V>;
+    fn remove(&mut self, key: &K) -> Option<V>;
+}
+
+impl<K: Ord, V> KeyValueStore<K, V> for BTreeMap<K, V> {
+    fn get(&self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+}
+
+/// A command a [`StoreServer`] runs against its store.
+///
+/// Commands carry their own [`Responder`](crate::responder::Responder), so
+/// the server never needs to know what a command replies with.
+pub trait StoreCommand<K, V>: Send + 'static {
+    type Store: KeyValueStore<K, V>;
+
+    /// The span the client waits for the reply in.
+    fn client_span(&self) -> Span;
+
+    /// The span the server handles the command in, nested under the client's.
+    fn server_span(&self, parent: &Span) -> Span;
+
+    /// Checks that a client acting as `role` may run this command. Runs on
+    /// the server, so a client can't skip it; by default everything is allowed.
+    fn authorize(&self, _role: Role) -> Result<(), PermissionDenied> {
+        Ok(())
+    }
+
+    fn handle(self, store: &mut Self::Store);
+
+    /// Answers the command with `error` without touching the store.
+    fn reject(self, error: StoreError);
+}
+
+/// A command on its way to the server, with what's needed to trace it there.
+struct Request<Cmd> {
+    command: Cmd,
+    /// When the client queued the command; the server logs how long it waited.
+    sent_at: Instant,
+    /// The client gives up after this, so the server doesn't start on it later.
+    deadline: Option<Instant>,
+    /// The client's span, used as the parent of the server's span.
+    span: Span,
+    /// Who sent the command, checked with [`StoreCommand::authorize`].
+    role: Role,
+}
+
+/// An actor that owns a store on its own thread and runs the commands sent
+/// to it one at a time through a bounded channel.
+pub struct StoreServer<K, V, Cmd: StoreCommand<K, V>> {
+    receiver: Receiver<Request<Cmd>>,
+    store: Cmd::Store,
+    metrics: Arc<Metrics>,
+    _values: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V, Cmd: StoreCommand<K, V>> StoreServer<K, V, Cmd> {
+    /// Starts a server with an empty store. At most `capacity` commands can
+    /// be queued; past that clients get [`StoreError::Overloaded`].
+    pub fn launch(capacity: usize) -> StoreClient<Cmd> {
+        let (sender, receiver) = sync_channel(capacity);
+        let metrics = Arc::new(Metrics::default());
+        let server_metrics = Arc::clone(&metrics);
+        std::thread::spawn(move || {
+            Self {
+                receiver,
+                store: Cmd::Store::default(),
+                metrics: server_metrics,
+                _values: PhantomData,
+            }
+            .run()
+        });
+        StoreClient {
+            sender,
+            timeout: None,
+            role: Role::default(),
+            metrics,
+        }
+    }
+
+    fn run(mut self) {
+        // Once every client is gone `recv` fails and the server shuts down.
+        while let Ok(Request {
+            command,
+            sent_at,
+            deadline,
+            span: parent,
+            role,
+        }) = self.receiver.recv()
+        {
+            // Time spent in the bounded channel: when this grows while processing
+            // time stays flat, the server can't keep up and clients will start
+            // seeing `Overloaded`.
+            let queue_wait = sent_at.elapsed();
+            // Commands still waiting behind this one.
+            let queued = self.metrics.dequeue();
+            let span = command.server_span(&parent);
+            let _entered = span.enter();
+
+            // The client has already stopped waiting: don't waste time on it.
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                tracing::debug!(?queue_wait, queued, "deadline passed, command skipped");
+                self.metrics.expired();
+                command.reject(StoreError::TimedOut);
+                continue;
+            }
+
+            if let Err(denied) = command.authorize(role) {
+                tracing::warn!(?role, "permission denied, command refused");
+                command.reject(denied.into());
+                continue;
+            }
+
+            let started = Instant::now();
+            command.handle(&mut self.store);
+            self.metrics.handled();
+            tracing::debug!(?queue_wait, queued, processing = ?started.elapsed(), "command handled");
+        }
+    }
+}
+
+/// The sending side of a [`StoreServer`]. Cheap to clone; the server shuts
+/// down once every client has been dropped.
+pub struct StoreClient<Cmd> {
+    sender: SyncSender<Request<Cmd>>,
+    /// How long each command may take, queueing included, before it is abandoned.
+    timeout: Option<Duration>,
+    /// Sent along with every command; see [`with_role`](Self::with_role).
+    role: Role,
+    metrics: Arc<Metrics>,
+}
+
+// Derived `Clone` would needlessly require `Cmd: Clone`.
+impl<Cmd> Clone for StoreClient<Cmd> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            timeout: self.timeout,
+            role: self.role,
+            metrics: Arc::clone(&self.metrics),
+        }
+    }
+}
+
+impl<Cmd> StoreClient<Cmd> {
+    /// Returns a client whose commands carry a deadline `timeout` after they
+    /// are sent. Past it the client stops waiting and the server skips the
+    /// command if it hasn't started on it yet; either way the call fails with
+    /// [`StoreError::TimedOut`].
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            timeout: Some(timeout),
+            role: self.role,
+            metrics: Arc::clone(&self.metrics),
+        }
+    }
+
+    /// Returns a client whose commands are sent as `role`. The server checks
+    /// each one with [`StoreCommand::authorize`] and answers those the role
+    /// may not run with [`StoreError::PermissionDenied`]. Clients start out
+    /// as [`Role::ReadWrite`].
+    pub fn with_role(&self, role: Role) -> Self {
+        Self {
+            role,
+            ..self.clone()
+        }
+    }
+
+    /// The server's queue and throughput counters, shared by every clone of
+    /// this client.
+    pub fn metrics(&self) -> StoreMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Sends `command` and waits for its `response` inside the command's
+    /// client span, so client-side logs show which request was rejected or slow.
+    pub fn request<K, V, T>(&self, command: Cmd, response: Response<T>) -> Result<T, StoreError>
+    where
+        Cmd: StoreCommand<K, V>,
+    {
+        let span = command.client_span();
+        let _entered = span.enter();
+        let started = Instant::now();
+        let deadline = self.timeout.map(|timeout| started + timeout);
+        let _in_flight = self.send(Request {
+            command,
+            sent_at: started,
+            deadline,
+            span: span.clone(),
+            role: self.role,
+        })?;
+        let response = match deadline {
+            Some(deadline) => response.wait_until(deadline),
+            None => response.wait(),
+        }
+        .inspect_err(|err| {
+            if matches!(err, StoreError::TimedOut) {
+                tracing::warn!("deadline passed before the store replied");
+            }
+        })?;
+        tracing::debug!(round_trip = ?started.elapsed(), "response received");
+        Ok(response)
+    }
+
+    fn send(&self, request: Request<Cmd>) -> Result<InFlight<'_>, StoreError> {
+        self.metrics.enqueue();
+        match self.sender.try_send(request) {
+            Ok(()) => Ok(self.metrics.accepted()),
+            Err(TrySendError::Full(_)) => {
+                self.metrics.refused(true);
+                tracing::warn!("command queue is full, rejecting command");
+                Err(StoreError::Overloaded(OverloadedError))
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.metrics.refused(false);
+                Err(StoreError::Disconnected)
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+/// Who a client acts as. It travels with every command, and the server
+/// checks it before running the command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Role {
+    /// May run every command.
+    #[default]
+    ReadWrite,
+    /// May only look: commands that change the store are refused.
+    ReadOnly,
+}
+
+impl Role {
+    pub fn may_write(self) -> bool {
+        matches!(self, Role::ReadWrite)
+    }
+}
+
+/// The server refused a command because of the client's [`Role`].
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("A {role:?} client may not run {command} commands")]
+pub struct PermissionDenied {
+    pub role: Role,
+    /// The kind of command that was refused, e.g. `"delete"`.
+    pub command: &'static str,
+}
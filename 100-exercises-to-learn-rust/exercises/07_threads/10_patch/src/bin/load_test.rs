@@ -0,0 +1,251 @@
+//! Hammers the ticket store with many clients issuing a mix of inserts and
+//! gets, and reports throughput and latency percentiles.
+//!
+//! ```text
+//! cargo run --release -p patch --bin load_test -- \
+//!     --clients 32 --requests 5000 --capacity 16,256 --shards 1,4 --get-ratio 0.8
+//! ```
+//!
+//! `--capacity` and `--shards` take comma-separated lists; every combination
+//! is run and printed as one row, so channel capacities and sharding can be
+//! compared side by side. `peak q` is the deepest any shard's command
+//! queue got: when it reaches the capacity, requests start being rejected.
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::data::TicketDraft;
+use patch::store::TicketId;
+use patch::{launch, StoreError, TicketStoreClient};
+use ticket_fields::{TicketDescription, TicketTitle};
+
+struct Options {
+    clients: usize,
+    requests: usize,
+    capacities: Vec<usize>,
+    shards: Vec<usize>,
+    get_ratio: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            clients: 16,
+            requests: 2_000,
+            capacities: vec![16, 256],
+            shards: vec![1, 4],
+            get_ratio: 0.8,
+        }
+    }
+}
+
+const USAGE: &str = "usage: load_test [--clients N] [--requests N] [--capacity N,...] \
+                     [--shards N,...] [--get-ratio 0.0-1.0]";
+
+fn parse_options() -> Result<Options, String> {
+    fn list(value: &str) -> Result<Vec<usize>, String> {
+        value
+            .split(',')
+            .map(|item| match item.parse() {
+                Ok(0) | Err(_) => Err(format!("expected positive numbers, got `{value}`")),
+                Ok(n) => Ok(n),
+            })
+            .collect()
+    }
+    fn single(value: &str) -> Result<usize, String> {
+        match list(value)?.as_slice() {
+            [n] => Ok(*n),
+            _ => Err(format!("expected a single number, got `{value}`")),
+        }
+    }
+
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {flag}"))?;
+        match flag.as_str() {
+            "--clients" => options.clients = single(&value)?,
+            "--requests" => options.requests = single(&value)?,
+            "--capacity" => options.capacities = list(&value)?,
+            "--shards" => options.shards = list(&value)?,
+            "--get-ratio" => {
+                options.get_ratio = value
+                    .parse()
+                    .ok()
+                    .filter(|ratio| (0.0..=1.0).contains(ratio))
+                    .ok_or_else(|| format!("--get-ratio must be between 0 and 1, got `{value}`"))?
+            }
+            _ => return Err(format!("unknown option {flag}")),
+        }
+    }
+    Ok(options)
+}
+
+/// A client-side pool over independent store servers. Inserts are spread
+/// round-robin; each ticket is then looked up on the shard that holds it.
+#[derive(Clone)]
+struct ShardedClient {
+    shards: Vec<TicketStoreClient>,
+    next: usize,
+}
+
+impl ShardedClient {
+    fn launch(shards: usize, capacity: usize) -> Self {
+        Self {
+            shards: (0..shards).map(|_| launch(capacity)).collect(),
+            next: 0,
+        }
+    }
+
+    fn insert(&mut self, draft: TicketDraft) -> Result<(usize, TicketId), StoreError> {
+        let shard = self.next;
+        self.next = (self.next + 1) % self.shards.len();
+        Ok((shard, self.shards[shard].insert(draft)?))
+    }
+
+    fn get(&self, (shard, id): (usize, TicketId)) -> Result<(), StoreError> {
+        self.shards[shard].get(id).map(drop)
+    }
+
+    /// The deepest any shard's queue has been.
+    fn peak_queued(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.metrics().peak_queued)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// What one client observed.
+#[derive(Default)]
+struct Samples {
+    /// Round-trip time of every request that was served.
+    latencies: Vec<Duration>,
+    /// Requests turned away with `Overloaded`.
+    rejected: usize,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.rejected += other.rejected;
+    }
+}
+
+/// Small deterministic generator so runs are repeatable.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn ratio(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn run_client(mut client: ShardedClient, seed: u64, options: &Options) -> Samples {
+    let draft = TicketDraft {
+        title: TicketTitle::try_from("Load test").unwrap(),
+        description: TicketDescription::try_from("Generated by the load test").unwrap(),
+    };
+    let mut rng = XorShift(seed * 2 + 1);
+    let mut inserted = Vec::new();
+    let mut samples = Samples::default();
+
+    for _ in 0..options.requests {
+        // Gets need something to look up: the first request is always an insert.
+        let is_get = !inserted.is_empty() && rng.ratio() < options.get_ratio;
+        let started = Instant::now();
+        let result = if is_get {
+            let ticket = inserted[rng.next() as usize % inserted.len()];
+            client.get(ticket)
+        } else {
+            client
+                .insert(draft.clone())
+                .map(|ticket| inserted.push(ticket))
+        };
+        let elapsed = started.elapsed();
+
+        match result {
+            Ok(()) => samples.latencies.push(elapsed),
+            Err(StoreError::Overloaded(_)) => samples.rejected += 1,
+            Err(err) => panic!("store failed under load: {err}"),
+        }
+    }
+    samples
+}
+
+fn run(options: &Options, shards: usize, capacity: usize) -> (Duration, Samples, usize) {
+    let client = ShardedClient::launch(shards, capacity);
+    let started = Instant::now();
+    let samples = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.clients)
+            .map(|seed| {
+                let client = client.clone();
+                scope.spawn(move || run_client(client, seed as u64, options))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .fold(Samples::default(), |mut all, worker| {
+                all.merge(worker.join().unwrap());
+                all
+            })
+    });
+    (started.elapsed(), samples, client.peak_queued())
+}
+
+/// The `p`th percentile of sorted latencies, in microseconds.
+fn percentile(sorted: &[Duration], p: f64) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index].as_micros()
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        process::exit(2);
+    });
+
+    println!(
+        "{} clients x {} requests, {:.0}% gets; latencies in µs",
+        options.clients,
+        options.requests,
+        options.get_ratio * 100.0
+    );
+    println!(
+        "{:>6} {:>8} {:>10} {:>9} {:>6} {:>7} {:>7} {:>7} {:>7}",
+        "shards", "capacity", "req/s", "rejected", "peak q", "p50", "p90", "p99", "max"
+    );
+    for &shards in &options.shards {
+        for &capacity in &options.capacities {
+            let (elapsed, mut samples, peak_queued) = run(&options, shards, capacity);
+            let latencies = &mut samples.latencies;
+            latencies.sort_unstable();
+            let served = latencies.len() as f64;
+            println!(
+                "{:>6} {:>8} {:>10.0} {:>9} {:>6} {:>7} {:>7} {:>7} {:>7}",
+                shards,
+                capacity,
+                served / elapsed.as_secs_f64(),
+                samples.rejected,
+                peak_queued,
+                percentile(latencies, 0.5),
+                percentile(latencies, 0.9),
+                percentile(latencies, 0.99),
+                percentile(latencies, 1.0),
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::store::TicketId;
+use crate::{StoreError, TicketStoreClient};
+
+/// A read-through cache in front of a [`TicketStoreClient`].
+///
+/// `get` responses are memoized for `ttl`; updates and deletes issued through
+/// this wrapper invalidate the affected entry. Writes made through other
+/// clients are only picked up once the cached entry expires.
+pub struct CachingClient {
+    client: TicketStoreClient,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: BTreeMap<TicketId, CacheEntry>,
+    stats: CacheStats,
+}
+
+struct CacheEntry {
+    ticket: Option<Ticket>,
+    expires_at: Instant,
+}
+
+/// Hit/miss counters for a [`CachingClient`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The fraction of `get` calls served from the cache, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl CachingClient {
+    pub fn new(client: TicketStoreClient, ttl: Duration) -> Self {
+        Self {
+            client,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, StoreError> {
+        let id = self.client.insert(draft)?;
+        // A `None` may have been cached for this id before it existed.
+        self.invalidate(id);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, StoreError> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let cached = state
+                .entries
+                .get(&id)
+                .filter(|entry| entry.expires_at > Instant::now())
+                .map(|entry| entry.ticket.clone());
+            if let Some(ticket) = cached {
+                state.stats.hits += 1;
+                return Ok(ticket);
+            }
+            state.stats.misses += 1;
+        }
+
+        // Don't hold the lock while waiting on the server.
+        let ticket = self.client.get(id)?;
+        self.state.lock().unwrap().entries.insert(
+            id,
+            CacheEntry {
+                ticket: ticket.clone(),
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+        Ok(ticket)
+    }
+
+    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), StoreError> {
+        let id = ticket_patch.id;
+        let result = self.client.update(ticket_patch);
+        self.invalidate(id);
+        result
+    }
+
+    pub fn delete(&self, id: TicketId) -> Result<Option<Ticket>, StoreError> {
+        let result = self.client.delete(id);
+        self.invalidate(id);
+        result
+    }
+
+    /// Drops the cached entry for `id`, if any.
+    pub fn invalidate(&self, id: TicketId) {
+        self.state.lock().unwrap().entries.remove(&id);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+}
@@ -1,7 +1,8 @@
 use crate::store::TicketId;
+use serde::{Deserialize, Serialize};
 use ticket_fields::{TicketDescription, TicketTitle};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Ticket {
     pub id: TicketId,
     pub title: TicketTitle,
@@ -23,7 +24,7 @@ pub struct TicketPatch {
     pub status: Option<Status>,
 }
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
     ToDo,
     InProgress,
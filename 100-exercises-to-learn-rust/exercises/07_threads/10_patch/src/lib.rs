@@ -1,50 +1,105 @@
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::time::Duration;
+
+use tracing::{field, Span};
 
 // TODO: Implement the patching functionality.
+use crate::actor::{StoreClient, StoreCommand, StoreServer};
+use crate::auth::{PermissionDenied, Role};
 use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::metrics::StoreMetrics;
+use crate::responder::{oneshot, Responder, Response};
+use crate::snapshot::{ConflictPolicy, ImportReport, Snapshot};
 use crate::store::{TicketId, TicketStore};
 
+pub mod actor;
+pub mod auth;
+pub mod cache;
 pub mod data;
+pub mod metrics;
+pub mod responder;
+pub mod snapshot;
 pub mod store;
 
+/// A client for the ticket store, the [`StoreServer`] instantiated with
+/// ticket commands.
 #[derive(Clone)]
 pub struct TicketStoreClient {
-    sender: SyncSender<Command>,
+    client: StoreClient<Command>,
 }
 
 impl TicketStoreClient {
-    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, OverloadedError> {
-        let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Insert {
-                draft,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
-        Ok(response_receiver.recv().unwrap())
-    }
-
-    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, OverloadedError> {
-        let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Get {
-                id,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
-        Ok(response_receiver.recv().unwrap())
-    }
-
-    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), OverloadedError> {
-        let (response_sender, response_receiver) = sync_channel(1);
-
-        self.sender
-            .try_send(Command::Update { 
+    /// See [`StoreClient::with_timeout`].
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            client: self.client.with_timeout(timeout),
+        }
+    }
+
+    /// See [`StoreClient::with_role`]. A [`Role::ReadOnly`] client can still
+    /// get and export tickets, but everything else fails with
+    /// [`StoreError::PermissionDenied`].
+    pub fn with_role(&self, role: Role) -> Self {
+        Self {
+            client: self.client.with_role(role),
+        }
+    }
+
+    /// See [`StoreClient::metrics`].
+    pub fn metrics(&self) -> StoreMetrics {
+        self.client.metrics()
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, StoreError> {
+        let (responder, response) = oneshot();
+        self.request(Command::Insert { draft, responder }, response)
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, StoreError> {
+        let (responder, response) = oneshot();
+        self.request(Command::Get { id, responder }, response)
+    }
+
+    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), StoreError> {
+        let (responder, response) = oneshot();
+
+        self.request(
+            Command::Update {
                 patch: ticket_patch,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
-        Ok(response_receiver.recv().unwrap())
+                responder,
+            },
+            response,
+        )
+    }
+
+    /// Removes a ticket, returning it if it existed.
+    pub fn delete(&self, id: TicketId) -> Result<Option<Ticket>, StoreError> {
+        let (responder, response) = oneshot();
+        self.request(Command::Delete { id, responder }, response)
+    }
+
+    /// Dumps every ticket in the store as a JSON [`Snapshot`].
+    pub fn export(&self) -> Result<String, StoreError> {
+        let (responder, response) = oneshot();
+        let tickets = self.request(Command::Export { responder }, response)?;
+        Ok(serde_json::to_string_pretty(&Snapshot { tickets })?)
+    }
+
+    /// Restores tickets from a JSON [`Snapshot`] produced by [`export`](Self::export).
+    pub fn import(&self, json: &str, policy: ConflictPolicy) -> Result<ImportReport, StoreError> {
+        let snapshot: Snapshot = serde_json::from_str(json)?;
+        let (responder, response) = oneshot();
+        self.request(
+            Command::Import {
+                tickets: snapshot.tickets,
+                policy,
+                responder,
+            },
+            response,
+        )
+    }
+
+    fn request<T>(&self, command: Command, response: Response<T>) -> Result<T, StoreError> {
+        self.client.request(command, response)
     }
 }
 
@@ -52,49 +107,131 @@ impl TicketStoreClient {
 #[error("The store is overloaded")]
 pub struct OverloadedError;
 
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error(transparent)]
+    Overloaded(#[from] OverloadedError),
+    #[error("The store server has shut down")]
+    Disconnected,
+    #[error("The store dropped the command without replying")]
+    NoResponse,
+    #[error("The command's deadline passed before the store handled it")]
+    TimedOut,
+    #[error(transparent)]
+    PermissionDenied(#[from] PermissionDenied),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(#[from] serde_json::Error),
+}
+
 pub fn launch(capacity: usize) -> TicketStoreClient {
-    let (sender, receiver) = sync_channel(capacity);
-    std::thread::spawn(move || server(receiver));
-    TicketStoreClient { sender }
+    TicketStoreClient {
+        client: StoreServer::<TicketId, Ticket, Command>::launch(capacity),
+    }
 }
 
 enum Command {
     Insert {
         draft: TicketDraft,
-        response_channel: SyncSender<TicketId>,
+        responder: Responder<TicketId>,
     },
     Get {
         id: TicketId,
-        response_channel: SyncSender<Option<Ticket>>,
+        responder: Responder<Option<Ticket>>,
     },
     Update {
         patch: TicketPatch,
-        response_channel: SyncSender<()>,
+        responder: Responder<()>,
+    },
+    Delete {
+        id: TicketId,
+        responder: Responder<Option<Ticket>>,
     },
+    Export {
+        responder: Responder<Vec<Ticket>>,
+    },
+    Import {
+        tickets: Vec<Ticket>,
+        policy: ConflictPolicy,
+        responder: Responder<ImportReport>,
+    },
+}
+
+impl Command {
+    fn kind(&self) -> &'static str {
+        match self {
+            Command::Insert { .. } => "insert",
+            Command::Get { .. } => "get",
+            Command::Update { .. } => "update",
+            Command::Delete { .. } => "delete",
+            Command::Export { .. } => "export",
+            Command::Import { .. } => "import",
+        }
+    }
+
+    /// Whether the command changes the store.
+    fn writes(&self) -> bool {
+        match self {
+            Command::Insert { .. }
+            | Command::Update { .. }
+            | Command::Delete { .. }
+            | Command::Import { .. } => true,
+            Command::Get { .. } | Command::Export { .. } => false,
+        }
+    }
+
+    /// The ticket the command targets. Inserts only learn theirs once handled.
+    fn ticket_id(&self) -> Option<TicketId> {
+        match self {
+            Command::Get { id, .. } | Command::Delete { id, .. } => Some(*id),
+            Command::Update { patch, .. } => Some(patch.id),
+            Command::Insert { .. } | Command::Export { .. } | Command::Import { .. } => None,
+        }
+    }
+
+    fn record_ticket_id(&self, span: &Span) {
+        if let Some(id) = self.ticket_id() {
+            span.record("ticket_id", field::display(id));
+        }
+    }
 }
 
-pub fn server(receiver: Receiver<Command>) {
-    let mut store = TicketStore::new();
-    loop {
-        match receiver.recv() {
-            Ok(Command::Insert {
-                draft,
-                response_channel,
-            }) => {
+impl StoreCommand<TicketId, Ticket> for Command {
+    type Store = TicketStore;
+
+    fn client_span(&self) -> Span {
+        let span = tracing::info_span!("command", kind = self.kind(), ticket_id = field::Empty);
+        self.record_ticket_id(&span);
+        span
+    }
+
+    fn server_span(&self, parent: &Span) -> Span {
+        let span = tracing::info_span!(parent: parent, "handle", kind = self.kind(), ticket_id = field::Empty);
+        self.record_ticket_id(&span);
+        span
+    }
+
+    fn authorize(&self, role: Role) -> Result<(), PermissionDenied> {
+        if self.writes() && !role.may_write() {
+            return Err(PermissionDenied {
+                role,
+                command: self.kind(),
+            });
+        }
+        Ok(())
+    }
+
+    fn handle(self, store: &mut TicketStore) {
+        match self {
+            Command::Insert { draft, responder } => {
                 let id = store.add_ticket(draft);
-                let _ = response_channel.send(id);
+                Span::current().record("ticket_id", field::display(id));
+                responder.send(id);
             }
-            Ok(Command::Get {
-                id,
-                response_channel,
-            }) => {
+            Command::Get { id, responder } => {
                 let ticket = store.get(id);
-                let _ = response_channel.send(ticket.cloned());
+                responder.send(ticket.cloned());
             }
-            Ok(Command::Update {
-                patch,
-                response_channel,
-            }) => {
+            Command::Update { patch, responder } => {
                 if let Some(ticket) = store.get_mut(patch.id) {
                     if let Some(title) = patch.title {
                         ticket.title = title;
@@ -108,13 +245,32 @@ pub fn server(receiver: Receiver<Command>) {
                         ticket.description = description;
                     }
                 }
-                let _ = response_channel.send(());
+                responder.send(());
+            }
+            Command::Delete { id, responder } => {
+                responder.send(store.remove(id));
+            }
+            Command::Export { responder } => {
+                responder.send(store.tickets().cloned().collect());
             }
-            Err(_) => {
-                // There are no more senders, so we can safely break
-                // and shut down the server.
-                break;
+            Command::Import {
+                tickets,
+                policy,
+                responder,
+            } => {
+                responder.send(store.import(tickets, policy));
             }
         }
     }
+
+    fn reject(self, error: StoreError) {
+        match self {
+            Command::Insert { responder, .. } => responder.fail(error),
+            Command::Get { responder, .. } => responder.fail(error),
+            Command::Update { responder, .. } => responder.fail(error),
+            Command::Delete { responder, .. } => responder.fail(error),
+            Command::Export { responder } => responder.fail(error),
+            Command::Import { responder, .. } => responder.fail(error),
+        }
+    }
 }
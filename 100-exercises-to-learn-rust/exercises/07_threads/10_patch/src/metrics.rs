@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A point-in-time copy of a store's counters, from
+/// [`StoreClient::metrics`](crate::actor::StoreClient::metrics).
+///
+/// Sampling it while the store is under load shows backpressure building up:
+/// `queued` climbs towards the channel capacity, `in_flight` grows with it,
+/// and once the queue is full `rejected` starts counting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StoreMetrics {
+    /// Commands waiting in the channel that the server hasn't picked up yet.
+    pub queued: usize,
+    /// The most commands that were ever queued at once.
+    pub peak_queued: usize,
+    /// Commands accepted into the queue whose clients are still waiting for
+    /// the response: the queued ones, the one being handled and replies the
+    /// client hasn't read yet.
+    pub in_flight: usize,
+    /// Commands the server ran.
+    pub handled: usize,
+    /// Commands the server skipped because their deadline had passed.
+    pub expired: usize,
+    /// Commands turned away with [`StoreError::Overloaded`](crate::StoreError::Overloaded)
+    /// because the queue was full.
+    pub rejected: usize,
+}
+
+/// The live counters, shared by a server and all of its clients.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    queued: AtomicUsize,
+    peak_queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    handled: AtomicUsize,
+    expired: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+impl Metrics {
+    pub(crate) fn snapshot(&self) -> StoreMetrics {
+        StoreMetrics {
+            queued: self.queued.load(Ordering::Relaxed),
+            peak_queued: self.peak_queued.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            handled: self.handled.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Called just before a command is offered to the channel. Counting it
+    /// first means the server can never dequeue a command that isn't counted.
+    pub(crate) fn enqueue(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The channel took the command: the client now waits for a reply until
+    /// the returned guard is dropped.
+    pub(crate) fn accepted(&self) -> InFlight<'_> {
+        let queued = self.queued.load(Ordering::Relaxed);
+        self.peak_queued.fetch_max(queued, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self)
+    }
+
+    /// The channel refused the command, full or disconnected.
+    pub(crate) fn refused(&self, overloaded: bool) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        if overloaded {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The server picked a command up; returns how many are still queued.
+    pub(crate) fn dequeue(&self) -> usize {
+        self.queued.fetch_sub(1, Ordering::Relaxed) - 1
+    }
+
+    pub(crate) fn handled(&self) {
+        self.handled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts one command as in flight for as long as it lives.
+pub(crate) struct InFlight<'a>(&'a Metrics);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::time::Instant;
+
+use crate::StoreError;
+
+/// Creates a single-use reply channel: the server gets the [`Responder`],
+/// the client waits on the [`Response`].
+pub fn oneshot<T>() -> (Responder<T>, Response<T>) {
+    // One slot, so replying never blocks the server even if the client is slow.
+    let (sender, receiver) = sync_channel(1);
+    (
+        Responder {
+            sender: Some(sender),
+        },
+        Response { receiver },
+    )
+}
+
+/// The sending half of a [`oneshot`] channel.
+///
+/// [`send`](Self::send) consumes the responder, so a command can be answered
+/// at most once. Dropping it without answering wakes the waiting client with
+/// [`StoreError::NoResponse`] instead of leaving it blocked.
+pub struct Responder<T> {
+    // Only `None` once `send` or `fail` has taken it.
+    sender: Option<SyncSender<Result<T, StoreError>>>,
+}
+
+impl<T> Responder<T> {
+    /// Replies to the client. A client that has stopped waiting is not an
+    /// error for the server, so the reply is silently discarded in that case.
+    pub fn send(self, value: T) {
+        self.reply(Ok(value));
+    }
+
+    /// Answers the command with an error instead of a value.
+    pub fn fail(self, error: StoreError) {
+        self.reply(Err(error));
+    }
+
+    fn reply(mut self, result: Result<T, StoreError>) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(result);
+        }
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if self.sender.is_some() {
+            tracing::warn!("responder dropped without replying");
+        }
+    }
+}
+
+/// The receiving half of a [`oneshot`] channel.
+pub struct Response<T> {
+    receiver: Receiver<Result<T, StoreError>>,
+}
+
+impl<T> Response<T> {
+    /// Blocks until the server replies or drops the [`Responder`].
+    pub fn wait(self) -> Result<T, StoreError> {
+        self.receiver.recv().map_err(|_| StoreError::NoResponse)?
+    }
+
+    /// Like [`wait`](Self::wait), but gives up with [`StoreError::TimedOut`]
+    /// once `deadline` has passed.
+    pub fn wait_until(self, deadline: Instant) -> Result<T, StoreError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(StoreError::TimedOut),
+            Err(RecvTimeoutError::Disconnected) => Err(StoreError::NoResponse),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::Ticket;
+use crate::store::TicketId;
+
+/// The serialized form of a whole store, as produced by `export`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tickets: Vec<Ticket>,
+}
+
+/// What `import` does with a ticket whose id is already in the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing ticket and drop the imported one.
+    Skip,
+    /// Replace the existing ticket with the imported one.
+    Overwrite,
+    /// Keep both, giving the imported ticket a fresh id.
+    Renumber,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Tickets added under their original id.
+    pub imported: usize,
+    pub skipped: usize,
+    pub overwritten: usize,
+    /// `(original id, new id)` for every renumbered ticket.
+    pub renumbered: Vec<(TicketId, TicketId)>,
+}
@@ -1,9 +1,20 @@
+use crate::actor::KeyValueStore;
 use crate::data::{Status, Ticket, TicketDraft};
+use crate::snapshot::{ConflictPolicy, ImportReport};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct TicketId(u64);
 
+impl fmt::Display for TicketId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Clone)]
 pub struct TicketStore {
     tickets: BTreeMap<TicketId, Ticket>,
@@ -19,8 +30,7 @@ impl TicketStore {
     }
 
     pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
-        let id = TicketId(self.counter);
-        self.counter += 1;
+        let id = self.next_id();
         let ticket = Ticket {
             id,
             title: ticket.title,
@@ -38,4 +48,67 @@ impl TicketStore {
     pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Ticket> {
         self.tickets.get_mut(&id)
     }
+
+    pub fn remove(&mut self, id: TicketId) -> Option<Ticket> {
+        self.tickets.remove(&id)
+    }
+
+    /// All tickets, ordered by id.
+    pub fn tickets(&self) -> impl Iterator<Item = &Ticket> {
+        self.tickets.values()
+    }
+
+    /// Adds previously exported tickets, keeping their ids unless they
+    /// clash with an existing ticket, in which case `policy` decides.
+    pub fn import(&mut self, tickets: Vec<Ticket>, policy: ConflictPolicy) -> ImportReport {
+        let mut report = ImportReport::default();
+        for mut ticket in tickets {
+            if self.tickets.contains_key(&ticket.id) {
+                match policy {
+                    ConflictPolicy::Skip => {
+                        report.skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Overwrite => report.overwritten += 1,
+                    ConflictPolicy::Renumber => {
+                        let id = self.next_id();
+                        report.renumbered.push((ticket.id, id));
+                        ticket.id = id;
+                    }
+                }
+            } else {
+                report.imported += 1;
+            }
+            // Ids handed out later must not collide with imported ones.
+            self.counter = self.counter.max(ticket.id.0 + 1);
+            self.tickets.insert(ticket.id, ticket);
+        }
+        report
+    }
+
+    fn next_id(&mut self) -> TicketId {
+        let id = TicketId(self.counter);
+        self.counter += 1;
+        id
+    }
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyValueStore<TicketId, Ticket> for TicketStore {
+    fn get(&self, id: &TicketId) -> Option<&Ticket> {
+        TicketStore::get(self, *id)
+    }
+
+    fn get_mut(&mut self, id: &TicketId) -> Option<&mut Ticket> {
+        TicketStore::get_mut(self, *id)
+    }
+
+    fn remove(&mut self, id: &TicketId) -> Option<Ticket> {
+        TicketStore::remove(self, *id)
+    }
 }
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use patch::actor::{KeyValueStore, StoreClient, StoreCommand, StoreServer};
+use patch::responder::{oneshot, Responder};
+use patch::StoreError;
+use tracing::Span;
+
+/// The `Order` from `03_ticket_v1`, hosted by the same actor as the tickets.
+#[derive(Clone, Debug, PartialEq)]
+struct Order {
+    product_name: String,
+    quantity: u32,
+    unit_price: u32,
+}
+
+type OrderBook = BTreeMap<u32, Order>;
+
+enum OrderCommand {
+    Place {
+        id: u32,
+        order: Order,
+        responder: Responder<Option<Order>>,
+    },
+    Get {
+        id: u32,
+        responder: Responder<Option<Order>>,
+    },
+    Cancel {
+        id: u32,
+        responder: Responder<Option<Order>>,
+    },
+}
+
+impl StoreCommand<u32, Order> for OrderCommand {
+    type Store = OrderBook;
+
+    fn client_span(&self) -> Span {
+        tracing::info_span!("order")
+    }
+
+    fn server_span(&self, parent: &Span) -> Span {
+        tracing::info_span!(parent: parent, "handle_order")
+    }
+
+    fn handle(self, orders: &mut OrderBook) {
+        match self {
+            OrderCommand::Place {
+                id,
+                order,
+                responder,
+            } => responder.send(orders.insert(id, order)),
+            OrderCommand::Get { id, responder } => {
+                responder.send(KeyValueStore::get(orders, &id).cloned())
+            }
+            OrderCommand::Cancel { id, responder } => {
+                responder.send(KeyValueStore::remove(orders, &id))
+            }
+        }
+    }
+
+    fn reject(self, error: StoreError) {
+        match self {
+            OrderCommand::Place { responder, .. }
+            | OrderCommand::Get { responder, .. }
+            | OrderCommand::Cancel { responder, .. } => responder.fail(error),
+        }
+    }
+}
+
+fn order(quantity: u32) -> Order {
+    Order {
+        product_name: "Rusty Book".into(),
+        quantity,
+        unit_price: 2999,
+    }
+}
+
+#[test]
+fn hosts_a_store_of_orders() {
+    let client: StoreClient<OrderCommand> = StoreServer::<u32, Order, OrderCommand>::launch(5);
+
+    let (responder, response) = oneshot();
+    let previous = client.request(
+        OrderCommand::Place {
+            id: 1,
+            order: order(3),
+            responder,
+        },
+        response,
+    );
+    assert_eq!(previous.unwrap(), None);
+
+    let (responder, response) = oneshot();
+    let placed = client.request(OrderCommand::Get { id: 1, responder }, response);
+    assert_eq!(placed.unwrap(), Some(order(3)));
+
+    let (responder, response) = oneshot();
+    let cancelled = client.request(OrderCommand::Cancel { id: 1, responder }, response);
+    assert_eq!(cancelled.unwrap(), Some(order(3)));
+
+    let (responder, response) = oneshot();
+    let gone = client.request(OrderCommand::Get { id: 1, responder }, response);
+    assert_eq!(gone.unwrap(), None);
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use patch::auth::{PermissionDenied, Role};
+use patch::data::TicketPatch;
+use patch::launch;
+use patch::snapshot::ConflictPolicy;
+use patch::StoreError;
+
+mod common;
+use common::draft;
+
+fn denied(command: &'static str) -> PermissionDenied {
+    PermissionDenied {
+        role: Role::ReadOnly,
+        command,
+    }
+}
+
+#[test]
+fn read_only_clients_cannot_change_the_store() {
+    let client = launch(5);
+    let id = client.insert(draft()).unwrap();
+    let json = client.export().unwrap();
+    let reader = client.with_role(Role::ReadOnly);
+
+    // Reads go through.
+    assert_eq!(reader.get(id).unwrap(), client.get(id).unwrap());
+    assert_eq!(reader.export().unwrap(), json);
+
+    // The server refuses everything else, leaving the store as it was.
+    match reader.delete(id) {
+        Err(StoreError::PermissionDenied(err)) => assert_eq!(err, denied("delete")),
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+    match reader.import(&json, ConflictPolicy::Renumber) {
+        Err(StoreError::PermissionDenied(err)) => assert_eq!(err, denied("import")),
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+    assert!(matches!(
+        reader.insert(draft()),
+        Err(StoreError::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        reader.update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: None,
+        }),
+        Err(StoreError::PermissionDenied(_))
+    ));
+    assert_eq!(client.export().unwrap(), json);
+
+    // The role survives other client settings, and the original client keeps its own.
+    let patient_reader = reader.with_timeout(Duration::from_secs(5));
+    assert!(matches!(
+        patient_reader.delete(id),
+        Err(StoreError::PermissionDenied(_))
+    ));
+    assert!(client.delete(id).unwrap().is_some());
+}
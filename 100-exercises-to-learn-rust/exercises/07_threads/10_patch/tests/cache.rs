@@ -0,0 +1,60 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use patch::cache::{CacheStats, CachingClient};
+use patch::data::{Status, TicketPatch};
+use patch::launch;
+
+mod common;
+use common::draft;
+
+#[test]
+fn repeated_gets_hit_the_cache() {
+    let client = CachingClient::new(launch(5), Duration::from_secs(60));
+    let id = client.insert(draft()).unwrap();
+
+    for _ in 0..4 {
+        assert_eq!(client.get(id).unwrap().unwrap().id, id);
+    }
+
+    let stats = client.stats();
+    assert_eq!(stats, CacheStats { hits: 3, misses: 1 });
+    assert_eq!(stats.hit_rate(), 0.75);
+}
+
+#[test]
+fn writes_through_the_wrapper_invalidate() {
+    let client = CachingClient::new(launch(5), Duration::from_secs(60));
+    let id = client.insert(draft()).unwrap();
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::ToDo);
+
+    client
+        .update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap();
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+
+    assert!(client.delete(id).unwrap().is_some());
+    assert_eq!(client.get(id).unwrap(), None);
+    assert_eq!(client.stats().misses, 3);
+}
+
+#[test]
+fn entries_expire_after_ttl() {
+    let store = launch(5);
+    let client = CachingClient::new(store.clone(), Duration::from_millis(20));
+    let id = client.insert(draft()).unwrap();
+    client.get(id).unwrap();
+
+    // Written through the underlying client, so the wrapper can't see it.
+    store.delete(id).unwrap();
+    assert!(client.get(id).unwrap().is_some());
+
+    sleep(Duration::from_millis(40));
+    assert_eq!(client.get(id).unwrap(), None);
+    assert_eq!(client.stats(), CacheStats { hits: 1, misses: 2 });
+}
@@ -0,0 +1,10 @@
+use patch::data::TicketDraft;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+/// A valid draft for tests that only need some ticket in the store.
+pub fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use patch::launch;
+use patch::StoreError;
+
+mod common;
+use common::draft;
+
+#[test]
+fn expired_commands_are_skipped() {
+    let client = launch(5);
+    // A zero timeout has expired by the time the server looks at the command.
+    let impatient = client.with_timeout(Duration::ZERO);
+    assert!(matches!(
+        impatient.insert(draft()),
+        Err(StoreError::TimedOut)
+    ));
+
+    // The insert was never applied.
+    let json = client.export().unwrap();
+    assert!(json.contains("\"tickets\": []"), "{json}");
+}
+
+#[test]
+fn commands_within_their_deadline_succeed() {
+    let client = launch(5).with_timeout(Duration::from_secs(5));
+    let id = client.insert(draft()).unwrap();
+    assert_eq!(client.get(id).unwrap().unwrap().id, id);
+}
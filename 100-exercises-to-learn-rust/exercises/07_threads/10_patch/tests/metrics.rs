@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::actor::{StoreClient, StoreCommand, StoreServer};
+use patch::metrics::StoreMetrics;
+use patch::responder::{oneshot, Responder};
+use patch::StoreError;
+use tracing::Span;
+
+/// Commands that let the test decide when the server gets to move on.
+enum GateCommand {
+    /// Keeps the server busy until something is sent on `release`.
+    Hold {
+        release: Receiver<()>,
+        responder: Responder<()>,
+    },
+    Ping {
+        responder: Responder<()>,
+    },
+}
+
+impl StoreCommand<u32, u32> for GateCommand {
+    type Store = BTreeMap<u32, u32>;
+
+    fn client_span(&self) -> Span {
+        Span::none()
+    }
+
+    fn server_span(&self, _parent: &Span) -> Span {
+        Span::none()
+    }
+
+    fn handle(self, _store: &mut Self::Store) {
+        match self {
+            GateCommand::Hold { release, responder } => {
+                let _ = release.recv();
+                responder.send(());
+            }
+            GateCommand::Ping { responder } => responder.send(()),
+        }
+    }
+
+    fn reject(self, error: StoreError) {
+        match self {
+            GateCommand::Hold { responder, .. } | GateCommand::Ping { responder } => {
+                responder.fail(error)
+            }
+        }
+    }
+}
+
+fn ping(client: &StoreClient<GateCommand>) -> Result<(), StoreError> {
+    let (responder, response) = oneshot();
+    client.request(GateCommand::Ping { responder }, response)
+}
+
+/// Polls the counters until `done` holds, failing the test after a few seconds.
+fn wait_for(
+    client: &StoreClient<GateCommand>,
+    done: impl Fn(&StoreMetrics) -> bool,
+) -> StoreMetrics {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let metrics = client.metrics();
+        if done(&metrics) {
+            return metrics;
+        }
+        assert!(Instant::now() < deadline, "gave up waiting: {metrics:?}");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn metrics_show_backpressure() {
+    let client: StoreClient<GateCommand> = StoreServer::<u32, u32, GateCommand>::launch(2);
+    let (release, held) = channel();
+
+    // Occupy the server, then fill the queue behind it.
+    let holder = {
+        let client = client.clone();
+        thread::spawn(move || {
+            let (responder, response) = oneshot();
+            client.request(
+                GateCommand::Hold {
+                    release: held,
+                    responder,
+                },
+                response,
+            )
+        })
+    };
+    wait_for(&client, |m| m.in_flight == 1 && m.queued == 0);
+    let pingers: Vec<_> = (0..2)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || ping(&client))
+        })
+        .collect();
+    wait_for(&client, |m| m.queued == 2 && m.in_flight == 3);
+
+    // The queue is full: the next command bounces off.
+    assert!(matches!(ping(&client), Err(StoreError::Overloaded(_))));
+    let busy = client.metrics();
+    assert_eq!(busy.in_flight, 3);
+    assert_eq!(busy.peak_queued, 2);
+    assert_eq!(busy.rejected, 1);
+    assert_eq!(busy.handled, 0);
+
+    release.send(()).unwrap();
+    holder.join().unwrap().unwrap();
+    for pinger in pingers {
+        pinger.join().unwrap().unwrap();
+    }
+    let drained = wait_for(&client, |m| m.handled == 3);
+    assert_eq!(
+        drained,
+        StoreMetrics {
+            queued: 0,
+            peak_queued: 2,
+            in_flight: 0,
+            handled: 3,
+            expired: 0,
+            rejected: 1,
+        }
+    );
+}
+
+#[test]
+fn expired_commands_are_counted() {
+    let client = patch::launch(5);
+    let impatient = client.with_timeout(Duration::ZERO);
+    let draft = patch::data::TicketDraft {
+        title: ticket_fields::test_helpers::ticket_title(),
+        description: ticket_fields::test_helpers::ticket_description(),
+    };
+    assert!(matches!(impatient.insert(draft), Err(StoreError::TimedOut)));
+
+    // The client gives up before the server skips the command.
+    let metrics = client.metrics();
+    assert_eq!(metrics.in_flight, 0);
+    assert_eq!(metrics.handled, 0);
+    client.export().unwrap();
+    assert_eq!(client.metrics().expired, 1);
+}
@@ -0,0 +1,25 @@
+use std::thread;
+
+use patch::responder::oneshot;
+use patch::StoreError;
+
+#[test]
+fn reply_is_delivered() {
+    let (responder, response) = oneshot();
+    thread::spawn(move || responder.send(42));
+    assert_eq!(response.wait().unwrap(), 42);
+}
+
+#[test]
+fn dropped_responder_is_an_error() {
+    let (responder, response) = oneshot::<u32>();
+    thread::spawn(move || drop(responder));
+    assert!(matches!(response.wait(), Err(StoreError::NoResponse)));
+}
+
+#[test]
+fn replying_after_the_client_left_does_not_panic() {
+    let (responder, response) = oneshot();
+    drop(response);
+    responder.send("too late");
+}
@@ -0,0 +1,89 @@
+use patch::data::{Status, TicketPatch};
+use patch::launch;
+use patch::snapshot::{ConflictPolicy, ImportReport};
+use patch::StoreError;
+
+mod common;
+use common::draft;
+
+#[test]
+fn export_import_round_trip() {
+    let source = launch(5);
+    let first = source.insert(draft()).unwrap();
+    let second = source.insert(draft()).unwrap();
+    source
+        .update(TicketPatch {
+            id: second,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap();
+    let json = source.export().unwrap();
+
+    let target = launch(5);
+    let report = target.import(&json, ConflictPolicy::Skip).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(target.get(first).unwrap(), source.get(first).unwrap());
+    assert_eq!(target.get(second).unwrap().unwrap().status, Status::Done);
+
+    // New ids continue after the imported ones.
+    let third = target.insert(draft()).unwrap();
+    assert!(third > second);
+    assert_eq!(target.export().unwrap().matches("\"id\"").count(), 3);
+}
+
+#[test]
+fn import_conflict_policies() {
+    let source = launch(5);
+    let id = source.insert(draft()).unwrap();
+    source
+        .update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::InProgress),
+        })
+        .unwrap();
+    let json = source.export().unwrap();
+
+    let target = launch(5);
+    target.insert(draft()).unwrap();
+
+    let report = target.import(&json, ConflictPolicy::Skip).unwrap();
+    assert_eq!(report.skipped, 1);
+    assert_eq!(target.get(id).unwrap().unwrap().status, Status::ToDo);
+
+    let report = target.import(&json, ConflictPolicy::Overwrite).unwrap();
+    assert_eq!(report.overwritten, 1);
+    assert_eq!(target.get(id).unwrap().unwrap().status, Status::InProgress);
+
+    let report = target.import(&json, ConflictPolicy::Renumber).unwrap();
+    let (original, renumbered) = report.renumbered[0];
+    assert_eq!(original, id);
+    assert_ne!(renumbered, id);
+    assert_eq!(target.get(renumbered).unwrap().unwrap().id, renumbered);
+    assert_eq!(
+        report,
+        ImportReport {
+            renumbered: vec![(id, renumbered)],
+            ..ImportReport::default()
+        }
+    );
+}
+
+#[test]
+fn import_rejects_invalid_snapshots() {
+    let client = launch(5);
+    assert!(matches!(
+        client.import("not json", ConflictPolicy::Skip),
+        Err(StoreError::InvalidSnapshot(_))
+    ));
+
+    // Field validation still applies to imported tickets.
+    let empty_title = r#"{"tickets":[{"id":0,"title":"","description":"d","status":"ToDo"}]}"#;
+    assert!(matches!(
+        client.import(empty_title, ConflictPolicy::Skip),
+        Err(StoreError::InvalidSnapshot(_))
+    ));
+}
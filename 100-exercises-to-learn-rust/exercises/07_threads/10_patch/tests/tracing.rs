@@ -0,0 +1,76 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::data::{TicketDraft, TicketPatch};
+use patch::launch;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use tracing::Level;
+
+/// Collects formatted log lines so the test can look at them.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The server runs on its own thread, so the subscriber has to be global;
+// keep this the only test in the file.
+#[test]
+fn commands_are_traced_on_both_sides() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .init();
+
+    let client = launch(5);
+    let id = client
+        .insert(TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+        })
+        .unwrap();
+    client
+        .update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: None,
+        })
+        .unwrap();
+
+    // The server logs after it has replied, so give it a moment to catch up.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let logs = loop {
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        if logs.matches("command handled").count() == 2 || Instant::now() > deadline {
+            break logs;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let handled: Vec<&str> = logs
+        .lines()
+        .filter(|line| line.contains("command handled"))
+        .collect();
+    assert_eq!(handled.len(), 2, "{logs}");
+    // Server spans nest under the client span and both carry the ticket id.
+    assert!(handled[0].contains("command{kind=\"insert\"}:handle{kind=\"insert\" ticket_id=0}"));
+    assert!(handled[1]
+        .contains("command{kind=\"update\" ticket_id=0}:handle{kind=\"update\" ticket_id=0}"));
+    assert!(handled
+        .iter()
+        .all(|line| line.contains("queue_wait=") && line.contains("processing=")));
+    assert_eq!(logs.matches("response received").count(), 2);
+}
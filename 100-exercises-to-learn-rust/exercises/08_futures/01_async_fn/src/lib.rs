@@ -1,3 +1,5 @@
+pub mod tls;
+
 use tokio::net::TcpListener;
 
 // TODO: write an echo server that accepts incoming TCP connections and
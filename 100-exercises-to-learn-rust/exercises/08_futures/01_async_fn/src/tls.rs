@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+// A TLS variant of `echo`: every accepted socket is wrapped in a `TlsStream`,
+// which implements `AsyncRead + AsyncWrite` just like `TcpStream`, so the
+// echo logic itself doesn't change.
+
+/// Builds a TLS acceptor from a PEM-encoded certificate chain and private key.
+pub fn acceptor_from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsAcceptor, anyhow::Error> {
+    let certs = rustls_pemfile::certs(&mut &*cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .context("invalid certificate PEM")?;
+    let key = rustls_pemfile::private_key(&mut &*key_pem)
+        .context("invalid private key PEM")?
+        .context("no private key found")?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Like `echo`, but speaks TLS. Connections are handled on their own task, so
+/// a client that fails the handshake doesn't stop the server.
+pub async fn echo_tls(listener: TcpListener, acceptor: TlsAcceptor) -> Result<(), anyhow::Error> {
+    loop {
+        let (socket, _) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            let stream = acceptor.accept(socket).await?;
+            let (mut reader, mut writer) = tokio::io::split(stream);
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            // Send `close_notify`, otherwise the client sees a truncated stream.
+            writer.shutdown().await?;
+            Ok::<_, anyhow::Error>(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    #[tokio::test]
+    async fn test_echo_tls() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let acceptor = acceptor_from_pem(
+            cert.serialize_pem().unwrap().as_bytes(),
+            cert.serialize_private_key_pem().as_bytes(),
+        )
+        .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(echo_tls(listener, acceptor));
+
+        // The client only trusts the certificate generated above.
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(cert.serialize_der().unwrap()))
+            .unwrap();
+        let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(config));
+
+        for request in ["hello", "world", "foo", "bar"] {
+            let socket = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let stream = connector.connect(server_name, socket).await.unwrap();
+            let (mut reader, mut writer) = tokio::io::split(stream);
+
+            writer.write_all(request.as_bytes()).await.unwrap();
+            writer.shutdown().await.unwrap();
+
+            let mut buf = Vec::with_capacity(request.len());
+            reader.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(&buf, request.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_acceptor_rejects_missing_key() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let pem = cert.serialize_pem().unwrap();
+        assert!(acceptor_from_pem(pem.as_bytes(), b"").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod server;
+
 use tokio::net::TcpListener;
 
 // TODO: write an echo server that accepts TCP connections on two listeners, concurrently.
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// A reusable version of `echo`: connections are still handled concurrently on
+// their own tasks, while atomic counters keep track of connections and echoed
+// bytes so that tests and a status port can read them at any time.
+
+#[derive(Debug, Default)]
+struct Counters {
+    active: AtomicUsize,
+    accepted: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// A handle to the counters; clones share the same counters as the server.
+#[derive(Clone, Debug, Default)]
+pub struct EchoStats(Arc<Counters>);
+
+/// The values of the counters at one point in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub active_connections: usize,
+    pub total_accepts: u64,
+    pub bytes_echoed: u64,
+}
+
+impl EchoStats {
+    pub fn active_connections(&self) -> usize {
+        self.0.active.load(Ordering::Relaxed)
+    }
+
+    pub fn total_accepts(&self) -> u64 {
+        self.0.accepted.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_echoed(&self) -> u64 {
+        self.0.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            active_connections: self.active_connections(),
+            total_accepts: self.total_accepts(),
+            bytes_echoed: self.bytes_echoed(),
+        }
+    }
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "active={} accepted={} bytes={}",
+            self.active_connections, self.total_accepts, self.bytes_echoed
+        )
+    }
+}
+
+/// Decrements the active connection count when a connection ends, including on error.
+struct ActiveGuard(EchoStats);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0 .0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct EchoServer {
+    listener: TcpListener,
+    stats: EchoStats,
+}
+
+impl EchoServer {
+    pub fn new(listener: TcpListener) -> Self {
+        Self {
+            listener,
+            stats: EchoStats::default(),
+        }
+    }
+
+    /// Grab a handle before calling `run` to read the counters while the server is running.
+    pub fn stats(&self) -> EchoStats {
+        self.stats.clone()
+    }
+
+    pub async fn run(self) -> Result<(), anyhow::Error> {
+        loop {
+            let (mut socket, _) = self.listener.accept().await?;
+            let counters = &self.stats.0;
+            counters.accepted.fetch_add(1, Ordering::Relaxed);
+            counters.active.fetch_add(1, Ordering::Relaxed);
+
+            let guard = ActiveGuard(self.stats.clone());
+            tokio::spawn(async move { echo_counted(&mut socket, &guard.0).await });
+        }
+    }
+}
+
+/// Echoes everything read from `socket` back to it. Bytes are counted as soon
+/// as they have been written back, so a connection that fails half-way still
+/// contributes what it echoed before the error.
+async fn echo_counted(socket: &mut TcpStream, stats: &EchoStats) -> Result<(), anyhow::Error> {
+    let (mut reader, mut writer) = socket.split();
+    let mut buf = [0u8; 8 * 1024];
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buf[..read]).await?;
+        stats.0.bytes.fetch_add(read as u64, Ordering::Relaxed);
+    }
+}
+
+/// Status port: every connection receives one line with the current counters and is then closed.
+pub async fn serve_status(listener: TcpListener, stats: EchoStats) -> Result<(), anyhow::Error> {
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let line = format!("{}\n", stats.snapshot());
+        socket.write_all(line.as_bytes()).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    async fn bind_random() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    /// The counters are updated on the server's tasks; wait for them to catch up.
+    async fn wait_for(stats: &EchoStats, expected: StatsSnapshot) {
+        for _ in 0..100 {
+            if stats.snapshot() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(stats.snapshot(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_counters() {
+        let (listener, addr) = bind_random().await;
+        let server = EchoServer::new(listener);
+        let stats = server.stats();
+        tokio::spawn(server.run());
+
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        wait_for(
+            &stats,
+            StatsSnapshot {
+                active_connections: 2,
+                total_accepts: 2,
+                bytes_echoed: 0,
+            },
+        )
+        .await;
+
+        for (socket, request) in [(&mut first, "hello"), (&mut second, "world!")] {
+            socket.write_all(request.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+            let mut buf = Vec::new();
+            socket.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(&buf, request.as_bytes());
+        }
+
+        wait_for(
+            &stats,
+            StatsSnapshot {
+                active_connections: 0,
+                total_accepts: 2,
+                bytes_echoed: 11,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoint() {
+        let (echo_listener, echo_addr) = bind_random().await;
+        let (status_listener, status_addr) = bind_random().await;
+        let server = EchoServer::new(echo_listener);
+        tokio::spawn(serve_status(status_listener, server.stats()));
+        let stats = server.stats();
+        tokio::spawn(server.run());
+
+        let mut socket = TcpStream::connect(echo_addr).await.unwrap();
+        socket.write_all(b"ping").await.unwrap();
+        socket.shutdown().await.unwrap();
+        socket.read_to_end(&mut Vec::new()).await.unwrap();
+        wait_for(
+            &stats,
+            StatsSnapshot {
+                active_connections: 0,
+                total_accepts: 1,
+                bytes_echoed: 4,
+            },
+        )
+        .await;
+
+        let mut status = String::new();
+        TcpStream::connect(status_addr)
+            .await
+            .unwrap()
+            .read_to_string(&mut status)
+            .await
+            .unwrap();
+        assert_eq!(status, "active=0 accepted=1 bytes=4\n");
+    }
+}
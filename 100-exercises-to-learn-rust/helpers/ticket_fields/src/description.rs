@@ -1,4 +1,9 @@
 #[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct TicketDescription(String);
 
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +32,12 @@ impl TryFrom<&str> for TicketDescription {
     }
 }
 
+impl From<TicketDescription> for String {
+    fn from(value: TicketDescription) -> Self {
+        value.0
+    }
+}
+
 fn validate(description: &str) -> Result<(), TicketDescriptionError> {
     if description.is_empty() {
         Err(TicketDescriptionError::Empty)
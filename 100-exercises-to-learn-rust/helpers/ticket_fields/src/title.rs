@@ -1,6 +1,11 @@
 use std::convert::TryFrom;
 
 #[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct TicketTitle(String);
 
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +34,12 @@ impl TryFrom<&str> for TicketTitle {
     }
 }
 
+impl From<TicketTitle> for String {
+    fn from(value: TicketTitle) -> Self {
+        value.0
+    }
+}
+
 fn validate(title: &str) -> Result<(), TicketTitleError> {
     if title.is_empty() {
         Err(TicketTitleError::Empty)
@@ -0,0 +1,91 @@
+// 走法生成基准：用 perft（数出固定深度内的所有走法序列）比较
+// 栈上的 `MoveList` 和每个节点分配一个 `Vec` 的速度
+//
+//   cargo bench --bench movegen
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chess_gui::board::Board;
+use chess_gui::notation::parse_fen;
+use chess_gui::types::{Color, Move};
+
+// 起始局面和走法很多的中局局面（"Kiwipete"），以及公开的 perft 节点数
+const POSITIONS: [(&str, &str, u32, u64); 2] = [
+    (
+        "startpos",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        4,
+        197_281,
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        3,
+        97_862,
+    ),
+];
+
+const ROUNDS: u32 = 5;
+
+fn perft<L: AsRef<[Move]>>(
+    board: &mut Board,
+    color: Color,
+    depth: u32,
+    generate: &impl Fn(&Board, Color) -> L,
+) -> u64 {
+    let moves = generate(board, color);
+    if depth == 1 {
+        return moves.as_ref().len() as u64;
+    }
+    let mut nodes = 0;
+    for &mv in moves.as_ref() {
+        let delta = board.make_move_with_delta(mv).unwrap();
+        nodes += perft(board, color.opposite(), depth - 1, generate);
+        board.undo_move(&delta);
+    }
+    nodes
+}
+
+// 跑 ROUNDS 轮取最快的一次，返回节点数和用时
+fn measure<L: AsRef<[Move]>>(
+    board: &Board,
+    color: Color,
+    depth: u32,
+    generate: impl Fn(&Board, Color) -> L,
+) -> (u64, Duration) {
+    let mut best = Duration::MAX;
+    let mut nodes = 0;
+    for _ in 0..ROUNDS {
+        let mut board = board.clone();
+        let started = Instant::now();
+        nodes = black_box(perft(&mut board, color, depth, &generate));
+        best = best.min(started.elapsed());
+    }
+    (nodes, best)
+}
+
+fn main() {
+    println!(
+        "{:<10} {:>5} {:>10} {:>12} {:>12} {:>8}",
+        "position", "depth", "nodes", "Vec", "MoveList", "speedup"
+    );
+    for (name, fen, depth, expected) in POSITIONS {
+        let (board, color) = parse_fen(fen).unwrap();
+        let (vec_nodes, vec_time) = measure(&board, color, depth, |board, color| {
+            board.legal_moves_iter(color).collect::<Vec<Move>>()
+        });
+        let (nodes, list_time) = measure(&board, color, depth, Board::generate_moves);
+        // 两种实现都要数出正确的节点数，不然比较速度没有意义
+        assert_eq!(vec_nodes, expected, "{name}: Vec");
+        assert_eq!(nodes, expected, "{name}: MoveList");
+        println!(
+            "{:<10} {:>5} {:>10} {:>10.1}ms {:>10.1}ms {:>7.2}x",
+            name,
+            depth,
+            nodes,
+            vec_time.as_secs_f64() * 1000.0,
+            list_time.as_secs_f64() * 1000.0,
+            vec_time.as_secs_f64() / list_time.as_secs_f64()
+        );
+    }
+}
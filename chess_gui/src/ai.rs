@@ -2,43 +2,237 @@
 //! 包含AI搜索算法、评估函数和移动排序
 
 use crate::board::Board;
+use crate::eval_cache;
+use crate::settings::GameSettings;
 use crate::types::*;
+use crate::zobrist;
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
 use std::time::Instant;
 
 /// 置换表条目
 #[derive(Clone)]
-struct TranspositionEntry {
-    depth: u32,
-    score: i32,
-    best_move: Option<Move>,
-    node_type: NodeType,
+pub(crate) struct TranspositionEntry {
+    pub(crate) depth: u32,
+    pub(crate) score: i32,
+    pub(crate) best_move: Option<Move>,
+    pub(crate) node_type: NodeType,
 }
 
 #[derive(Clone)]
-enum NodeType {
+pub(crate) enum NodeType {
     Exact,      // 精确值
     LowerBound, // α截断
     UpperBound, // β截断
 }
 
-/// 优化后的AI结构
+/// 置换表分成的片数，每片一把锁；并行搜索的线程很少同时抢同一把
+const TT_SHARDS: usize = 64;
+
+/// 并行搜索最多使用的线程数
+const MAX_SEARCH_THREADS: usize = 8;
+
+/// 按局面键分片加锁的置换表，多个搜索线程可以同时读写
+pub(crate) struct TranspositionTable {
+    shards: Vec<Mutex<HashMap<u64, TranspositionEntry>>>,
+}
+
+impl TranspositionTable {
+    fn new() -> Self {
+        Self {
+            shards: (0..TT_SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    // 某个线程在持锁时 panic 也不影响别的线程继续用这张表
+    fn shard(&self, hash: u64) -> MutexGuard<'_, HashMap<u64, TranspositionEntry>> {
+        self.shards[hash as usize % TT_SHARDS]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn get(&self, hash: u64) -> Option<TranspositionEntry> {
+        self.shard(hash).get(&hash).cloned()
+    }
+
+    pub(crate) fn insert(&self, hash: u64, entry: TranspositionEntry) {
+        self.shard(hash).insert(hash, entry);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        (0..TT_SHARDS as u64).map(|i| self.shard(i).len()).sum()
+    }
+
+    fn clear(&self) {
+        for i in 0..TT_SHARDS as u64 {
+            self.shard(i).clear();
+        }
+    }
+
+    /// 所有条目的拷贝，用于写入磁盘缓存
+    pub(crate) fn entries(&self) -> Vec<(u64, TranspositionEntry)> {
+        (0..TT_SHARDS as u64)
+            .flat_map(|i| {
+                self.shard(i)
+                    .iter()
+                    .map(|(hash, entry)| (*hash, entry.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// 默认的搜索线程数：CPU 核数，最多 `MAX_SEARCH_THREADS`
+fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(MAX_SEARCH_THREADS)
+}
+
+/// 评估函数中可调的权重（以兵 = 100 计），某一项设为 0 即关闭该项
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalParams {
+    /// 车在没有兵的开放线上
+    pub rook_open_file: i32,
+    /// 车在只有对方兵的半开放线上
+    pub rook_half_open_file: i32,
+    /// 车在第七横排（对方王在底线或者对方还有兵在第七排时才算）
+    pub rook_on_seventh: i32,
+    /// 两个车在同一条线上，中间没有别的子
+    pub connected_rooks: i32,
+    /// 每个被绝对牵制的棋子的扣分（兵减半）
+    pub pinned_piece: i32,
+    /// 双方走法数之差乘以这个系数（白方多为正）
+    pub mobility: i32,
+    /// 轮到走棋的一方的加分，抵消奇偶层评估的来回摆动；只在搜索中使用
+    pub tempo: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            rook_open_file: 25,
+            rook_half_open_file: 12,
+            rook_on_seventh: 20,
+            connected_rooks: 15,
+            pinned_piece: 20,
+            mobility: 5,
+            tempo: 10,
+        }
+    }
+}
+
+impl EvalParams {
+    /// 可以按名字调整的权重，供自对弈调参使用
+    pub const NAMES: &'static [&'static str] = &[
+        "rook_open_file",
+        "rook_half_open_file",
+        "rook_on_seventh",
+        "connected_rooks",
+        "pinned_piece",
+        "mobility",
+        "tempo",
+    ];
+
+    /// 按名字读取权重，名字不对时返回 `None`
+    pub fn get(&self, name: &str) -> Option<i32> {
+        let mut params = *self;
+        params.field(name).copied()
+    }
+
+    /// 按名字设置权重，名字不对时返回 `false`
+    pub fn set(&mut self, name: &str, value: i32) -> bool {
+        match self.field(name) {
+            Some(field) => {
+                *field = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn field(&mut self, name: &str) -> Option<&mut i32> {
+        Some(match name {
+            "rook_open_file" => &mut self.rook_open_file,
+            "rook_half_open_file" => &mut self.rook_half_open_file,
+            "rook_on_seventh" => &mut self.rook_on_seventh,
+            "connected_rooks" => &mut self.connected_rooks,
+            "pinned_piece" => &mut self.pinned_piece,
+            "mobility" => &mut self.mobility,
+            "tempo" => &mut self.tempo,
+            _ => return None,
+        })
+    }
+}
+
+/// 在后台线程搜索时和界面共享的进度：已经搜完的深度、累计节点数和这一层的评估。
+/// 界面可以用 `stop` 让搜索尽快结束
+#[derive(Debug, Default)]
+pub struct SearchProgress {
+    depth: AtomicU32,
+    nodes: AtomicU64,
+    score: AtomicI32,
+    stop: AtomicBool,
+}
+
+impl SearchProgress {
+    /// 已经完整搜完的深度
+    pub fn depth(&self) -> u32 {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    /// 最近搜完的一层的评估（白方视角），一层都没搜完时为 `None`
+    pub fn score(&self) -> Option<i32> {
+        (self.depth() > 0).then(|| self.score.load(Ordering::Relaxed))
+    }
+
+    /// 让正在进行的搜索在下一个节点停下，返回目前为止的最佳走法
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// 优化后的AI结构。克隆出来的 AI 和原来的共用同一张置换表
 #[derive(Clone)]
 pub struct ChessAI {
     max_depth: u32,
-    transposition_table: HashMap<u64, TranspositionEntry>,
+    transposition_table: Arc<TranspositionTable>,
     pub time_limit: u64,
     pub nodes_searched: u64,
-    zobrist_pieces: [[[u64; 2]; 6]; 64], // [square][piece_type][color]
-    zobrist_turn: u64,
-    zobrist_castling: [u64; 4], // [white_king, white_queen, black_king, black_queen]
+    key_salt: u64, // 异或进置换表的键，不同种子的 AI 不共用磁盘缓存
+    /// 最近一次搜索的评估（白方视角）
+    pub last_score: Option<i32>,
+    eval_history: Vec<i32>, // 本局每步的评估（AI 自己的视角）
+    /// 节点预算；设置后按搜索节点数而不是时间截止，结果与机器速度无关
+    pub node_budget: Option<u64>,
+    budget_used: u64, // 本次搜索累计的节点数
+    /// 搜索时使用的评估权重
+    pub eval_params: EvalParams,
+    /// 设置后每搜完一层就更新进度，并在进度被 `stop` 时停止搜索
+    pub progress: Option<Arc<SearchProgress>>,
+    /// 根节点的走法分给几个线程同时搜索；为 1 或者设置了节点预算时单线程搜索
+    pub threads: usize,
+    /// 吃子按 SEE 排序，静态搜索剪掉交换后净亏的吃子；关掉时只按 MVV-LVA 排序，用来比较搜索的节点数
+    pub use_see: bool,
 }
 
 impl ChessAI {
     pub fn new(depth: u32) -> Self {
-        let mut ai = ChessAI {
+        ChessAI {
             max_depth: depth,
-            transposition_table: HashMap::new(),
+            transposition_table: Arc::new(TranspositionTable::new()),
             time_limit: match depth {
                 2 => 200,
                 4 => 800,
@@ -47,94 +241,159 @@ impl ChessAI {
                 _ => 1000,
             },
             nodes_searched: 0,
-            zobrist_pieces: [[[0u64; 2]; 6]; 64],
-            zobrist_turn: 0,
-            zobrist_castling: [0u64; 4],
-        };
+            key_salt: 0,
+            last_score: None,
+            eval_history: Vec::new(),
+            node_budget: None,
+            budget_used: 0,
+            eval_params: EvalParams::default(),
+            progress: None,
+            threads: default_threads(),
+            use_see: true,
+        }
+    }
 
-        // 初始化Zobrist哈希表
-        ai.init_zobrist();
+    /// 确定性模式：不看时钟，按节点预算截止。Zobrist 键是固定的表，
+    /// 所以相同的种子、深度和局面在任何机器上都会得到相同的结果
+    pub fn with_seed(depth: u32, seed: u64) -> Self {
+        let mut ai = Self::new(depth);
+        ai.node_budget = Some(match depth {
+            0..=2 => 20_000,
+            3..=4 => 200_000,
+            5..=6 => 1_000_000,
+            _ => 4_000_000,
+        });
+        ai.key_salt = zobrist::splitmix64(seed);
+        // 多线程时哪个线程先搜完是不确定的
+        ai.threads = 1;
         ai
     }
 
-    fn init_zobrist(&mut self) {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
+    /// 是否应该停止搜索：确定性模式看节点预算，否则看时间
+    fn out_of_budget(&self, start_time: Instant) -> bool {
+        if self
+            .progress
+            .as_ref()
+            .is_some_and(|progress| progress.is_stopped())
+        {
+            return true;
+        }
+        match self.node_budget {
+            Some(budget) => self.budget_used >= budget,
+            None => start_time.elapsed().as_millis() > self.time_limit as u128,
+        }
+    }
 
-        // 为每个棋子位置生成随机数
-        for square in 0..64 {
-            for piece_type in 0..6 {
-                for color in 0..2 {
-                    (square * 12 + piece_type * 2 + color).hash(&mut hasher);
-                    self.zobrist_pieces[square][piece_type][color] = hasher.finish();
-                    hasher = DefaultHasher::new();
-                }
-            }
+    // 预算是否已用掉一半，用于决定要不要开始更深一层
+    fn past_half_budget(&self, start_time: Instant) -> bool {
+        match self.node_budget {
+            Some(budget) => self.budget_used > budget / 2,
+            None => start_time.elapsed().as_millis() > (self.time_limit / 2) as u128,
         }
+    }
 
-        // 生成其他哈希值
-        999999u64.hash(&mut hasher);
-        self.zobrist_turn = hasher.finish();
+    /// 局面的 Zobrist 键，加上走棋方；用作置换表的键和判断重复局面
+    pub fn position_key(&self, board: &Board, side_to_move: Color) -> u64 {
+        board.zobrist_key(side_to_move) ^ self.key_salt
+    }
 
-        for i in 0..4 {
-            hasher = DefaultHasher::new();
-            (888888u64 + i as u64).hash(&mut hasher);
-            self.zobrist_castling[i] = hasher.finish();
-        }
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
     }
 
-    fn get_board_hash(&self, board: &Board) -> u64 {
-        let mut hash = 0u64;
+    /// 改变迭代加深的最大深度，保留置换表
+    pub fn set_max_depth(&mut self, depth: u32) {
+        self.max_depth = depth.max(1);
+    }
 
-        for row in 0..8 {
-            for col in 0..8 {
-                if let Some(piece) = board.get_piece((row, col)) {
-                    let square = row * 8 + col;
-                    let piece_type = match piece.piece_type {
-                        PieceType::Pawn => 0,
-                        PieceType::Rook => 1,
-                        PieceType::Knight => 2,
-                        PieceType::Bishop => 3,
-                        PieceType::Queen => 4,
-                        PieceType::King => 5,
-                    };
-                    let color = match piece.color {
-                        Color::White => 0,
-                        Color::Black => 1,
-                    };
-                    hash ^= self.zobrist_pieces[square][piece_type][color];
-                }
-            }
-        }
+    /// 获取最佳走法
+    pub fn get_best_move(&mut self, board: &Board, color: Color) -> Option<Move> {
+        let best_move = self.iterative_deepening(board, color);
 
-        // 添加其他状态到哈希
-        if !board.white_king_moved {
-            hash ^= self.zobrist_castling[0];
-        }
-        if !board.white_rook_a_moved {
-            hash ^= self.zobrist_castling[1];
-        }
-        if !board.black_king_moved {
-            hash ^= self.zobrist_castling[2];
-        }
-        if !board.black_rook_a_moved {
-            hash ^= self.zobrist_castling[3];
+        if let Some(score) = self.last_score {
+            self.eval_history.push(match color {
+                Color::White => score,
+                Color::Black => -score,
+            });
         }
 
-        hash
+        best_move
     }
 
-    /// 获取最佳走法
-    pub fn get_best_move(&mut self, board: &Board, color: Color) -> Option<Move> {
-        self.iterative_deepening(board, color)
+    /// 新对局开始时清空评估历史
+    pub fn reset_game_history(&mut self) {
+        self.eval_history.clear();
+        self.last_score = None;
+    }
+
+    /// 评估连续多步低于认输阈值时认输
+    pub fn should_resign(&self, settings: &GameSettings) -> bool {
+        let n = settings.resign_after_moves;
+        settings.ai_can_resign
+            && n > 0
+            && self.eval_history.len() >= n
+            && self.eval_history[self.eval_history.len() - n..]
+                .iter()
+                .all(|&score| score < -settings.resign_threshold)
+    }
+
+    /// 残局中评估连续多步接近均势时愿意和棋（用于提和和接受和棋）
+    pub fn wants_draw(&self, board: &Board, settings: &GameSettings) -> bool {
+        let n = settings.draw_after_moves;
+        settings.ai_draw_offers
+            && n > 0
+            && board.is_endgame()
+            && self.eval_history.len() >= n
+            && self.eval_history[self.eval_history.len() - n..]
+                .iter()
+                .all(|&score| score.abs() <= settings.draw_margin)
+    }
+
+    /// Zobrist 键的指纹，用于判断磁盘缓存是否由同一套键生成
+    fn zobrist_fingerprint(&self) -> u64 {
+        zobrist::fingerprint() ^ self.key_salt
+    }
+
+    /// 同步地把置换表写入磁盘
+    pub fn save_cache(&self, path: &Path) -> io::Result<()> {
+        let entries = self.transposition_table.entries();
+        eval_cache::save(path, self.zobrist_fingerprint(), entries)
+    }
+
+    /// 在后台线程中写入置换表，不阻塞界面
+    pub fn save_cache_async(&self, path: &Path) -> JoinHandle<io::Result<()>> {
+        let entries = self.transposition_table.entries();
+        let fingerprint = self.zobrist_fingerprint();
+        let path = path.to_path_buf();
+        thread::spawn(move || eval_cache::save(&path, fingerprint, entries))
+    }
+
+    /// 从磁盘加载置换表，返回载入的条目数。
+    /// 已有条目只有在缓存中的搜索更深时才会被覆盖
+    pub fn load_cache(&mut self, path: &Path) -> io::Result<usize> {
+        let entries = eval_cache::load(path, self.zobrist_fingerprint())?;
+        let count = entries.len();
+
+        for (hash, entry) in entries {
+            match self.transposition_table.get(hash) {
+                Some(existing) if existing.depth >= entry.depth => {}
+                _ => self.transposition_table.insert(hash, entry),
+            }
+        }
+
+        Ok(count)
     }
 
     /// 迭代深化搜索
     fn iterative_deepening(&mut self, board: &Board, color: Color) -> Option<Move> {
         let start_time = Instant::now();
         let mut best_move = None;
+        self.last_score = None;
+        self.budget_used = 0;
+        if let Some(progress) = &self.progress {
+            progress.depth.store(0, Ordering::Relaxed);
+            progress.nodes.store(0, Ordering::Relaxed);
+        }
 
         // 清空置换表以避免内存过多使用
         if self.transposition_table.len() > 100000 {
@@ -143,18 +402,24 @@ impl ChessAI {
 
         // 从深度1开始，逐步加深
         for depth in 1..=self.max_depth {
-            if start_time.elapsed().as_millis() > self.time_limit as u128 {
+            if self.out_of_budget(start_time) {
                 break;
             }
 
             self.nodes_searched = 0;
             let result = self.search_depth(board, depth, color, start_time);
 
-            if let Some(mv) = result {
+            if let Some((mv, score)) = result {
                 best_move = Some(mv);
+                self.last_score = Some(score);
+                if let Some(progress) = &self.progress {
+                    progress.score.store(score, Ordering::Relaxed);
+                    progress.depth.store(depth, Ordering::Relaxed);
+                    progress.nodes.store(self.budget_used, Ordering::Relaxed);
+                }
 
                 // 如果剩余时间不足，提前结束
-                if start_time.elapsed().as_millis() > (self.time_limit / 2) as u128 {
+                if self.past_half_budget(start_time) {
                     break;
                 }
             }
@@ -170,40 +435,40 @@ impl ChessAI {
         depth: u32,
         color: Color,
         start_time: Instant,
-    ) -> Option<Move> {
+    ) -> Option<(Move, i32)> {
         let mut moves = board.generate_moves(color);
         if moves.is_empty() {
             return None;
         }
 
         // 移动排序
-        self.advanced_move_ordering(&mut moves, board);
+        self.advanced_move_ordering(&mut moves, board, color);
+
+        let scores = if self.threads > 1 && self.node_budget.is_none() {
+            self.search_root_parallel(board, &moves, depth, color, start_time)
+        } else {
+            let mut scores = Vec::new();
+            for &mv in &moves {
+                // 检查时间限制
+                if self.out_of_budget(start_time) {
+                    break;
+                }
+                scores.push(Some(
+                    self.search_root_move(board, mv, depth, color, start_time),
+                ));
+            }
+            scores
+        };
 
+        // 按排序后的顺序挑分数最好的，分数相同时取排在前面的
         let mut best_move = moves[0];
         let mut best_score = if color == Color::White {
             i32::MIN
         } else {
             i32::MAX
         };
-
-        for mv in moves {
-            // 检查时间限制
-            if start_time.elapsed().as_millis() > self.time_limit as u128 {
-                break;
-            }
-
-            let mut new_board = board.clone();
-            new_board.make_move(mv);
-
-            let score = self.minimax_with_tt(
-                &new_board,
-                depth - 1,
-                i32::MIN,
-                i32::MAX,
-                color == Color::Black,
-                start_time,
-            );
-
+        for (&mv, score) in moves.iter().zip(scores) {
+            let Some(score) = score else { continue };
             if (color == Color::White && score > best_score)
                 || (color == Color::Black && score < best_score)
             {
@@ -212,7 +477,84 @@ impl ChessAI {
             }
         }
 
-        Some(best_move)
+        // 时间耗尽时一步都没有搜完
+        if best_score == i32::MIN || best_score == i32::MAX {
+            return None;
+        }
+
+        Some((best_move, best_score))
+    }
+
+    // 搜索根节点的一步棋，返回走完之后局面的分数
+    fn search_root_move(
+        &mut self,
+        board: &Board,
+        mv: Move,
+        depth: u32,
+        color: Color,
+        start_time: Instant,
+    ) -> i32 {
+        let mut new_board = board.clone();
+        new_board.make_move(mv);
+        self.minimax_with_tt(
+            &new_board,
+            depth - 1,
+            i32::MIN,
+            i32::MAX,
+            color == Color::Black,
+            start_time,
+        )
+    }
+
+    /// 在根节点分工：每个线程用一份克隆的 AI（共用置换表）轮流领取下一步还没搜的走法。
+    /// 返回每步棋的分数，下标与 `moves` 相同；时间用完时没搜完的为 `None`
+    fn search_root_parallel(
+        &mut self,
+        board: &Board,
+        moves: &[Move],
+        depth: u32,
+        color: Color,
+        start_time: Instant,
+    ) -> Vec<Option<i32>> {
+        let next = AtomicUsize::new(0);
+        let scores: Vec<Mutex<Option<i32>>> = moves.iter().map(|_| Mutex::new(None)).collect();
+        let threads = self.threads.min(moves.len());
+
+        let nodes: u64 = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    let mut worker = self.clone();
+                    worker.nodes_searched = 0;
+                    let (next, scores) = (&next, &scores);
+                    scope.spawn(move || {
+                        while !worker.out_of_budget(start_time) {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(&mv) = moves.get(index) else { break };
+                            let score =
+                                worker.search_root_move(board, mv, depth, color, start_time);
+                            // 时间在这步棋搜到一半时用完，分数不可靠
+                            if worker.out_of_budget(start_time) {
+                                break;
+                            }
+                            *scores[index].lock().unwrap_or_else(PoisonError::into_inner) =
+                                Some(score);
+                        }
+                        worker.nodes_searched
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or(0))
+                .sum()
+        });
+        self.nodes_searched += nodes;
+        self.budget_used += nodes;
+
+        scores
+            .into_iter()
+            .map(|score| score.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect()
     }
 
     /// 带置换表的minimax搜索
@@ -226,29 +568,35 @@ impl ChessAI {
         start_time: Instant,
     ) -> i32 {
         // 时间检查
-        if start_time.elapsed().as_millis() > self.time_limit as u128 {
-            return board.evaluate();
+        if self.out_of_budget(start_time) {
+            return self.static_eval(board, maximizing);
         }
 
         self.nodes_searched += 1;
+        self.budget_used += 1;
 
         if depth == 0 {
-            return board.evaluate();
+            return self.quiescence(board, alpha, beta, maximizing, start_time);
         }
 
-        let board_hash = self.get_board_hash(board);
+        let side_to_move = if maximizing {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let board_hash = self.position_key(board, side_to_move);
 
         // 查找置换表
-        if let Some(entry) = self.transposition_table.get(&board_hash) {
-            if entry.depth >= depth {
-                match entry.node_type {
-                    NodeType::Exact => return entry.score,
-                    NodeType::LowerBound => alpha = alpha.max(entry.score),
-                    NodeType::UpperBound => beta = beta.min(entry.score),
-                }
-                if alpha >= beta {
-                    return entry.score;
-                }
+        if let Some(entry) = self.transposition_table.get(board_hash)
+            && entry.depth >= depth
+        {
+            match entry.node_type {
+                NodeType::Exact => return entry.score,
+                NodeType::LowerBound => alpha = alpha.max(entry.score),
+                NodeType::UpperBound => beta = beta.min(entry.score),
+            }
+            if alpha >= beta {
+                return entry.score;
             }
         }
 
@@ -272,7 +620,7 @@ impl ChessAI {
         }
 
         // 移动排序
-        self.advanced_move_ordering(&mut moves, board);
+        self.advanced_move_ordering(&mut moves, board, side_to_move);
 
         let original_alpha = alpha;
         let mut best_score = if maximizing { i32::MIN } else { i32::MAX };
@@ -326,24 +674,134 @@ impl ChessAI {
         best_score
     }
 
+    // 搜索中的静态评估（白方视角），加上走棋方的先手分
+    fn static_eval(&self, board: &Board, white_to_move: bool) -> i32 {
+        let tempo = if white_to_move {
+            self.eval_params.tempo
+        } else {
+            -self.eval_params.tempo
+        };
+        board.evaluate_with(&self.eval_params) + tempo
+    }
+
+    /// 静态搜索：深度用完后只继续搜吃子，直到局面平静再评估。
+    /// 交换后净亏的吃子（SEE < 0）直接剪掉，不再展开；
+    /// 吃掉的子（加上升变）再加 `DELTA_MARGIN` 也追不上 alpha / beta 的吃子同样跳过（delta 剪枝）
+    fn quiescence(
+        &mut self,
+        board: &Board,
+        mut alpha: i32,
+        mut beta: i32,
+        maximizing: bool,
+        start_time: Instant,
+    ) -> i32 {
+        let stand_pat = self.static_eval(board, maximizing);
+        if self.out_of_budget(start_time) {
+            return stand_pat;
+        }
+
+        self.nodes_searched += 1;
+        self.budget_used += 1;
+
+        // 不吃子时的静态分已经够剪枝了
+        if maximizing {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        } else {
+            if stand_pat <= alpha {
+                return stand_pat;
+            }
+            beta = beta.min(stand_pat);
+        }
+
+        let color = if maximizing {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let mut captures: Vec<(i32, Move)> = board
+            .legal_moves_iter(color)
+            .filter(|mv| board.get_piece(mv.to).is_some())
+            .map(|mv| {
+                let order = if self.use_see {
+                    board.static_exchange_eval(mv)
+                } else {
+                    mvv_lva(board, mv)
+                };
+                (order, mv)
+            })
+            .filter(|&(see, _)| !self.use_see || see >= 0)
+            .collect();
+        captures.sort_by_key(|&(order, _)| -order);
+
+        let mut best_score = stand_pat;
+        for (_, mv) in captures {
+            let gain = board
+                .get_piece(mv.to)
+                .map_or(0, |piece| piece_value(piece.piece_type))
+                + mv.promotion.map_or(0, |promotion| {
+                    piece_value(promotion) - piece_value(PieceType::Pawn)
+                })
+                + DELTA_MARGIN;
+            let hopeless = if maximizing {
+                stand_pat + gain <= alpha
+            } else {
+                stand_pat - gain >= beta
+            };
+            if hopeless {
+                continue;
+            }
+
+            let mut new_board = board.clone();
+            new_board.make_move(mv);
+
+            let score = self.quiescence(&new_board, alpha, beta, !maximizing, start_time);
+
+            if maximizing {
+                best_score = best_score.max(score);
+                alpha = alpha.max(score);
+            } else {
+                best_score = best_score.min(score);
+                beta = beta.min(score);
+            }
+
+            if beta <= alpha {
+                break;
+            }
+        }
+
+        best_score
+    }
+
     /// 高级移动排序
-    fn advanced_move_ordering(&self, moves: &mut [Move], board: &Board) {
+    fn advanced_move_ordering(&self, moves: &mut [Move], board: &Board, side_to_move: Color) {
+        let tt_move = self
+            .transposition_table
+            .get(self.position_key(board, side_to_move))
+            .and_then(|entry| entry.best_move);
         moves.sort_by_cached_key(|mv| {
             let mut score = 0;
 
             // 1. 置换表中的最佳移动
-            let board_hash = self.get_board_hash(board);
-            if let Some(entry) = self.transposition_table.get(&board_hash) {
-                if entry.best_move == Some(*mv) {
-                    score += 10000;
-                }
+            if tt_move == Some(*mv) {
+                score += 10000;
             }
 
-            // 2. 吃子移动 (MVV-LVA)
-            if let Some(victim) = board.get_piece(mv.to) {
-                let victim_value = self.piece_value(victim.piece_type);
-                let attacker_value = self.piece_value(board.get_piece(mv.from).unwrap().piece_type);
-                score += victim_value * 10 - attacker_value;
+            // 2. 吃子移动 (MVV-LVA)：按 SEE 交换后净赚的排最前，净亏的排到普通走法之后
+            if board.get_piece(mv.to).is_some() {
+                score += mvv_lva(board, *mv);
+                let see = if self.use_see {
+                    board.static_exchange_eval(*mv)
+                } else {
+                    0
+                };
+                if see > 0 {
+                    score += 2000 + see;
+                } else if see < 0 {
+                    score -= 2000;
+                }
             }
 
             // 3. 将军移动
@@ -358,12 +816,11 @@ impl ChessAI {
             }
 
             // 4. 城堡移动
-            if let Some(piece) = board.get_piece(mv.from) {
-                if piece.piece_type == PieceType::King
-                    && (mv.to.1 as i32 - mv.from.1 as i32).abs() == 2
-                {
-                    score += 300;
-                }
+            if let Some(piece) = board.get_piece(mv.from)
+                && piece.piece_type == PieceType::King
+                && (mv.to.1 as i32 - mv.from.1 as i32).abs() == 2
+            {
+                score += 300;
             }
 
             // 5. 中心控制
@@ -388,23 +845,42 @@ impl ChessAI {
             -score // 降序排列
         });
     }
+}
 
-    fn piece_value(&self, piece_type: PieceType) -> i32 {
-        match piece_type {
-            PieceType::Pawn => 100,
-            PieceType::Knight => 320,
-            PieceType::Bishop => 330,
-            PieceType::Rook => 500,
-            PieceType::Queen => 900,
-            PieceType::King => 20000,
-        }
+/// delta 剪枝的余量：吃子之后局面分还可能因为位置因素多出来的分数
+const DELTA_MARGIN: i32 = 200;
+
+fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20000,
     }
 }
 
+// MVV-LVA：先吃价值高的子，同样的子用价值低的棋子去吃
+fn mvv_lva(board: &Board, mv: Move) -> i32 {
+    let victim = board
+        .get_piece(mv.to)
+        .map_or(0, |piece| piece_value(piece.piece_type));
+    let attacker = board
+        .get_piece(mv.from)
+        .map_or(0, |piece| piece_value(piece.piece_type));
+    victim * 10 - attacker
+}
+
 // 为Board实现评估函数
 impl Board {
-    /// 改进的评估函数
+    /// 改进的评估函数，使用默认权重
     pub fn evaluate(&self) -> i32 {
+        self.evaluate_with(&EvalParams::default())
+    }
+
+    /// 按给定权重评估局面（白方视角）
+    pub fn evaluate_with(&self, params: &EvalParams) -> i32 {
         let mut score = 0;
 
         // 1. 基础子力价值
@@ -417,11 +893,72 @@ impl Board {
         score += self.king_safety_evaluation();
 
         // 4. 机动性评估
-        score += self.mobility_evaluation();
+        score += self.mobility_evaluation(params);
+
+        // 5. 车的位置
+        score += self.rook_evaluation(params);
+
+        // 6. 牵制
+        score += self.pin_evaluation(params);
 
         score
     }
 
+    /// 静态交换评估（SEE）：双方轮流用最便宜的棋子在 `mv.to` 上吃子，
+    /// 每一方都可以在不划算时停手，返回走棋方的净得分（兵 = 100）。
+    /// 被吃的子挪开后，后面的车、象、后会自动加入交换（X 光攻击）
+    pub fn static_exchange_eval(&self, mv: Move) -> i32 {
+        let Some(mover) = self.get_piece(mv.from) else {
+            return 0;
+        };
+        let target = mv.to;
+
+        // gains[i] 为第 i 次吃子后，吃子方的累计得分
+        let mut gains = vec![
+            self.get_piece(target)
+                .map_or(0, |victim| piece_value(victim.piece_type)),
+        ];
+        let mut on_square = piece_value(mv.promotion.unwrap_or(mover.piece_type));
+
+        let mut board = self.clone();
+        board.set_piece(mv.from, None);
+        board.set_piece(target, Some(mover));
+        let mut side = mover.color.opposite();
+
+        loop {
+            let attackers = board.attackers_of(target, side);
+            let Some(&from) = attackers.iter().min_by_key(|&&pos| {
+                board
+                    .get_piece(pos)
+                    .map_or(0, |p| piece_value(p.piece_type))
+            }) else {
+                break;
+            };
+            let attacker = board.get_piece(from).unwrap();
+
+            // 王只能吃没有保护的子
+            if attacker.piece_type == PieceType::King
+                && board.is_square_attacked(target, side.opposite())
+            {
+                break;
+            }
+
+            gains.push(on_square - gains.last().unwrap());
+            on_square = piece_value(attacker.piece_type);
+            board.set_piece(from, None);
+            board.set_piece(target, Some(attacker));
+            side = side.opposite();
+        }
+
+        // 从最后一次吃子往前推：每一方都可以选择不继续吃
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -(-*previous).max(last);
+        }
+        gains[0]
+    }
+
     fn material_evaluation(&self) -> i32 {
         let mut score = 0;
 
@@ -505,6 +1042,127 @@ impl Board {
         score
     }
 
+    /// 双方都没有后，或者双方的子力（不含兵和王）都不超过一车一轻子时视为残局
+    pub fn is_endgame(&self) -> bool {
+        let mut queens = 0;
+        let mut white_material = 0;
+        let mut black_material = 0;
+
+        for row in 0..8 {
+            for col in 0..8 {
+                if let Some(piece) = self.get_piece((row, col)) {
+                    let value = match piece.piece_type {
+                        PieceType::Knight => 320,
+                        PieceType::Bishop => 330,
+                        PieceType::Rook => 500,
+                        PieceType::Queen => {
+                            queens += 1;
+                            900
+                        }
+                        PieceType::Pawn | PieceType::King => 0,
+                    };
+
+                    match piece.color {
+                        Color::White => white_material += value,
+                        Color::Black => black_material += value,
+                    }
+                }
+            }
+        }
+
+        queens == 0 || (white_material <= 830 && black_material <= 830)
+    }
+
+    /// 车在开放线、半开放线、第七横排，以及双车相连的加分
+    fn rook_evaluation(&self, params: &EvalParams) -> i32 {
+        let mut score = 0;
+
+        for color in [Color::White, Color::Black] {
+            let sign = if color == Color::White { 1 } else { -1 };
+            // 第七横排和对方底线（白方从第 7 行往上走）
+            let (seventh, back_rank) = match color {
+                Color::White => (1, 0),
+                Color::Black => (6, 7),
+            };
+            let enemy_king = match color {
+                Color::White => self.black_king_pos,
+                Color::Black => self.white_king_pos,
+            };
+
+            let mut rooks = Vec::new();
+            for row in 0..8 {
+                for col in 0..8 {
+                    if self.get_piece((row, col)) == Some(Piece::new(PieceType::Rook, color)) {
+                        rooks.push((row, col));
+                    }
+                }
+            }
+
+            for &(row, col) in &rooks {
+                let own_pawn = self.file_has_pawn(col, color);
+                let enemy_pawn = self.file_has_pawn(col, color.opposite());
+                if !own_pawn && !enemy_pawn {
+                    score += sign * params.rook_open_file;
+                } else if !own_pawn {
+                    score += sign * params.rook_half_open_file;
+                }
+
+                if row == seventh {
+                    let enemy_pawns_on_seventh = (0..8).any(|c| {
+                        self.get_piece((seventh, c))
+                            == Some(Piece::new(PieceType::Pawn, color.opposite()))
+                    });
+                    if enemy_king.0 == back_rank || enemy_pawns_on_seventh {
+                        score += sign * params.rook_on_seventh;
+                    }
+                }
+            }
+
+            if let [a, b] = rooks[..]
+                && self.is_clear_line(a, b)
+            {
+                score += sign * params.connected_rooks;
+            }
+        }
+
+        score
+    }
+
+    /// 被牵制的棋子不能自由走动，也不能保护别的子
+    fn pin_evaluation(&self, params: &EvalParams) -> i32 {
+        let mut score = 0;
+        for color in [Color::White, Color::Black] {
+            let sign = if color == Color::White { 1 } else { -1 };
+            let pinned = self.pinned_pieces(color);
+            for index in 0..64 {
+                if pinned & (1 << index) == 0 {
+                    continue;
+                }
+                let penalty = match self.get_piece((index / 8, index % 8)) {
+                    Some(piece) if piece.piece_type == PieceType::Pawn => params.pinned_piece / 2,
+                    _ => params.pinned_piece,
+                };
+                score -= sign * penalty;
+            }
+        }
+        score
+    }
+
+    fn file_has_pawn(&self, col: usize, color: Color) -> bool {
+        (0..8).any(|row| self.get_piece((row, col)) == Some(Piece::new(PieceType::Pawn, color)))
+    }
+
+    /// 两格在同一横排或竖线上，而且中间没有棋子
+    fn is_clear_line(&self, a: Square, b: Square) -> bool {
+        if a.0 == b.0 {
+            (a.1.min(b.1) + 1..a.1.max(b.1)).all(|col| self.get_piece((a.0, col)).is_none())
+        } else if a.1 == b.1 {
+            (a.0.min(b.0) + 1..a.0.max(b.0)).all(|row| self.get_piece((row, a.1)).is_none())
+        } else {
+            false
+        }
+    }
+
     fn king_safety_evaluation(&self) -> i32 {
         let mut score = 0;
 
@@ -515,17 +1173,37 @@ impl Board {
             score += 50;
         }
 
+        // 王周围被对方控制的格子越多越危险；残局中王要主动出击，不计算
+        if !self.is_endgame() {
+            let white_attacks = self.attack_map(Color::White);
+            let black_attacks = self.attack_map(Color::Black);
+            score -= 8 * (king_zone(self.white_king_pos) & black_attacks).count_ones() as i32;
+            score += 8 * (king_zone(self.black_king_pos) & white_attacks).count_ones() as i32;
+        }
+
         score
     }
 
-    fn mobility_evaluation(&self) -> i32 {
+    // 和其他各项一样是白方视角：白方走法多时为正，与轮到谁走无关
+    fn mobility_evaluation(&self, params: &EvalParams) -> i32 {
         let white_moves = self.generate_moves(Color::White).len() as i32;
         let black_moves = self.generate_moves(Color::Black).len() as i32;
 
-        (white_moves - black_moves) * 5
+        (white_moves - black_moves) * params.mobility
     }
 }
 
+// 王所在格及其周围 8 格的位图，位序与 `Board::attack_map` 相同
+fn king_zone(king_pos: Square) -> u64 {
+    let mut zone = 0u64;
+    for row in king_pos.0.saturating_sub(1)..=(king_pos.0 + 1).min(7) {
+        for col in king_pos.1.saturating_sub(1)..=(king_pos.1 + 1).min(7) {
+            zone |= 1 << (row * 8 + col);
+        }
+    }
+    zone
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -536,8 +1214,49 @@ mod tests {
     fn test_ai_new() {
         let ai = ChessAI::new(3);
         assert_eq!(ai.max_depth, 3);
-        // Fix: Correct assertion syntax
-        assert!(ai.zobrist_turn != 0, "Zobrist keys should be initialized");
+        assert_ne!(
+            ai.position_key(&Board::new(), Color::White),
+            ai.position_key(&Board::new(), Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_resign_after_consecutive_bad_evaluations() {
+        let settings = GameSettings::default();
+        let mut ai = ChessAI::new(2);
+
+        ai.eval_history = vec![-900, -950];
+        assert!(!ai.should_resign(&settings));
+
+        ai.eval_history.push(-1200);
+        assert!(ai.should_resign(&settings));
+
+        let disabled = GameSettings {
+            ai_can_resign: false,
+            ..settings
+        };
+        assert!(!ai.should_resign(&disabled));
+    }
+
+    #[test]
+    fn test_draw_only_in_equal_endgames() {
+        let settings = GameSettings::default();
+        let mut ai = ChessAI::new(2);
+        ai.eval_history = vec![0; settings.draw_after_moves];
+
+        // 开局有后，不是残局
+        assert!(!ai.wants_draw(&Board::new(), &settings));
+
+        let mut board = Board::new();
+        board.clear();
+        board.set_piece((7, 4), Some(Piece::new(PieceType::King, Color::White)));
+        board.set_piece((0, 4), Some(Piece::new(PieceType::King, Color::Black)));
+        board.set_piece((7, 0), Some(Piece::new(PieceType::Rook, Color::White)));
+        board.set_piece((0, 0), Some(Piece::new(PieceType::Rook, Color::Black)));
+        assert!(ai.wants_draw(&board, &settings));
+
+        ai.eval_history.push(150);
+        assert!(!ai.wants_draw(&board, &settings));
     }
 
     #[test]
@@ -554,10 +1273,63 @@ mod tests {
         assert!(best_move.is_some());
     }
 
+    #[test]
+    fn test_seeded_search_is_reproducible() {
+        let mut board = Board::new();
+        board.make_move(Move {
+            from: (6, 4),
+            to: (4, 4),
+            promotion: None,
+        });
+
+        let mut first = ChessAI::with_seed(3, 7);
+        let mut second = ChessAI::with_seed(3, 7);
+        let first_move = first.get_best_move(&board, Color::Black);
+        let second_move = second.get_best_move(&board, Color::Black);
+
+        assert!(first_move.is_some());
+        assert_eq!(first_move, second_move);
+        assert_eq!(first.last_score, second.last_score);
+        assert_eq!(first.nodes_searched, second.nodes_searched);
+        assert_eq!(
+            first.position_key(&board, Color::Black),
+            second.position_key(&board, Color::Black)
+        );
+        assert_ne!(
+            first.position_key(&board, Color::Black),
+            ChessAI::with_seed(3, 8).position_key(&board, Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_node_budget_limits_search() {
+        let mut ai = ChessAI::with_seed(6, 1);
+        ai.node_budget = Some(500);
+        assert!(ai.get_best_move(&Board::new(), Color::White).is_some());
+        assert!(ai.budget_used <= 500);
+    }
+
+    #[test]
+    fn test_cache_persists_between_instances() {
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_ai_cache_{}", std::process::id()))
+            .join("eval_cache.bin");
+
+        let mut ai = ChessAI::new(2);
+        ai.get_best_move(&Board::new(), Color::White);
+        let searched = ai.transposition_table.len();
+        assert!(searched > 0);
+        ai.save_cache_async(&path).join().unwrap().unwrap();
+
+        let mut fresh = ChessAI::new(4);
+        assert_eq!(fresh.load_cache(&path).unwrap(), searched);
+        assert_eq!(fresh.transposition_table.len(), searched);
+    }
+
     #[test]
     fn test_evaluation_for_checkmate() {
         let mut board = Board::new();
-        board.squares = [[None; 8]; 8]; // Clear board
+        board.clear(); // Clear board
 
         // Fix: Set up a real checkmate position.
         // Black king at a8, White queen at a7, White king at b6 (protecting the queen).
@@ -569,16 +1341,328 @@ mod tests {
 
         // Black is in checkmate, so there are no legal moves.
         let moves = board.generate_moves(Color::Black);
-        assert!(moves.is_empty(), "In a checkmate position, there should be no legal moves.");
+        assert!(
+            moves.is_empty(),
+            "In a checkmate position, there should be no legal moves."
+        );
         assert!(board.is_in_check(Color::Black));
 
         // The evaluation for a checkmated position should be extremely low for the losing side.
         // The minimax function should return a value close to -100000.
         let mut ai = ChessAI::new(2);
-        let score = ai.minimax_with_tt(&board, 2, i32::MIN, i32::MAX, false, std::time::Instant::now());
+        let score = ai.minimax_with_tt(
+            &board,
+            2,
+            i32::MIN,
+            i32::MAX,
+            false,
+            std::time::Instant::now(),
+        );
 
         // Since it's black's turn (minimizing player) and they are checkmated, the score
         // should be a large positive number (good for white).
-        assert!(score > 90000, "Score was {}, expected > 90000 for a checkmated position", score);
+        assert!(
+            score > 90000,
+            "Score was {}, expected > 90000 for a checkmated position",
+            score
+        );
+    }
+
+    #[test]
+    fn test_static_exchange_eval() {
+        use crate::notation::{parse_fen, parse_uci_move};
+
+        // 兵吃有兵保护的马：320 - 100
+        let (board, _) = parse_fen("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let mv = parse_uci_move("e4d5").unwrap();
+        assert_eq!(board.static_exchange_eval(mv), 220);
+
+        // 后吃有兵保护的兵：100 - 900
+        let (board, _) = parse_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mv = parse_uci_move("d1d5").unwrap();
+        assert_eq!(board.static_exchange_eval(mv), -800);
+
+        // 车在后面 X 光支援，兑完车后白方多赚一个兵
+        let (board, _) = parse_fen("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        let mv = parse_uci_move("d2d5").unwrap();
+        assert_eq!(board.static_exchange_eval(mv), 100);
+    }
+
+    #[test]
+    fn test_rook_activity_terms() {
+        use crate::notation::parse_fen;
+
+        let no_rook_terms = EvalParams {
+            rook_open_file: 0,
+            rook_half_open_file: 0,
+            rook_on_seventh: 0,
+            connected_rooks: 0,
+            ..EvalParams::default()
+        };
+        let rook_score = |fen: &str| {
+            let (board, _) = parse_fen(fen).unwrap();
+            board.evaluate() - board.evaluate_with(&no_rook_terms)
+        };
+
+        // d 线开放，e 线上有白兵
+        assert_eq!(rook_score("4k3/8/8/8/8/8/4P3/3RK3 w - - 0 1"), 25);
+        assert_eq!(rook_score("4k3/8/8/8/8/8/4P3/4KR2 w - - 0 1"), 25);
+        assert_eq!(rook_score("4k3/4p3/8/8/8/8/8/4RK2 w - - 0 1"), 12);
+        assert_eq!(rook_score("4k3/8/8/8/8/8/4P3/4RK2 w - - 0 1"), 0);
+
+        // 第七横排：对方王在底线才加分
+        assert_eq!(rook_score("4k3/R7/8/8/8/8/P7/4K3 w - - 0 1"), 20);
+        assert_eq!(rook_score("8/R7/4k3/8/8/8/P7/4K3 w - - 0 1"), 0);
+
+        // 双车相连，黑方得分记为负
+        assert_eq!(
+            rook_score("r2r2k1/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"),
+            -15
+        );
+        assert_eq!(
+            rook_score("r1nr2k1/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_pinned_piece_penalty() {
+        use crate::notation::parse_fen;
+
+        let no_pins = EvalParams {
+            pinned_piece: 0,
+            ..EvalParams::default()
+        };
+        // e2 的马被 e8 的车牵制
+        let (board, _) = parse_fen("k3r3/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.evaluate() - board.evaluate_with(&no_pins), -20);
+    }
+
+    #[test]
+    fn test_mobility_favours_the_more_active_side() {
+        use crate::notation::parse_fen;
+
+        let no_mobility = EvalParams {
+            mobility: 0,
+            ..EvalParams::default()
+        };
+        // 白后在中心，黑方只有一个王；轮到谁走都不影响符号
+        for fen in [
+            "k7/8/8/3Q4/8/8/8/K7 w - - 0 1",
+            "k7/8/8/3Q4/8/8/8/K7 b - - 0 1",
+        ] {
+            let (board, _) = parse_fen(fen).unwrap();
+            assert!(board.evaluate() > board.evaluate_with(&no_mobility));
+        }
+    }
+
+    #[test]
+    fn test_eval_params_by_name() {
+        let mut params = EvalParams::default();
+        for name in EvalParams::NAMES {
+            assert!(params.set(name, 7));
+            assert_eq!(params.get(name), Some(7));
+        }
+        assert!(!params.set("king_tropism", 1));
+        assert_eq!(params.get("king_tropism"), None);
+    }
+
+    #[test]
+    fn test_quiescence_sees_recapture() {
+        use crate::notation::{parse_fen, parse_uci_move};
+
+        // 只搜一层时，静态搜索要看到 Qxd5 之后 exd5 的反吃
+        let (board, side) = parse_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut ai = ChessAI::with_seed(1, 1);
+        let best = ai.get_best_move(&board, side).unwrap();
+        assert_ne!(best, parse_uci_move("d1d5").unwrap());
+    }
+
+    #[test]
+    fn test_quiescence_delta_pruning() {
+        use crate::notation::parse_fen;
+
+        let (board, _) = parse_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut ai = ChessAI::with_seed(1, 1);
+        let stand_pat = ai.static_eval(&board, true);
+
+        // 吃一个兵远远不够追上 alpha，Qxd5 不再展开
+        let alpha = stand_pat + 1000;
+        let score = ai.quiescence(&board, alpha, i32::MAX, true, Instant::now());
+        assert_eq!(score, stand_pat);
+        assert_eq!(ai.nodes_searched, 1);
+
+        // 差距在兵的价值加余量之内时照常搜这步吃子
+        ai.nodes_searched = 0;
+        ai.quiescence(&board, stand_pat + 100, i32::MAX, true, Instant::now());
+        assert_eq!(ai.nodes_searched, 2);
+    }
+
+    #[test]
+    fn test_see_reduces_nodes() {
+        use crate::notation::parse_fen;
+
+        // Kiwipete：双方都有很多吃子，固定深度下比较 SEE 排序和剪枝前后的节点数
+        let (board, side) =
+            parse_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let nodes = |use_see| {
+            let mut ai = ChessAI::with_seed(2, 1);
+            ai.node_budget = Some(u64::MAX);
+            ai.use_see = use_see;
+            ai.get_best_move(&board, side).unwrap();
+            ai.nodes_searched
+        };
+        let (with_see, without_see) = (nodes(true), nodes(false));
+        // 目前大约是 1 : 4，留出余量
+        assert!(
+            with_see * 2 < without_see,
+            "with SEE {} nodes, without {}",
+            with_see,
+            without_see
+        );
+    }
+
+    // 战术题：局面、搜索深度、正确答案（坐标记谱，有多个解时都列出）、是否要看出杀棋
+    struct Tactic {
+        name: &'static str,
+        fen: &'static str,
+        depth: u32,
+        solutions: &'static [&'static str],
+        mate: bool,
+    }
+
+    const TACTICS: &[Tactic] = &[
+        Tactic {
+            name: "back rank mate in 1",
+            fen: "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+            depth: 2,
+            solutions: &["d1d8"],
+            mate: true,
+        },
+        Tactic {
+            name: "scholar's mate",
+            fen: "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 0 1",
+            depth: 2,
+            solutions: &["h5f7"],
+            mate: true,
+        },
+        Tactic {
+            name: "rook sacrifice mate in 2",
+            fen: "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1",
+            depth: 4,
+            solutions: &["a1a6"],
+            mate: true,
+        },
+        Tactic {
+            name: "rook sacrifice mate in 2, black to move",
+            fen: "r7/8/8/8/8/1p6/PP6/KBk5 b - - 0 1",
+            depth: 4,
+            solutions: &["a8a3"],
+            mate: true,
+        },
+        Tactic {
+            name: "king and rook mate in 3",
+            fen: "2k5/8/3K4/8/8/8/8/7R w - - 0 1",
+            depth: 6,
+            solutions: &["d6c6", "h1b1"],
+            mate: true,
+        },
+        Tactic {
+            name: "undefended queen",
+            fen: "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1",
+            depth: 2,
+            solutions: &["d2d5"],
+            mate: false,
+        },
+        Tactic {
+            name: "free rook, not the defended knight",
+            fen: "4k3/8/2p5/3n4/r7/8/8/3QK3 w - - 0 1",
+            depth: 3,
+            solutions: &["d1a4"],
+            mate: false,
+        },
+        Tactic {
+            name: "knight fork",
+            fen: "q3k3/8/8/3N4/8/8/8/4K3 w - - 0 1",
+            depth: 3,
+            solutions: &["d5c7"],
+            mate: false,
+        },
+        Tactic {
+            name: "promotion",
+            fen: "8/4P1k1/8/8/8/8/8/4K3 w - - 0 1",
+            depth: 3,
+            solutions: &["e7e8q"],
+            mate: false,
+        },
+    ];
+
+    #[test]
+    fn test_tactics_suite() {
+        use crate::notation::{move_to_uci, parse_fen};
+
+        // 固定种子和节点预算，结果不受机器速度影响；失败时列出每道题 AI 的走法和评估
+        let mut failures = Vec::new();
+        for tactic in TACTICS {
+            let (board, side) = parse_fen(tactic.fen).unwrap();
+            let mut ai = ChessAI::with_seed(tactic.depth, 1);
+            let best = ai.get_best_move(&board, side).map(move_to_uci);
+            let score = ai.last_score.unwrap_or(0);
+            let solved = best
+                .as_deref()
+                .is_some_and(|mv| tactic.solutions.contains(&mv));
+            if !solved || (tactic.mate && score.abs() < 90000) {
+                failures.push(format!(
+                    "{}: played {:?}, expected one of {:?}, score {}, {} nodes",
+                    tactic.name, best, tactic.solutions, score, ai.budget_used
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_parallel_search_solves_mates() {
+        use crate::notation::{move_to_uci, parse_fen};
+
+        // 几个线程分着搜根节点的走法，共用置换表，结果应该和单线程一样找到杀棋
+        for tactic in TACTICS
+            .iter()
+            .filter(|tactic| tactic.mate && tactic.depth <= 4)
+        {
+            let (board, side) = parse_fen(tactic.fen).unwrap();
+            let mut ai = ChessAI::new(tactic.depth);
+            ai.time_limit = 60_000;
+            ai.threads = 4;
+            let best = ai.get_best_move(&board, side).map(move_to_uci);
+            assert!(
+                best.as_deref()
+                    .is_some_and(|mv| tactic.solutions.contains(&mv)),
+                "{}: played {:?}",
+                tactic.name,
+                best
+            );
+            assert!(ai.last_score.unwrap().abs() >= 90000);
+            assert!(ai.budget_used > 0);
+        }
+    }
+
+    #[test]
+    fn test_search_progress() {
+        let board = Board::new();
+        let progress = Arc::new(SearchProgress::default());
+        let mut ai = ChessAI::with_seed(3, 1);
+        ai.progress = Some(Arc::clone(&progress));
+        assert!(ai.get_best_move(&board, Color::White).is_some());
+        assert_eq!(progress.depth(), 3);
+        assert_eq!(progress.nodes(), ai.budget_used);
+        assert_eq!(progress.score(), ai.last_score);
+
+        // 被中止的搜索立刻返回，不会完成任何一层
+        progress.stop();
+        assert!(ai.get_best_move(&board, Color::White).is_none());
+        assert_eq!(progress.depth(), 0);
+        assert_eq!(progress.score(), None);
     }
 }
@@ -0,0 +1,79 @@
+// 自对弈调参：把一个评估权重设为不同的值，与默认权重对下多盘快棋，比较胜率
+//
+//   cargo run --release --bin tune -- mobility 0 5 10 --depth 2 --openings 16
+use std::process;
+
+use chess_gui::ai::EvalParams;
+use chess_gui::tuning::{self, MatchConfig};
+
+const USAGE: &str = "usage: tune <param> <value>... [--depth N] [--openings N] [--plies N] [--threads N] [--seed N]";
+
+fn main() {
+    let (name, values, config) = parse_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        eprintln!("params: {}", EvalParams::NAMES.join(", "));
+        process::exit(2);
+    });
+
+    let baseline = EvalParams::default();
+    println!(
+        "{name}: baseline {}, depth {}, {} games per value",
+        baseline.get(&name).unwrap(),
+        config.depth,
+        config.openings * 2
+    );
+    println!(
+        "{:>8}  {:>5}  {:>5}  {:>5}  {:>7}  {:>7}",
+        "value", "win", "loss", "draw", "score", "elo"
+    );
+
+    let results = tuning::grid_search(&baseline, &name, &values, &config).unwrap();
+    for (value, result) in results {
+        println!(
+            "{:>8}  {:>5}  {:>5}  {:>5}  {:>6.1}%  {:>+7.0}",
+            value,
+            result.wins,
+            result.losses,
+            result.draws,
+            result.score() * 100.0,
+            result.elo_diff()
+        );
+    }
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(String, Vec<i32>, MatchConfig), String> {
+    let name = args.next().ok_or("missing parameter name")?;
+    if EvalParams::default().get(&name).is_none() {
+        return Err(format!("unknown parameter `{name}`"));
+    }
+
+    let mut values = Vec::new();
+    let mut config = MatchConfig::default();
+    while let Some(arg) = args.next() {
+        let mut option = |flag: &str| -> Result<u64, String> {
+            let value = args.next().ok_or(format!("{flag} needs a value"))?;
+            value
+                .parse()
+                .map_err(|_| format!("invalid value for {flag}: {value}"))
+        };
+        match arg.as_str() {
+            "--depth" => config.depth = option("--depth")? as u32,
+            "--openings" => config.openings = option("--openings")? as usize,
+            "--plies" => config.max_plies = option("--plies")? as usize,
+            "--threads" => config.threads = option("--threads")? as usize,
+            "--seed" => config.seed = option("--seed")?,
+            value => values.push(
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid value: {value}"))?,
+            ),
+        }
+    }
+
+    if values.is_empty() {
+        return Err("no values to try".to_string());
+    }
+    Ok((name, values, config))
+}
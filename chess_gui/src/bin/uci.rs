@@ -0,0 +1,37 @@
+// UCI 引擎：从标准输入读命令，把回复写到标准输出，可以加到 Arena、Cute Chess 等界面里
+//
+//   cargo build --release --bin uci
+//   cutechess-cli -engine cmd=target/release/uci -engine cmd=stockfish ...
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use chess_gui::uci::UciEngine;
+
+fn main() {
+    // 搜索线程和主线程都会回复，统一交给一个线程写出并立即 flush
+    let (sender, receiver) = mpsc::channel::<String>();
+    let printer = thread::spawn(move || {
+        let mut stdout = io::stdout().lock();
+        for line in receiver {
+            if writeln!(stdout, "{line}")
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let mut engine = UciEngine::new(sender);
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !engine.handle(&line) {
+            break;
+        }
+    }
+    // 标准输入关闭时不中止搜索，等它给出结果（管道里测试引擎时很有用）
+    engine.wait_for_search();
+    drop(engine);
+    let _ = printer.join();
+}
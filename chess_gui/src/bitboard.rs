@@ -0,0 +1,214 @@
+//! 位棋盘模块
+//! 一个 `u64` 表示一组格子，第 `row * 8 + col` 位表示 `(row, col)`（与 `Board::attack_map` 相同）。
+//! 马、王和兵的攻击范围是预先算好的表，车、象、后沿射线扫描到第一个阻挡的棋子为止
+
+use crate::types::*;
+
+pub type Bitboard = u64;
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+];
+
+// 前四个是横竖方向，后四个是对角线方向
+pub(crate) const ALL_DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 0),
+    (0, -1),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// 每个格子上的马能走到的格子
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = step_table(&KNIGHT_OFFSETS);
+/// 每个格子上的王能走到的格子（不含王车易位）
+pub const KING_ATTACKS: [Bitboard; 64] = step_table(&ALL_DIRECTIONS);
+/// 每个格子上的兵斜着吃子的格子，`[白, 黑]`；白兵向第 0 行走
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    step_table(&[(-1, -1), (-1, 1)]),
+    step_table(&[(1, -1), (1, 1)]),
+];
+// 从每个格子出发，沿 `ALL_DIRECTIONS` 中每个方向直到棋盘边缘的格子（不含出发格）
+const RAYS: [[Bitboard; 64]; 8] = ray_table();
+
+/// 格子对应的位
+pub const fn square_bit(pos: Square) -> Bitboard {
+    1 << (pos.0 * 8 + pos.1)
+}
+
+/// 位的编号对应的格子
+pub const fn square_at(index: u32) -> Square {
+    (index as usize / 8, index as usize % 8)
+}
+
+/// 按位的编号从小到大（即从第 0 行的 a 列开始逐行）列出集合中的格子
+pub fn squares(mut bits: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let index = bits.trailing_zeros();
+        bits &= bits - 1;
+        Some(square_at(index))
+    })
+}
+
+/// 沿 `ALL_DIRECTIONS[direction]` 滑动能到达的格子：遇到的第一个棋子（`occupied` 中的格子）也算，之后的不算
+pub fn ray_attacks(square: Square, direction: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square.0 * 8 + square.1];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    // 位的编号沿射线递增的方向取最低位，递减的方向取最高位
+    let (dr, dc) = ALL_DIRECTIONS[direction];
+    let first = if dr * 8 + dc > 0 {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[direction][first as usize]
+}
+
+/// 车从 `square` 出发能攻击到的格子
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    (0..4).fold(0, |bits, direction| {
+        bits | ray_attacks(square, direction, occupied)
+    })
+}
+
+/// 象从 `square` 出发能攻击到的格子
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    (4..8).fold(0, |bits, direction| {
+        bits | ray_attacks(square, direction, occupied)
+    })
+}
+
+/// 某种棋子在 `square` 上攻击到的格子；兵只算斜着吃子的两格
+pub fn piece_attacks(piece: Piece, square: Square, occupied: Bitboard) -> Bitboard {
+    let index = square.0 * 8 + square.1;
+    match piece.piece_type {
+        PieceType::Pawn => PAWN_ATTACKS[color_index(piece.color)][index],
+        PieceType::Knight => KNIGHT_ATTACKS[index],
+        PieceType::King => KING_ATTACKS[index],
+        PieceType::Bishop => bishop_attacks(square, occupied),
+        PieceType::Rook => rook_attacks(square, occupied),
+        PieceType::Queen => rook_attacks(square, occupied) | bishop_attacks(square, occupied),
+    }
+}
+
+pub(crate) const fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+pub(crate) const fn piece_type_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+const fn step_table(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let (row, col) = ((index / 8) as i32, (index % 8) as i32);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (r, c) = (row + offsets[i].0, col + offsets[i].1);
+            if r >= 0 && r < 8 && c >= 0 && c < 8 {
+                table[index] |= 1 << (r * 8 + c);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let (dr, dc) = ALL_DIRECTIONS[direction];
+        let mut index = 0;
+        while index < 64 {
+            let (mut r, mut c) = ((index / 8) as i32 + dr, (index % 8) as i32 + dc);
+            while r >= 0 && r < 8 && c >= 0 && c < 8 {
+                table[direction][index] |= 1 << (r * 8 + c);
+                r += dr;
+                c += dc;
+            }
+            index += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_tables() {
+        // a8 的马只能走 b6、c7；e4 的马有 8 个去处
+        assert_eq!(KNIGHT_ATTACKS[0], square_bit((2, 1)) | square_bit((1, 2)));
+        assert_eq!(KNIGHT_ATTACKS[4 * 8 + 4].count_ones(), 8);
+        assert_eq!(KING_ATTACKS[7 * 8 + 7].count_ones(), 3);
+        // e2 的白兵攻击 d3、f3，e7 的黑兵攻击 d6、f6
+        assert_eq!(
+            PAWN_ATTACKS[0][6 * 8 + 4],
+            square_bit((5, 3)) | square_bit((5, 5))
+        );
+        assert_eq!(
+            PAWN_ATTACKS[1][8 + 4],
+            square_bit((2, 3)) | square_bit((2, 5))
+        );
+    }
+
+    #[test]
+    fn test_sliding_attacks_stop_at_first_blocker() {
+        // d4 的车，d6 和 b4 上有棋子
+        let occupied = square_bit((2, 3)) | square_bit((4, 1));
+        let attacks = rook_attacks((4, 3), occupied);
+        let expected: Vec<Square> = vec![
+            (2, 3),
+            (3, 3),
+            (4, 1),
+            (4, 2),
+            (4, 4),
+            (4, 5),
+            (4, 6),
+            (4, 7),
+            (5, 3),
+            (6, 3),
+            (7, 3),
+        ];
+        assert_eq!(squares(attacks).collect::<Vec<_>>(), expected);
+
+        // 空棋盘上角落的象攻击整条对角线
+        assert_eq!(bishop_attacks((7, 0), 0).count_ones(), 7);
+        assert_eq!(
+            bishop_attacks((7, 0), square_bit((5, 2))),
+            square_bit((6, 1)) | square_bit((5, 2))
+        );
+    }
+}
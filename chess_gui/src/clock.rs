@@ -0,0 +1,242 @@
+//! 棋钟模块
+//! 每方一个倒计时。走完一步按下自己的钟：扣掉这一步用的时间、加上加秒，开始走对方的钟。
+//! 白方走出第一步之前棋钟不走；时间用完（落旗）的一方超时判负
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::bitboard::color_index;
+use crate::types::*;
+
+/// 估计每方还要走的步数，用来给 AI 分配这一步的思考时间（与 UCI 的 `go wtime` 相同）
+const MOVES_TO_GO: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 时限：每方的基本时间和每步之后的加秒
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// 界面中可以选的时限，从快到慢
+    pub const PRESETS: [TimeControl; 5] = [
+        TimeControl::minutes(1, 0),
+        TimeControl::minutes(3, 2),
+        TimeControl::minutes(5, 0),
+        TimeControl::minutes(10, 5),
+        TimeControl::minutes(15, 10),
+    ];
+
+    /// `minutes` 分钟加每步 `increment` 秒
+    pub const fn minutes(minutes: u64, increment: u64) -> Self {
+        Self {
+            initial: Duration::from_secs(minutes * 60),
+            increment: Duration::from_secs(increment),
+        }
+    }
+
+    /// 按基本时间分类：不到 3 分钟为 Bullet，不到 10 分钟为 Blitz，30 分钟以内为 Rapid
+    pub fn category(&self) -> &'static str {
+        match self.initial.as_secs() {
+            0..180 => "Bullet",
+            180..600 => "Blitz",
+            600..=1800 => "Rapid",
+            _ => "Classical",
+        }
+    }
+
+    /// PGN `TimeControl` 标签的写法：秒数加加秒，例如 `300+2`
+    pub fn to_pgn(&self) -> String {
+        format!("{}+{}", self.initial.as_secs(), self.increment.as_secs())
+    }
+
+    pub fn from_pgn(text: &str) -> Option<Self> {
+        let (initial, increment) = text.split_once('+').unwrap_or((text, "0"));
+        Some(Self {
+            initial: Duration::from_secs(initial.trim().parse().ok()?),
+            increment: Duration::from_secs(increment.trim().parse().ok()?),
+        })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    /// 例如 `Blitz 3+2`（分钟加秒）
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}+{}",
+            self.category(),
+            self.initial.as_secs() / 60,
+            self.increment.as_secs()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 双方的棋钟
+pub struct ChessClock {
+    control: TimeControl,
+    /// 停钟时各方剩下的时间，`[白, 黑]`；正在走的一方还要减去从 `running` 起经过的时间
+    remaining: [Duration; 2],
+    /// 正在走的钟和它开始走的时刻
+    running: Option<(Color, Instant)>,
+}
+
+impl ChessClock {
+    pub fn new(control: TimeControl) -> Self {
+        Self::with_remaining(control, control.initial, control.initial)
+    }
+
+    /// 从存档恢复的棋钟，停着
+    pub fn with_remaining(control: TimeControl, white: Duration, black: Duration) -> Self {
+        Self {
+            control,
+            remaining: [white, black],
+            running: None,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// 正在走的一方
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// `now` 时某一方剩下的时间
+    pub fn remaining(&self, color: Color, now: Instant) -> Duration {
+        let remaining = self.remaining[color_index(color)];
+        match self.running {
+            Some((running, since)) if running == color => {
+                remaining.saturating_sub(now.saturating_duration_since(since))
+            }
+            _ => remaining,
+        }
+    }
+
+    /// `mover` 走完一步后按钟：扣掉用的时间，加上加秒，开始走对方的钟
+    pub fn press(&mut self, mover: Color, now: Instant) {
+        self.stop(now);
+        let remaining = &mut self.remaining[color_index(mover)];
+        *remaining += self.control.increment;
+        self.running = Some((mover.opposite(), now));
+    }
+
+    /// 改走 `color` 的钟，不加秒（悔棋或者在棋谱中跳到别的局面时）
+    pub fn switch_to(&mut self, color: Color, now: Instant) {
+        self.stop(now);
+        self.running = Some((color, now));
+    }
+
+    /// 停钟，已经用掉的时间记下来
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((color, _)) = self.running {
+            self.remaining[color_index(color)] = self.remaining(color, now);
+            self.running = None;
+        }
+    }
+
+    /// 时间用完的一方
+    pub fn flagged(&self, now: Instant) -> Option<Color> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| self.remaining(color, now).is_zero())
+    }
+
+    /// 给 AI 这一步的思考时间：剩余时间的 1/30 加上 3/4 的加秒，但不超过剩余时间的一半
+    pub fn move_budget(&self, color: Color, now: Instant) -> Duration {
+        let remaining = self.remaining(color, now);
+        (remaining / MOVES_TO_GO + self.control.increment * 3 / 4).min(remaining / 2)
+    }
+}
+
+/// 棋钟上显示的时间：`m:ss`，最后 10 秒带上十分之一秒，例如 `0:09.4`
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds < 10 {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_press_adds_increment_and_switches_sides() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::minutes(3, 2));
+        assert_eq!(clock.running(), None);
+
+        // 白方第一步之前钟不走，走完之后加秒并开始走黑方的钟
+        clock.press(Color::White, start);
+        assert_eq!(
+            clock.remaining(Color::White, start),
+            Duration::from_secs(182)
+        );
+        assert_eq!(clock.running(), Some(Color::Black));
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            clock.remaining(Color::Black, later),
+            Duration::from_secs(170)
+        );
+        clock.press(Color::Black, later);
+        assert_eq!(
+            clock.remaining(Color::Black, later),
+            Duration::from_secs(172)
+        );
+        assert_eq!(clock.running(), Some(Color::White));
+
+        // 悔棋时换回黑方的钟，不加秒
+        let undo = later + Duration::from_secs(5);
+        clock.switch_to(Color::Black, undo);
+        assert_eq!(
+            clock.remaining(Color::White, undo),
+            Duration::from_secs(177)
+        );
+        clock.stop(undo + Duration::from_secs(2));
+        assert_eq!(
+            clock.remaining(Color::Black, undo),
+            Duration::from_secs(170)
+        );
+        assert_eq!(clock.running(), None);
+    }
+
+    #[test]
+    fn test_flag_fall() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::minutes(1, 0));
+        clock.press(Color::White, start);
+        assert_eq!(clock.flagged(start + Duration::from_secs(59)), None);
+        assert_eq!(
+            clock.flagged(start + Duration::from_secs(61)),
+            Some(Color::Black)
+        );
+        assert_eq!(
+            clock.remaining(Color::Black, start + Duration::from_secs(61)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_time_control_text() {
+        let blitz = TimeControl::minutes(3, 2);
+        assert_eq!(blitz.to_string(), "Blitz 3+2");
+        assert_eq!(blitz.to_pgn(), "180+2");
+        assert_eq!(TimeControl::from_pgn("180+2"), Some(blitz));
+        assert_eq!(
+            TimeControl::from_pgn("600"),
+            Some(TimeControl::minutes(10, 0))
+        );
+        assert_eq!(TimeControl::from_pgn("-"), None);
+
+        assert_eq!(format_time(Duration::from_millis(272_400)), "4:32");
+        assert_eq!(format_time(Duration::from_millis(9_470)), "0:09.4");
+    }
+}
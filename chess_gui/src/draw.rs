@@ -0,0 +1,145 @@
+// 和棋规则：三次重复、五十步规则和子力不足（逼和和协议和棋由调用方判断）
+use crate::ai::ChessAI;
+use crate::board::Board;
+use crate::notation::MoveHistory;
+use crate::types::*;
+
+/// 五十步规则：双方各走 50 步（100 个半步）没有吃子也没有走兵
+pub const FIFTY_MOVE_PLIES: usize = 100;
+
+/// 同一局面出现这么多次时和棋
+pub const REPETITION_LIMIT: usize = 3;
+
+/// 按棋谱当前这条线从起始局面重放，判断走到的局面是否已经和棋。
+/// 局面用 AI 的 Zobrist 键比较，键里包括走棋方、王车易位的权利和能吃的过路兵
+pub fn draw_reason(history: &MoveHistory, ai: &ChessAI) -> Option<DrawReason> {
+    let mut board = history.start_board();
+    let mut color = Color::White;
+    let mut positions = Positions::new(ai.position_key(&board, color));
+    for record in history.records() {
+        let irreversible = resets_clock(&board, record.mv);
+        board.make_move(record.mv);
+        color = color.opposite();
+        positions.push(ai.position_key(&board, color), irreversible);
+    }
+
+    if board.has_insufficient_material() {
+        Some(DrawReason::InsufficientMaterial)
+    } else if positions.repetitions() >= REPETITION_LIMIT {
+        Some(DrawReason::ThreefoldRepetition)
+    } else if positions.halfmove_clock() >= FIFTY_MOVE_PLIES {
+        Some(DrawReason::FiftyMoveRule)
+    } else {
+        None
+    }
+}
+
+/// 吃子或走兵之后以前的局面不可能再出现，五十步的计数也从头开始
+pub fn resets_clock(board: &Board, mv: Move) -> bool {
+    board.get_piece(mv.to).is_some()
+        || board
+            .get_piece(mv.from)
+            .is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+}
+
+// 上一次吃子或走兵之后出现过的局面，最早的在最前面
+struct Positions {
+    keys: Vec<u64>,
+}
+
+impl Positions {
+    fn new(start: u64) -> Self {
+        Self { keys: vec![start] }
+    }
+
+    fn push(&mut self, key: u64, irreversible: bool) {
+        if irreversible {
+            self.keys.clear();
+        }
+        self.keys.push(key);
+    }
+
+    // 上一次吃子或走兵之后走了几个半步
+    fn halfmove_clock(&self) -> usize {
+        self.keys.len() - 1
+    }
+
+    // 当前局面出现过几次（包括现在这次）
+    fn repetitions(&self) -> usize {
+        let current = self.keys[self.keys.len() - 1];
+        self.keys.iter().filter(|&&key| key == current).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{parse_fen, parse_uci_move};
+
+    fn history(moves: &[&str]) -> MoveHistory {
+        let mut board = Board::new();
+        let mut history = MoveHistory::new();
+        for text in moves {
+            let mv = parse_uci_move(text).unwrap();
+            history.record(&board, mv);
+            board.make_move(mv);
+        }
+        history
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let ai = ChessAI::new(2);
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let twice: Vec<&str> = shuffle.iter().chain(&shuffle).copied().collect();
+
+        // 初始局面出现第二次还不算
+        assert_eq!(draw_reason(&history(&shuffle), &ai), None);
+        assert_eq!(
+            draw_reason(&history(&twice), &ai),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        // 走兵之后重新计数
+        let mut reset = vec!["e2e4"];
+        reset.extend(&twice[..6]);
+        assert_eq!(draw_reason(&history(&reset), &ai), None);
+    }
+
+    #[test]
+    fn test_fifty_move_clock() {
+        let mut positions = Positions::new(0);
+        for key in 1..FIFTY_MOVE_PLIES as u64 {
+            positions.push(key, false);
+        }
+        assert_eq!(positions.halfmove_clock(), FIFTY_MOVE_PLIES - 1);
+        positions.push(FIFTY_MOVE_PLIES as u64, false);
+        assert_eq!(positions.halfmove_clock(), FIFTY_MOVE_PLIES);
+        assert_eq!(positions.repetitions(), 1);
+
+        positions.push(0, true);
+        assert_eq!(positions.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn test_resets_clock() {
+        let (board, _) = parse_fen("4k3/8/8/3p4/4P3/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert!(resets_clock(&board, parse_uci_move("e4d5").unwrap()));
+        assert!(resets_clock(&board, parse_uci_move("e4e5").unwrap()));
+        assert!(!resets_clock(&board, parse_uci_move("a1a7").unwrap()));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let insufficient = |fen: &str| parse_fen(fen).unwrap().0.has_insufficient_material();
+
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // 两边的象都走深色格
+        assert!(insufficient("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // 异色格象、两个马、一个兵都还有可能将死
+        assert!(!insufficient("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+    }
+}
@@ -0,0 +1,247 @@
+//! 评估缓存持久化模块
+//! 把置换表以 Zobrist 哈希为键写入磁盘，下次启动时重新加载，
+//! 让重复出现的开局搜索得更快
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ai::{NodeType, TranspositionEntry};
+use crate::settings;
+use crate::types::*;
+
+/// 文件头魔数
+const MAGIC: [u8; 4] = *b"CHTT";
+/// 格式版本，条目布局或评估函数有不兼容的改动时递增
+pub const CACHE_VERSION: u32 = 2;
+/// 磁盘上最多保存的条目数
+pub const MAX_CACHE_ENTRIES: usize = 100_000;
+
+/// 默认缓存路径：`$HOME/.chess_gui/eval_cache.bin`
+pub fn default_cache_path() -> PathBuf {
+    settings::data_dir().join("eval_cache.bin")
+}
+
+/// 写入缓存文件。`fingerprint` 标识生成哈希所用的 Zobrist 键，
+/// 键不同的缓存在加载时会被拒绝。超过上限时优先保留搜索深度大的条目
+pub(crate) fn save(
+    path: &Path,
+    fingerprint: u64,
+    mut entries: Vec<(u64, TranspositionEntry)>,
+) -> io::Result<()> {
+    if entries.len() > MAX_CACHE_ENTRIES {
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.depth));
+        entries.truncate(MAX_CACHE_ENTRIES);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // 先写临时文件再重命名，避免写到一半的缓存被下次读取
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&fingerprint.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    for (hash, entry) in &entries {
+        writer.write_all(&hash.to_le_bytes())?;
+        writer.write_all(&entry.depth.to_le_bytes())?;
+        writer.write_all(&entry.score.to_le_bytes())?;
+        writer.write_all(&[node_type_to_byte(&entry.node_type)])?;
+        writer.write_all(&encode_move(entry.best_move))?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+    fs::rename(tmp_path, path)
+}
+
+/// 读取缓存文件，版本或指纹不匹配时返回 `InvalidData` 错误
+pub(crate) fn load(path: &Path, fingerprint: u64) -> io::Result<Vec<(u64, TranspositionEntry)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("not an evaluation cache file"));
+    }
+
+    let version = u32::from_le_bytes(read_array(&mut reader)?);
+    if version != CACHE_VERSION {
+        return Err(invalid_data("unsupported evaluation cache version"));
+    }
+
+    let file_fingerprint = u64::from_le_bytes(read_array(&mut reader)?);
+    if file_fingerprint != fingerprint {
+        return Err(invalid_data(
+            "evaluation cache was built with other Zobrist keys",
+        ));
+    }
+
+    let count = u64::from_le_bytes(read_array(&mut reader)?) as usize;
+    if count > MAX_CACHE_ENTRIES {
+        return Err(invalid_data("evaluation cache is larger than the size cap"));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let hash = u64::from_le_bytes(read_array(&mut reader)?);
+        let depth = u32::from_le_bytes(read_array(&mut reader)?);
+        let score = i32::from_le_bytes(read_array(&mut reader)?);
+        let [node_type] = read_array::<1>(&mut reader)?;
+        let best_move = read_array::<6>(&mut reader)?;
+
+        entries.push((
+            hash,
+            TranspositionEntry {
+                depth,
+                score,
+                best_move: decode_move(best_move)?,
+                node_type: node_type_from_byte(node_type)?,
+            },
+        ));
+    }
+
+    Ok(entries)
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn node_type_to_byte(node_type: &NodeType) -> u8 {
+    match node_type {
+        NodeType::Exact => 0,
+        NodeType::LowerBound => 1,
+        NodeType::UpperBound => 2,
+    }
+}
+
+fn node_type_from_byte(byte: u8) -> io::Result<NodeType> {
+    match byte {
+        0 => Ok(NodeType::Exact),
+        1 => Ok(NodeType::LowerBound),
+        2 => Ok(NodeType::UpperBound),
+        _ => Err(invalid_data("invalid node type")),
+    }
+}
+
+// [有无走法, 起始行, 起始列, 目标行, 目标列, 升变]
+fn encode_move(mv: Option<Move>) -> [u8; 6] {
+    match mv {
+        None => [0; 6],
+        Some(mv) => [
+            1,
+            mv.from.0 as u8,
+            mv.from.1 as u8,
+            mv.to.0 as u8,
+            mv.to.1 as u8,
+            match mv.promotion {
+                None => 0,
+                Some(PieceType::Queen) => 1,
+                Some(PieceType::Rook) => 2,
+                Some(PieceType::Bishop) => 3,
+                Some(PieceType::Knight) => 4,
+                Some(_) => 0,
+            },
+        ],
+    }
+}
+
+fn decode_move(bytes: [u8; 6]) -> io::Result<Option<Move>> {
+    if bytes[0] == 0 {
+        return Ok(None);
+    }
+    if bytes[1..5].iter().any(|&coord| coord >= 8) {
+        return Err(invalid_data("invalid square in cached move"));
+    }
+
+    let promotion = match bytes[5] {
+        0 => None,
+        1 => Some(PieceType::Queen),
+        2 => Some(PieceType::Rook),
+        3 => Some(PieceType::Bishop),
+        4 => Some(PieceType::Knight),
+        _ => return Err(invalid_data("invalid promotion in cached move")),
+    };
+
+    Ok(Some(Move {
+        from: (bytes[1] as usize, bytes[2] as usize),
+        to: (bytes[3] as usize, bytes[4] as usize),
+        promotion,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("chess_gui_test_{}", std::process::id()))
+            .join(name)
+    }
+
+    fn sample_entries() -> Vec<(u64, TranspositionEntry)> {
+        vec![
+            (
+                42,
+                TranspositionEntry {
+                    depth: 3,
+                    score: -120,
+                    best_move: Some(Move {
+                        from: (1, 0),
+                        to: (0, 0),
+                        promotion: Some(PieceType::Knight),
+                    }),
+                    node_type: NodeType::LowerBound,
+                },
+            ),
+            (
+                7,
+                TranspositionEntry {
+                    depth: 1,
+                    score: 15,
+                    best_move: None,
+                    node_type: NodeType::Exact,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round_trip.bin");
+        save(&path, 99, sample_entries()).unwrap();
+
+        let loaded = load(&path, 99).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].0, 42);
+        assert_eq!(loaded[0].1.score, -120);
+        assert_eq!(loaded[0].1.best_move, sample_entries()[0].1.best_move);
+        assert_eq!(loaded[1].1.best_move, None);
+    }
+
+    #[test]
+    fn test_rejects_other_fingerprint_and_version() {
+        let path = temp_path("fingerprint.bin");
+        save(&path, 1, sample_entries()).unwrap();
+        assert!(load(&path, 2).is_err());
+
+        // 篡改版本号
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[4..8].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(load(&path, 1).is_err());
+    }
+}
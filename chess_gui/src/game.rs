@@ -1,9 +1,12 @@
 // Game logic module - handles game state management and game flow
 use std::time::Instant;
 
-use crate::types::*;
-use crate::board::Board;
 use crate::ai::ChessAI;
+use crate::board::{Board, BoardDelta};
+use crate::draw;
+use crate::notation::MoveHistory;
+use crate::settings::GameSettings;
+use crate::types::*;
 
 /// Game controller that manages the game state and flow
 pub struct ChessGame {
@@ -13,7 +16,13 @@ pub struct ChessGame {
     pub game_state: GameState,
     pub ai_thinking: bool,
     pub ai_move_start: Option<Instant>,
+    /// AI 走棋前至少停顿多少毫秒，思考时间算在里面；0 为立即走棋
+    pub ai_move_delay_ms: u64,
+    ai_move_ready: Option<Move>, // 已经算好、等停顿结束才返回的走法
     pub ai_difficulty: AIDifficulty,
+    pub move_history: MoveHistory,
+    undo_stack: Vec<BoardDelta>, // 每一步改变的格子，撤销时交给 `Board::undo_move`
+    redo_stack: Vec<Move>,       // 撤销掉的走法，最近撤销的在最后
 }
 
 impl ChessGame {
@@ -25,7 +34,12 @@ impl ChessGame {
             game_state: GameState::Playing,
             ai_thinking: false,
             ai_move_start: None,
+            ai_move_delay_ms: GameSettings::default().ai_move_delay_ms,
+            ai_move_ready: None,
             ai_difficulty: AIDifficulty::Medium,
+            move_history: MoveHistory::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
         }
     }
 
@@ -35,6 +49,10 @@ impl ChessGame {
         self.game_state = GameState::Playing;
         self.ai_thinking = false;
         self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.move_history.clear();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
     }
 
     pub fn set_ai_difficulty(&mut self, difficulty: AIDifficulty) {
@@ -43,9 +61,7 @@ impl ChessGame {
     }
 
     pub fn update_game_state(&mut self) -> String {
-        let moves = self.board.generate_moves(self.current_player);
-
-        if moves.is_empty() {
+        if !self.board.has_legal_move(self.current_player) {
             if self.board.is_in_check(self.current_player) {
                 self.game_state = match self.current_player {
                     Color::White => GameState::BlackWins,
@@ -59,9 +75,12 @@ impl ChessGame {
                     }
                 )
             } else {
-                self.game_state = GameState::Draw;
+                self.game_state = GameState::Draw(DrawReason::Stalemate);
                 "Draw by stalemate!".to_string()
             }
+        } else if let Some(reason) = draw::draw_reason(&self.move_history, &self.ai) {
+            self.game_state = GameState::Draw(reason);
+            format!("Draw by {}!", reason.description())
         } else if self.board.is_in_check(self.current_player) {
             format!("{:?} is in check!", self.current_player)
         } else {
@@ -74,31 +93,101 @@ impl ChessGame {
             return false;
         }
 
-        let valid_moves = self.board.generate_moves(self.current_player);
-        let is_valid = valid_moves.iter().any(|valid_mv| {
-            valid_mv.from == mv.from && valid_mv.to == mv.to && valid_mv.promotion == mv.promotion
-        });
+        let is_valid = self
+            .board
+            .legal_moves_iter(self.current_player)
+            .any(|valid_mv| {
+                valid_mv.from == mv.from
+                    && valid_mv.to == mv.to
+                    && valid_mv.promotion == mv.promotion
+            });
 
         if is_valid {
-            self.board.make_move(mv);
-            self.current_player = match self.current_player {
-                Color::White => Color::Black,
-                Color::Black => Color::White,
-            };
+            // 走了新的一步，之前撤销的走法不能再重做
+            self.redo_stack.clear();
+            self.apply_move(mv);
             true
         } else {
             false
         }
     }
 
+    fn apply_move(&mut self, mv: Move) {
+        self.move_history.record(&self.board, mv);
+        if let Some(delta) = self.board.make_move_with_delta(mv) {
+            self.undo_stack.push(delta);
+        }
+        self.current_player = self.current_player.opposite();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// 悔棋：撤销到上一次轮到白方（玩家）走的局面，AI 的应着一起撤销；
+    /// AI 正在思考时只撤销玩家刚走的那步。没有可撤销的走法时返回 `false`
+    pub fn undo(&mut self) -> bool {
+        if !self.undo_one() {
+            return false;
+        }
+        while self.current_player == Color::Black && self.undo_one() {}
+        self.after_undo_redo();
+        true
+    }
+
+    /// 重做被 `undo` 撤销的走法，直到又轮到白方或者没有可重做的走法
+    pub fn redo(&mut self) -> bool {
+        if !self.redo_one() {
+            return false;
+        }
+        while self.current_player == Color::Black && self.redo_one() {}
+        self.after_undo_redo();
+        true
+    }
+
+    fn undo_one(&mut self) -> bool {
+        let (Some(delta), Some(record)) = (self.undo_stack.pop(), self.move_history.last()) else {
+            return false;
+        };
+        self.redo_stack.push(record.mv);
+        self.board.undo_move(&delta);
+        self.move_history.go_back();
+        self.current_player = self.current_player.opposite();
+        true
+    }
+
+    fn redo_one(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(mv) => {
+                self.apply_move(mv);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 撤销或重做后停止 AI 思考，重新判断局面（撤销将死之后对局继续）
+    fn after_undo_redo(&mut self) {
+        self.ai_thinking = false;
+        self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.game_state = GameState::Playing;
+        self.update_game_state();
+        // 重做到轮到 AI 的局面时让 AI 接着走
+        self.start_ai_thinking();
+    }
+
     pub fn get_valid_moves_for_piece(&self, pos: (usize, usize)) -> Vec<Move> {
         if self.game_state != GameState::Playing {
             return Vec::new();
         }
 
         self.board
-            .generate_moves(self.current_player)
-            .into_iter()
+            .legal_moves_iter(self.current_player)
             .filter(|mv| mv.from == pos)
             .collect()
     }
@@ -110,17 +199,28 @@ impl ChessGame {
         }
     }
 
+    /// AI 的走法：第一次调用时就搜索，停顿时间过去之后才返回
     pub fn get_ai_move(&mut self) -> Option<Move> {
-        if self.ai_thinking && self.current_player == Color::Black {
-            if let Some(start_time) = self.ai_move_start {
-                let elapsed = start_time.elapsed().as_millis();
-                if elapsed > 500 {
-                    let ai_move = self.ai.get_best_move(&self.board, Color::Black);
+        if self.ai_thinking
+            && self.current_player == Color::Black
+            && let Some(start_time) = self.ai_move_start
+        {
+            if self.ai_move_ready.is_none() {
+                self.ai_move_ready = self.ai.get_best_move(&self.board, Color::Black);
+                // 没有走法可走，不用再等
+                if self.ai_move_ready.is_none() {
                     self.ai_thinking = false;
                     self.ai_move_start = None;
-                    return ai_move;
+                    return None;
                 }
             }
+            if start_time.elapsed().as_millis() >= self.ai_move_delay_ms as u128
+                && let Some(ai_move) = self.ai_move_ready.take()
+            {
+                self.ai_thinking = false;
+                self.ai_move_start = None;
+                return Some(ai_move);
+            }
         }
         None
     }
@@ -159,3 +259,65 @@ impl Default for ChessGame {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_uci_move;
+
+    fn play(game: &mut ChessGame, moves: &[&str]) {
+        for mv in moves {
+            assert!(game.make_move(parse_uci_move(mv).unwrap()), "{mv}");
+        }
+    }
+
+    #[test]
+    fn test_undo_takes_back_ai_reply() {
+        let mut game = ChessGame::new();
+        play(&mut game, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+        let after_two_moves = game.board.clone();
+
+        // 撤销一次回到玩家上一步之前：白方的 Nf3 和黑方的应着都撤销
+        assert!(game.undo());
+        assert_eq!(game.current_player, Color::White);
+        assert_eq!(game.move_history.len(), 2);
+        assert!(game.board.get_piece((7, 6)).is_some());
+        assert!(game.board.get_piece((0, 1)).is_some());
+
+        assert!(game.redo());
+        assert_eq!(game.current_player, Color::White);
+        assert_eq!(game.move_history.len(), 4);
+        assert_eq!(game.board.squares(), after_two_moves.squares());
+        assert!(!game.can_redo());
+
+        // 撤销到开局后再撤销就没有走法了
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(!game.undo());
+        assert_eq!(game.board.squares(), Board::new().squares());
+    }
+
+    #[test]
+    fn test_new_move_clears_redo() {
+        let mut game = ChessGame::new();
+        play(&mut game, &["e2e4", "e7e5"]);
+        assert!(game.undo());
+        assert!(game.can_redo());
+        play(&mut game, &["d2d4"]);
+        assert!(!game.can_redo());
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn test_undo_after_checkmate_resumes_play() {
+        let mut game = ChessGame::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        game.update_game_state();
+        assert_eq!(game.game_state, GameState::BlackWins);
+
+        assert!(game.undo());
+        assert_eq!(game.game_state, GameState::Playing);
+        assert_eq!(game.current_player, Color::White);
+        assert!(!game.ai_thinking);
+    }
+}
@@ -1,15 +1,36 @@
 // Chess GUI Library
 // This file exports all the modules for the chess game
 
-pub mod types;
-pub mod board;
 pub mod ai;
-pub mod ui;
+pub mod bitboard;
+pub mod board;
+pub mod clock;
+pub mod draw;
+pub mod eval_cache;
 pub mod game;
+pub mod library;
+pub mod mates;
+pub mod notation;
+pub mod opening;
+pub mod pgn;
+pub mod profile;
+pub mod puzzle;
+pub mod savegame;
+pub mod settings;
+pub mod share;
+pub mod training;
+pub mod tuning;
+pub mod types;
+pub mod uci;
+pub mod ui;
+pub mod variant;
+pub mod zobrist;
 
 // Re-export commonly used types
-pub use types::*;
-pub use board::Board;
 pub use ai::ChessAI;
-pub use ui::ChessApp;
+pub use board::Board;
 pub use game::ChessGame;
+pub use notation::MoveHistory;
+pub use settings::GameSettings;
+pub use types::*;
+pub use ui::ChessApp;
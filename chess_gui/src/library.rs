@@ -0,0 +1,270 @@
+//! 对局库模块
+//! 对局结束时把存档自动保存到对局库目录（默认 `~/.chess_gui/games`，可以在对局库窗口中修改），
+//! 每局一个文件，格式和存档相同。对局库窗口列出这些对局和 "Save Game" 的存档，
+//! 显示日期、结果和对手难度，可以载入、回放、导出和删除
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::savegame::{self, SavedGame};
+use crate::settings;
+use crate::types::*;
+
+/// 对局库的设置，保存在 `~/.chess_gui/library.cfg`，每行 `名称=值`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryConfig {
+    /// 自动保存的对局放在这个目录
+    pub dir: PathBuf,
+    /// 对局结束时是否自动保存
+    pub autosave: bool,
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            dir: settings::data_dir().join("games"),
+            autosave: true,
+        }
+    }
+}
+
+impl LibraryConfig {
+    pub fn to_text(&self) -> String {
+        format!("dir={}\nautosave={}\n", self.dir.display(), self.autosave)
+    }
+
+    /// 不认识的行被忽略，缺少的设置用默认值
+    pub fn from_text(text: &str) -> LibraryConfig {
+        let mut config = LibraryConfig::default();
+        for line in text.lines() {
+            match line.split_once('=') {
+                Some(("dir", dir)) if !dir.trim().is_empty() => {
+                    config.dir = PathBuf::from(dir.trim())
+                }
+                Some(("autosave", value)) => config.autosave = value.trim() != "false",
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// 没有设置文件时用默认设置
+    pub fn load(path: &Path) -> io::Result<LibraryConfig> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(LibraryConfig::from_text(&text)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(LibraryConfig::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+}
+
+/// 默认设置文件：`$HOME/.chess_gui/library.cfg`
+pub fn default_config_path() -> PathBuf {
+    settings::data_dir().join("library.cfg")
+}
+
+/// 对局库中的一局
+#[derive(Debug, Clone)]
+pub struct GameEntry {
+    pub path: PathBuf,
+    /// 文件的修改时间，也就是保存的时间
+    pub saved_at: SystemTime,
+    pub game: SavedGame,
+}
+
+impl GameEntry {
+    /// 保存日期，例如 `2026-10-16 14:05`（UTC）
+    pub fn date(&self) -> String {
+        format_date(self.saved_at)
+    }
+
+    /// 例如 `1-0 · White resigned`，还没下完时为 `In progress`
+    pub fn result(&self) -> String {
+        match (self.game.state, self.game.reason.is_empty()) {
+            (GameState::Playing, _) => "In progress".to_string(),
+            (state, true) => crate::pgn::result_text(state).to_string(),
+            (state, false) => format!("{} · {}", crate::pgn::result_text(state), self.game.reason),
+        }
+    }
+
+    /// 对手，例如 `AI (Hard), playing Black` 或 `Two players`
+    pub fn opponent(&self) -> String {
+        match self.game.mode {
+            GameMode::HumanVsAI => format!(
+                "AI ({}), playing {:?}",
+                self.game.difficulty.to_string(),
+                self.game.player
+            ),
+            GameMode::HumanVsHuman => "Two players".to_string(),
+        }
+    }
+
+    /// 是否是 "Save Game" 的存档而不是自动保存的对局
+    pub fn is_quick_save(&self) -> bool {
+        self.path == savegame::default_save_path()
+    }
+}
+
+/// 列出对局库目录中的对局和 `extra` 中的存档文件，最近保存的在前面；
+/// 读不出来的文件（例如别的 PGN）被跳过，目录不存在时为空
+pub fn list_games(dir: &Path, extra: &[PathBuf]) -> Vec<GameEntry> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pgn"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    for path in extra {
+        if path.exists() && !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+
+    let mut games: Vec<GameEntry> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let saved_at = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+            let game = SavedGame::load(&path).ok()?;
+            Some(GameEntry {
+                path,
+                saved_at,
+                game,
+            })
+        })
+        .collect();
+    games.sort_by_key(|entry| std::cmp::Reverse(entry.saved_at));
+    games
+}
+
+/// 把一局棋保存到对局库目录，文件名是保存时间，例如 `game-20261016-140502.pgn`；
+/// 同一秒内保存多局时加上序号
+pub fn autosave(game: &SavedGame, dir: &Path) -> io::Result<PathBuf> {
+    let stamp = civil_time(SystemTime::now())
+        .replace(['-', ':'], "")
+        .replace(' ', "-");
+    let mut path = dir.join(format!("game-{}.pgn", stamp));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("game-{}-{}.pgn", stamp, number));
+        number += 1;
+    }
+    game.save(&path)?;
+    Ok(path)
+}
+
+// 列表中显示到分钟
+fn format_date(time: SystemTime) -> String {
+    civil_time(time)[..16].to_string()
+}
+
+// UTC 时间 `YYYY-MM-DD HH:MM:SS`，按公历从 1970-01-01 起的天数推算日期
+fn civil_time(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Howard Hinnant 的 civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::notation::{self, MoveHistory};
+    use std::time::Duration;
+
+    fn finished_game() -> SavedGame {
+        // 1. f3 e5 2. g4 Qh4#
+        let mut history = MoveHistory::new();
+        let mut board = Board::new();
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let mv = notation::parse_uci_move(uci).unwrap();
+            history.record(&board, mv);
+            board.make_move(mv);
+        }
+        SavedGame {
+            history,
+            mode: GameMode::HumanVsAI,
+            player: Color::White,
+            difficulty: AIDifficulty::Easy,
+            variant: Default::default(),
+            flipped: false,
+            state: GameState::BlackWins,
+            reason: "Victory by Checkmate".to_string(),
+            clock: None,
+        }
+    }
+
+    #[test]
+    fn test_civil_time() {
+        assert_eq!(civil_time(UNIX_EPOCH), "1970-01-01 00:00:00");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723);
+        assert_eq!(civil_time(leap_day), "2000-02-29 01:02:03");
+        assert_eq!(format_date(leap_day), "2000-02-29 01:02");
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = LibraryConfig {
+            dir: PathBuf::from("/tmp/my games"),
+            autosave: false,
+        };
+        assert_eq!(LibraryConfig::from_text(&config.to_text()), config);
+        // 空的或不认识的行用默认值
+        assert_eq!(
+            LibraryConfig::from_text("dir=\nfoo=bar"),
+            LibraryConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_autosave_and_list() {
+        let dir = std::env::temp_dir().join(format!("chess_gui_library_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(list_games(&dir, &[]).is_empty());
+
+        let game = finished_game();
+        let first = autosave(&game, &dir).unwrap();
+        let second = autosave(&game, &dir).unwrap();
+        assert_ne!(first, second);
+        fs::write(dir.join("notes.pgn"), "1. e4 *").unwrap();
+
+        let games = list_games(&dir, &[dir.join("missing.pgn")]);
+        assert_eq!(games.len(), 2);
+        let entry = &games[0];
+        assert_eq!(entry.result(), "0-1 · Victory by Checkmate");
+        assert_eq!(entry.opponent(), "AI (Easy), playing White");
+        assert_eq!(entry.game.history.len(), 4);
+        assert!(!entry.is_quick_save());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,439 @@
+//! 将杀题模块
+//! 内置一组 N 步杀（mate-in-N）局面，另外把 `puzzle` 模块从对局中生成、保存在 `~/.chess_gui/puzzles.tsv`
+//! 的练习题一起列出来做。玩家走解法中自己一方的走法，对方的应着按解法自动走出；将杀题的最后一步只要将死就算对。
+//! 每次做题的结果（解出或失败）追加到 `~/.chess_gui/puzzle_stats.tsv`
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::board::Board;
+use crate::notation;
+use crate::puzzle::{self, Puzzle};
+use crate::settings;
+use crate::types::*;
+
+/// 内置题目：名称、FEN 和解法（坐标记谱，双方交替）
+const BUILTIN: [(&str, &str, &str); 7] = [
+    (
+        "Scholar's mate",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 0 4",
+        "h5f7",
+    ),
+    (
+        "Fool's mate",
+        "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2",
+        "d8h4",
+    ),
+    ("Back rank", "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", "a1a8"),
+    (
+        "Légal's mate",
+        "rn1qkbnr/ppp2p1p/3p2p1/4N3/2B1P3/2N5/PPPP1PPP/R1BbK2R w KQkq - 0 6",
+        "c4f7 e8e7 c3d5",
+    ),
+    (
+        "Rook roller",
+        "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1",
+        "a2a7 h8g8 b1b8",
+    ),
+    (
+        "King and rook",
+        "k7/8/2K5/8/8/8/8/7R w - - 0 1",
+        "c6b6 a8b8 h1h8",
+    ),
+    (
+        "Corner squeeze",
+        "7r/8/8/8/8/2k5/8/K7 b - - 0 1",
+        "c3b3 a1b1 h8h1",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+/// 一道题：轮到走棋的一方按 `solution` 走；将杀题的最后一步将死对方
+pub struct MatePuzzle {
+    pub name: String,
+    pub fen: String,
+    /// 双方交替的解法，长度是 2N - 1
+    pub solution: Vec<Move>,
+    /// 解法以将死结束；从对局生成的练习题通常不是
+    pub mate: bool,
+}
+
+impl MatePuzzle {
+    /// 内置的将杀题，解法不合法或者最后一步不是将死时返回 `None`
+    pub fn new(name: &str, fen: &str, solution: &str) -> Option<MatePuzzle> {
+        let solution = solution
+            .split_whitespace()
+            .map(notation::parse_uci_move)
+            .collect::<Option<Vec<_>>>()?;
+        let puzzle = MatePuzzle::checked(name.to_string(), fen.to_string(), solution)?;
+        puzzle.mate.then_some(puzzle)
+    }
+
+    /// 从对局生成的练习题：玩家走最佳变例中自己一方的走法，变例以对方的应着结尾时去掉这一步
+    pub fn from_puzzle(puzzle: &Puzzle) -> Option<MatePuzzle> {
+        let (board, _) = notation::parse_fen(&puzzle.fen)?;
+        let name = format!(
+            "From your games, instead of {}",
+            notation::move_to_san(&board, puzzle.played)
+        );
+        let mut solution = puzzle.best_line.clone();
+        if solution.len().is_multiple_of(2) {
+            solution.pop();
+        }
+        MatePuzzle::checked(name, puzzle.fen.clone(), solution)
+    }
+
+    // 解法的每一步都合法并且长度是奇数时返回题目，同时记下最后一步是否将死
+    fn checked(name: String, fen: String, solution: Vec<Move>) -> Option<MatePuzzle> {
+        let (mut board, mut color) = notation::parse_fen(&fen)?;
+        if solution.len().is_multiple_of(2) {
+            return None;
+        }
+        for &mv in &solution {
+            if !board.generate_moves(color).contains(&mv) {
+                return None;
+            }
+            board.make_move(mv);
+            color = color.opposite();
+        }
+        Some(MatePuzzle {
+            name,
+            fen,
+            solution,
+            mate: is_checkmate(&board, color),
+        })
+    }
+
+    /// 几步杀，也就是玩家要走几步
+    pub fn mate_in(&self) -> usize {
+        self.solution.len().div_ceil(2)
+    }
+
+    /// 题目的起始局面和先走的一方
+    pub fn position(&self) -> Option<(Board, Color)> {
+        notation::parse_fen(&self.fen)
+    }
+
+    /// 例如 `Mate in 2 · White to move`，不是将杀题时是 `Best move · White to move`
+    pub fn title(&self) -> String {
+        let side = self.position().map_or(Color::White, |(_, side)| side);
+        if self.mate {
+            format!("Mate in {} · {:?} to move", self.mate_in(), side)
+        } else {
+            format!("Best move · {:?} to move", side)
+        }
+    }
+
+    /// 解法的 SAN，例如 `["Bxf7+", "Ke7", "Nd5#"]`
+    pub fn solution_san(&self) -> Vec<String> {
+        let Some((mut board, _)) = self.position() else {
+            return Vec::new();
+        };
+        self.solution
+            .iter()
+            .map(|&mv| {
+                let san = notation::move_to_san(&board, mv);
+                board.make_move(mv);
+                san
+            })
+            .collect()
+    }
+}
+
+/// 内置的将杀题
+pub fn builtin_puzzles() -> Vec<MatePuzzle> {
+    BUILTIN
+        .iter()
+        .filter_map(|&(name, fen, solution)| MatePuzzle::new(name, fen, solution))
+        .collect()
+}
+
+/// 内置的将杀题加上练习题文件（见 `puzzle::default_puzzle_path`）中从对局生成的题目；
+/// 还没有练习题文件时只有内置题目
+pub fn load_puzzles(path: &Path) -> io::Result<Vec<MatePuzzle>> {
+    let mut puzzles = builtin_puzzles();
+    match puzzle::load_puzzles(path) {
+        Ok(generated) => puzzles.extend(generated.iter().filter_map(MatePuzzle::from_puzzle)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    Ok(puzzles)
+}
+
+fn is_checkmate(board: &Board, color: Color) -> bool {
+    !board.has_legal_move(color) && board.is_in_check(color)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 玩家走一步之后的结果
+pub enum Attempt {
+    /// 走对了，接着由防守一方走出应着
+    Correct(Move),
+    /// 走对了最后一步，题目解出
+    Solved,
+    /// 走错了，这道题算失败
+    Wrong,
+}
+
+/// 正在做的一道题
+#[derive(Debug, Clone)]
+pub struct PuzzleRun {
+    pub puzzle: MatePuzzle,
+    /// 下一步在解法中的位置
+    ply: usize,
+    /// 走错过一次之后即使再做对也不算解出
+    pub failed: bool,
+}
+
+impl PuzzleRun {
+    pub fn new(puzzle: MatePuzzle) -> Self {
+        Self {
+            puzzle,
+            ply: 0,
+            failed: false,
+        }
+    }
+
+    /// 检查玩家在 `board` 上走的 `mv`。和解法相同，或者最后一步走了别的将死走法都算对；
+    /// 走对时前进到下一步，返回的应着需要调用方走出
+    pub fn try_move(&mut self, board: &Board, mv: Move) -> Attempt {
+        let Some(&expected) = self.puzzle.solution.get(self.ply) else {
+            return Attempt::Wrong;
+        };
+        let last = self.ply + 1 == self.puzzle.solution.len();
+        let mates = || {
+            let mut after = board.clone();
+            let color = board
+                .get_piece(mv.from)
+                .map_or(Color::White, |piece| piece.color);
+            after.make_move(mv) && is_checkmate(&after, color.opposite())
+        };
+        if mv != expected && !(last && mates()) {
+            self.failed = true;
+            return Attempt::Wrong;
+        }
+        if last {
+            self.ply += 1;
+            return Attempt::Solved;
+        }
+        let reply = self.puzzle.solution[self.ply + 1];
+        self.ply += 2;
+        Attempt::Correct(reply)
+    }
+
+    /// 解法中玩家下一步应该走的棋，题目做完后为 `None`
+    pub fn next_move(&self) -> Option<Move> {
+        self.puzzle.solution.get(self.ply).copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.ply >= self.puzzle.solution.len()
+    }
+}
+
+/// 做题记录：每道题解出和失败的次数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PuzzleStats {
+    /// (FEN, 是否解出)，按做题的先后顺序
+    pub attempts: Vec<(String, bool)>,
+}
+
+impl PuzzleStats {
+    pub fn solved(&self) -> usize {
+        self.attempts.iter().filter(|(_, solved)| *solved).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.attempts.len() - self.solved()
+    }
+
+    /// 这道题是否解出过
+    pub fn is_solved(&self, fen: &str) -> bool {
+        self.attempts
+            .iter()
+            .any(|(attempt, solved)| *solved && attempt == fen)
+    }
+
+    /// 记下一次做题的结果并追加到记录文件
+    pub fn record(&mut self, path: &Path, fen: &str, solved: bool) -> io::Result<()> {
+        self.attempts.push((fen.to_string(), solved));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let result = if solved { "solved" } else { "failed" };
+        writeln!(file, "{}\t{}", fen, result)
+    }
+
+    /// 读取记录文件，格式不对的行会被忽略；没有记录文件时为空
+    pub fn load(path: &Path) -> io::Result<PuzzleStats> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let attempts = text
+            .lines()
+            .filter_map(|line| {
+                let (fen, result) = line.split_once('\t')?;
+                match result {
+                    "solved" => Some((fen.to_string(), true)),
+                    "failed" => Some((fen.to_string(), false)),
+                    _ => None,
+                }
+            })
+            .collect();
+        Ok(PuzzleStats { attempts })
+    }
+}
+
+/// 默认做题记录文件：`$HOME/.chess_gui/puzzle_stats.tsv`
+pub fn default_stats_path() -> PathBuf {
+    settings::data_dir().join("puzzle_stats.tsv")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uci(text: &str) -> Move {
+        notation::parse_uci_move(text).unwrap()
+    }
+
+    // `color` 先走，能否在 `moves` 步之内强制将死对方
+    fn forces_mate(board: &Board, color: Color, moves: usize) -> bool {
+        moves > 0
+            && board.generate_moves(color).iter().any(|&mv| {
+                let mut after = board.clone();
+                after.make_move(mv);
+                let opponent = color.opposite();
+                if is_checkmate(&after, opponent) {
+                    return true;
+                }
+                let replies = after.generate_moves(opponent);
+                !replies.is_empty()
+                    && replies.iter().all(|&reply| {
+                        let mut next = after.clone();
+                        next.make_move(reply);
+                        forces_mate(&next, color, moves - 1)
+                    })
+            })
+    }
+
+    #[test]
+    fn test_builtin_puzzles_are_forced_mates() {
+        let puzzles = builtin_puzzles();
+        assert_eq!(puzzles.len(), BUILTIN.len());
+        for puzzle in &puzzles {
+            let (board, color) = puzzle.position().unwrap();
+            assert!(
+                forces_mate(&board, color, puzzle.mate_in()),
+                "{} is not a forced mate",
+                puzzle.name
+            );
+        }
+        let legal = puzzles.iter().find(|p| p.name == "Légal's mate").unwrap();
+        assert_eq!(legal.title(), "Mate in 2 · White to move");
+        assert_eq!(legal.solution_san(), vec!["Bxf7+", "Ke7", "Nd5#"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_solutions() {
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+        assert!(MatePuzzle::new("Back rank", fen, "a1a8").is_some());
+        // 不将死、不合法、长度是偶数、不是坐标记谱
+        assert!(MatePuzzle::new("Back rank", fen, "a1a7").is_none());
+        assert!(MatePuzzle::new("Back rank", fen, "a1h8").is_none());
+        assert!(MatePuzzle::new("Back rank", fen, "a1a7 g8f8").is_none());
+        assert!(MatePuzzle::new("Back rank", fen, "Ra8#").is_none());
+    }
+
+    #[test]
+    fn test_load_includes_generated_puzzles() {
+        let generated = Puzzle {
+            fen: "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 3".to_string(),
+            best_line: vec![uci("g7g6"), uci("h5f3"), uci("g8f6")],
+            played: uci("g8f6"),
+            eval_loss: 1200,
+        };
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_mates_load_{}", std::process::id()))
+            .join("puzzles.tsv");
+        let _ = fs::remove_file(&path);
+        // 还没有练习题文件时只有内置题目
+        assert_eq!(load_puzzles(&path).unwrap(), builtin_puzzles());
+
+        puzzle::save_puzzles(&path, std::slice::from_ref(&generated)).unwrap();
+        let puzzles = load_puzzles(&path).unwrap();
+        assert_eq!(puzzles.len(), BUILTIN.len() + 1);
+        let from_game = puzzles.last().unwrap();
+        assert_eq!(from_game.name, "From your games, instead of Nf6");
+        assert_eq!(from_game.fen, generated.fen);
+        assert!(!from_game.mate);
+        assert_eq!(from_game.title(), "Best move · Black to move");
+        assert_eq!(from_game.solution_san(), vec!["g6", "Qf3", "Nf6"]);
+
+        // 变例以对方的应着结尾时去掉这一步
+        let short = Puzzle {
+            best_line: vec![uci("g7g6"), uci("h5f3")],
+            ..generated
+        };
+        assert_eq!(
+            MatePuzzle::from_puzzle(&short).unwrap().solution,
+            vec![uci("g7g6")]
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_run_checks_moves() {
+        let puzzle = builtin_puzzles()
+            .into_iter()
+            .find(|p| p.name == "King and rook")
+            .unwrap();
+        let (mut board, _) = puzzle.position().unwrap();
+        let mut run = PuzzleRun::new(puzzle.clone());
+
+        // 走错一步算失败，局面不变，还可以接着做
+        assert_eq!(run.try_move(&board, uci("h1h7")), Attempt::Wrong);
+        assert!(run.failed);
+        assert_eq!(
+            run.try_move(&board, uci("c6b6")),
+            Attempt::Correct(uci("a8b8"))
+        );
+        board.make_move(uci("c6b6"));
+        board.make_move(uci("a8b8"));
+        assert_eq!(run.next_move(), Some(uci("h1h8")));
+        assert_eq!(run.try_move(&board, uci("h1h8")), Attempt::Solved);
+        assert!(run.is_finished());
+
+        // 最后一步走别的将死也算对：两个车都能在底线将死
+        let puzzle =
+            MatePuzzle::new("Two rooks", "6k1/5ppp/8/8/8/8/5PPP/RR4K1 w - - 0 1", "a1a8").unwrap();
+        let (board, _) = puzzle.position().unwrap();
+        let mut run = PuzzleRun::new(puzzle);
+        assert_eq!(run.try_move(&board, uci("b1b7")), Attempt::Wrong);
+        assert_eq!(run.try_move(&board, uci("b1b8")), Attempt::Solved);
+    }
+
+    #[test]
+    fn test_stats_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_mates_{}", std::process::id()))
+            .join("puzzle_stats.tsv");
+        let _ = fs::remove_file(&path);
+        assert_eq!(PuzzleStats::load(&path).unwrap(), PuzzleStats::default());
+
+        let mut stats = PuzzleStats::default();
+        stats.record(&path, "fen a", false).unwrap();
+        stats.record(&path, "fen a", true).unwrap();
+        stats.record(&path, "fen b", false).unwrap();
+        let loaded = PuzzleStats::load(&path).unwrap();
+        assert_eq!(loaded, stats);
+        assert_eq!((loaded.solved(), loaded.failed()), (1, 2));
+        assert!(loaded.is_solved("fen a"));
+        assert!(!loaded.is_solved("fen b"));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,819 @@
+//! 棋谱记录模块
+//! 负责把走法转换为标准代数记谱法（SAN），并维护对局的走法历史
+
+use crate::board::Board;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 走完一步之后对方王的状态
+pub enum CheckStatus {
+    None,
+    Check,
+    Checkmate,
+}
+
+impl CheckStatus {
+    /// SAN 中对应的后缀
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            CheckStatus::None => "",
+            CheckStatus::Check => "+",
+            CheckStatus::Checkmate => "#",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 历史中的一步棋
+pub struct MoveRecord {
+    pub mv: Move,
+    pub color: Color,
+    pub san: String,
+    pub check: CheckStatus,
+    /// 注解符号（NAG）编号，例如 1 = `!`、2 = `?`
+    pub nags: Vec<u8>,
+    /// 这步棋后面的注释（PGN 中的 `{...}`）
+    pub comment: Option<String>,
+}
+
+impl MoveRecord {
+    /// 带注解符号的 SAN，例如 `Nf3!?`；没有对应符号的 NAG 写成 `$n`
+    pub fn annotated_san(&self) -> String {
+        let mut text = self.san.clone();
+        for &nag in &self.nags {
+            match nag_glyph(nag) {
+                Some(glyph) => text.push_str(glyph),
+                None => text.push_str(&format!(" ${}", nag)),
+            }
+        }
+        text
+    }
+}
+
+/// 常用 NAG 对应的符号
+pub fn nag_glyph(nag: u8) -> Option<&'static str> {
+    match nag {
+        1 => Some("!"),
+        2 => Some("?"),
+        3 => Some("!!"),
+        4 => Some("??"),
+        5 => Some("!?"),
+        6 => Some("?!"),
+        _ => None,
+    }
+}
+
+/// 符号对应的 NAG，`nag_glyph` 的反向
+pub fn glyph_nag(glyph: &str) -> Option<u8> {
+    (1..=6).find(|&nag| nag_glyph(nag) == Some(glyph))
+}
+
+/// 变着树中一个节点的编号
+pub type NodeId = usize;
+
+#[derive(Debug, Clone)]
+struct Node {
+    record: MoveRecord,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>, // 第一个是主线，其余是变着
+    ply: usize,            // 从 0 开始的半回合数
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 按 PGN 顺序遍历变着树时的一项
+pub enum LineItem {
+    /// 一步棋；`number` 是需要写在前面的回合数，例如 `3.` 或 `3...`
+    Move {
+        node: NodeId,
+        number: Option<String>,
+    },
+    /// 变着开始 `(`
+    StartVariation,
+    /// 变着结束 `)`
+    EndVariation,
+}
+
+#[derive(Debug, Clone, Default)]
+/// 对局的走法历史：一棵变着树加上一个当前位置。
+/// 退回到前面的局面再走另一步时，新走法作为变着加入，原来的走法保留
+pub struct MoveHistory {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,              // 第一步的各个走法
+    current: Option<NodeId>,         // 当前局面之前的最后一步，None 表示初始局面
+    initial_comment: Option<String>, // 第一步之前的注释
+    start: Option<Board>,            // 不是标准初始局面时的起始局面（让子棋、FEN），总是白方先走
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从 `start` 开始的空棋谱，白方先走
+    pub fn with_start(start: Board) -> Self {
+        Self {
+            start: Some(start),
+            ..Self::default()
+        }
+    }
+
+    /// 不是从标准初始局面开始时的起始局面
+    pub fn start(&self) -> Option<&Board> {
+        self.start.as_ref()
+    }
+
+    /// 第一步之前的局面，重放棋谱时从这里开始
+    pub fn start_board(&self) -> Board {
+        self.start.clone().unwrap_or_default()
+    }
+
+    /// 在执行走法之前调用：根据走棋前的棋盘生成 SAN，作为当前位置之后的一步记录，
+    /// 并把当前位置移到这一步。这一步已经在树里时只移动当前位置
+    pub fn record(&mut self, board: &Board, mv: Move) -> &MoveRecord {
+        if let Some(&existing) = self
+            .children(self.current)
+            .iter()
+            .find(|&&id| self.nodes[id].record.mv == mv)
+        {
+            self.current = Some(existing);
+            return &self.nodes[existing].record;
+        }
+
+        let color = board
+            .get_piece(mv.from)
+            .map(|piece| piece.color)
+            .unwrap_or(Color::White);
+        let check = check_status_after(board, mv);
+        let san = format!("{}{}", move_to_san_body(board, mv), check.suffix());
+
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            record: MoveRecord {
+                mv,
+                color,
+                san,
+                check,
+                nags: Vec::new(),
+                comment: None,
+            },
+            parent: self.current,
+            children: Vec::new(),
+            ply: self.len(),
+        });
+        match self.current {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.current = Some(id);
+        &self.nodes[id].record
+    }
+
+    /// 清空走法和注释，起始局面不变
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.roots.clear();
+        self.current = None;
+        self.initial_comment = None;
+    }
+
+    /// 当前局面之前的最后一步
+    pub fn current(&self) -> Option<NodeId> {
+        self.current
+    }
+
+    pub fn record_at(&self, node: NodeId) -> &MoveRecord {
+        &self.nodes[node].record
+    }
+
+    /// 某一步是第几个半回合（从 0 开始）
+    pub fn ply(&self, node: NodeId) -> usize {
+        self.nodes[node].ply
+    }
+
+    /// 跳到某一步之后的局面，`None` 为初始局面
+    pub fn go_to(&mut self, node: Option<NodeId>) {
+        self.current = node.filter(|&id| id < self.nodes.len());
+    }
+
+    /// 退回一步，已经在初始局面时返回 `false`
+    pub fn go_back(&mut self) -> bool {
+        match self.current {
+            Some(id) => {
+                self.current = self.nodes[id].parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 沿主线前进一步，后面没有走法时返回 `false`
+    pub fn go_forward(&mut self) -> bool {
+        match self.children(self.current).first() {
+            Some(&next) => {
+                self.current = Some(next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 当前位置后面是否还有走法
+    pub fn has_next(&self) -> bool {
+        !self.children(self.current).is_empty()
+    }
+
+    /// 某个节点是否在主线上（从第一步起每一步都是第一个子节点）
+    pub fn is_mainline(&self, node: NodeId) -> bool {
+        let mut id = node;
+        loop {
+            let siblings = self.children(self.nodes[id].parent);
+            if siblings.first() != Some(&id) {
+                return false;
+            }
+            match self.nodes[id].parent {
+                Some(parent) => id = parent,
+                None => return true,
+            }
+        }
+    }
+
+    fn children(&self, node: Option<NodeId>) -> &[NodeId] {
+        match node {
+            Some(id) => &self.nodes[id].children,
+            None => &self.roots,
+        }
+    }
+
+    /// 设置某一步的注释，空白注释等于删除
+    pub fn set_comment(&mut self, node: NodeId, comment: Option<String>) {
+        if let Some(node) = self.nodes.get_mut(node) {
+            node.record.comment = comment.filter(|text| !text.trim().is_empty());
+        }
+    }
+
+    /// 给某一步加上注解符号，已有的不重复添加
+    pub fn add_nag(&mut self, node: NodeId, nag: u8) {
+        if let Some(node) = self.nodes.get_mut(node)
+            && !node.record.nags.contains(&nag)
+        {
+            node.record.nags.push(nag);
+        }
+    }
+
+    /// 有这个注解符号时去掉，没有时加上
+    pub fn toggle_nag(&mut self, node: NodeId, nag: u8) {
+        if let Some(node) = self.nodes.get_mut(node) {
+            let nags = &mut node.record.nags;
+            match nags.iter().position(|&n| n == nag) {
+                Some(index) => {
+                    nags.remove(index);
+                }
+                None => nags.push(nag),
+            }
+        }
+    }
+
+    pub fn initial_comment(&self) -> Option<&str> {
+        self.initial_comment.as_deref()
+    }
+
+    pub fn set_initial_comment(&mut self, comment: Option<String>) {
+        self.initial_comment = comment.filter(|text| !text.trim().is_empty());
+    }
+
+    /// 从第一步到当前位置的走法，也就是走到当前局面的那条线
+    pub fn records(&self) -> Vec<&MoveRecord> {
+        let mut line = Vec::new();
+        let mut node = self.current;
+        while let Some(id) = node {
+            line.push(&self.nodes[id].record);
+            node = self.nodes[id].parent;
+        }
+        line.reverse();
+        line
+    }
+
+    pub fn last(&self) -> Option<&MoveRecord> {
+        self.current.map(|id| &self.nodes[id].record)
+    }
+
+    /// 当前局面之前走了多少步
+    pub fn len(&self) -> usize {
+        self.current.map_or(0, |id| self.nodes[id].ply + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// 按回合分组当前这条线，例如 `["1. e4 e5", "2. Nf3"]`
+    pub fn numbered_lines(&self) -> Vec<String> {
+        self.records()
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let sans: Vec<&str> = pair.iter().map(|r| r.san.as_str()).collect();
+                format!("{}. {}", i + 1, sans.join(" "))
+            })
+            .collect()
+    }
+
+    /// 按 PGN 的顺序遍历整棵树：主线上每一步之后，紧接着写出这一步的其他选择（变着），
+    /// 再继续主线。白方的走法总是带回合数；黑方的走法在变着开头、注释或变着之后带 `n...`
+    pub fn walk(&self) -> Vec<LineItem> {
+        let mut items = Vec::new();
+        self.walk_line(&self.roots, true, &mut items);
+        items
+    }
+
+    fn walk_line(&self, first: &[NodeId], mut needs_number: bool, items: &mut Vec<LineItem>) {
+        let mut choices = first;
+        while let Some((&main, alternatives)) = choices.split_first() {
+            self.push_move(main, needs_number, items);
+            needs_number = self.nodes[main].record.comment.is_some();
+
+            for &alternative in alternatives {
+                items.push(LineItem::StartVariation);
+                self.push_move(alternative, true, items);
+                let after_comment = self.nodes[alternative].record.comment.is_some();
+                self.walk_line(&self.nodes[alternative].children, after_comment, items);
+                items.push(LineItem::EndVariation);
+                needs_number = true;
+            }
+
+            choices = &self.nodes[main].children;
+        }
+    }
+
+    fn push_move(&self, node: NodeId, needs_number: bool, items: &mut Vec<LineItem>) {
+        let Node { record, ply, .. } = &self.nodes[node];
+        let number = match record.color {
+            Color::White => Some(format!("{}.", ply / 2 + 1)),
+            Color::Black if needs_number => Some(format!("{}...", ply / 2 + 1)),
+            Color::Black => None,
+        };
+        items.push(LineItem::Move { node, number });
+    }
+}
+
+/// 把棋盘坐标转换成格子名，例如 `(6, 4)` -> `"e2"`
+pub fn square_name(pos: (usize, usize)) -> String {
+    format!("{}{}", (b'a' + pos.1 as u8) as char, 8 - pos.0)
+}
+
+/// 生成一步棋的完整 SAN（包括 `+` / `#` 后缀），`board` 为走棋前的局面
+pub fn move_to_san(board: &Board, mv: Move) -> String {
+    format!(
+        "{}{}",
+        move_to_san_body(board, mv),
+        check_status_after(board, mv).suffix()
+    )
+}
+
+/// 走完这步棋后，对方是否被将军或将死
+pub fn check_status_after(board: &Board, mv: Move) -> CheckStatus {
+    let color = match board.get_piece(mv.from) {
+        Some(piece) => piece.color,
+        None => return CheckStatus::None,
+    };
+    let opponent = color.opposite();
+
+    let mut after = board.clone();
+    after.make_move(mv);
+
+    if !after.is_in_check(opponent) {
+        CheckStatus::None
+    } else if !after.has_legal_move(opponent) {
+        CheckStatus::Checkmate
+    } else {
+        CheckStatus::Check
+    }
+}
+
+fn piece_letter(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Pawn => "",
+        PieceType::Knight => "N",
+        PieceType::Bishop => "B",
+        PieceType::Rook => "R",
+        PieceType::Queen => "Q",
+        PieceType::King => "K",
+    }
+}
+
+/// 坐标记谱（UCI 格式），例如 `e2e4`、`e7e8q`
+pub fn move_to_uci(mv: Move) -> String {
+    let promotion = match mv.promotion {
+        Some(PieceType::Queen) => "q",
+        Some(PieceType::Rook) => "r",
+        Some(PieceType::Bishop) => "b",
+        Some(PieceType::Knight) => "n",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        square_name(mv.from),
+        square_name(mv.to),
+        promotion
+    )
+}
+
+/// 解析坐标记谱，格式不对时返回 `None`（不检查走法是否合法）
+pub fn parse_uci_move(text: &str) -> Option<Move> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 4 && bytes.len() != 5 {
+        return None;
+    }
+
+    let promotion = match bytes.get(4) {
+        None => None,
+        Some(b'q') => Some(PieceType::Queen),
+        Some(b'r') => Some(PieceType::Rook),
+        Some(b'b') => Some(PieceType::Bishop),
+        Some(b'n') => Some(PieceType::Knight),
+        Some(_) => return None,
+    };
+
+    Some(Move {
+        from: parse_square(&bytes[0..2])?,
+        to: parse_square(&bytes[2..4])?,
+        promotion,
+    })
+}
+
+fn parse_square(bytes: &[u8]) -> Option<(usize, usize)> {
+    match bytes {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+            Some(((b'8' - rank) as usize, (file - b'a') as usize))
+        }
+        _ => None,
+    }
+}
+
+/// 生成局面的 FEN。棋盘不记录五十步计数，半回合数总是写 0
+pub fn to_fen(board: &Board, side_to_move: Color, fullmove_number: usize) -> String {
+    let mut placement = String::new();
+    for row in 0..8 {
+        let mut empty = 0;
+        for col in 0..8 {
+            match board.get_piece((row, col)) {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(fen_letter(piece));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if row < 7 {
+            placement.push('/');
+        }
+    }
+
+    let side = match side_to_move {
+        Color::White => "w",
+        Color::Black => "b",
+    };
+
+    let mut castling = String::new();
+    if !board.white_king_moved && !board.white_rook_h_moved {
+        castling.push('K');
+    }
+    if !board.white_king_moved && !board.white_rook_a_moved {
+        castling.push('Q');
+    }
+    if !board.black_king_moved && !board.black_rook_h_moved {
+        castling.push('k');
+    }
+    if !board.black_king_moved && !board.black_rook_a_moved {
+        castling.push('q');
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    let en_passant = board
+        .en_passant_target
+        .map(square_name)
+        .unwrap_or_else(|| "-".to_string());
+
+    format!(
+        "{} {} {} {} 0 {}",
+        placement, side, castling, en_passant, fullmove_number
+    )
+}
+
+/// 解析 FEN，返回棋盘和轮到走棋的一方；半回合数和回合数被忽略
+pub fn parse_fen(fen: &str) -> Option<(Board, Color)> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next()?;
+    let side = fields.next()?;
+    let castling = fields.next().unwrap_or("-");
+    let en_passant = fields.next().unwrap_or("-");
+
+    let mut board = Board::new();
+    board.clear();
+
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return None;
+    }
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut col = 0;
+        for ch in rank.chars() {
+            if let Some(skip) = ch.to_digit(10) {
+                col += skip as usize;
+            } else {
+                if col >= 8 {
+                    return None;
+                }
+                let piece = piece_from_fen(ch)?;
+                if piece.piece_type == PieceType::King {
+                    match piece.color {
+                        Color::White => board.white_king_pos = (row, col),
+                        Color::Black => board.black_king_pos = (row, col),
+                    }
+                }
+                board.set_piece((row, col), Some(piece));
+                col += 1;
+            }
+        }
+        if col != 8 {
+            return None;
+        }
+    }
+
+    let side_to_move = match side {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return None,
+    };
+
+    // 棋盘用“是否移动过”记录易位权，没有某侧易位权就当作对应的车动过
+    board.white_king_moved = !castling.contains(['K', 'Q']);
+    board.white_rook_h_moved = !castling.contains('K');
+    board.white_rook_a_moved = !castling.contains('Q');
+    board.black_king_moved = !castling.contains(['k', 'q']);
+    board.black_rook_h_moved = !castling.contains('k');
+    board.black_rook_a_moved = !castling.contains('q');
+
+    board.en_passant_target = match en_passant {
+        "-" => None,
+        square => Some(parse_square(square.as_bytes())?),
+    };
+
+    Some((board, side_to_move))
+}
+
+fn fen_letter(piece: Piece) -> char {
+    let letter = match piece.piece_type {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    match piece.color {
+        Color::White => letter.to_ascii_uppercase(),
+        Color::Black => letter,
+    }
+}
+
+fn piece_from_fen(ch: char) -> Option<Piece> {
+    let piece_type = match ch.to_ascii_lowercase() {
+        'p' => PieceType::Pawn,
+        'n' => PieceType::Knight,
+        'b' => PieceType::Bishop,
+        'r' => PieceType::Rook,
+        'q' => PieceType::Queen,
+        'k' => PieceType::King,
+        _ => return None,
+    };
+    let color = if ch.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    Some(Piece::new(piece_type, color))
+}
+
+// 不含将军后缀的 SAN
+fn move_to_san_body(board: &Board, mv: Move) -> String {
+    let piece = match board.get_piece(mv.from) {
+        Some(piece) => piece,
+        None => return format!("{}{}", square_name(mv.from), square_name(mv.to)),
+    };
+
+    // 王车易位
+    if piece.piece_type == PieceType::King {
+        let col_diff = mv.to.1 as i32 - mv.from.1 as i32;
+        if col_diff == 2 {
+            return "O-O".to_string();
+        } else if col_diff == -2 {
+            return "O-O-O".to_string();
+        }
+    }
+
+    let is_capture = board.get_piece(mv.to).is_some()
+        || (piece.piece_type == PieceType::Pawn && mv.from.1 != mv.to.1);
+
+    let mut san = String::new();
+
+    if piece.piece_type == PieceType::Pawn {
+        if is_capture {
+            san.push((b'a' + mv.from.1 as u8) as char);
+        }
+    } else {
+        san.push_str(piece_letter(piece.piece_type));
+        san.push_str(&disambiguation(board, mv, piece));
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&square_name(mv.to));
+
+    if let Some(promotion) = mv.promotion {
+        san.push('=');
+        san.push_str(piece_letter(promotion));
+    }
+
+    san
+}
+
+// 同类棋子可以走到同一格时，需要补充起始列/行
+fn disambiguation(board: &Board, mv: Move, piece: Piece) -> String {
+    let rivals: Vec<(usize, usize)> = board
+        .legal_moves_iter(piece.color)
+        .filter(|other| {
+            other.to == mv.to && other.from != mv.from && board.get_piece(other.from) == Some(piece)
+        })
+        .map(|other| other.from)
+        .collect();
+
+    if rivals.is_empty() {
+        return String::new();
+    }
+
+    let file = (b'a' + mv.from.1 as u8) as char;
+    let rank = (8 - mv.from.0).to_string();
+
+    if rivals.iter().all(|from| from.1 != mv.from.1) {
+        file.to_string()
+    } else if rivals.iter().all(|from| from.0 != mv.from.0) {
+        rank
+    } else {
+        format!("{}{}", file, rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from: (usize, usize), to: (usize, usize)) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    fn empty_board() -> Board {
+        let mut board = Board::new();
+        board.clear();
+        board
+    }
+
+    #[test]
+    fn test_simple_moves() {
+        let board = Board::new();
+        assert_eq!(move_to_san(&board, mv((6, 4), (4, 4))), "e4");
+        assert_eq!(move_to_san(&board, mv((7, 6), (5, 5))), "Nf3");
+    }
+
+    #[test]
+    fn test_knight_disambiguation() {
+        let mut board = empty_board();
+        board.set_piece((7, 4), Some(Piece::new(PieceType::King, Color::White)));
+        board.set_piece((0, 4), Some(Piece::new(PieceType::King, Color::Black)));
+        board.white_king_pos = (7, 4);
+        board.black_king_pos = (0, 4);
+        board.set_piece((7, 1), Some(Piece::new(PieceType::Knight, Color::White)));
+        board.set_piece((7, 5), Some(Piece::new(PieceType::Knight, Color::White)));
+
+        // Nb1 和 Nf1 都能到 d2
+        assert_eq!(move_to_san(&board, mv((7, 1), (6, 3))), "Nbd2");
+    }
+
+    #[test]
+    fn test_check_and_checkmate_suffix() {
+        let mut board = empty_board();
+        board.set_piece((0, 0), Some(Piece::new(PieceType::King, Color::Black)));
+        board.set_piece((2, 1), Some(Piece::new(PieceType::King, Color::White)));
+        board.set_piece((3, 7), Some(Piece::new(PieceType::Queen, Color::White)));
+        board.black_king_pos = (0, 0);
+        board.white_king_pos = (2, 1);
+
+        // Qh7 不是将军
+        assert_eq!(move_to_san(&board, mv((3, 7), (1, 7))), "Qh7");
+        // Qh8 直接将死，Qa5 只是将军（王可以逃到 b8）
+        assert_eq!(move_to_san(&board, mv((3, 7), (0, 7))), "Qh8#");
+        assert_eq!(move_to_san(&board, mv((3, 7), (3, 0))), "Qa5+");
+    }
+
+    #[test]
+    fn test_history_numbering() {
+        let mut board = Board::new();
+        let mut history = MoveHistory::new();
+        for m in [mv((6, 4), (4, 4)), mv((1, 4), (3, 4)), mv((7, 6), (5, 5))] {
+            history.record(&board, m);
+            board.make_move(m);
+        }
+        assert_eq!(history.numbered_lines(), vec!["1. e4 e5", "2. Nf3"]);
+    }
+
+    #[test]
+    fn test_history_branches_instead_of_truncating() {
+        let board = Board::new();
+        let mut history = MoveHistory::new();
+        let e4 = mv((6, 4), (4, 4));
+        let mut after_e4 = board.clone();
+        after_e4.make_move(e4);
+        history.record(&board, e4);
+        history.record(&after_e4, mv((1, 4), (3, 4)));
+
+        // 退回一步改走 c5，e5 作为主线保留
+        assert!(history.go_back());
+        history.record(&after_e4, mv((1, 2), (3, 2)));
+        assert_eq!(history.numbered_lines(), vec!["1. e4 c5"]);
+        assert!(!history.has_next());
+
+        let sans: Vec<String> = history
+            .walk()
+            .iter()
+            .map(|item| match item {
+                LineItem::Move { node, number } => {
+                    let san = &history.record_at(*node).san;
+                    number
+                        .as_ref()
+                        .map_or(san.clone(), |n| format!("{} {}", n, san))
+                }
+                LineItem::StartVariation => "(".to_string(),
+                LineItem::EndVariation => ")".to_string(),
+            })
+            .collect();
+        assert_eq!(sans, vec!["1. e4", "e5", "(", "1... c5", ")"]);
+
+        // 再走一遍 e5 只是回到原来的节点
+        history.go_back();
+        history.record(&after_e4, mv((1, 4), (3, 4)));
+        assert_eq!(history.walk().len(), 5);
+        assert!(history.is_mainline(history.current().unwrap()));
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let mut board = Board::new();
+        assert_eq!(
+            to_fen(&board, Color::White, 1),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        board.make_move(mv((6, 4), (4, 4)));
+        let fen = to_fen(&board, Color::Black, 1);
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+
+        let (parsed, side) = parse_fen(&fen).unwrap();
+        assert_eq!(side, Color::Black);
+        assert_eq!(parsed.squares(), board.squares());
+        assert_eq!(parsed.en_passant_target, Some((5, 4)));
+        assert_eq!(to_fen(&parsed, side, 1), fen);
+
+        assert!(parse_fen("8/8/8 w - - 0 1").is_none());
+        assert!(parse_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_none());
+    }
+
+    #[test]
+    fn test_uci_moves() {
+        let promotion = Move {
+            from: (1, 4),
+            to: (0, 4),
+            promotion: Some(PieceType::Knight),
+        };
+        assert_eq!(move_to_uci(promotion), "e7e8n");
+        assert_eq!(parse_uci_move("e7e8n"), Some(promotion));
+        assert_eq!(parse_uci_move("e2e4"), Some(mv((6, 4), (4, 4))));
+        assert_eq!(parse_uci_move("e2e9"), None);
+        assert_eq!(parse_uci_move("e2e4x"), None);
+    }
+}
@@ -0,0 +1,308 @@
+//! 开局识别模块
+//! 内置一张小的 ECO 开局表，按走法序列（UCI）匹配当前对局所在的开局
+
+use std::fmt;
+
+use crate::notation::{self, MoveHistory};
+use crate::types::Move;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 开局表中的一项
+pub struct Opening {
+    /// ECO 编号，例如 `B90`
+    pub eco: &'static str,
+    pub name: &'static str,
+    /// 从初始局面开始的 UCI 走法，用空格分隔
+    moves: &'static str,
+}
+
+impl Opening {
+    /// 这个开局包含的半回合数
+    pub fn plies(&self) -> usize {
+        self.moves.split_whitespace().count()
+    }
+
+    /// 开局的走法序列
+    pub fn moves(&self) -> Vec<Move> {
+        self.moves
+            .split_whitespace()
+            .filter_map(notation::parse_uci_move)
+            .collect()
+    }
+}
+
+impl fmt::Display for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.eco, self.name)
+    }
+}
+
+const fn opening(eco: &'static str, name: &'static str, moves: &'static str) -> Opening {
+    Opening { eco, name, moves }
+}
+
+/// 常见开局，只按走法顺序匹配，不识别移形换位
+const OPENINGS: &[Opening] = &[
+    // 侧翼开局
+    opening("A00", "Polish Opening", "b2b4"),
+    opening("A01", "Nimzo-Larsen Attack", "b2b3"),
+    opening("A02", "Bird's Opening", "f2f4"),
+    opening("A04", "Zukertort Opening", "g1f3"),
+    opening("A09", "Réti Opening", "g1f3 d7d5 c2c4"),
+    opening("A10", "English Opening", "c2c4"),
+    opening("A20", "English Opening: King's English", "c2c4 e7e5"),
+    // 后兵开局
+    opening("A40", "Queen's Pawn Game", "d2d4"),
+    opening("A45", "Indian Defense", "d2d4 g8f6"),
+    opening("A56", "Benoni Defense", "d2d4 g8f6 c2c4 c7c5"),
+    opening("A57", "Benko Gambit", "d2d4 g8f6 c2c4 c7c5 d4d5 b7b5"),
+    opening("A80", "Dutch Defense", "d2d4 f7f5"),
+    opening("D00", "Queen's Pawn Game", "d2d4 d7d5"),
+    opening("D00", "Queen's Pawn Game: London System", "d2d4 d7d5 c1f4"),
+    opening("D06", "Queen's Gambit", "d2d4 d7d5 c2c4"),
+    opening(
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d2d4 d7d5 c2c4 b8c6",
+    ),
+    opening(
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d2d4 d7d5 c2c4 e7e5",
+    ),
+    opening("D10", "Slav Defense", "d2d4 d7d5 c2c4 c7c6"),
+    opening("D20", "Queen's Gambit Accepted", "d2d4 d7d5 c2c4 d5c4"),
+    opening("D30", "Queen's Gambit Declined", "d2d4 d7d5 c2c4 e7e6"),
+    opening("D80", "Grünfeld Defense", "d2d4 g8f6 c2c4 g7g6 b1c3 d7d5"),
+    opening("E00", "Catalan Opening", "d2d4 g8f6 c2c4 e7e6 g2g3"),
+    opening(
+        "E11",
+        "Bogo-Indian Defense",
+        "d2d4 g8f6 c2c4 e7e6 g1f3 f8b4",
+    ),
+    opening(
+        "E12",
+        "Queen's Indian Defense",
+        "d2d4 g8f6 c2c4 e7e6 g1f3 b7b6",
+    ),
+    opening(
+        "E20",
+        "Nimzo-Indian Defense",
+        "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4",
+    ),
+    opening("E60", "King's Indian Defense", "d2d4 g8f6 c2c4 g7g6"),
+    // 王兵开局
+    opening("B00", "King's Pawn Game", "e2e4"),
+    opening("B01", "Scandinavian Defense", "e2e4 d7d5"),
+    opening("B02", "Alekhine's Defense", "e2e4 g8f6"),
+    opening("B06", "Modern Defense", "e2e4 g7g6"),
+    opening("B07", "Pirc Defense", "e2e4 d7d6 d2d4 g8f6"),
+    opening("B10", "Caro-Kann Defense", "e2e4 c7c6"),
+    opening(
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e2e4 c7c6 d2d4 d7d5 e4e5",
+    ),
+    opening(
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e2e4 c7c6 d2d4 d7d5 e4d5 c6d5",
+    ),
+    opening("B20", "Sicilian Defense", "e2e4 c7c5"),
+    opening(
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e2e4 c7c5 d2d4 c5d4 c2c3",
+    ),
+    opening(
+        "B22",
+        "Sicilian Defense: Alapin Variation",
+        "e2e4 c7c5 c2c3",
+    ),
+    opening("B23", "Sicilian Defense: Closed", "e2e4 c7c5 b1c3"),
+    opening(
+        "B30",
+        "Sicilian Defense: Old Sicilian",
+        "e2e4 c7c5 g1f3 b8c6",
+    ),
+    opening(
+        "B33",
+        "Sicilian Defense: Sveshnikov Variation",
+        "e2e4 c7c5 g1f3 b8c6 d2d4 c5d4 f3d4 g8f6 b1c3 e7e5",
+    ),
+    opening(
+        "B40",
+        "Sicilian Defense: French Variation",
+        "e2e4 c7c5 g1f3 e7e6",
+    ),
+    opening("B50", "Sicilian Defense", "e2e4 c7c5 g1f3 d7d6"),
+    opening(
+        "B54",
+        "Sicilian Defense: Open",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4",
+    ),
+    opening(
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 g7g6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6",
+    ),
+    opening("C00", "French Defense", "e2e4 e7e6"),
+    opening(
+        "C01",
+        "French Defense: Exchange Variation",
+        "e2e4 e7e6 d2d4 d7d5 e4d5 e6d5",
+    ),
+    opening(
+        "C02",
+        "French Defense: Advance Variation",
+        "e2e4 e7e6 d2d4 d7d5 e4e5",
+    ),
+    opening(
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e2e4 e7e6 d2d4 d7d5 b1d2",
+    ),
+    opening("C10", "French Defense", "e2e4 e7e6 d2d4 d7d5 b1c3"),
+    opening(
+        "C15",
+        "French Defense: Winawer Variation",
+        "e2e4 e7e6 d2d4 d7d5 b1c3 f8b4",
+    ),
+    opening("C20", "King's Pawn Game", "e2e4 e7e5"),
+    opening("C23", "Bishop's Opening", "e2e4 e7e5 f1c4"),
+    opening("C25", "Vienna Game", "e2e4 e7e5 b1c3"),
+    opening("C30", "King's Gambit", "e2e4 e7e5 f2f4"),
+    opening("C33", "King's Gambit Accepted", "e2e4 e7e5 f2f4 e5f4"),
+    opening("C40", "King's Knight Opening", "e2e4 e7e5 g1f3"),
+    opening("C41", "Philidor Defense", "e2e4 e7e5 g1f3 d7d6"),
+    opening("C42", "Petrov's Defense", "e2e4 e7e5 g1f3 g8f6"),
+    opening("C44", "King's Knight Opening", "e2e4 e7e5 g1f3 b8c6"),
+    opening("C44", "Scotch Game", "e2e4 e7e5 g1f3 b8c6 d2d4"),
+    opening("C47", "Four Knights Game", "e2e4 e7e5 g1f3 b8c6 b1c3 g8f6"),
+    opening("C50", "Italian Game", "e2e4 e7e5 g1f3 b8c6 f1c4"),
+    opening(
+        "C50",
+        "Italian Game: Giuoco Piano",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5",
+    ),
+    opening(
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 b2b4",
+    ),
+    opening(
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6",
+    ),
+    opening("C60", "Ruy Lopez", "e2e4 e7e5 g1f3 b8c6 f1b5"),
+    opening(
+        "C65",
+        "Ruy Lopez: Berlin Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 g8f6",
+    ),
+    opening(
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5c6",
+    ),
+    opening(
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6",
+    ),
+];
+
+/// 开局表中的所有开局
+pub fn openings() -> &'static [Opening] {
+    OPENINGS
+}
+
+/// 识别走法序列所在的开局：取开局表中最长的、是对局前缀的那一项。
+/// 走出开局表之后仍然返回最后匹配到的开局
+pub fn classify_moves(moves: &[Move]) -> Option<&'static Opening> {
+    let played: Vec<String> = moves.iter().map(|&mv| notation::move_to_uci(mv)).collect();
+
+    OPENINGS
+        .iter()
+        .filter(|opening| {
+            let line: Vec<&str> = opening.moves.split_whitespace().collect();
+            line.len() <= played.len() && line.iter().zip(&played).all(|(a, b)| *a == b)
+        })
+        .max_by_key(|opening| opening.plies())
+}
+
+/// 识别当前对局的开局，不是从标准初始局面开始的对局（例如让子棋）没有开局名
+pub fn classify(history: &MoveHistory) -> Option<&'static Opening> {
+    if history.start().is_some() {
+        return None;
+    }
+    let moves: Vec<Move> = history.records().iter().map(|record| record.mv).collect();
+    classify_moves(&moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_uci_move;
+
+    fn moves(line: &str) -> Vec<Move> {
+        line.split_whitespace()
+            .map(|text| parse_uci_move(text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_longest_match() {
+        assert_eq!(classify_moves(&[]), None);
+
+        let najdorf = moves("e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6");
+        let opening = classify_moves(&najdorf).unwrap();
+        assert_eq!(opening.eco, "B90");
+        assert_eq!(
+            opening.to_string(),
+            "B90 Sicilian Defense: Najdorf Variation"
+        );
+
+        // 还没走到 Najdorf 时显示更一般的名字
+        assert_eq!(classify_moves(&najdorf[..7]).unwrap().eco, "B54");
+        assert_eq!(classify_moves(&najdorf[..2]).unwrap().eco, "B20");
+    }
+
+    #[test]
+    fn test_classify_keeps_name_out_of_book() {
+        let game = moves("e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1");
+        assert_eq!(
+            classify_moves(&game).unwrap().name,
+            "Ruy Lopez: Morphy Defense"
+        );
+
+        // 第一步就不在表里
+        assert_eq!(classify_moves(&moves("h2h3")), None);
+    }
+
+    #[test]
+    fn test_opening_table_moves_are_legal() {
+        use crate::board::Board;
+        use crate::types::Color;
+
+        for opening in OPENINGS {
+            let mut board = Board::new();
+            let mut color = Color::White;
+            for mv in moves(opening.moves) {
+                assert!(
+                    board.legal_moves_iter(color).any(|legal| legal == mv),
+                    "{} has an illegal move",
+                    opening
+                );
+                board.make_move(mv);
+                color = color.opposite();
+            }
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! PGN 导入导出模块
+//! 读取 PGN 时保留注释 `{...}`、注解符号（`$1`、`!?` 等）和变着 `(...)`，导出时写回
+
+use std::fmt;
+
+use crate::board::Board;
+use crate::notation::{self, LineItem, MoveHistory};
+use crate::types::*;
+
+/// 从 PGN 读出的对局
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// 标签对，例如 `("White", "Carlsen")`，按出现顺序
+    pub tags: Vec<(String, String)>,
+    pub history: MoveHistory,
+    /// 走完主线后的局面
+    pub board: Board,
+    pub side_to_move: Color,
+    /// 棋谱末尾的结果：`1-0`、`0-1`、`1/2-1/2` 或 `*`
+    pub result: String,
+}
+
+impl PgnGame {
+    /// 按名字查标签
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 读取 PGN 时的错误
+pub enum PgnError {
+    /// `{` 或 `(` 没有闭合
+    Unterminated(char),
+    /// `FEN` 标签无法解析，或者轮到黑方先走
+    CustomStartPosition,
+    /// 第 `ply` 步（从 1 开始）在当前局面下不合法或者无法识别
+    IllegalMove { ply: usize, san: String },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Unterminated(open) => write!(f, "unterminated `{}`", open),
+            PgnError::CustomStartPosition => {
+                write!(
+                    f,
+                    "only FEN start positions with White to move are supported"
+                )
+            }
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal move {} at ply {}", san, ply),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Move(String),
+    StartVariation,
+    EndVariation,
+    Result(String),
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' => {
+                chars.next();
+                let tag: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let (name, value) = tag
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((tag.trim(), ""));
+                tokens.push(Token::Tag(
+                    name.to_string(),
+                    value.trim().trim_matches('"').replace("\\\"", "\""),
+                ));
+            }
+            '{' => {
+                chars.next();
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::Unterminated('{')),
+                    }
+                }
+                tokens.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<_>>().join(" "),
+                ));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().skip(1).take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::StartVariation);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::EndVariation);
+            }
+            '$' => {
+                chars.next();
+                let digits: String =
+                    std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect();
+                if let Ok(nag) = digits.parse() {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+            _ => {
+                let word: String = std::iter::from_fn(|| {
+                    chars.next_if(|&c| !c.is_whitespace() && !"[]{}();$".contains(c))
+                })
+                .collect();
+                if word.is_empty() {
+                    // 多余的 `]`
+                    chars.next();
+                    continue;
+                }
+                tokens.extend(word_tokens(&word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// 一个单词可能是结果、回合数（`12.`、`12...`）、带回合数的走法（`12...e5`），
+// 或者后面跟着注解符号的走法（`Nf3!?`）
+fn word_tokens(word: &str) -> Vec<Token> {
+    if RESULTS.contains(&word) {
+        return vec![Token::Result(word.to_string())];
+    }
+
+    let san = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = if san.len() < word.len() && san.starts_with('.') {
+        san.trim_start_matches('.')
+    } else {
+        word
+    };
+    if san.is_empty() {
+        return Vec::new();
+    }
+
+    let glyph_start = san.trim_end_matches(['!', '?']).len();
+    let (san, glyph) = san.split_at(glyph_start);
+    let mut tokens = vec![Token::Move(san.to_string())];
+    if let Some(nag) = notation::glyph_nag(glyph) {
+        tokens.push(Token::Nag(nag));
+    }
+    tokens
+}
+
+// 在合法走法中找 SAN 相同的一步；比较时忽略 `+`/`#`，兼容 `0-0` 写法
+fn find_move(board: &Board, color: Color, san: &str) -> Option<Move> {
+    let wanted = san.trim_end_matches(['+', '#']).replace('0', "O");
+    board
+        .legal_moves_iter(color)
+        .find(|&mv| notation::move_to_san(board, mv).trim_end_matches(['+', '#']) == wanted)
+}
+
+/// 读取一盘 PGN 棋谱（只读第一盘）。带 `FEN` 标签的棋谱（例如让子棋）从这个局面开始
+pub fn parse(text: &str) -> Result<PgnGame, PgnError> {
+    let mut tags = Vec::new();
+    let mut history = MoveHistory::new();
+    let mut board = Board::new();
+    let mut color = Color::White;
+    let mut result = "*".to_string();
+    // 每层变着开始前的位置，变着结束后回到那里接着读
+    let mut variations = Vec::new();
+
+    for token in tokenize(text)? {
+        let last = history.current();
+        match token {
+            Token::Tag(name, value) => {
+                if name == "FEN" {
+                    board = match notation::parse_fen(&value) {
+                        Some((start, Color::White)) if history.is_empty() => start,
+                        _ => return Err(PgnError::CustomStartPosition),
+                    };
+                    history = MoveHistory::with_start(board.clone());
+                }
+                tags.push((name, value));
+            }
+            Token::Comment(comment) => match last {
+                Some(node) => {
+                    // 一步后面有多段注释时合并
+                    let merged = match &history.record_at(node).comment {
+                        Some(previous) => format!("{} {}", previous, comment),
+                        None => comment,
+                    };
+                    history.set_comment(node, Some(merged));
+                }
+                None => history.set_initial_comment(Some(comment)),
+            },
+            Token::Nag(nag) => {
+                if let Some(node) = last {
+                    history.add_nag(node, nag);
+                }
+            }
+            // 变着是上一步的替代走法，从上一步之前的局面开始
+            Token::StartVariation => {
+                variations.push(last);
+                history.go_back();
+                (board, color) = position(&history);
+            }
+            Token::EndVariation => {
+                if let Some(resume) = variations.pop() {
+                    history.go_to(resume);
+                    (board, color) = position(&history);
+                }
+            }
+            Token::Move(san) => {
+                let mv = find_move(&board, color, &san).ok_or(PgnError::IllegalMove {
+                    ply: history.len() + 1,
+                    san,
+                })?;
+                history.record(&board, mv);
+                board.make_move(mv);
+                color = color.opposite();
+            }
+            Token::Result(text) => {
+                result = text;
+                break;
+            }
+        }
+    }
+    if !variations.is_empty() {
+        return Err(PgnError::Unterminated('('));
+    }
+
+    Ok(PgnGame {
+        tags,
+        history,
+        board,
+        side_to_move: color,
+        result,
+    })
+}
+
+/// 导出 PGN：七个必需标签加上带注释和注解符号的走法，每行不超过 80 个字符。
+/// 不是从标准初始局面开始的棋谱另外写上 `SetUp` 和 `FEN` 标签
+pub fn export(history: &MoveHistory, white: &str, black: &str, result: &str) -> String {
+    let mut tags = vec![
+        ("Event", "Casual Game".to_string()),
+        ("Site", "chess_gui".to_string()),
+        ("Date", "????.??.??".to_string()),
+        ("Round", "-".to_string()),
+        ("White", white.to_string()),
+        ("Black", black.to_string()),
+        ("Result", result.to_string()),
+    ];
+    if let Some(start) = history.start() {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", notation::to_fen(start, Color::White, 1)));
+    }
+    let mut text = String::new();
+    for (name, value) in tags {
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+    }
+    text.push('\n');
+
+    let mut words: Vec<String> = Vec::new();
+    if let Some(comment) = history.initial_comment() {
+        words.push(comment_text(comment));
+    }
+    // 变着的括号和里面的第一个/最后一个词连在一起写，例如 `(3. Bc4 Bc5)`
+    let mut open_variation = false;
+    for item in history.walk() {
+        match item {
+            LineItem::Move { node, number } => {
+                let record = history.record_at(node);
+                let mut first = number.unwrap_or_else(|| record.san.clone());
+                if open_variation {
+                    first.insert(0, '(');
+                    open_variation = false;
+                }
+                let is_number = first.ends_with('.');
+                words.push(first);
+                if is_number {
+                    words.push(record.san.clone());
+                }
+                words.extend(record.nags.iter().map(|nag| format!("${}", nag)));
+                if let Some(comment) = &record.comment {
+                    words.push(comment_text(comment));
+                }
+            }
+            LineItem::StartVariation => open_variation = true,
+            LineItem::EndVariation => {
+                if let Some(last) = words.last_mut() {
+                    last.push(')');
+                }
+            }
+        }
+    }
+    words.push(result.to_string());
+
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+// 从起始局面走到历史当前位置的局面，以及轮到哪一方
+fn position(history: &MoveHistory) -> (Board, Color) {
+    let mut board = history.start_board();
+    let mut color = Color::White;
+    for record in history.records() {
+        board.make_move(record.mv);
+        color = color.opposite();
+    }
+    (board, color)
+}
+
+// PGN 注释里不能出现 `}`
+fn comment_text(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', ")"))
+}
+
+/// 对局状态对应的 PGN 结果
+pub fn result_text(state: GameState) -> &'static str {
+    match state {
+        GameState::Playing => "*",
+        GameState::WhiteWins | GameState::TimeForfeit(Color::Black) => "1-0",
+        GameState::BlackWins | GameState::TimeForfeit(Color::White) => "0-1",
+        GameState::Draw(_) => "1/2-1/2",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOTATED: &str = r#"[Event "Test"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+{Prepared line} 1. e4 e5 2. Nf3 $1 {Develops} Nc6 3. Bb5!? (3. Bc4 Bc5 {Giuoco}) a6?! ; the Morphy
+4. Ba4 Nf6 5. O-O 1-0"#;
+
+    #[test]
+    fn test_parse_keeps_comments_and_nags() {
+        let game = parse(ANNOTATED).unwrap();
+        assert_eq!(game.tag("White"), Some("Alice"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.side_to_move, Color::Black);
+
+        let history = &game.history;
+        assert_eq!(history.len(), 9);
+        assert_eq!(history.initial_comment(), Some("Prepared line"));
+        let records = history.records();
+        assert_eq!(records[2].nags, vec![1]);
+        assert_eq!(records[2].comment.as_deref(), Some("Develops"));
+        assert_eq!(records[4].annotated_san(), "Bb5!?");
+        assert_eq!(records[5].annotated_san(), "a6?!");
+        assert_eq!(records[5].comment.as_deref(), Some("the Morphy"));
+        assert_eq!(records[8].san, "O-O");
+    }
+
+    #[test]
+    fn test_parse_variations() {
+        let mut history = parse(ANNOTATED).unwrap().history;
+        // 变着 3. Bc4 Bc5 挂在 3. Bb5 旁边，主线不受影响
+        let bc5 = history
+            .walk()
+            .into_iter()
+            .filter_map(|item| match item {
+                LineItem::Move { node, .. } => Some(node),
+                _ => None,
+            })
+            .find(|&node| history.record_at(node).san == "Bc5")
+            .unwrap();
+        assert!(!history.is_mainline(bc5));
+        assert_eq!(history.record_at(bc5).comment.as_deref(), Some("Giuoco"));
+
+        history.go_to(Some(bc5));
+        let line: Vec<&str> = history.records().iter().map(|r| r.san.as_str()).collect();
+        assert_eq!(line, vec!["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+
+        assert_eq!(
+            parse("1. e4 (1. d4 d5").unwrap_err(),
+            PgnError::Unterminated('(')
+        );
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let game = parse(ANNOTATED).unwrap();
+        let text = export(&game.history, "Alice", "Bob", "1-0");
+        assert!(text.contains("[White \"Alice\"]"));
+        let movetext = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(
+            movetext.contains("{Prepared line} 1. e4 e5 2. Nf3 $1 {Develops} 2... Nc6 3. Bb5 $5")
+        );
+        assert!(movetext.contains("(3. Bc4 Bc5 {Giuoco}) 3... a6 $6 {the Morphy} 4. Ba4"));
+        assert!(text.lines().all(|line| line.len() <= 80));
+
+        let again = parse(&text).unwrap();
+        assert_eq!(again.history.records(), game.history.records());
+        assert_eq!(again.history.walk(), game.history.walk());
+        assert_eq!(again.history.initial_comment(), Some("Prepared line"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("1. e4 e5 2. Ke3").unwrap_err(),
+            PgnError::IllegalMove {
+                ply: 3,
+                san: "Ke3".to_string()
+            }
+        );
+        assert_eq!(
+            parse("1. e4 {open").unwrap_err(),
+            PgnError::Unterminated('{')
+        );
+        assert_eq!(
+            parse("[FEN \"8/8/8/8/8/8/8/K6k b - - 0 1\"]\n*").unwrap_err(),
+            PgnError::CustomStartPosition
+        );
+    }
+
+    #[test]
+    fn test_handicap_start_round_trip() {
+        // 黑方让后翼的车：1. Nf3 之后黑方只能短易位
+        let mut history =
+            MoveHistory::with_start(Board::with_handicap(Handicap::Rook, Color::Black));
+        let mut board = history.start_board();
+        for text in ["g2g3", "b8c6", "f1g2"] {
+            let mv = notation::parse_uci_move(text).unwrap();
+            history.record(&board, mv);
+            board.make_move(mv);
+        }
+        let text = export(&history, "Alice", "Bob", "*");
+        assert!(text.contains("[SetUp \"1\"]"));
+        assert!(text.contains("[FEN \"1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1\"]"));
+
+        let game = parse(&text).unwrap();
+        assert_eq!(game.history.records(), history.records());
+        assert_eq!(game.board.squares(), board.squares());
+        assert_eq!(
+            game.history.start().map(|start| start.squares()),
+            history.start().map(|start| start.squares())
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! 玩家档案模块
+//! 按 AI 难度统计和 AI 对局的胜负和、连胜连败和最快的胜局，
+//! 保存在 `~/.chess_gui/profile.tsv`，统计面板根据它显示徽章
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::settings;
+use crate::types::*;
+
+/// 连胜达到这么多局时获得 "Hat trick" 徽章
+pub const HAT_TRICK_STREAK: u32 = 3;
+/// 连胜达到这么多局时获得 "Unstoppable" 徽章
+pub const UNSTOPPABLE_STREAK: u32 = 5;
+/// 在这么多回合以内取胜获得 "Miniature" 徽章
+pub const MINIATURE_MOVES: u32 = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 一局结束时玩家的结果
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    /// 玩家执 `player` 和 AI 下时，从对局状态得出结果；还没结束时为 `None`
+    pub fn for_player(state: GameState, player: Color) -> Option<Outcome> {
+        let winner = match state {
+            GameState::Playing => return None,
+            GameState::Draw(_) => return Some(Outcome::Draw),
+            GameState::WhiteWins | GameState::TimeForfeit(Color::Black) => Color::White,
+            GameState::BlackWins | GameState::TimeForfeit(Color::White) => Color::Black,
+        };
+        Some(if winner == player {
+            Outcome::Win
+        } else {
+            Outcome::Loss
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// 某个难度下的统计
+pub struct DifficultyStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// 当前的连胜（正数）或连败（负数），和棋时清零
+    pub streak: i32,
+    pub best_win_streak: u32,
+    pub worst_loss_streak: u32,
+    /// 最快的胜局用了多少回合
+    pub fastest_win: Option<u32>,
+}
+
+impl DifficultyStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// 记录一局的结果，`moves` 为这局的回合数
+    pub fn record(&mut self, outcome: Outcome, moves: u32) {
+        match outcome {
+            Outcome::Win => {
+                self.wins += 1;
+                self.streak = self.streak.max(0) + 1;
+                self.best_win_streak = self.best_win_streak.max(self.streak as u32);
+                self.fastest_win = Some(self.fastest_win.map_or(moves, |best| best.min(moves)));
+            }
+            Outcome::Loss => {
+                self.losses += 1;
+                self.streak = self.streak.min(0) - 1;
+                self.worst_loss_streak = self.worst_loss_streak.max(self.streak.unsigned_abs());
+            }
+            Outcome::Draw => {
+                self.draws += 1;
+                self.streak = 0;
+            }
+        }
+    }
+
+    /// 当前连胜连败的说明，例如 `3 wins in a row`；没有时为 `None`
+    pub fn streak_text(&self) -> Option<String> {
+        match self.streak {
+            0 => None,
+            1 => Some("1 win".to_string()),
+            -1 => Some("1 loss".to_string()),
+            n if n > 0 => Some(format!("{} wins in a row", n)),
+            n => Some(format!("{} losses in a row", -n)),
+        }
+    }
+
+    /// 已经获得的徽章
+    pub fn badges(&self) -> Vec<&'static str> {
+        let mut badges = Vec::new();
+        if self.wins > 0 {
+            badges.push("First win");
+        }
+        if self.best_win_streak >= HAT_TRICK_STREAK {
+            badges.push("Hat trick");
+        }
+        if self.best_win_streak >= UNSTOPPABLE_STREAK {
+            badges.push("Unstoppable");
+        }
+        if self
+            .fastest_win
+            .is_some_and(|moves| moves <= MINIATURE_MOVES)
+        {
+            badges.push("Miniature");
+        }
+        badges
+    }
+
+    // 存储格式：`难度<TAB>胜<TAB>负<TAB>和<TAB>连胜连败<TAB>最长连胜<TAB>最长连败<TAB>最快胜局`，
+    // 没有胜局时最后一项为 `-`
+    fn format_line(&self, difficulty: AIDifficulty) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            difficulty.to_string(),
+            self.wins,
+            self.losses,
+            self.draws,
+            self.streak,
+            self.best_win_streak,
+            self.worst_loss_streak,
+            self.fastest_win
+                .map_or_else(|| "-".to_string(), |moves| moves.to_string())
+        )
+    }
+
+    fn from_line(line: &str) -> Option<(AIDifficulty, DifficultyStats)> {
+        let mut fields = line.split('\t');
+        let name = fields.next()?;
+        let difficulty = AIDifficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.to_string() == name)?;
+        let mut number = || fields.next()?.parse::<u32>().ok();
+        let wins = number()?;
+        let losses = number()?;
+        let draws = number()?;
+        let streak = fields.next()?.parse().ok()?;
+        let mut number = || fields.next()?.parse::<u32>().ok();
+        let best_win_streak = number()?;
+        let worst_loss_streak = number()?;
+        let fastest_win = match fields.next()? {
+            "-" => None,
+            moves => Some(moves.parse().ok()?),
+        };
+
+        Some((
+            difficulty,
+            DifficultyStats {
+                wins,
+                losses,
+                draws,
+                streak,
+                best_win_streak,
+                worst_loss_streak,
+                fastest_win,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// 玩家档案：每个难度一份统计
+pub struct Profile {
+    stats: [DifficultyStats; 4],
+}
+
+impl Profile {
+    pub fn stats(&self, difficulty: AIDifficulty) -> &DifficultyStats {
+        &self.stats[Self::index(difficulty)]
+    }
+
+    pub fn record(&mut self, difficulty: AIDifficulty, outcome: Outcome, moves: u32) {
+        self.stats[Self::index(difficulty)].record(outcome, moves);
+    }
+
+    /// 读取档案，格式不对的行会被忽略
+    pub fn load(path: &Path) -> io::Result<Profile> {
+        let mut profile = Profile::default();
+        for line in fs::read_to_string(path)?.lines() {
+            if let Some((difficulty, stats)) = DifficultyStats::from_line(line) {
+                profile.stats[Self::index(difficulty)] = stats;
+            }
+        }
+        Ok(profile)
+    }
+
+    /// 读取默认位置的档案，文件不存在或读不出来时从空档案开始
+    pub fn load_default() -> Profile {
+        Profile::load(&default_profile_path()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let lines: Vec<String> = AIDifficulty::ALL
+            .into_iter()
+            .map(|difficulty| self.stats(difficulty).format_line(difficulty))
+            .collect();
+        fs::write(path, lines.join("\n") + "\n")
+    }
+
+    fn index(difficulty: AIDifficulty) -> usize {
+        match difficulty {
+            AIDifficulty::Easy => 0,
+            AIDifficulty::Medium => 1,
+            AIDifficulty::Hard => 2,
+            AIDifficulty::Expert => 3,
+        }
+    }
+}
+
+/// 默认档案文件：`$HOME/.chess_gui/profile.tsv`
+pub fn default_profile_path() -> PathBuf {
+    settings::data_dir().join("profile.tsv")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaks_and_badges() {
+        let mut stats = DifficultyStats::default();
+        for moves in [40, 30, 22] {
+            stats.record(Outcome::Win, moves);
+        }
+        assert_eq!(stats.streak, 3);
+        assert_eq!(stats.fastest_win, Some(22));
+        assert_eq!(stats.streak_text().unwrap(), "3 wins in a row");
+        assert_eq!(stats.badges(), vec!["First win", "Hat trick", "Miniature"]);
+
+        // 输棋打断连胜，但最长连胜保留
+        stats.record(Outcome::Loss, 50);
+        stats.record(Outcome::Loss, 20);
+        assert_eq!(stats.streak, -2);
+        assert_eq!(stats.best_win_streak, 3);
+        assert_eq!(stats.worst_loss_streak, 2);
+
+        stats.record(Outcome::Draw, 60);
+        assert_eq!(stats.streak, 0);
+        assert_eq!(stats.streak_text(), None);
+        assert_eq!(stats.games(), 6);
+    }
+
+    #[test]
+    fn test_outcome_for_player() {
+        let state = GameState::TimeForfeit(Color::White);
+        assert_eq!(Outcome::for_player(state, Color::Black), Some(Outcome::Win));
+        assert_eq!(
+            Outcome::for_player(state, Color::White),
+            Some(Outcome::Loss)
+        );
+        assert_eq!(
+            Outcome::for_player(GameState::Draw(DrawReason::Agreement), Color::Black),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(Outcome::for_player(GameState::Playing, Color::White), None);
+    }
+
+    #[test]
+    fn test_profile_round_trip() {
+        let mut profile = Profile::default();
+        profile.record(AIDifficulty::Hard, Outcome::Win, 31);
+        profile.record(AIDifficulty::Easy, Outcome::Loss, 18);
+        profile.record(AIDifficulty::Easy, Outcome::Draw, 45);
+
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_profile_{}", std::process::id()))
+            .join("profile.tsv");
+        profile.save(&path).unwrap();
+        assert_eq!(Profile::load(&path).unwrap(), profile);
+        assert_eq!(profile.stats(AIDifficulty::Medium).games(), 0);
+
+        // 残缺的行被忽略
+        fs::write(&path, "Hard\t1\t2\nBogus\t1\t1\t1\t0\t1\t1\t-\n").unwrap();
+        assert_eq!(Profile::load(&path).unwrap(), Profile::default());
+    }
+}
@@ -0,0 +1,296 @@
+//! 练习题模块
+//! 对局结束后回放走法历史，找出实战走法比最佳走法差很多的局面，
+//! 以 FEN + 最佳变例的形式保存为练习题
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ai::ChessAI;
+use crate::board::Board;
+use crate::notation::{self, MoveHistory};
+use crate::settings;
+use crate::types::*;
+
+/// 实战走法比最佳走法至少差这么多分（兵 = 100）才算失误
+pub const BLUNDER_THRESHOLD: i32 = 200;
+/// 分析每个局面的搜索深度
+pub const ANALYSIS_DEPTH: u32 = 3;
+/// 保存的最佳变例长度（半回合）
+pub const BEST_LINE_PLIES: usize = 3;
+/// 分析用的固定种子，同一盘棋总是得到同样的练习题
+const ANALYSIS_SEED: u64 = 0x5EED_CAFE;
+/// 将死的分数，超出普通评估的范围
+const MATE_SCORE: i32 = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+/// 一道练习题：轮到走棋的一方应该找到 `best_line` 的第一步
+pub struct Puzzle {
+    pub fen: String,
+    pub best_line: Vec<Move>,
+    /// 对局中实际走的那步棋
+    pub played: Move,
+    /// 实际走法相对最佳走法损失的分数
+    pub eval_loss: i32,
+}
+
+impl Puzzle {
+    /// 轮到走棋的一方
+    pub fn side_to_move(&self) -> Option<Color> {
+        notation::parse_fen(&self.fen).map(|(_, side)| side)
+    }
+
+    /// 最佳变例的 SAN，例如 `["Qe7", "Nc3", "Nf6"]`
+    pub fn best_line_san(&self) -> Vec<String> {
+        let Some((mut board, _)) = notation::parse_fen(&self.fen) else {
+            return Vec::new();
+        };
+
+        self.best_line
+            .iter()
+            .map(|&mv| {
+                let san = notation::move_to_san(&board, mv);
+                board.make_move(mv);
+                san
+            })
+            .collect()
+    }
+
+    /// 存储格式：`FEN<TAB>最佳变例<TAB>实战走法<TAB>损失`，走法用坐标记谱
+    pub fn to_line(&self) -> String {
+        let best_line: Vec<String> = self
+            .best_line
+            .iter()
+            .map(|&mv| notation::move_to_uci(mv))
+            .collect();
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.fen,
+            best_line.join(" "),
+            notation::move_to_uci(self.played),
+            self.eval_loss
+        )
+    }
+
+    pub fn from_line(line: &str) -> Option<Puzzle> {
+        let mut fields = line.split('\t');
+        let fen = fields.next()?.to_string();
+        notation::parse_fen(&fen)?;
+
+        let best_line = fields
+            .next()?
+            .split_whitespace()
+            .map(notation::parse_uci_move)
+            .collect::<Option<Vec<_>>>()?;
+        let played = notation::parse_uci_move(fields.next()?)?;
+        let eval_loss = fields.next()?.parse().ok()?;
+
+        if best_line.is_empty() {
+            return None;
+        }
+
+        Some(Puzzle {
+            fen,
+            best_line,
+            played,
+            eval_loss,
+        })
+    }
+}
+
+/// 默认练习题文件：`$HOME/.chess_gui/puzzles.tsv`
+pub fn default_puzzle_path() -> PathBuf {
+    settings::data_dir().join("puzzles.tsv")
+}
+
+/// 回放整盘棋，返回所有失误局面生成的练习题
+pub fn find_puzzles(history: &MoveHistory) -> Vec<Puzzle> {
+    let mut board = history.start_board();
+    let mut puzzles = Vec::new();
+
+    for (ply, record) in history.records().iter().enumerate() {
+        if let Some(puzzle) = puzzle_at(&board, record.color, record.mv, ply / 2 + 1) {
+            puzzles.push(puzzle);
+        }
+        board.make_move(record.mv);
+    }
+
+    puzzles
+}
+
+/// 分析一个局面：实战走法比最佳走法差 `BLUNDER_THRESHOLD` 以上时生成练习题
+pub fn puzzle_at(
+    board: &Board,
+    color: Color,
+    played: Move,
+    fullmove_number: usize,
+) -> Option<Puzzle> {
+    let (best, _) = analyse(board, color, ANALYSIS_DEPTH)?;
+    if best == played {
+        return None;
+    }
+
+    // 两步都用同样的深度评估走完之后的局面，分数才有可比性
+    let sign = if color == Color::White { 1 } else { -1 };
+    let best_score = score_after(board, best, color)? * sign;
+    let played_score = score_after(board, played, color)? * sign;
+    let eval_loss = best_score - played_score;
+    if eval_loss < BLUNDER_THRESHOLD {
+        return None;
+    }
+
+    Some(Puzzle {
+        fen: notation::to_fen(board, color, fullmove_number),
+        best_line: best_line(board, color, best),
+        played,
+        eval_loss,
+    })
+}
+
+/// 把练习题追加到文件，已经存在相同局面的题目会被跳过，返回新增的数量
+pub fn save_puzzles(path: &Path, puzzles: &[Puzzle]) -> io::Result<usize> {
+    let mut known: Vec<String> = match load_puzzles(path) {
+        Ok(existing) => existing.into_iter().map(|puzzle| puzzle.fen).collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    let mut added = 0;
+    for puzzle in puzzles {
+        if known.contains(&puzzle.fen) {
+            continue;
+        }
+        writeln!(file, "{}", puzzle.to_line())?;
+        known.push(puzzle.fen.clone());
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+/// 读取练习题文件，格式不对的行会被忽略
+pub fn load_puzzles(path: &Path) -> io::Result<Vec<Puzzle>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(Puzzle::from_line)
+        .collect())
+}
+
+// 返回最佳走法和白方视角的评估
+fn analyse(board: &Board, color: Color, depth: u32) -> Option<(Move, i32)> {
+    let mut ai = ChessAI::with_seed(depth, ANALYSIS_SEED);
+    let best = ai.get_best_move(board, color)?;
+    Some((best, ai.last_score?))
+}
+
+/// 走完 `mv` 之后的局面评估（白方视角），搜索比根节点浅一层
+pub fn score_after(board: &Board, mv: Move, color: Color) -> Option<i32> {
+    let mut after = board.clone();
+    after.make_move(mv);
+    let opponent = color.opposite();
+
+    if !after.has_legal_move(opponent) {
+        if !after.is_in_check(opponent) {
+            return Some(0);
+        }
+        return Some(match color {
+            Color::White => MATE_SCORE,
+            Color::Black => -MATE_SCORE,
+        });
+    }
+
+    analyse(&after, opponent, ANALYSIS_DEPTH - 1).map(|(_, score)| score)
+}
+
+fn best_line(board: &Board, color: Color, first: Move) -> Vec<Move> {
+    let mut line = vec![first];
+    let mut board = board.clone();
+    let mut color = color;
+    board.make_move(first);
+
+    while line.len() < BEST_LINE_PLIES {
+        color = color.opposite();
+        match analyse(&board, color, ANALYSIS_DEPTH) {
+            Some((mv, _)) => {
+                board.make_move(mv);
+                line.push(mv);
+            }
+            None => break,
+        }
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uci(text: &str) -> Move {
+        notation::parse_uci_move(text).unwrap()
+    }
+
+    fn play(moves: &[&str]) -> MoveHistory {
+        let mut board = Board::new();
+        let mut history = MoveHistory::new();
+        for text in moves {
+            let mv = uci(text);
+            history.record(&board, mv);
+            board.make_move(mv);
+        }
+        history
+    }
+
+    #[test]
+    fn test_finds_blunder_allowing_mate() {
+        // 1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6?? 4. Qxf7#
+        let history = play(&["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]);
+        let puzzles = find_puzzles(&history);
+
+        let puzzle = puzzles
+            .iter()
+            .find(|puzzle| puzzle.played == uci("g8f6"))
+            .expect("3...Nf6 should be a puzzle");
+        assert_eq!(
+            puzzle.fen,
+            "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 3"
+        );
+        assert_eq!(puzzle.side_to_move(), Some(Color::Black));
+        assert_ne!(puzzle.best_line[0], puzzle.played);
+        assert!(puzzle.eval_loss >= BLUNDER_THRESHOLD);
+
+        // 白方的将死是最佳走法，不应该生成练习题
+        assert!(puzzles.iter().all(|puzzle| puzzle.played != uci("h5f7")));
+    }
+
+    #[test]
+    fn test_line_round_trip_and_dedup() {
+        let puzzle = Puzzle {
+            fen: "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 3".to_string(),
+            best_line: vec![uci("g7g6"), uci("h5f3"), uci("g8f6")],
+            played: uci("g8f6"),
+            eval_loss: 1200,
+        };
+        assert_eq!(Puzzle::from_line(&puzzle.to_line()), Some(puzzle.clone()));
+        assert_eq!(puzzle.best_line_san(), vec!["g6", "Qf3", "Nf6"]);
+
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_puzzles_{}", std::process::id()))
+            .join("puzzles.tsv");
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            save_puzzles(&path, std::slice::from_ref(&puzzle)).unwrap(),
+            1
+        );
+        assert_eq!(
+            save_puzzles(&path, std::slice::from_ref(&puzzle)).unwrap(),
+            0
+        );
+        assert_eq!(load_puzzles(&path).unwrap(), vec![puzzle]);
+    }
+}
@@ -0,0 +1,362 @@
+//! 存档模块
+//! 把对局保存到 `~/.chess_gui/saved_game.pgn`，下次打开程序后接着下。
+//! 存档是一份普通的 PGN 棋谱（包括注释和变着），另外用几个标签记下对局模式、
+//! 玩家执的颜色、AI 难度、棋盘方向、棋谱中的当前位置、双方的棋钟，以及认输、协议和棋这类从局面上看不出来的结果
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::clock::{ChessClock, TimeControl};
+use crate::notation::{self, MoveHistory};
+use crate::pgn;
+use crate::settings;
+use crate::types::*;
+use crate::variant::Variant;
+
+/// 存档中的一局棋
+#[derive(Debug, Clone)]
+pub struct SavedGame {
+    /// 走法树，当前位置就是存档时棋盘所在的那一步
+    pub history: MoveHistory,
+    pub mode: GameMode,
+    /// 和 AI 下时玩家执的一方
+    pub player: Color,
+    pub difficulty: AIDifficulty,
+    /// 变体规则，标准规则的存档不写这个标签
+    pub variant: Variant,
+    pub flipped: bool,
+    /// 当前局面的对局状态；只有认输、协议和棋这类需要记下来的结果才用到
+    pub state: GameState,
+    /// 对局结束的说明，例如 `Black resigned`，还没结束时为空
+    pub reason: String,
+    /// 停着的棋钟，不计时的对局为 `None`
+    pub clock: Option<ChessClock>,
+}
+
+impl SavedGame {
+    /// 不带存档标签的普通 PGN 棋谱
+    pub fn to_pgn(&self) -> String {
+        let (white, black) = match (self.mode, self.player) {
+            (GameMode::HumanVsAI, Color::White) => ("Player", "Computer"),
+            (GameMode::HumanVsAI, Color::Black) => ("Computer", "Player"),
+            (GameMode::HumanVsHuman, _) => ("White", "Black"),
+        };
+        pgn::export(&self.history, white, black, pgn::result_text(self.state))
+    }
+
+    /// 存档的文本：PGN 标签之后紧接着存档用的标签，然后是棋谱
+    pub fn to_text(&self) -> String {
+        let text = self.to_pgn();
+        let current_line: Vec<String> = self
+            .history
+            .records()
+            .iter()
+            .map(|record| notation::move_to_uci(record.mv))
+            .collect();
+
+        let mut tags = vec![
+            ("Mode", mode_name(self.mode).to_string()),
+            ("Player", format!("{:?}", self.player)),
+            ("Difficulty", self.difficulty.to_string().to_string()),
+            ("Flipped", self.flipped.to_string()),
+            ("CurrentLine", current_line.join(" ")),
+        ];
+        if self.variant != Variant::Standard {
+            tags.push(("Variant", self.variant.to_string().to_string()));
+        }
+        if !self.reason.is_empty() {
+            tags.push(("Termination", self.reason.clone()));
+        }
+        // 剩余时间以毫秒记
+        if let Some(clock) = &self.clock {
+            let now = Instant::now();
+            tags.push(("TimeControl", clock.control().to_pgn()));
+            for (name, color) in [("WhiteClock", Color::White), ("BlackClock", Color::Black)] {
+                let remaining = clock.remaining(color, now).as_millis();
+                tags.push((name, remaining.to_string()));
+            }
+        }
+        let extra: String = tags
+            .iter()
+            .map(|(name, value)| format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")))
+            .collect();
+
+        // PGN 标签和走法之间隔着一个空行，存档标签插在空行前面
+        match text.split_once("\n\n") {
+            Some((header, moves)) => format!("{}\n{}\n{}", header, extra, moves),
+            None => text + &extra,
+        }
+    }
+
+    /// 读取存档文本，不是存档或者棋谱有误时返回 `InvalidData` 错误
+    pub fn from_text(text: &str) -> io::Result<SavedGame> {
+        let game = pgn::parse(text).map_err(|err| invalid_data(&err.to_string()))?;
+        let mode = game
+            .tag("Mode")
+            .and_then(mode_from_name)
+            .ok_or_else(|| invalid_data("missing or unknown Mode tag"))?;
+        let difficulty = game
+            .tag("Difficulty")
+            .and_then(|name| {
+                AIDifficulty::ALL
+                    .into_iter()
+                    .find(|difficulty| difficulty.to_string() == name)
+            })
+            .ok_or_else(|| invalid_data("missing or unknown Difficulty tag"))?;
+        // 早先的存档没有 Player 标签，那时玩家总是执白
+        let player = match game.tag("Player") {
+            Some("Black") => Color::Black,
+            _ => Color::White,
+        };
+        let variant = match game.tag("Variant") {
+            Some(name) => {
+                Variant::from_name(name).ok_or_else(|| invalid_data("unknown Variant tag"))?
+            }
+            None => Variant::Standard,
+        };
+        let flipped = game.tag("Flipped") == Some("true");
+        let reason = game.tag("Termination").unwrap_or_default().to_string();
+        let current_line = game.tag("CurrentLine").unwrap_or_default().to_string();
+        let clock = read_clock(&game)?;
+
+        // 从起始局面沿当前这条线走一遍，回到存档时的位置
+        let mut history = game.history;
+        history.go_to(None);
+        let mut board = history.start_board();
+        let mut color = Color::White;
+        for uci in current_line.split_whitespace() {
+            let mv = notation::parse_uci_move(uci)
+                .filter(|&mv| board.generate_moves(color).contains(&mv))
+                .ok_or_else(|| invalid_data("illegal move in CurrentLine tag"))?;
+            history.record(&board, mv);
+            board.make_move(mv);
+            color = color.opposite();
+        }
+
+        // 输的一方棋钟归零说明是超时判负
+        let flagged = |color| {
+            clock
+                .as_ref()
+                .is_some_and(|clock| clock.remaining(color, Instant::now()).is_zero())
+        };
+        let state = match game.result.as_str() {
+            "1-0" if flagged(Color::Black) => GameState::TimeForfeit(Color::Black),
+            "0-1" if flagged(Color::White) => GameState::TimeForfeit(Color::White),
+            "1-0" => GameState::WhiteWins,
+            "0-1" => GameState::BlackWins,
+            "1/2-1/2" => GameState::Draw(DrawReason::Agreement),
+            _ => GameState::Playing,
+        };
+
+        Ok(SavedGame {
+            history,
+            mode,
+            player,
+            difficulty,
+            variant,
+            flipped,
+            state,
+            reason,
+            clock,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: &Path) -> io::Result<SavedGame> {
+        SavedGame::from_text(&fs::read_to_string(path)?)
+    }
+}
+
+/// 默认存档文件：`$HOME/.chess_gui/saved_game.pgn`
+pub fn default_save_path() -> PathBuf {
+    settings::data_dir().join("saved_game.pgn")
+}
+
+// `TimeControl` 标签和双方的剩余时间，没有剩余时间的标签时按基本时间算
+fn read_clock(game: &pgn::PgnGame) -> io::Result<Option<ChessClock>> {
+    let Some(text) = game.tag("TimeControl") else {
+        return Ok(None);
+    };
+    let control =
+        TimeControl::from_pgn(text).ok_or_else(|| invalid_data("invalid TimeControl tag"))?;
+    let remaining = |name| match game.tag(name) {
+        Some(ms) => ms
+            .parse()
+            .map(Duration::from_millis)
+            .map_err(|_| invalid_data("invalid clock tag")),
+        None => Ok(control.initial),
+    };
+    Ok(Some(ChessClock::with_remaining(
+        control,
+        remaining("WhiteClock")?,
+        remaining("BlackClock")?,
+    )))
+}
+
+fn mode_name(mode: GameMode) -> &'static str {
+    match mode {
+        GameMode::HumanVsAI => "HumanVsAI",
+        GameMode::HumanVsHuman => "HumanVsHuman",
+    }
+}
+
+fn mode_from_name(name: &str) -> Option<GameMode> {
+    match name {
+        "HumanVsAI" => Some(GameMode::HumanVsAI),
+        "HumanVsHuman" => Some(GameMode::HumanVsHuman),
+        _ => None,
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::notation::parse_uci_move;
+
+    fn play(history: &mut MoveHistory, board: &mut Board, moves: &[&str]) {
+        for uci in moves {
+            let mv = parse_uci_move(uci).unwrap();
+            history.record(board, mv);
+            board.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_keeps_current_position() {
+        // 主线 1. e4 e5 2. Nf3，退回到 1... e5 之前改走 1... c5 并加上注释
+        let mut history = MoveHistory::new();
+        let mut board = Board::new();
+        play(&mut history, &mut board, &["e2e4", "e7e5", "g1f3"]);
+        history.go_to(None);
+        let mut board = Board::new();
+        play(&mut history, &mut board, &["e2e4", "c7c5"]);
+        history.set_comment(history.current().unwrap(), Some("Sicilian".to_string()));
+
+        let saved = SavedGame {
+            history,
+            mode: GameMode::HumanVsHuman,
+            player: Color::White,
+            difficulty: AIDifficulty::Hard,
+            variant: Variant::KingOfTheHill,
+            flipped: true,
+            state: GameState::Playing,
+            reason: String::new(),
+            clock: None,
+        };
+        let text = saved.to_text();
+        assert!(text.contains("[Difficulty \"Hard\"]"));
+        assert!(text.contains("[Variant \"King of the Hill\"]"));
+
+        let loaded = SavedGame::from_text(&text).unwrap();
+        assert_eq!(loaded.mode, GameMode::HumanVsHuman);
+        assert_eq!(loaded.difficulty, AIDifficulty::Hard);
+        assert_eq!(loaded.variant, Variant::KingOfTheHill);
+        assert!(loaded.flipped);
+        assert_eq!(loaded.state, GameState::Playing);
+        let line: Vec<&str> = loaded
+            .history
+            .records()
+            .iter()
+            .map(|record| record.san.as_str())
+            .collect();
+        assert_eq!(line, vec!["e4", "c5"]);
+        assert_eq!(
+            loaded.history.last().unwrap().comment.as_deref(),
+            Some("Sicilian")
+        );
+        // 原来的主线仍然在走法树里，c5 是变着
+        assert!(
+            loaded
+                .to_text()
+                .contains("1. e4 e5 (1... c5 {Sicilian}) 2. Nf3")
+        );
+    }
+
+    #[test]
+    fn test_handicap_game_round_trip() {
+        // AI 执黑让马，走完 1. e4 之后存档
+        let start = Board::with_handicap(Handicap::Knight, Color::Black);
+        let mut history = MoveHistory::with_start(start.clone());
+        play(&mut history, &mut start.clone(), &["e2e4"]);
+        let saved = SavedGame {
+            history,
+            mode: GameMode::HumanVsAI,
+            player: Color::White,
+            difficulty: AIDifficulty::Medium,
+            variant: Variant::Standard,
+            flipped: false,
+            state: GameState::Playing,
+            reason: String::new(),
+            clock: None,
+        };
+
+        let loaded = SavedGame::from_text(&saved.to_text()).unwrap();
+        assert_eq!(
+            loaded.history.start().map(Board::squares),
+            Some(start.squares())
+        );
+        assert_eq!(loaded.history.last().unwrap().san, "e4");
+    }
+
+    #[test]
+    fn test_resignation_and_file_round_trip() {
+        let mut history = MoveHistory::new();
+        play(&mut history, &mut Board::new(), &["d2d4"]);
+        let saved = SavedGame {
+            history,
+            mode: GameMode::HumanVsAI,
+            player: Color::Black,
+            difficulty: AIDifficulty::Easy,
+            variant: Variant::Standard,
+            flipped: false,
+            state: GameState::WhiteWins,
+            reason: "Black resigned".to_string(),
+            clock: None,
+        };
+
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_savegame_{}", std::process::id()))
+            .join("saved_game.pgn");
+        saved.save(&path).unwrap();
+        assert!(saved.to_text().contains("[White \"Computer\"]"));
+        let loaded = SavedGame::load(&path).unwrap();
+        assert_eq!(loaded.player, Color::Black);
+        assert_eq!(loaded.state, GameState::WhiteWins);
+        assert_eq!(loaded.reason, "Black resigned");
+        assert_eq!(loaded.history.len(), 1);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        // 超时判负：输的一方棋钟归零
+        let clock = ChessClock::with_remaining(
+            TimeControl::minutes(3, 2),
+            Duration::from_millis(61_500),
+            Duration::ZERO,
+        );
+        let flagged = SavedGame {
+            state: GameState::TimeForfeit(Color::Black),
+            reason: "Black lost on time".to_string(),
+            clock: Some(clock.clone()),
+            ..saved
+        };
+        let loaded = SavedGame::from_text(&flagged.to_text()).unwrap();
+        assert_eq!(loaded.state, GameState::TimeForfeit(Color::Black));
+        assert_eq!(loaded.clock, Some(clock));
+
+        // 普通的 PGN 没有存档标签，不能当存档读
+        let err = SavedGame::from_text("1. e4 e5 *").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
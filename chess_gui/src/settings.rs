@@ -0,0 +1,52 @@
+//! 游戏设置模块
+//! 集中保存可以在界面中调整的选项
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 用户可调整的游戏设置
+pub struct GameSettings {
+    /// AI 是否会在局面无望时认输
+    pub ai_can_resign: bool,
+    /// 认输阈值（以兵 = 100 计的分数，AI 视角低于 -阈值 视为无望）
+    pub resign_threshold: i32,
+    /// 连续多少步低于阈值才认输
+    pub resign_after_moves: usize,
+    /// AI 是否会在均势残局中提和 / 接受和棋
+    pub ai_draw_offers: bool,
+    /// 视为均势的评估范围
+    pub draw_margin: i32,
+    /// 连续多少步处于均势才提和
+    pub draw_after_moves: usize,
+    /// 鼠标悬停时显示格子信息（棋子、攻击和保护数量）
+    pub show_square_info: bool,
+    /// 两步确认走法：先点目标格，再按 Confirm 才真正走棋，避免触屏误触
+    pub confirm_moves: bool,
+    /// 双人对弈时每步之后翻转棋盘，让走棋的一方在下面
+    pub auto_rotate: bool,
+    /// AI 走棋前至少停顿多少毫秒，让玩家看清自己的上一步；思考时间算在里面，0 为立即走棋
+    pub ai_move_delay_ms: u64,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            ai_can_resign: true,
+            resign_threshold: 800,
+            resign_after_moves: 3,
+            ai_draw_offers: true,
+            draw_margin: 30,
+            draw_after_moves: 6,
+            show_square_info: true,
+            confirm_moves: false,
+            auto_rotate: true,
+            ai_move_delay_ms: 500,
+        }
+    }
+}
+
+/// 保存评估缓存、练习题等数据的目录：`$HOME/.chess_gui`
+pub fn data_dir() -> std::path::PathBuf {
+    std::env::var_os("HOME")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join(".chess_gui")
+}
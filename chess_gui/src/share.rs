@@ -0,0 +1,228 @@
+//! 分享码模块
+//! 把走到当前局面的走法编码成一段很短的 URL 安全 base64 字符串，
+//! 两个用户不用传文件，复制粘贴这段字符串就能交换对局
+
+use std::fmt;
+
+use crate::board::Board;
+use crate::notation::MoveHistory;
+use crate::types::*;
+
+/// 分享码格式的版本，放在第一个字节，格式改变时加一
+const SHARE_VERSION: u8 = 1;
+
+/// URL 安全的 base64 字母表（RFC 4648 §5），不带 `=` 填充
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// 从分享码恢复的对局
+#[derive(Debug, Clone)]
+pub struct SharedGame {
+    pub history: MoveHistory,
+    /// 走完所有走法后的局面
+    pub board: Board,
+    pub side_to_move: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 读取分享码时的错误
+pub enum ShareError {
+    /// 不是 base64，或者长度不对
+    InvalidCode,
+    /// 更新版本的程序生成的分享码
+    UnsupportedVersion(u8),
+    /// 第 `ply` 步（从 1 开始）在当前局面下不合法
+    IllegalMove { ply: usize },
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::InvalidCode => write!(f, "not a valid share code"),
+            ShareError::UnsupportedVersion(version) => {
+                write!(f, "share code version {} is not supported", version)
+            }
+            ShareError::IllegalMove { ply } => write!(f, "illegal move at ply {}", ply),
+        }
+    }
+}
+
+/// 生成分享码：版本字节加上当前这条线的每一步，每步两个字节
+/// （起点 6 位、终点 6 位、升变 3 位），注释和变着不包括在内
+pub fn encode(history: &MoveHistory) -> String {
+    let mut bytes = vec![SHARE_VERSION];
+    for record in history.records() {
+        bytes.extend_from_slice(&pack_move(record.mv).to_be_bytes());
+    }
+    base64_encode(&bytes)
+}
+
+/// 读取分享码，从初始局面重放并检查每一步是否合法
+pub fn decode(code: &str) -> Result<SharedGame, ShareError> {
+    let bytes = base64_decode(code.trim()).ok_or(ShareError::InvalidCode)?;
+    let (&version, moves) = bytes.split_first().ok_or(ShareError::InvalidCode)?;
+    if version != SHARE_VERSION {
+        return Err(ShareError::UnsupportedVersion(version));
+    }
+    if moves.len() % 2 != 0 {
+        return Err(ShareError::InvalidCode);
+    }
+
+    let mut board = Board::new();
+    let mut side_to_move = Color::White;
+    let mut history = MoveHistory::new();
+    for (index, pair) in moves.chunks(2).enumerate() {
+        let illegal = ShareError::IllegalMove { ply: index + 1 };
+        let mv = unpack_move(u16::from_be_bytes([pair[0], pair[1]])).ok_or(illegal.clone())?;
+        if !board.generate_moves(side_to_move).contains(&mv) {
+            return Err(illegal);
+        }
+        history.record(&board, mv);
+        board.make_move(mv);
+        side_to_move = side_to_move.opposite();
+    }
+
+    Ok(SharedGame {
+        history,
+        board,
+        side_to_move,
+    })
+}
+
+fn pack_move(mv: Move) -> u16 {
+    let square = |(row, col): (usize, usize)| (row * 8 + col) as u16;
+    let promotion = match mv.promotion {
+        None => 0,
+        Some(PieceType::Queen) => 1,
+        Some(PieceType::Rook) => 2,
+        Some(PieceType::Bishop) => 3,
+        Some(PieceType::Knight) => 4,
+        // 不会升变成兵或王，当作没有升变
+        Some(_) => 0,
+    };
+    (square(mv.from) << 9) | (square(mv.to) << 3) | promotion
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    let square = |index: u16| ((index / 8) as usize, (index % 8) as usize);
+    let promotion = match packed & 0b111 {
+        0 => None,
+        1 => Some(PieceType::Queen),
+        2 => Some(PieceType::Rook),
+        3 => Some(PieceType::Bishop),
+        4 => Some(PieceType::Knight),
+        _ => return None,
+    };
+    Some(Move {
+        from: square((packed >> 9) & 0x3f),
+        to: square((packed >> 3) & 0x3f),
+        promotion,
+    })
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        // n 个字节需要 n + 1 个字符
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        let mut group = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|&letter| letter == c)? as u32;
+            group |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_uci_move;
+
+    fn play(moves: &[&str]) -> MoveHistory {
+        let mut board = Board::new();
+        let mut history = MoveHistory::new();
+        for text in moves {
+            let mv = parse_uci_move(text).unwrap();
+            history.record(&board, mv);
+            board.make_move(mv);
+        }
+        history
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let history = play(&["e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c7c6"]);
+        let code = encode(&history);
+        // 一个版本字节加六步，每步两个字节，共 13 字节
+        assert_eq!(code.len(), 18);
+        assert!(
+            code.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+
+        let game = decode(&format!("  {}\n", code)).unwrap();
+        assert_eq!(game.side_to_move, Color::White);
+        assert_eq!(game.history.numbered_lines(), history.numbered_lines());
+        assert_eq!(
+            game.board.get_piece((2, 5)).map(|piece| piece.piece_type),
+            Some(PieceType::Knight)
+        );
+
+        let empty = decode(&encode(&MoveHistory::new())).unwrap();
+        assert!(empty.history.is_empty());
+    }
+
+    #[test]
+    fn test_promotion_survives() {
+        let mv = parse_uci_move("b7a8n").unwrap();
+        assert_eq!(unpack_move(pack_move(mv)), Some(mv));
+    }
+
+    #[test]
+    fn test_rejects_bad_codes() {
+        assert_eq!(decode("not a code!").unwrap_err(), ShareError::InvalidCode);
+        assert_eq!(decode("").unwrap_err(), ShareError::InvalidCode);
+        assert_eq!(
+            decode(&base64_encode(&[9])).unwrap_err(),
+            ShareError::UnsupportedVersion(9)
+        );
+
+        // 第二步 e7e4 不合法
+        let mut bytes = vec![SHARE_VERSION];
+        for text in ["e2e4", "e7e4"] {
+            let mv = parse_uci_move(text).unwrap();
+            bytes.extend_from_slice(&pack_move(mv).to_be_bytes());
+        }
+        assert_eq!(
+            decode(&base64_encode(&bytes)).unwrap_err(),
+            ShareError::IllegalMove { ply: 2 }
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", b"\xfb\xff"] {
+            assert_eq!(base64_decode(&base64_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg");
+        assert_eq!(base64_encode(b"\xfb\xff"), "-_8");
+    }
+}
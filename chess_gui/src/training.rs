@@ -0,0 +1,361 @@
+//! 猜步训练模块
+//! 载入一盘 PGN 棋谱（例如大师对局），玩家执其中一方：轮到这一方时先猜实战走法，猜完再揭晓并走出实战走法。
+//! 和实战走法相同得满分，否则用引擎比较两步棋走完之后的局面给部分分数。
+//! 每盘棋的得分记在 `~/.chess_gui/training.tsv`，再练同一盘棋时可以和上次比较
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::board::Board;
+use crate::notation;
+use crate::pgn::PgnGame;
+use crate::puzzle;
+use crate::settings;
+use crate::types::*;
+
+/// 一步棋的满分，猜中实战走法时得到
+pub const MAX_POINTS: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+/// 一次猜测和它的得分
+pub struct Guess {
+    /// 第几个半回合（从 0 开始）
+    pub ply: usize,
+    pub guess_san: String,
+    pub actual_san: String,
+    /// 引擎认为猜的走法比实战走法差多少分（兵 = 100），猜中或者更好时为 0
+    pub eval_loss: i32,
+    pub points: u32,
+}
+
+impl Guess {
+    /// 例如 `12... Nf6 (game: Nf6) 10/10`
+    pub fn summary(&self) -> String {
+        let number = self.ply / 2 + 1;
+        let dots = if self.ply.is_multiple_of(2) {
+            "."
+        } else {
+            "..."
+        };
+        format!(
+            "{}{} {} (game: {}) {}/{}",
+            number, dots, self.guess_san, self.actual_san, self.points, MAX_POINTS
+        )
+    }
+}
+
+/// 进行中的一次训练
+#[derive(Debug, Clone)]
+pub struct Training {
+    /// 区分不同棋谱的名字，由 Event、White、Black 和 Date 标签组成
+    pub game: String,
+    /// 玩家猜哪一方的走法
+    pub side: Color,
+    /// 棋谱的主线
+    moves: Vec<Move>,
+    /// 下一步在主线中的位置
+    ply: usize,
+    pub guesses: Vec<Guess>,
+}
+
+impl Training {
+    /// 用棋谱的主线开始训练；`side` 在这盘棋里一步都没走时返回 `None`
+    pub fn new(game: &PgnGame, side: Color) -> Option<Training> {
+        let moves: Vec<Move> = game
+            .history
+            .records()
+            .iter()
+            .map(|record| record.mv)
+            .collect();
+        let first = match side {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+        if moves.len() <= first {
+            return None;
+        }
+
+        let name: Vec<&str> = ["Event", "White", "Black", "Date"]
+            .into_iter()
+            .filter_map(|tag| game.tag(tag))
+            .filter(|value| !value.is_empty() && *value != "?")
+            .collect();
+        Some(Training {
+            game: if name.is_empty() {
+                "Untitled game".to_string()
+            } else {
+                name.join(" · ")
+            },
+            side,
+            moves,
+            ply: 0,
+            guesses: Vec::new(),
+        })
+    }
+
+    /// 主线中接下来的实战走法，棋谱走完后为 `None`
+    pub fn next_move(&self) -> Option<Move> {
+        self.moves.get(self.ply).copied()
+    }
+
+    /// 下一步是否该玩家来猜
+    pub fn is_guessing(&self) -> bool {
+        let to_move = if self.ply.is_multiple_of(2) {
+            Color::White
+        } else {
+            Color::Black
+        };
+        to_move == self.side && !self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.ply >= self.moves.len()
+    }
+
+    /// 揭晓并返回下一步实战走法
+    pub fn advance(&mut self) -> Option<Move> {
+        let mv = self.next_move()?;
+        self.ply += 1;
+        Some(mv)
+    }
+
+    /// 记下一次猜测，之后用 `advance` 走出实战走法
+    pub fn record(&mut self, guess: Guess) {
+        self.guesses.push(guess);
+    }
+
+    /// 玩家还要猜几步
+    pub fn guesses_left(&self) -> usize {
+        (self.ply..self.moves.len())
+            .filter(|ply| ply.is_multiple_of(2) == (self.side == Color::White))
+            .count()
+    }
+
+    pub fn points(&self) -> u32 {
+        self.guesses.iter().map(|guess| guess.points).sum()
+    }
+
+    /// 得分率（百分比），还没猜过时为 `None`
+    pub fn accuracy(&self) -> Option<f32> {
+        accuracy(self.points(), self.guesses.len())
+    }
+
+    /// 这次训练的记录，用来保存到训练记录文件
+    pub fn result(&self) -> TrainingResult {
+        TrainingResult {
+            game: self.game.clone(),
+            side: self.side,
+            guesses: self.guesses.len(),
+            points: self.points(),
+        }
+    }
+}
+
+/// 给 `color` 在 `board` 上猜的走法打分，要搜索几次，应该在后台线程上调用
+pub fn judge(board: &Board, color: Color, ply: usize, guess: Move, actual: Move) -> Guess {
+    let eval_loss = if guess == actual {
+        0
+    } else {
+        let sign = if color == Color::White { 1 } else { -1 };
+        let score = |mv| puzzle::score_after(board, mv, color).map(|score| score * sign);
+        match (score(actual), score(guess)) {
+            (Some(actual), Some(guess)) => (actual - guess).max(0),
+            _ => i32::MAX,
+        }
+    };
+    Guess {
+        ply,
+        guess_san: notation::move_to_san(board, guess),
+        actual_san: notation::move_to_san(board, actual),
+        eval_loss,
+        points: if guess == actual {
+            MAX_POINTS
+        } else {
+            points_for(eval_loss)
+        },
+    }
+}
+
+/// 没猜中时的分数：引擎认为和实战走法差不多（30 分以内）的得 7 分，
+/// 差一个兵以内得 4 分，差两个兵以内得 1 分
+pub fn points_for(eval_loss: i32) -> u32 {
+    match eval_loss {
+        ..=30 => 7,
+        31..=100 => 4,
+        101..=200 => 1,
+        _ => 0,
+    }
+}
+
+fn accuracy(points: u32, guesses: usize) -> Option<f32> {
+    (guesses > 0).then(|| points as f32 * 100.0 / (guesses as u32 * MAX_POINTS) as f32)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// 训练记录文件中的一行：某盘棋执某一方训练一次的得分
+pub struct TrainingResult {
+    pub game: String,
+    pub side: Color,
+    pub guesses: usize,
+    pub points: u32,
+}
+
+impl TrainingResult {
+    pub fn accuracy(&self) -> Option<f32> {
+        accuracy(self.points, self.guesses)
+    }
+
+    /// 存储格式：`棋谱<TAB>White|Black<TAB>猜测次数<TAB>得分`
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{:?}\t{}\t{}",
+            self.game.replace('\t', " "),
+            self.side,
+            self.guesses,
+            self.points
+        )
+    }
+
+    pub fn from_line(line: &str) -> Option<TrainingResult> {
+        let mut fields = line.split('\t');
+        let game = fields.next()?.to_string();
+        let side = match fields.next()? {
+            "White" => Color::White,
+            "Black" => Color::Black,
+            _ => return None,
+        };
+        Some(TrainingResult {
+            game,
+            side,
+            guesses: fields.next()?.parse().ok()?,
+            points: fields.next()?.parse().ok()?,
+        })
+    }
+}
+
+/// 默认训练记录文件：`$HOME/.chess_gui/training.tsv`
+pub fn default_training_path() -> PathBuf {
+    settings::data_dir().join("training.tsv")
+}
+
+/// 把一次训练的结果追加到记录文件
+pub fn save_result(path: &Path, result: &TrainingResult) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", result.to_line())
+}
+
+/// 读取训练记录，格式不对的行会被忽略
+pub fn load_results(path: &Path) -> io::Result<Vec<TrainingResult>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(TrainingResult::from_line)
+        .collect())
+}
+
+/// 同一盘棋执同一方以前训练时的最高得分率
+pub fn best_accuracy(results: &[TrainingResult], game: &str, side: Color) -> Option<f32> {
+    results
+        .iter()
+        .filter(|result| result.game == game && result.side == side)
+        .filter_map(TrainingResult::accuracy)
+        .reduce(f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn;
+
+    fn uci(text: &str) -> Move {
+        notation::parse_uci_move(text).unwrap()
+    }
+
+    #[test]
+    fn test_training_follows_main_line() {
+        let game = pgn::parse(
+            "[Event \"Casual\"]\n[White \"Anderssen\"]\n[Black \"?\"]\n\n1. e4 e5 (1... c5) 2. Nf3 Nc6 *",
+        )
+        .unwrap();
+        let mut training = Training::new(&game, Color::Black).unwrap();
+        assert_eq!(training.game, "Casual · Anderssen");
+        assert_eq!(training.guesses_left(), 2);
+
+        // 白方的走法直接揭晓，轮到黑方时才猜
+        assert!(!training.is_guessing());
+        assert_eq!(training.advance(), Some(uci("e2e4")));
+        assert!(training.is_guessing());
+        assert_eq!(training.next_move(), Some(uci("e7e5")));
+        training.advance();
+        training.advance();
+        assert_eq!(training.guesses_left(), 1);
+        training.advance();
+        assert!(training.is_finished());
+        assert_eq!(training.advance(), None);
+
+        // 执白时一步都没走过的棋谱不能训练
+        let empty = pgn::parse("*").unwrap();
+        assert!(Training::new(&empty, Color::White).is_none());
+    }
+
+    #[test]
+    fn test_judge_scores_guesses() {
+        // 1. e4 e5 2. Bc4 Nc6 3. Qh5 之后，实战走了 3... g6
+        let mut board = Board::new();
+        for text in ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5"] {
+            board.make_move(uci(text));
+        }
+
+        let exact = judge(&board, Color::Black, 5, uci("g7g6"), uci("g7g6"));
+        assert_eq!(exact.points, MAX_POINTS);
+        assert_eq!(exact.summary(), "3... g6 (game: g6) 10/10");
+
+        // 3... Nf6?? 让白方将死
+        let blunder = judge(&board, Color::Black, 5, uci("g8f6"), uci("g7g6"));
+        assert_eq!(blunder.points, 0);
+        assert!(blunder.eval_loss > 200);
+        assert_eq!(blunder.actual_san, "g6");
+
+        assert_eq!(points_for(0), 7);
+        assert_eq!(points_for(80), 4);
+    }
+
+    #[test]
+    fn test_results_round_trip() {
+        let results = vec![
+            TrainingResult {
+                game: "Casual · Anderssen".to_string(),
+                side: Color::Black,
+                guesses: 4,
+                points: 20,
+            },
+            TrainingResult {
+                game: "Casual · Anderssen".to_string(),
+                side: Color::Black,
+                guesses: 4,
+                points: 34,
+            },
+        ];
+        let path = std::env::temp_dir()
+            .join(format!("chess_gui_training_{}", std::process::id()))
+            .join("training.tsv");
+        let _ = fs::remove_file(&path);
+        for result in &results {
+            save_result(&path, result).unwrap();
+        }
+        let loaded = load_results(&path).unwrap();
+        assert_eq!(loaded, results);
+        assert_eq!(
+            best_accuracy(&loaded, "Casual · Anderssen", Color::Black),
+            Some(85.0)
+        );
+        assert_eq!(
+            best_accuracy(&loaded, "Casual · Anderssen", Color::White),
+            None
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
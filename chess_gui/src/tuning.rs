@@ -0,0 +1,263 @@
+//! 自对弈调参模块
+//! 让两组评估权重用确定性 AI 对下多盘快棋并统计胜率，
+//! 用对局结果而不是手感来调整 `EvalParams` 中的权重
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::ai::{ChessAI, EvalParams};
+use crate::board::Board;
+use crate::opening;
+use crate::types::{Color, Move};
+
+/// 一场对抗赛的设置
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// 双方的搜索深度
+    pub depth: u32,
+    /// 使用的开局数；每个开局双方各执白一次
+    pub openings: usize,
+    /// 超过这么多半回合还没分出胜负时按评估判定
+    pub max_plies: usize,
+    /// 判定时评估超过这个分数（兵 = 100）算胜，否则算和
+    pub adjudicate_margin: i32,
+    /// 同时进行的对局数
+    pub threads: usize,
+    /// 确定性 AI 的种子，相同的设置总是得到相同的结果
+    pub seed: u64,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            openings: 16,
+            max_plies: 160,
+            adjudicate_margin: 300,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: 1,
+        }
+    }
+}
+
+/// 一盘棋的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// 对抗赛的结果，站在候选权重一方统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// 得分率：胜 1 分，和 0.5 分；没有对局时为 0.5
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.0) / games as f64,
+        }
+    }
+
+    /// 由得分率换算的等级分差，全胜或全负时按 ±800 封顶
+    pub fn elo_diff(&self) -> f64 {
+        let score = self.score().clamp(0.01, 0.99);
+        (400.0 * (score / (1.0 - score)).log10()).clamp(-800.0, 800.0)
+    }
+
+    fn record(&mut self, outcome: Outcome, candidate: Color) {
+        match (outcome, candidate) {
+            (Outcome::Draw, _) => self.draws += 1,
+            (Outcome::WhiteWins, Color::White) | (Outcome::BlackWins, Color::Black) => {
+                self.wins += 1
+            }
+            _ => self.losses += 1,
+        }
+    }
+}
+
+/// 从开局走法之后开始，白方用 `white`、黑方用 `black` 权重下完一盘
+pub fn play_game(
+    white: &EvalParams,
+    black: &EvalParams,
+    opening: &[Move],
+    config: &MatchConfig,
+) -> Outcome {
+    let mut board = Board::new();
+    let mut color = Color::White;
+    for &mv in opening {
+        board.make_move(mv);
+        color = color.opposite();
+    }
+
+    let mut white_ai = ChessAI::with_seed(config.depth, config.seed);
+    white_ai.eval_params = *white;
+    let mut black_ai = ChessAI::with_seed(config.depth, config.seed);
+    black_ai.eval_params = *black;
+
+    for _ in 0..config.max_plies {
+        if !board.has_legal_move(color) {
+            return match (board.is_in_check(color), color) {
+                (false, _) => Outcome::Draw,
+                (true, Color::White) => Outcome::BlackWins,
+                (true, Color::Black) => Outcome::WhiteWins,
+            };
+        }
+        let ai = match color {
+            Color::White => &mut white_ai,
+            Color::Black => &mut black_ai,
+        };
+        let Some(mv) = ai.get_best_move(&board, color) else {
+            return Outcome::Draw;
+        };
+        board.make_move(mv);
+        color = color.opposite();
+    }
+
+    // 用默认权重判定，免得被测的权重给自己打分
+    match board.evaluate() {
+        score if score > config.adjudicate_margin => Outcome::WhiteWins,
+        score if score < -config.adjudicate_margin => Outcome::BlackWins,
+        _ => Outcome::Draw,
+    }
+}
+
+/// 候选权重和基准权重的对抗赛。开局取开局表里最长的几个变例，
+/// 每个开局双方各执白一次，抵消开局本身的优劣
+pub fn run_match(
+    candidate: &EvalParams,
+    baseline: &EvalParams,
+    config: &MatchConfig,
+) -> MatchResult {
+    let mut openings: Vec<_> = opening::openings().iter().collect();
+    openings.sort_by_key(|opening| std::cmp::Reverse(opening.plies()));
+    let games: Vec<(Vec<Move>, Color)> = openings
+        .iter()
+        .take(config.openings)
+        .flat_map(|opening| {
+            [
+                (opening.moves(), Color::White),
+                (opening.moves(), Color::Black),
+            ]
+        })
+        .collect();
+
+    let next = AtomicUsize::new(0);
+    let result = Mutex::new(MatchResult::default());
+    thread::scope(|scope| {
+        for _ in 0..config.threads.clamp(1, games.len().max(1)) {
+            scope.spawn(|| {
+                while let Some((moves, candidate_color)) =
+                    games.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let outcome = match candidate_color {
+                        Color::White => play_game(candidate, baseline, moves, config),
+                        Color::Black => play_game(baseline, candidate, moves, config),
+                    };
+                    result.lock().unwrap().record(outcome, *candidate_color);
+                }
+            });
+        }
+    });
+    result.into_inner().unwrap()
+}
+
+/// 网格搜索：把 `name` 权重依次设为 `values` 中的每个值，与 `baseline` 对抗。
+/// 权重名不对时返回 `None`
+pub fn grid_search(
+    baseline: &EvalParams,
+    name: &str,
+    values: &[i32],
+    config: &MatchConfig,
+) -> Option<Vec<(i32, MatchResult)>> {
+    baseline.get(name)?;
+    Some(
+        values
+            .iter()
+            .map(|&value| {
+                let mut candidate = *baseline;
+                candidate.set(name, value);
+                (value, run_match(&candidate, baseline, config))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick_config() -> MatchConfig {
+        MatchConfig {
+            depth: 1,
+            openings: 2,
+            max_plies: 12,
+            threads: 2,
+            ..MatchConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_match_result_score() {
+        let result = MatchResult {
+            wins: 3,
+            losses: 1,
+            draws: 2,
+        };
+        assert_eq!(result.games(), 6);
+        assert!((result.score() - 4.0 / 6.0).abs() < 1e-9);
+        assert!(result.elo_diff() > 100.0 && result.elo_diff() < 130.0);
+        assert_eq!(MatchResult::default().elo_diff(), 0.0);
+    }
+
+    #[test]
+    fn test_fools_mate_is_a_black_win() {
+        let moves: Vec<Move> = ["f2f3", "e7e5", "g2g4", "d8h4"]
+            .iter()
+            .map(|text| crate::notation::parse_uci_move(text).unwrap())
+            .collect();
+        let params = EvalParams::default();
+        assert_eq!(
+            play_game(&params, &params, &moves, &quick_config()),
+            Outcome::BlackWins
+        );
+    }
+
+    #[test]
+    fn test_match_is_reproducible() {
+        let config = quick_config();
+        let baseline = EvalParams::default();
+        let candidate = EvalParams {
+            mobility: 0,
+            ..baseline
+        };
+        let first = run_match(&candidate, &baseline, &config);
+        assert_eq!(first.games(), 4);
+        assert_eq!(first, run_match(&candidate, &baseline, &config));
+    }
+
+    #[test]
+    fn test_grid_search_rejects_unknown_params() {
+        let config = MatchConfig {
+            openings: 0,
+            ..quick_config()
+        };
+        assert!(grid_search(&EvalParams::default(), "bishop_pair", &[10], &config).is_none());
+        let results = grid_search(&EvalParams::default(), "tempo", &[0, 20], &config).unwrap();
+        assert_eq!(
+            results.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            vec![0, 20]
+        );
+    }
+}
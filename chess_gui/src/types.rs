@@ -25,6 +25,9 @@ pub struct Piece {
     pub color: Color,
 }
 
+/// 棋盘坐标 `(行, 列)`，第 0 行是黑方底线（第 8 横排）
+pub type Square = (usize, usize);
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 /// 表示一步棋，包括起始位置、目标位置和可能的升变
 pub struct Move {
@@ -39,7 +42,39 @@ pub enum GameState {
     Playing,
     WhiteWins,
     BlackWins,
-    Draw,
+    Draw(DrawReason),
+    /// 这一方的棋钟落旗，超时判负
+    TimeForfeit(Color),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 和棋的原因
+pub enum DrawReason {
+    Stalemate,
+    Agreement,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl DrawReason {
+    /// 状态栏里 "Draw by ..." 后面的部分
+    pub fn description(self) -> &'static str {
+        match self {
+            DrawReason::Stalemate => "stalemate",
+            DrawReason::Agreement => "agreement",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::FiftyMoveRule => "the fifty-move rule",
+            DrawReason::InsufficientMaterial => "insufficient material",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 对局模式：和 AI 下（玩家可以执白或执黑），或者两个人轮流在同一台设备上下
+pub enum GameMode {
+    HumanVsAI,
+    HumanVsHuman,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -52,6 +87,14 @@ pub enum AIDifficulty {
 }
 
 impl AIDifficulty {
+    /// 所有难度，从易到难
+    pub const ALL: [AIDifficulty; 4] = [
+        AIDifficulty::Easy,
+        AIDifficulty::Medium,
+        AIDifficulty::Hard,
+        AIDifficulty::Expert,
+    ];
+
     pub fn get_depth(&self) -> u32 {
         match self {
             AIDifficulty::Easy => 2,
@@ -80,6 +123,46 @@ impl AIDifficulty {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// 让子棋：和 AI 下时 AI 少一个子开局，或者计时棋里玩家多一倍时间
+pub enum Handicap {
+    None,
+    Knight,
+    Rook,
+    Queen,
+    ExtraTime,
+}
+
+impl Handicap {
+    pub const ALL: [Handicap; 5] = [
+        Handicap::None,
+        Handicap::Knight,
+        Handicap::Rook,
+        Handicap::Queen,
+        Handicap::ExtraTime,
+    ];
+
+    /// 让掉的子和它在底线上的列：按惯例让后翼的马（b 线）、车（a 线）或者后
+    pub fn removed_piece(&self) -> Option<(PieceType, usize)> {
+        match self {
+            Handicap::Knight => Some((PieceType::Knight, 1)),
+            Handicap::Rook => Some((PieceType::Rook, 0)),
+            Handicap::Queen => Some((PieceType::Queen, 3)),
+            Handicap::None | Handicap::ExtraTime => None,
+        }
+    }
+
+    pub fn to_string(&self) -> &str {
+        match self {
+            Handicap::None => "No handicap",
+            Handicap::Knight => "Knight odds",
+            Handicap::Rook => "Rook odds",
+            Handicap::Queen => "Queen odds",
+            Handicap::ExtraTime => "Double time",
+        }
+    }
+}
+
 impl Color {
     /// 获取相反的颜色
     pub fn opposite(&self) -> Color {
@@ -0,0 +1,310 @@
+// UCI（Universal Chess Interface）协议：让 Arena、Cute Chess 等图形界面把 ChessAI 当作引擎使用。
+// 搜索在后台线程进行，搜索时仍然能响应 isready 和 stop
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+
+use crate::ai::{ChessAI, SearchProgress};
+use crate::board::Board;
+use crate::notation::{move_to_uci, parse_fen, parse_uci_move};
+use crate::types::*;
+
+/// 没有指定深度时迭代加深的上限，实际由时间决定停在哪一层
+pub const MAX_DEPTH: u32 = 32;
+
+/// `go infinite` 或只给深度时的时间限制：直到 `stop` 或搜完指定深度
+const NO_TIME_LIMIT: u64 = u64::MAX;
+
+/// 没有 `movestogo` 时假设还要走的步数，用来从剩余时间里分出这一步的时间
+const MOVES_TO_GO: u64 = 30;
+
+/// 一行一行处理 GUI 发来的命令，回复通过 `out` 发出（每条一行，不带换行符）
+pub struct UciEngine {
+    ai: ChessAI,
+    board: Board,
+    side_to_move: Color,
+    out: Sender<String>,
+    search: Option<(Arc<SearchProgress>, JoinHandle<ChessAI>)>,
+}
+
+/// `go` 命令的参数，没有给出的为 `None`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GoParams {
+    pub depth: Option<u32>,
+    pub movetime: Option<u64>,
+    pub nodes: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    pub fn parse(args: &str) -> GoParams {
+        let mut params = GoParams::default();
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            let mut value = || words.next().and_then(|value| value.parse::<u64>().ok());
+            match word {
+                "depth" => params.depth = value().map(|depth| depth as u32),
+                "movetime" => params.movetime = value(),
+                "nodes" => params.nodes = value(),
+                "wtime" => params.wtime = value(),
+                "btime" => params.btime = value(),
+                "winc" => params.winc = value(),
+                "binc" => params.binc = value(),
+                "movestogo" => params.movestogo = value(),
+                "infinite" => params.infinite = true,
+                _ => {}
+            }
+        }
+        params
+    }
+
+    /// 这一步可以用的毫秒数：给了 movetime 就用它，否则从自己的剩余时间中分出一份加上大部分加秒
+    pub fn time_limit(&self, side: Color) -> u64 {
+        if self.infinite {
+            return NO_TIME_LIMIT;
+        }
+        if let Some(movetime) = self.movetime {
+            return movetime;
+        }
+        let (time, increment) = match side {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+        match time {
+            // 留出一半余量，免得在最后几秒超时
+            Some(time) => (time / self.movestogo.unwrap_or(MOVES_TO_GO).max(1)
+                + increment.unwrap_or(0) * 3 / 4)
+                .min(time / 2),
+            None => NO_TIME_LIMIT,
+        }
+    }
+}
+
+impl UciEngine {
+    pub fn new(out: Sender<String>) -> Self {
+        Self {
+            ai: ChessAI::new(MAX_DEPTH),
+            board: Board::new(),
+            side_to_move: Color::White,
+            out,
+            search: None,
+        }
+    }
+
+    /// 处理一行命令，收到 `quit` 时返回 `false`。不认识的命令按协议忽略
+    pub fn handle(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "uci" => {
+                self.send("id name chess_gui");
+                self.send("id author chess_gui");
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.wait_for_search();
+                self.ai = ChessAI::new(MAX_DEPTH);
+                self.set_position("startpos");
+            }
+            "position" => {
+                self.wait_for_search();
+                self.set_position(args);
+            }
+            "go" => {
+                self.wait_for_search();
+                self.go(GoParams::parse(args));
+            }
+            "stop" => self.stop(),
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn send(&self, line: impl Into<String>) {
+        // 接收端关闭说明 GUI 已经退出，没有人再听了
+        let _ = self.out.send(line.into());
+    }
+
+    /// `startpos [moves ...]` 或 `fen <FEN> [moves ...]`。走法不合法时停在它之前
+    fn set_position(&mut self, args: &str) {
+        let (position, moves) = match args.split_once("moves") {
+            Some((position, moves)) => (position.trim(), moves),
+            None => (args.trim(), ""),
+        };
+        let parsed = match position.strip_prefix("fen") {
+            Some(fen) => parse_fen(fen.trim()),
+            None if position == "startpos" => Some((Board::new(), Color::White)),
+            None => None,
+        };
+        let Some((board, side_to_move)) = parsed else {
+            self.send(format!("info string invalid position: {}", position));
+            return;
+        };
+        self.board = board;
+        self.side_to_move = side_to_move;
+
+        for text in moves.split_whitespace() {
+            let legal = parse_uci_move(text)
+                .filter(|mv| self.board.generate_moves(self.side_to_move).contains(mv));
+            let Some(mv) = legal else {
+                self.send(format!("info string illegal move: {}", text));
+                return;
+            };
+            self.board.make_move(mv);
+            self.side_to_move = self.side_to_move.opposite();
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        let progress = Arc::new(SearchProgress::default());
+        let mut ai = std::mem::replace(&mut self.ai, ChessAI::new(1));
+        ai.set_max_depth(params.depth.unwrap_or(MAX_DEPTH));
+        ai.time_limit = params.time_limit(self.side_to_move);
+        ai.node_budget = params.nodes;
+        ai.progress = Some(Arc::clone(&progress));
+
+        let board = self.board.clone();
+        let side = self.side_to_move;
+        let out = self.out.clone();
+        let handle = thread::spawn(move || {
+            let best_move = ai.get_best_move(&board, side);
+            if let Some(score) = ai.last_score {
+                // last_score 是白方视角，UCI 要的是走棋方视角
+                let score = match side {
+                    Color::White => score,
+                    Color::Black => -score,
+                };
+                let progress = ai.progress.as_ref().unwrap();
+                let _ = out.send(format!(
+                    "info depth {} nodes {} score cp {}",
+                    progress.depth(),
+                    progress.nodes(),
+                    score
+                ));
+            }
+            // 第一层都没搜完就被停下时随便走一步合法的棋，总比不回复好
+            let best_move = best_move.or_else(|| board.generate_moves(side).first().copied());
+            let _ = out.send(match best_move {
+                Some(mv) => format!("bestmove {}", move_to_uci(mv)),
+                None => "bestmove 0000".to_string(),
+            });
+            ai.progress = None;
+            ai
+        });
+        self.search = Some((progress, handle));
+    }
+
+    /// 让正在进行的搜索尽快给出结果
+    fn stop(&mut self) {
+        if let Some((progress, _)) = &self.search {
+            progress.stop();
+        }
+        self.wait_for_search();
+    }
+
+    /// 等搜索结束（它会自己发出 bestmove），取回带着置换表的 AI
+    pub fn wait_for_search(&mut self) {
+        if let Some((_, handle)) = self.search.take() {
+            self.ai = handle.join().expect("search thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+
+    fn engine() -> (UciEngine, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (UciEngine::new(sender), receiver)
+    }
+
+    #[test]
+    fn test_handshake() {
+        let (mut engine, out) = engine();
+        assert!(engine.handle("uci"));
+        assert!(engine.handle("isready"));
+        let replies: Vec<String> = out.try_iter().collect();
+        assert_eq!(replies.last().unwrap(), "readyok");
+        assert!(replies.contains(&"uciok".to_string()));
+        assert!(!engine.handle("quit"));
+    }
+
+    #[test]
+    fn test_position_with_moves() {
+        let (mut engine, out) = engine();
+        engine.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(engine.side_to_move, Color::Black);
+        assert_eq!(
+            engine.board.get_piece((5, 5)).map(|piece| piece.piece_type),
+            Some(PieceType::Knight)
+        );
+
+        engine.handle("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 moves e2e4 e8d7");
+        assert_eq!(engine.side_to_move, Color::White);
+        assert_eq!(engine.board.en_passant_target, None);
+
+        // 不合法的走法之后的走法都不走
+        engine.handle("position startpos moves e2e5 e7e5");
+        assert_eq!(engine.side_to_move, Color::White);
+        assert_eq!(
+            out.try_iter().last().unwrap(),
+            "info string illegal move: e2e5"
+        );
+    }
+
+    #[test]
+    fn test_go_depth_finds_mate() {
+        let (mut engine, out) = engine();
+        // 后 h5-f7 杀
+        engine.handle(
+            "position fen r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 0 1",
+        );
+        engine.handle("go depth 2");
+        engine.handle("isready");
+        engine.wait_for_search();
+        let replies: Vec<String> = out.try_iter().collect();
+        assert_eq!(replies.last().unwrap(), "bestmove h5f7");
+        assert!(replies.iter().any(|line| line.starts_with("info depth 2")));
+    }
+
+    #[test]
+    fn test_stop_infinite_search() {
+        let (mut engine, out) = engine();
+        engine.handle("position startpos");
+        engine.handle("go infinite");
+        engine.handle("stop");
+        let bestmove = out.try_iter().last().unwrap();
+        let mv = parse_uci_move(bestmove.strip_prefix("bestmove ").unwrap()).unwrap();
+        assert!(Board::new().generate_moves(Color::White).contains(&mv));
+    }
+
+    #[test]
+    fn test_time_allotment() {
+        let params = GoParams::parse("wtime 60000 btime 1000 winc 1000 binc 0");
+        assert_eq!(params.time_limit(Color::White), 60000 / 30 + 750);
+        assert_eq!(params.time_limit(Color::Black), 1000 / 30);
+        assert_eq!(
+            GoParams::parse("movetime 250").time_limit(Color::Black),
+            250
+        );
+        assert_eq!(
+            GoParams::parse("depth 4").time_limit(Color::White),
+            NO_TIME_LIMIT
+        );
+        // 剩余时间很少时最多用掉一半
+        let params = GoParams::parse("btime 100 binc 1000");
+        assert_eq!(params.time_limit(Color::Black), 50);
+    }
+}
@@ -1,10 +1,69 @@
 use eframe::egui;
 use egui::{Color32, Pos2, Rect, Sense, Vec2};
-use std::time::Instant;
-
-use crate::ai::ChessAI;
-use crate::board::Board;
+use std::io;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::ai::{ChessAI, SearchProgress};
+use crate::bitboard::color_index;
+use crate::board::{Board, BoardDelta};
+use crate::clock::{self, ChessClock, TimeControl};
+use crate::draw;
+use crate::eval_cache;
+use crate::library::{self, GameEntry, LibraryConfig};
+use crate::mates::{self, Attempt, MatePuzzle, PuzzleRun, PuzzleStats};
+use crate::notation::{self, CheckStatus, LineItem, MoveHistory, NodeId};
+use crate::opening;
+use crate::pgn;
+use crate::profile::{self, Outcome, Profile};
+use crate::puzzle;
+use crate::savegame::{self, SavedGame};
+use crate::settings::GameSettings;
+use crate::share;
+use crate::training::{self, Guess, Training};
 use crate::types::*;
+use crate::variant::{Ending, Rules, Variant};
+
+/// 对局库窗口中对一局棋的操作
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LibraryAction {
+    Load,
+    Replay,
+    Export,
+    Delete,
+}
+
+/// 双人对弈自动翻转棋盘后，"换人" 提示显示的时间
+const HANDOFF_DURATION: Duration = Duration::from_millis(1500);
+
+/// "Hint" 按钮替玩家搜索的深度和时间上限（毫秒）
+const HINT_DEPTH: u32 = 3;
+const HINT_TIME_LIMIT_MS: u64 = 1000;
+
+/// 分析模式对每个局面最多搜索的深度和时间（毫秒），搜完就停在那里等局面改变
+const ANALYSIS_DEPTH: u32 = 8;
+const ANALYSIS_TIME_LIMIT_MS: u64 = 10_000;
+
+/// 分析模式下棋盘左边评估条的宽度
+const EVAL_BAR_WIDTH: f32 = 24.0;
+
+/// 评估条上白方占的比例：把评估（白方视角，兵 = 100）按 Elo 公式换算成白方的胜率，杀棋时占满
+fn white_share(score: i32) -> f32 {
+    if score.abs() > 90000 {
+        return if score > 0 { 1.0 } else { 0.0 };
+    }
+    1.0 / (1.0 + 10f32.powf(-score as f32 / 400.0))
+}
+
+/// 从初始局面走了 `plies` 个半回合之后轮到的一方
+fn color_after(plies: usize) -> Color {
+    if plies.is_multiple_of(2) {
+        Color::White
+    } else {
+        Color::Black
+    }
+}
 
 /// Main application structure that holds the board, AI, and game state
 pub struct ChessApp {
@@ -17,15 +76,84 @@ pub struct ChessApp {
     pub status_message: String,
     pub ai_thinking: bool,
     pub ai_move_start: Option<Instant>,
+    ai_move_ready: Option<Move>, // 已经算好、等停顿结束才走出的 AI 走法
+    ai_search: Option<JoinHandle<(ChessAI, Option<Move>)>>, // 后台进行的搜索，结束后交回 AI 和走法
+    ai_progress: Option<Arc<SearchProgress>>, // 后台搜索的进度，也用来中止它
     pub ai_difficulty: AIDifficulty,
+    pub game_mode: GameMode,
+    pub human_color: Color, // 和 AI 下时玩家执哪一方，执黑时 AI 先走
+    pub handicap: Handicap, // 新开局的让子，只在和 AI 下时生效
+    pub variant: Variant,   // 对局的变体规则，换变体时重新开局
+    pub time_control: Option<TimeControl>, // 新开局使用的时限，None 为不计时
+    pub clock: Option<ChessClock>,
+    pub board_flipped: bool,             // 翻转显示：黑方在下
+    handoff_until: Option<Instant>,      // 双人对弈翻转棋盘后 "换人" 提示的结束时间
     pub promotion_pending: Option<Move>, // 待升变的走法
+    pub pending_move: Option<Move>,      // 确认模式下已选好、等待 Confirm 的走法
+    dragging: Option<Square>,            // 正在拖动的棋子原来所在的格子
+    pub move_history: MoveHistory,
+    last_move: Option<BoardDelta>, // 上一步棋改变的格子，在棋盘上标出来
+    pub settings: GameSettings,
+    pub game_over_reason: String,
+    pub draw_offer_pending: bool,       // AI 提和，等待玩家回应
+    pub last_draw_offer: Option<usize>, // 上一次提和时的步数
+    cache_saved: bool,                  // 本局结束后是否已保存评估缓存
+    cache_save: Option<JoinHandle<io::Result<()>>>, // 正在进行的后台保存
+    puzzles_collected: bool,            // 本局结束后是否已开始查找练习题
+    puzzle_scan: Option<JoinHandle<io::Result<usize>>>, // 后台查找练习题，结果为新增题数
+    puzzle_message: Option<String>,     // 查找结束后显示在结算界面上
+    profile: Profile,                   // 按难度统计的战绩，和 AI 下完一局后更新并保存
+    result_recorded: bool,              // 本局结果是否已记入档案
+    profile_message: Option<String>,    // 连胜和新徽章，显示在结算界面上
+    annotating: Option<NodeId>,         // 正在编辑注释的那一步
+    redo_stack: Vec<NodeId>,            // 悔棋前所在的位置，重做时跳回去；最近一次在最后
+    comment_draft: String,              // 注释输入框的内容
+    pgn_text: String,                   // PGN 导入导出文本框
+    pgn_message: Option<String>,        // 导入失败等提示
+    save_message: Option<String>,       // 存档、读档的结果
+    library_config: LibraryConfig,      // 对局库目录和是否自动保存
+    library_dir_text: String,           // 对局库窗口中目录输入框的内容
+    library_open: bool,                 // 是否显示对局库窗口
+    library_games: Vec<GameEntry>,      // 打开窗口或者刷新时读到的对局
+    library_delete: Option<usize>,      // 等待确认删除的对局
+    library_message: Option<String>,    // 自动保存、删除等操作的结果
+    autosaved: bool,                    // 本局结束后是否已经自动保存过
+    training: Option<Training>,         // 进行中的猜步训练
+    training_side: Color,               // 猜步训练时玩家猜哪一方
+    training_judge: Option<JoinHandle<Guess>>, // 后台给玩家的猜测打分
+    training_best: Option<f32>,         // 以前练这盘棋的最高得分率
+    training_message: Option<String>,   // 上一次猜测的结果或者训练的总分
+    mate_puzzles: Vec<MatePuzzle>,      // 内置的将杀题和从对局生成的练习题
+    mate_index: usize,                  // 选中的将杀题
+    mate_run: Option<PuzzleRun>,        // 正在做的将杀题
+    mate_stats: PuzzleStats,            // 做题记录
+    mate_message: Option<String>,       // 上一步的结果、解法或者读取题目文件的错误
+    hint: Option<(u64, Move)>,          // 提示的走法和它所在局面的键，局面变了就不再显示
+    hint_search: Option<(u64, JoinHandle<Option<Move>>)>, // 后台计算的提示
+    pub analysis_mode: bool,            // 持续分析当前局面，在棋盘左边显示评估条
+    analysis: Option<(u64, Arc<SearchProgress>, JoinHandle<()>)>, // 正在分析的局面、进度和线程
 }
 
 impl ChessApp {
     pub fn new() -> Self {
+        let mut ai = ChessAI::new(4);
+        // 缓存不存在或已失效时直接从空置换表开始
+        let _ = ai.load_cache(&eval_cache::default_cache_path());
+        let library_config =
+            LibraryConfig::load(&library::default_config_path()).unwrap_or_default();
+        // 练习题文件读不出来时只做内置的将杀题，在 Puzzles 部分显示错误
+        let (mate_puzzles, mate_message) = match mates::load_puzzles(&puzzle::default_puzzle_path())
+        {
+            Ok(puzzles) => (puzzles, None),
+            Err(err) => (
+                mates::builtin_puzzles(),
+                Some(format!("Could not read puzzles: {}", err)),
+            ),
+        };
+
         Self {
             board: Board::new(),
-            ai: ChessAI::new(4),
+            ai,
             current_player: Color::White,
             selected_square: None,
             valid_moves: Vec::new(),
@@ -33,175 +161,2109 @@ impl ChessApp {
             status_message: "White to move".to_string(),
             ai_thinking: false,
             ai_move_start: None,
+            ai_move_ready: None,
+            ai_search: None,
+            ai_progress: None,
             ai_difficulty: AIDifficulty::Medium,
+            game_mode: GameMode::HumanVsAI,
+            human_color: Color::White,
+            handicap: Handicap::None,
+            variant: Variant::Standard,
+            time_control: None,
+            clock: None,
+            board_flipped: false,
+            handoff_until: None,
             promotion_pending: None,
+            pending_move: None,
+            dragging: None,
+            move_history: MoveHistory::new(),
+            last_move: None,
+            settings: GameSettings::default(),
+            game_over_reason: String::new(),
+            draw_offer_pending: false,
+            last_draw_offer: None,
+            cache_saved: false,
+            cache_save: None,
+            puzzles_collected: false,
+            puzzle_scan: None,
+            puzzle_message: None,
+            profile: Profile::load_default(),
+            result_recorded: false,
+            profile_message: None,
+            annotating: None,
+            redo_stack: Vec::new(),
+            comment_draft: String::new(),
+            pgn_text: String::new(),
+            pgn_message: None,
+            save_message: None,
+            library_dir_text: library_config.dir.display().to_string(),
+            library_config,
+            library_open: false,
+            library_games: Vec::new(),
+            library_delete: None,
+            library_message: None,
+            autosaved: false,
+            training: None,
+            training_side: Color::White,
+            training_judge: None,
+            training_best: None,
+            training_message: None,
+            mate_puzzles,
+            mate_index: 0,
+            mate_run: None,
+            mate_stats: PuzzleStats::load(&mates::default_stats_path()).unwrap_or_default(),
+            mate_message,
+            hint: None,
+            hint_search: None,
+            analysis_mode: false,
+            analysis: None,
+        }
+    }
+
+    pub fn piece_to_unicode(&self, piece: Piece) -> &str {
+        match (piece.color, piece.piece_type) {
+            (Color::White, PieceType::Pawn) => "♙ ",
+            (Color::White, PieceType::Rook) => "♖ ",
+            (Color::White, PieceType::Knight) => "♘ ",
+            (Color::White, PieceType::Bishop) => "♗ ",
+            (Color::White, PieceType::Queen) => "♕ ",
+            (Color::White, PieceType::King) => "♔ ",
+            (Color::Black, PieceType::Pawn) => "♟ ",
+            (Color::Black, PieceType::Rook) => "♜ ",
+            (Color::Black, PieceType::Knight) => "♞ ",
+            (Color::Black, PieceType::Bishop) => "♝ ",
+            (Color::Black, PieceType::Queen) => "♛ ",
+            (Color::Black, PieceType::King) => "♚ ",
+        }
+    }
+
+    pub fn handle_square_click(&mut self, row: usize, col: usize) {
+        if self.game_state != GameState::Playing
+            || !self.is_human_turn()
+            || self.handoff_until.is_some()
+            || self.ai_thinking
+            || self.promotion_pending.is_some()
+            || self.draw_offer_pending
+        // 如果正在等待升变选择，不处理点击
+        {
+            return;
+        }
+
+        if let Some(selected) = self.selected_square {
+            // 确认模式下任何新的点击都会取消之前等待确认的走法
+            self.cancel_pending_move();
+
+            // Try to make a move
+            let mv = Move {
+                from: selected,
+                to: (row, col),
+                promotion: None,
+            };
+
+            // Check if the move is in valid moves list (without considering promotion type)
+            let move_found = self
+                .valid_moves
+                .iter()
+                .find(|valid_mv| valid_mv.from == mv.from && valid_mv.to == mv.to);
+
+            if move_found.is_some() {
+                if self.settings.confirm_moves {
+                    // 先记下走法，等玩家按 Confirm
+                    self.pending_move = Some(mv);
+                    self.status_message = format!(
+                        "Play {}? Press Confirm",
+                        notation::move_to_san(&self.board, mv)
+                    );
+                } else {
+                    self.commit_move(mv);
+                }
+            } else {
+                // Select new piece or deselect
+                if let Some(piece) = self.board.get_piece((row, col)) {
+                    if piece.color == self.current_player {
+                        self.selected_square = Some((row, col));
+                        self.valid_moves = self
+                            .rules()
+                            .legal_moves(&self.board, self.current_player)
+                            .into_iter()
+                            .filter(|mv| mv.from == (row, col))
+                            .collect();
+                    } else {
+                        self.selected_square = None;
+                        self.valid_moves.clear();
+                    }
+                } else {
+                    self.selected_square = None;
+                    self.valid_moves.clear();
+                }
+            }
+        } else {
+            // Select a piece
+            if let Some(piece) = self.board.get_piece((row, col))
+                && piece.color == self.current_player
+            {
+                self.selected_square = Some((row, col));
+                self.valid_moves = self
+                    .rules()
+                    .legal_moves(&self.board, self.current_player)
+                    .into_iter()
+                    .filter(|mv| mv.from == (row, col))
+                    .collect();
+            }
+        }
+    }
+
+    /// 现在是否轮到人走棋（猜步训练时轮到玩家猜、上一次猜测已经打完分；做将杀题时题目还没做完）
+    fn is_human_turn(&self) -> bool {
+        if let Some(training) = &self.training {
+            return training.is_guessing() && self.training_judge.is_none();
+        }
+        if let Some(run) = &self.mate_run {
+            return !run.is_finished();
+        }
+        self.game_mode == GameMode::HumanVsHuman || self.current_player == self.human_color
+    }
+
+    /// 和 AI 下时 AI 执的一方
+    fn ai_color(&self) -> Color {
+        self.human_color.opposite()
+    }
+
+    /// 和 AI 下、对局还没结束并且轮到 AI 时，让 AI 开始思考
+    fn resume_ai(&mut self) {
+        if self.game_mode == GameMode::HumanVsAI
+            && self.current_player == self.ai_color()
+            && self.game_state == GameState::Playing
+        {
+            self.ai_thinking = true;
+            self.ai_move_start = Some(Instant::now());
+        }
+    }
+
+    /// 执行玩家选定的走法：升变时先弹出选择对话框，否则直接走棋并交给对方
+    fn commit_move(&mut self, mv: Move) {
+        // Check if this is pawn promotion
+        let is_promotion = self.board.get_piece(mv.from).is_some_and(|piece| {
+            piece.piece_type == PieceType::Pawn
+                && ((piece.color == Color::White && mv.to.0 == 0)
+                    || (piece.color == Color::Black && mv.to.0 == 7))
+        });
+
+        if is_promotion {
+            // 设置待升变的走法，等待用户选择
+            self.promotion_pending = Some(mv);
+            self.status_message = "Choose piece for promotion".to_string();
+        } else {
+            // 普通走法，直接执行
+            self.play_human_move(mv);
+        }
+    }
+
+    /// 走出玩家的走法；猜步训练时不走，而是作为猜测交给后台打分；做将杀题时先和解法比较
+    fn play_human_move(&mut self, mv: Move) {
+        if self.training.is_some() {
+            self.submit_guess(mv);
+        } else if self.mate_run.is_some() {
+            self.check_mate_move(mv);
+        } else {
+            self.play_move(mv);
+            self.after_human_move();
+        }
+    }
+
+    /// 人走完一步后：和 AI 下时让 AI 开始思考，双人对弈时按设置翻转棋盘并提示换人
+    fn after_human_move(&mut self) {
+        self.selected_square = None;
+        self.valid_moves.clear();
+        if self.game_state != GameState::Playing {
+            return;
+        }
+
+        match self.game_mode {
+            GameMode::HumanVsAI => {
+                self.status_message = format!("{}AI is thinking...", self.check_announcement());
+                self.ai_thinking = true;
+                self.ai_move_start = Some(Instant::now());
+            }
+            GameMode::HumanVsHuman => {
+                if self.settings.auto_rotate {
+                    self.board_flipped = self.current_player == Color::Black;
+                    self.handoff_until = Some(Instant::now() + HANDOFF_DURATION);
+                }
+            }
+        }
+    }
+
+    /// 当前局面的键，用来判断提示和分析是不是针对这个局面的
+    fn position_key(&self) -> u64 {
+        self.ai.position_key(&self.board, self.current_player)
+    }
+
+    /// 棋盘格子中心在屏幕上的位置
+    fn square_center(&self, board_rect: Rect, square_size: f32, square: Square) -> Pos2 {
+        let (row, col) = self.view_square(square.0, square.1);
+        board_rect.min + Vec2::new(col as f32 + 0.5, row as f32 + 0.5) * square_size
+    }
+
+    /// 屏幕上第 `row` 行第 `col` 列对应的棋盘格子；翻转是中心对称的，反过来换算也用这个函数
+    fn view_square(&self, row: usize, col: usize) -> Square {
+        if self.board_flipped {
+            (7 - row, 7 - col)
+        } else {
+            (row, col)
+        }
+    }
+
+    /// 屏幕坐标 `pos` 下的棋盘格子，不在棋盘上时为 `None`
+    fn square_at(&self, board_rect: Rect, square_size: f32, pos: Pos2) -> Option<Square> {
+        if !board_rect.contains(pos) {
+            return None;
+        }
+        let relative_pos = pos - board_rect.min;
+        let col = ((relative_pos.x / square_size) as usize).min(7);
+        let row = ((relative_pos.y / square_size) as usize).min(7);
+        Some(self.view_square(row, col))
+    }
+
+    /// 按住棋子开始拖动：和点击一样选中它，能走的时候才真正拖起来
+    fn start_drag(&mut self, square: Square) {
+        if self.selected_square != Some(square) {
+            self.handle_square_click(square.0, square.1);
+        }
+        if self.selected_square == Some(square) {
+            self.dragging = Some(square);
+        }
+    }
+
+    /// 松开拖动的棋子：放到别的格子上相当于点击那个格子，放回原处或棋盘外则保持选中
+    fn finish_drag(&mut self, target: Option<Square>) {
+        let Some(from) = self.dragging.take() else {
+            return;
+        };
+        if let Some((row, col)) = target
+            && (row, col) != from
+            && self.selected_square == Some(from)
+        {
+            self.handle_square_click(row, col);
+        }
+    }
+
+    /// 确认模式下按 Confirm：执行等待中的走法
+    pub fn confirm_pending_move(&mut self) {
+        if let Some(mv) = self.pending_move.take() {
+            self.commit_move(mv);
+        }
+    }
+
+    /// 放弃等待确认的走法，棋子保持选中，状态栏恢复
+    pub fn cancel_pending_move(&mut self) {
+        if self.pending_move.take().is_some() {
+            self.update_game_state();
+        }
+    }
+
+    /// 记录走法的 SAN 并执行，然后轮到对方
+    pub fn play_move(&mut self, mv: Move) {
+        self.redo_stack.clear();
+        self.move_history.record(&self.board, mv);
+        if let Some(clock) = &mut self.clock {
+            clock.press(self.current_player, Instant::now());
+        }
+        self.last_move = self.rules().apply_move(&mut self.board, mv);
+        self.current_player = self.current_player.opposite();
+        self.update_game_state();
+    }
+
+    /// 上一步棋将军时，在状态栏前面加上 "Check!" 提示
+    fn check_announcement(&self) -> &'static str {
+        match self.move_history.last().map(|record| record.check) {
+            Some(CheckStatus::Check) => "Check! ",
+            _ => "",
+        }
+    }
+
+    /// 当前对局的规则；猜步训练和将杀题总是按标准规则
+    fn rules(&self) -> &'static dyn Rules {
+        if self.in_exercise() {
+            Variant::Standard.rules()
+        } else {
+            self.variant.rules()
+        }
+    }
+
+    pub fn update_game_state(&mut self) {
+        let rules = self.rules();
+        let draw_reason = draw::draw_reason(&self.move_history, &self.ai).filter(|&reason| {
+            reason != DrawReason::InsufficientMaterial || rules.insufficient_material_draws()
+        });
+        if let Some(ending) = rules.ending(&self.board, self.current_player) {
+            match ending {
+                Ending::Checkmate => {
+                    self.game_state = match self.current_player {
+                        Color::White => GameState::BlackWins,
+                        Color::Black => GameState::WhiteWins,
+                    };
+                    self.game_over_reason = "Victory by Checkmate".to_string();
+                    self.status_message =
+                        format!("{:?} wins by checkmate!", self.current_player.opposite());
+                }
+                Ending::Stalemate => {
+                    self.game_state = GameState::Draw(DrawReason::Stalemate);
+                    self.game_over_reason = "Game ended in Stalemate".to_string();
+                    self.status_message = "Draw by stalemate!".to_string();
+                }
+                Ending::Win { winner, reason } => {
+                    self.game_state = match winner {
+                        Color::White => GameState::WhiteWins,
+                        Color::Black => GameState::BlackWins,
+                    };
+                    self.game_over_reason = reason.to_string();
+                    self.status_message = format!("{:?} wins: {}!", winner, reason);
+                }
+            }
+        } else if let Some(reason) = draw_reason {
+            self.game_state = GameState::Draw(reason);
+            self.game_over_reason = format!("Draw by {}", reason.description());
+            self.status_message = format!("Draw by {}!", reason.description());
+        } else if self.board.is_in_check(self.current_player) {
+            self.status_message = format!("Check! {:?} to move", self.current_player);
+        } else {
+            self.status_message = format!("{:?} to move", self.current_player);
+        }
+    }
+
+    pub fn new_game(&mut self) {
+        // 让子只在和 AI 下时生效：AI 少一个子开局，或者玩家的棋钟多一倍时间
+        let handicap = match self.game_mode {
+            GameMode::HumanVsAI => self.handicap,
+            GameMode::HumanVsHuman => Handicap::None,
+        };
+        self.board = Board::with_handicap(handicap, self.ai_color());
+        self.current_player = Color::White;
+        self.selected_square = None;
+        self.valid_moves.clear();
+        self.hint = None;
+        self.game_state = GameState::Playing;
+        self.status_message = "White to move".to_string();
+        // 和 AI 下时玩家的一方在下面
+        self.board_flipped =
+            self.game_mode == GameMode::HumanVsAI && self.human_color == Color::Black;
+        self.clock = self.time_control.map(|control| {
+            let mut remaining = [control.initial; 2];
+            if handicap == Handicap::ExtraTime {
+                remaining[color_index(self.human_color)] *= 2;
+            }
+            ChessClock::with_remaining(control, remaining[0], remaining[1])
+        });
+        self.handoff_until = None;
+        self.cancel_ai_search();
+        self.ai_thinking = false;
+        self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.promotion_pending = None;
+        self.pending_move = None;
+        self.move_history = match handicap.removed_piece() {
+            Some(_) => MoveHistory::with_start(self.board.clone()),
+            None => MoveHistory::new(),
+        };
+        self.last_move = None;
+        self.ai.reset_game_history();
+        self.game_over_reason.clear();
+        self.draw_offer_pending = false;
+        self.last_draw_offer = None;
+        self.cache_saved = false;
+        self.puzzles_collected = false;
+        self.puzzle_message = None;
+        self.autosaved = false;
+        self.result_recorded = false;
+        self.profile_message = None;
+        self.annotating = None;
+        self.redo_stack.clear();
+        // 打分线程自己结束，结果不再需要
+        self.training = None;
+        self.training_judge = None;
+        self.mate_run = None;
+        // 玩家执黑时 AI 先走
+        self.resume_ai();
+    }
+
+    /// 导入 PGN 文本框中的棋谱，棋盘停在最后一步之后；和 AI 下时轮到 AI 则 AI 接着走
+    pub fn import_pgn(&mut self) {
+        match pgn::parse(&self.pgn_text) {
+            Ok(game) => {
+                self.new_game();
+                self.board = game.board;
+                self.current_player = game.side_to_move;
+                self.move_history = game.history;
+                self.update_game_state();
+                self.ai_thinking = false;
+                self.ai_move_start = None;
+                self.resume_ai();
+                self.pgn_message = Some(format!("Imported {} moves", self.move_history.len()));
+            }
+            Err(err) => self.pgn_message = Some(format!("Import failed: {}", err)),
+        }
+    }
+
+    /// 从 PGN 文本框中的分享码载入对局，棋盘停在最后一步之后
+    pub fn load_share_code(&mut self) {
+        match share::decode(&self.pgn_text) {
+            Ok(game) => {
+                self.new_game();
+                self.board = game.board;
+                self.current_player = game.side_to_move;
+                self.move_history = game.history;
+                self.update_game_state();
+                self.ai_thinking = false;
+                self.ai_move_start = None;
+                self.resume_ai();
+                self.pgn_message = Some(format!("Loaded {} moves", self.move_history.len()));
+            }
+            Err(err) => self.pgn_message = Some(format!("Load failed: {}", err)),
+        }
+    }
+
+    /// 把当前对局（包括注释）导出到 PGN 文本框
+    pub fn export_pgn(&mut self) {
+        let (white, black) = match (self.game_mode, self.human_color) {
+            (GameMode::HumanVsAI, Color::White) => ("Player", "Computer"),
+            (GameMode::HumanVsAI, Color::Black) => ("Computer", "Player"),
+            (GameMode::HumanVsHuman, _) => ("White", "Black"),
+        };
+        self.pgn_text = pgn::export(
+            &self.move_history,
+            white,
+            black,
+            pgn::result_text(self.game_state),
+        );
+        self.pgn_message = None;
+    }
+
+    /// 当前对局（走法树、当前位置、模式、玩家的颜色、难度和棋盘方向）的存档
+    fn saved_game(&self) -> SavedGame {
+        SavedGame {
+            history: self.move_history.clone(),
+            mode: self.game_mode,
+            player: self.human_color,
+            difficulty: self.ai_difficulty,
+            variant: self.variant,
+            flipped: self.board_flipped,
+            state: self.game_state,
+            reason: self.game_over_reason.clone(),
+            clock: self.clock.clone().map(|mut clock| {
+                clock.stop(Instant::now());
+                clock
+            }),
+        }
+    }
+
+    /// 把当前对局保存到存档文件
+    pub fn save_game(&mut self) {
+        self.save_message = Some(
+            match self.saved_game().save(&savegame::default_save_path()) {
+                Ok(()) => format!("Saved after {} moves", self.move_history.len()),
+                Err(err) => format!("Save failed: {}", err),
+            },
+        );
+    }
+
+    /// 读取存档，回到保存时的局面接着下；和 AI 下时轮到 AI 则 AI 接着走
+    pub fn load_game(&mut self) {
+        match SavedGame::load(&savegame::default_save_path()) {
+            Ok(saved) => self.load_saved(saved),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.save_message = Some("No saved game".to_string());
+            }
+            Err(err) => self.save_message = Some(format!("Load failed: {}", err)),
+        }
+    }
+
+    // 摆出存档中的对局；已经结束的对局不再记战绩，也不再自动保存
+    fn load_saved(&mut self, saved: SavedGame) {
+        self.time_control = saved.clock.as_ref().map(ChessClock::control);
+        self.new_game();
+        self.game_mode = saved.mode;
+        self.human_color = saved.player;
+        self.variant = saved.variant;
+        self.set_ai_difficulty(saved.difficulty);
+        self.clock = saved.clock;
+        self.move_history = saved.history;
+        self.sync_board_to_history();
+        self.board_flipped = saved.flipped;
+        // 认输、协议和棋从局面上看不出来，按存档恢复；存档前已经记过战绩
+        if self.game_state == GameState::Playing && saved.state != GameState::Playing {
+            self.ai_thinking = false;
+            self.ai_move_start = None;
+            self.game_state = saved.state;
+            self.status_message = format!("{}!", saved.reason);
+            self.game_over_reason = saved.reason;
+        }
+        if self.game_state != GameState::Playing {
+            self.result_recorded = true;
+            self.autosaved = true;
+        } else if let Some(clock) = &mut self.clock
+            && !self.move_history.is_empty()
+        {
+            // 存档时停着的棋钟从当前一方接着走
+            clock.switch_to(self.current_player, Instant::now());
+        }
+        self.save_message = Some(format!("Loaded {} moves", self.move_history.len()));
+    }
+
+    /// 跳到棋谱中某一步之后的局面（`None` 为初始局面）。
+    /// 从这里走出不同的一步时，新走法作为变着加入棋谱
+    pub fn jump_to(&mut self, node: Option<NodeId>) {
+        self.move_history.go_to(node);
+        self.sync_board_to_history();
+    }
+
+    /// 按棋谱当前位置重新摆出局面
+    fn sync_board_to_history(&mut self) {
+        self.redo_stack.clear();
+        // 界面在 AI 思考时仍可操作，换了局面的搜索结果作废
+        self.cancel_ai_search();
+        self.ai_thinking = false;
+        self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.board = self.move_history.start_board();
+        self.current_player = Color::White;
+        self.last_move = None;
+        for record in self.move_history.records() {
+            self.last_move = self.rules().apply_move(&mut self.board, record.mv);
+            self.current_player = self.current_player.opposite();
+        }
+        self.selected_square = None;
+        self.valid_moves.clear();
+        self.pending_move = None;
+        self.game_state = GameState::Playing;
+        self.update_game_state();
+        // 悔棋和翻看棋谱时改走当前一方的钟，不加秒
+        if let Some(clock) = &mut self.clock
+            && clock.running().is_some()
+        {
+            clock.switch_to(self.current_player, Instant::now());
+        }
+
+        match self.game_mode {
+            // 退回到轮到 AI 的局面时只浏览；已经是这条线的最后一步，AI 接着走
+            GameMode::HumanVsAI => {
+                if !self.move_history.has_next() {
+                    self.resume_ai();
+                }
+            }
+            GameMode::HumanVsHuman => {
+                if self.settings.auto_rotate {
+                    self.board_flipped = self.current_player == Color::Black;
+                }
+            }
+        }
+    }
+
+    /// 能否在棋谱中前后移动：AI 思考中或者等待升变选择时不行
+    fn can_undo(&self) -> bool {
+        // 和 AI 下时执黑，只有 AI 的第一步时没有可悔的棋
+        let first_own_move = match self.game_mode {
+            GameMode::HumanVsAI if self.human_color == Color::Black => 2,
+            _ => 1,
+        };
+        self.move_history.len() >= first_own_move
+            && self.promotion_pending.is_none()
+            && !self.in_exercise()
+    }
+
+    /// 悔棋：和 AI 下时连同 AI 的应着一起撤销，回到玩家上一次走棋之前；
+    /// AI 还没走出时只撤销玩家刚走的一步。撤销的走法仍留在棋谱里，可以重做
+    pub fn undo(&mut self) {
+        let Some(node) = self.move_history.current() else {
+            return;
+        };
+        self.cancel_ai_search();
+        self.ai_thinking = false;
+        self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.draw_offer_pending = false;
+        self.move_history.go_back();
+        while self.game_mode == GameMode::HumanVsAI
+            && color_after(self.move_history.len()) == self.ai_color()
+            && self.move_history.go_back()
+        {}
+
+        let mut redo_stack = std::mem::take(&mut self.redo_stack);
+        redo_stack.push(node);
+        self.sync_board_to_history();
+        self.redo_stack = redo_stack;
+    }
+
+    /// 重做最近一次悔棋撤销的走法
+    pub fn redo(&mut self) {
+        let mut redo_stack = std::mem::take(&mut self.redo_stack);
+        if let Some(node) = redo_stack.pop() {
+            self.move_history.go_to(Some(node));
+            self.sync_board_to_history();
+        }
+        self.redo_stack = redo_stack;
+    }
+
+    fn can_navigate(&self) -> bool {
+        !self.ai_thinking && self.promotion_pending.is_none() && !self.in_exercise()
+    }
+
+    /// 点击棋谱中的一步：跳到这一步并编辑它的注释；再点一次当前这一步则收起编辑区
+    fn select_move(&mut self, node: NodeId) {
+        if self.annotating == Some(node) {
+            self.annotating = None;
+            return;
+        }
+        if self.can_navigate() {
+            self.jump_to(Some(node));
+        }
+        self.annotating = Some(node);
+        self.comment_draft = self
+            .move_history
+            .record_at(node)
+            .comment
+            .clone()
+            .unwrap_or_default();
+    }
+
+    // 棋谱面板顶部的前后翻动按钮
+    fn show_navigation(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.can_navigate(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⏮").on_hover_text("Start position").clicked() {
+                    self.jump_to(None);
+                }
+                if ui.button("◀").on_hover_text("Back").clicked() && self.move_history.go_back() {
+                    self.sync_board_to_history();
+                }
+                if ui.button("▶").on_hover_text("Forward").clicked()
+                    && self.move_history.go_forward()
+                {
+                    self.sync_board_to_history();
+                }
+                if ui.button("⏭").on_hover_text("End of line").clicked() {
+                    while self.move_history.go_forward() {}
+                    self.sync_board_to_history();
+                }
+            });
+        });
+    }
+
+    // 棋谱面板：主线和嵌套的变着（括号里、颜色较淡），注解符号跟在走法后面，
+    // 注释以斜体显示；当前局面对应的那一步高亮
+    fn show_move_list(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        let current = self.move_history.current();
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 4.0;
+            if let Some(comment) = self.move_history.initial_comment() {
+                ui.label(egui::RichText::new(comment).italics().weak());
+            }
+            let mut depth = 0;
+            for item in self.move_history.walk() {
+                match item {
+                    LineItem::StartVariation => {
+                        depth += 1;
+                        ui.weak("(");
+                    }
+                    LineItem::EndVariation => {
+                        depth -= 1;
+                        ui.weak(")");
+                    }
+                    LineItem::Move { node, number } => {
+                        let record = self.move_history.record_at(node);
+                        let mut label = egui::RichText::new(record.annotated_san()).monospace();
+                        if let Some(number) = number {
+                            ui.monospace(number);
+                        }
+                        if depth > 0 {
+                            label = label.weak();
+                        }
+                        if ui
+                            .selectable_label(current == Some(node), label)
+                            .on_hover_text("Go to this move and annotate it")
+                            .clicked()
+                        {
+                            clicked = Some(node);
+                        }
+                        if let Some(comment) = &record.comment {
+                            ui.label(egui::RichText::new(comment).italics().weak());
+                        }
+                    }
+                }
+            }
+        });
+        if let Some(node) = clicked {
+            self.select_move(node);
+        }
+    }
+
+    // 选中一步后的注释编辑区：注释输入框和注解符号按钮
+    fn show_annotation_editor(&mut self, ui: &mut egui::Ui) {
+        let Some(node) = self.annotating else {
+            return;
+        };
+        ui.separator();
+        let record = self.move_history.record_at(node);
+        let dots = match record.color {
+            Color::White => ".",
+            Color::Black => "...",
+        };
+        ui.label(format!(
+            "Annotate {}{} {}",
+            self.move_history.ply(node) / 2 + 1,
+            dots,
+            record.san
+        ));
+        if ui
+            .add(egui::TextEdit::multiline(&mut self.comment_draft).desired_rows(2))
+            .changed()
+        {
+            self.move_history
+                .set_comment(node, Some(self.comment_draft.clone()));
+        }
+        ui.horizontal_wrapped(|ui| {
+            for nag in 1..=6 {
+                let active = self.move_history.record_at(node).nags.contains(&nag);
+                let glyph = notation::nag_glyph(nag).unwrap_or_default();
+                if ui.selectable_label(active, glyph).clicked() {
+                    self.move_history.toggle_nag(node, nag);
+                }
+            }
+        });
+    }
+
+    // 右侧面板底部的 PGN 导入导出
+    fn show_pgn_section(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("PGN", |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Import").clicked() {
+                    self.import_pgn();
+                }
+                if ui.button("Export").clicked() {
+                    self.export_pgn();
+                    ui.ctx().copy_text(self.pgn_text.clone());
+                    self.pgn_message = Some("Copied to clipboard".to_string());
+                }
+            });
+            ui.horizontal(|ui| {
+                // 分享码只记走法，让子棋这类特殊的起始局面放不进去
+                if ui
+                    .add_enabled(
+                        self.move_history.start().is_none(),
+                        egui::Button::new("Copy share code"),
+                    )
+                    .on_hover_text("A short code with the moves up to this position")
+                    .on_disabled_hover_text("Use PGN export for games from a custom position")
+                    .clicked()
+                {
+                    self.pgn_text = share::encode(&self.move_history);
+                    ui.ctx().copy_text(self.pgn_text.clone());
+                    self.pgn_message = Some("Share code copied to clipboard".to_string());
+                }
+                if ui
+                    .button("Load from code")
+                    .on_hover_text("Paste a share code into the box below first")
+                    .clicked()
+                {
+                    self.load_share_code();
+                }
+            });
+            if let Some(message) = &self.pgn_message {
+                ui.small(message);
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("pgn_text")
+                .max_height(160.0)
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.pgn_text)
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        });
+    }
+
+    /// 用 PGN 文本框中的棋谱开始猜步训练：对方的走法直接走出，轮到玩家一方时先猜再揭晓
+    pub fn start_training(&mut self) {
+        let game = match pgn::parse(&self.pgn_text) {
+            Ok(game) => game,
+            Err(err) => {
+                self.training_message = Some(format!("Import failed: {}", err));
+                return;
+            }
+        };
+        let Some(training) = Training::new(&game, self.training_side) else {
+            self.training_message = Some(format!("{:?} has no moves to guess", self.training_side));
+            return;
+        };
+
+        self.time_control = None;
+        self.game_mode = GameMode::HumanVsHuman;
+        self.new_game();
+        // 从棋谱的起始局面开始，例如让子棋
+        if let Some(start) = game.history.start() {
+            self.board = start.clone();
+            self.move_history = MoveHistory::with_start(start.clone());
+        }
+        // 棋谱不是玩家下的，不从中找练习题，也不存进对局库
+        self.puzzles_collected = true;
+        self.autosaved = true;
+        self.board_flipped = training.side == Color::Black;
+        self.training_best = training::load_results(&training::default_training_path())
+            .ok()
+            .and_then(|results| training::best_accuracy(&results, &training.game, training.side));
+        self.training_message = Some(format!("Training on {}", training.game));
+        self.training = Some(training);
+        self.advance_training();
+    }
+
+    /// 走出对方的实战走法，直到轮到玩家猜或者棋谱走完
+    fn advance_training(&mut self) {
+        while let Some(training) = &mut self.training
+            && !training.is_guessing()
+            && self.game_state == GameState::Playing
+        {
+            match training.advance() {
+                Some(mv) => self.play_move(mv),
+                None => {
+                    self.finish_training();
+                    return;
+                }
+            }
+        }
+        if self.training.is_some() {
+            self.status_message = format!("Guess {:?}'s move", self.current_player);
+        }
+    }
+
+    // 把玩家的猜测交给后台打分，打完分之前棋盘不响应
+    fn submit_guess(&mut self, mv: Move) {
+        let Some(actual) = self.training.as_ref().and_then(Training::next_move) else {
+            return;
+        };
+        self.selected_square = None;
+        self.valid_moves.clear();
+        self.status_message = "Checking your guess...".to_string();
+        let board = self.board.clone();
+        let color = self.current_player;
+        let ply = self.move_history.len();
+        self.training_judge = Some(thread::spawn(move || {
+            training::judge(&board, color, ply, mv, actual)
+        }));
+    }
+
+    // 打完分后显示结果，走出实战走法并接着走到下一次猜测
+    fn poll_training_judge(&mut self) {
+        if !self
+            .training_judge
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            return;
+        }
+        let Ok(guess) = self.training_judge.take().unwrap().join() else {
+            self.training = None;
+            self.training_message = Some("Scoring the guess failed".to_string());
+            return;
+        };
+        let Some(training) = &mut self.training else {
+            return;
+        };
+
+        self.training_message = Some(guess.summary());
+        training.record(guess);
+        if let Some(actual) = training.advance() {
+            self.play_move(actual);
+        }
+        self.advance_training();
+    }
+
+    // 棋谱走完或者分出胜负：显示总分并记入训练记录
+    fn finish_training(&mut self) {
+        let Some(training) = self.training.take() else {
+            return;
+        };
+        let result = training.result();
+        let mut message = match result.accuracy() {
+            Some(accuracy) => format!(
+                "Done: {}/{} points ({:.0}%)",
+                result.points,
+                result.guesses as u32 * training::MAX_POINTS,
+                accuracy
+            ),
+            None => "Done: no guesses".to_string(),
+        };
+        if result.guesses > 0
+            && let Err(err) = training::save_result(&training::default_training_path(), &result)
+        {
+            message.push_str(&format!(" · could not save: {}", err));
+        }
+        self.training_message = Some(message);
+        self.status_message = "Training finished".to_string();
+    }
+
+    // 右侧面板 "Training" 部分：选择执哪一方、开始或结束训练，显示得分和最近的几次猜测
+    fn show_training(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Training", |ui| {
+            if let Some(training) = &self.training {
+                ui.label(egui::RichText::new(&training.game).italics());
+                let accuracy = training
+                    .accuracy()
+                    .map_or_else(|| "-".to_string(), |accuracy| format!("{:.0}%", accuracy));
+                ui.label(format!(
+                    "{} points · accuracy {} · {} moves left",
+                    training.points(),
+                    accuracy,
+                    training.guesses_left()
+                ));
+                if let Some(best) = self.training_best {
+                    ui.small(format!("Best so far: {:.0}%", best));
+                }
+                for guess in training.guesses.iter().rev().skip(1).take(4) {
+                    ui.small(guess.summary());
+                }
+                if ui.button("Stop training").clicked() {
+                    self.finish_training();
+                }
+            } else {
+                ui.horizontal(|ui| {
+                    ui.label("Guess moves for");
+                    ui.radio_value(&mut self.training_side, Color::White, "White");
+                    ui.radio_value(&mut self.training_side, Color::Black, "Black");
+                });
+                if ui
+                    .button("Start from PGN")
+                    .on_hover_text("Paste a game into the PGN box first")
+                    .clicked()
+                {
+                    self.start_training();
+                }
+            }
+            if let Some(message) = &self.training_message {
+                ui.label(message);
+            }
+        });
+    }
+
+    /// 猜步训练或者做将杀题时不能悔棋、翻看棋谱、要提示和分析
+    fn in_exercise(&self) -> bool {
+        self.training.is_some() || self.mate_run.is_some()
+    }
+
+    /// 开始做第 `index` 道将杀题：从题目局面开始双人模式，棋盘转到玩家一方
+    pub fn start_mate_puzzle(&mut self, index: usize) {
+        let Some(puzzle) = self.mate_puzzles.get(index).cloned() else {
+            return;
+        };
+        let Some((board, side)) = puzzle.position() else {
+            return;
+        };
+        self.time_control = None;
+        self.game_mode = GameMode::HumanVsHuman;
+        self.new_game();
+        self.board = board.clone();
+        self.current_player = side;
+        self.move_history = MoveHistory::with_start(board);
+        // 题目不是玩家下的对局，不从中找练习题，也不存进对局库
+        self.puzzles_collected = true;
+        self.autosaved = true;
+        self.board_flipped = side == Color::Black;
+        self.mate_index = index;
+        self.status_message = puzzle.title();
+        self.mate_message = None;
+        self.mate_run = Some(PuzzleRun::new(puzzle));
+    }
+
+    // 和解法比较玩家的走法：走对时走出这步和防守方的应着，走错时不走，可以再试
+    fn check_mate_move(&mut self, mv: Move) {
+        let Some(run) = &mut self.mate_run else {
+            return;
+        };
+        let failed_before = run.failed;
+        let attempt = run.try_move(&self.board, mv);
+        let fen = run.puzzle.fen.clone();
+        let failed = run.failed;
+        self.selected_square = None;
+        self.valid_moves.clear();
+
+        match attempt {
+            Attempt::Wrong => {
+                self.mate_message = Some(format!(
+                    "{} is not the solution, try again",
+                    notation::move_to_san(&self.board, mv)
+                ));
+                if !failed_before {
+                    self.record_mate_attempt(&fen, false);
+                }
+            }
+            Attempt::Correct(reply) => {
+                self.play_move(mv);
+                self.play_move(reply);
+                self.mate_message = Some("Correct, keep going".to_string());
+                self.status_message = format!("{:?} to move", self.current_player);
+            }
+            Attempt::Solved => {
+                self.play_move(mv);
+                self.mate_run = None;
+                let message = if failed {
+                    "Puzzle finished"
+                } else {
+                    "Puzzle solved!"
+                };
+                self.mate_message = Some(message.to_string());
+                if !failed {
+                    self.record_mate_attempt(&fen, true);
+                }
+                // 将死后显示结算界面，结果写在找练习题的提示的位置
+                self.puzzle_message = Some(format!(
+                    "{} · {} solved, {} failed",
+                    message,
+                    self.mate_stats.solved(),
+                    self.mate_stats.failed()
+                ));
+                self.mate_index = (self.mate_index + 1) % self.mate_puzzles.len();
+            }
+        }
+    }
+
+    // 认输看解法：这道题算失败，解法显示在 Puzzles 部分，还可以照着走完
+    fn show_mate_solution(&mut self) {
+        let Some(run) = &mut self.mate_run else {
+            return;
+        };
+        let fen = run.puzzle.fen.clone();
+        let solution = run.puzzle.solution_san().join(" ");
+        if !std::mem::replace(&mut run.failed, true) {
+            self.record_mate_attempt(&fen, false);
+        }
+        self.mate_message = Some(format!("Solution: {}", solution));
+    }
+
+    fn record_mate_attempt(&mut self, fen: &str, solved: bool) {
+        if let Err(err) = self
+            .mate_stats
+            .record(&mates::default_stats_path(), fen, solved)
+        {
+            self.mate_message = Some(format!("Could not save puzzle stats: {}", err));
+        }
+    }
+
+    // 重新读取练习题文件
+    fn reload_mate_puzzles(&mut self) {
+        match mates::load_puzzles(&puzzle::default_puzzle_path()) {
+            Ok(puzzles) => {
+                let builtin = mates::builtin_puzzles().len();
+                self.mate_message = Some(format!(
+                    "{} built-in puzzles and {} from your games",
+                    builtin,
+                    puzzles.len() - builtin
+                ));
+                self.mate_puzzles = puzzles;
+                self.mate_index = self.mate_index.min(self.mate_puzzles.len() - 1);
+            }
+            Err(err) => self.mate_message = Some(format!("Could not read puzzles: {}", err)),
+        }
+    }
+
+    // 右侧面板 "Puzzles" 部分：选题、开始做题、看解法，显示解出和失败的次数
+    fn show_mate_puzzles(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Puzzles", |ui| {
+            if let Some(run) = &self.mate_run {
+                ui.label(egui::RichText::new(&run.puzzle.name).italics());
+                ui.label(run.puzzle.title());
+                ui.horizontal(|ui| {
+                    if ui.button("Show solution").clicked() {
+                        self.show_mate_solution();
+                    }
+                    if ui.button("Stop").clicked() {
+                        self.mate_run = None;
+                        self.status_message = "Puzzle stopped".to_string();
+                    }
+                });
+            } else if let Some(puzzle) = self.mate_puzzles.get(self.mate_index) {
+                let solved = if self.mate_stats.is_solved(&puzzle.fen) {
+                    " ✔"
+                } else {
+                    ""
+                };
+                ui.label(format!(
+                    "{}/{}: {}{}",
+                    self.mate_index + 1,
+                    self.mate_puzzles.len(),
+                    puzzle.name,
+                    solved
+                ));
+                ui.small(puzzle.title());
+                let count = self.mate_puzzles.len();
+                ui.horizontal(|ui| {
+                    if ui.button("◀").clicked() {
+                        self.mate_index = (self.mate_index + count - 1) % count;
+                    }
+                    if ui.button("▶").clicked() {
+                        self.mate_index = (self.mate_index + 1) % count;
+                    }
+                    if ui.button("Start").clicked() {
+                        self.start_mate_puzzle(self.mate_index);
+                    }
+                    if ui
+                        .button("Reload")
+                        .on_hover_text(format!(
+                            "Read the puzzles generated from your games again from {}",
+                            puzzle::default_puzzle_path().display()
+                        ))
+                        .clicked()
+                    {
+                        self.reload_mate_puzzles();
+                    }
+                });
+            }
+            let solved_puzzles = self
+                .mate_puzzles
+                .iter()
+                .filter(|puzzle| self.mate_stats.is_solved(&puzzle.fen))
+                .count();
+            ui.small(format!(
+                "Solved {} · failed {} · {}/{} puzzles solved",
+                self.mate_stats.solved(),
+                self.mate_stats.failed(),
+                solved_puzzles,
+                self.mate_puzzles.len()
+            ));
+            if let Some(message) = &self.mate_message {
+                ui.label(message);
+            }
+        });
+    }
+
+    /// 对局结束后把对局存进对局库，每局只保存一次
+    fn autosave_game(&mut self) {
+        if self.autosaved {
+            return;
+        }
+        self.autosaved = true;
+        if !self.library_config.autosave || self.move_history.is_empty() {
+            return;
+        }
+        self.library_message = Some(
+            match library::autosave(&self.saved_game(), &self.library_config.dir) {
+                Ok(path) => format!("Saved {}", path.display()),
+                Err(err) => format!("Autosave failed: {}", err),
+            },
+        );
+    }
+
+    /// 打开对局库窗口并重新读取对局列表
+    pub fn open_library(&mut self) {
+        self.library_open = true;
+        self.refresh_library();
+    }
+
+    fn refresh_library(&mut self) {
+        self.library_delete = None;
+        self.library_games =
+            library::list_games(&self.library_config.dir, &[savegame::default_save_path()]);
+    }
+
+    fn save_library_config(&mut self) {
+        if let Err(err) = self.library_config.save(&library::default_config_path()) {
+            self.library_message = Some(format!("Could not save library settings: {}", err));
+        }
+    }
+
+    // 对局库中的一局：载入后接着下，回放则停在起始局面，用棋谱导航一步步看
+    fn run_library_action(&mut self, index: usize, action: LibraryAction) {
+        let Some(entry) = self.library_games.get(index).cloned() else {
+            return;
+        };
+        match action {
+            LibraryAction::Load => {
+                self.load_saved(entry.game);
+                self.library_open = false;
+            }
+            LibraryAction::Replay => {
+                self.load_saved(entry.game);
+                self.jump_to(None);
+                self.save_message = Some("Use the move list to replay the game".to_string());
+                self.library_open = false;
+            }
+            LibraryAction::Export => {
+                self.pgn_text = entry.game.to_pgn();
+                self.pgn_message = Some(format!("Exported the game from {}", entry.date()));
+                self.library_message = Some("Copied to the PGN box".to_string());
+            }
+            LibraryAction::Delete if self.library_delete == Some(index) => {
+                self.library_message = Some(match std::fs::remove_file(&entry.path) {
+                    Ok(()) => format!("Deleted {}", entry.path.display()),
+                    Err(err) => format!("Delete failed: {}", err),
+                });
+                self.refresh_library();
+            }
+            // 第一次点删除只是要求确认
+            LibraryAction::Delete => self.library_delete = Some(index),
+        }
+    }
+
+    // 对局库窗口：目录和自动保存设置，以及对局列表（日期、结果、对手、步数和操作按钮）
+    fn show_library(&mut self, ctx: &egui::Context) {
+        let mut open = self.library_open;
+        let mut action = None;
+        egui::Window::new("Game library")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut self.library_config.autosave, "Autosave finished games")
+                        .changed()
+                    {
+                        self.save_library_config();
+                    }
+                    if ui.button("Refresh").clicked() {
+                        self.refresh_library();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Folder:");
+                    ui.text_edit_singleline(&mut self.library_dir_text);
+                    if ui.button("Apply").clicked() {
+                        self.library_config.dir = self.library_dir_text.trim().into();
+                        self.save_library_config();
+                        self.refresh_library();
+                    }
+                });
+                if let Some(message) = &self.library_message {
+                    ui.small(message);
+                }
+                ui.separator();
+
+                if self.library_games.is_empty() {
+                    ui.label("No saved games yet");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("library_games")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (index, entry) in self.library_games.iter().enumerate() {
+                                    let date = if entry.is_quick_save() {
+                                        format!("{} (saved)", entry.date())
+                                    } else {
+                                        entry.date()
+                                    };
+                                    ui.label(date);
+                                    ui.label(entry.result());
+                                    ui.label(entry.opponent());
+                                    ui.label(format!(
+                                        "{} moves",
+                                        entry.game.history.len().div_ceil(2)
+                                    ));
+                                    ui.horizontal(|ui| {
+                                        if ui.small_button("Load").clicked() {
+                                            action = Some((index, LibraryAction::Load));
+                                        }
+                                        if ui.small_button("Replay").clicked() {
+                                            action = Some((index, LibraryAction::Replay));
+                                        }
+                                        if ui.small_button("Export").clicked() {
+                                            action = Some((index, LibraryAction::Export));
+                                        }
+                                        let delete = if self.library_delete == Some(index) {
+                                            "Really delete?"
+                                        } else {
+                                            "Delete"
+                                        };
+                                        if ui.small_button(delete).clicked() {
+                                            action = Some((index, LibraryAction::Delete));
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        if let Some((index, action)) = action {
+            self.run_library_action(index, action);
+        }
+        self.library_open &= open;
+    }
+
+    /// 对局结束后在后台保存评估缓存，每局只保存一次
+    fn persist_eval_cache(&mut self) {
+        if self.cache_saved {
+            return;
+        }
+        self.cache_saved = true;
+
+        // 上一次保存还没结束时先等它完成，避免同时写同一个文件
+        if let Some(handle) = self.cache_save.take() {
+            let _ = handle.join();
+        }
+        self.cache_save = Some(self.ai.save_cache_async(&eval_cache::default_cache_path()));
+    }
+
+    /// 对局结束后在后台回放棋谱，把失误局面保存为练习题，每局只做一次
+    fn collect_puzzles(&mut self) {
+        if self.puzzles_collected {
+            return;
+        }
+        self.puzzles_collected = true;
+
+        // 上一局的查找还没结束时先等它写完文件
+        if let Some(handle) = self.puzzle_scan.take() {
+            let _ = handle.join();
+        }
+        let history = self.move_history.clone();
+        self.puzzle_scan = Some(thread::spawn(move || {
+            let puzzles = puzzle::find_puzzles(&history);
+            puzzle::save_puzzles(&puzzle::default_puzzle_path(), &puzzles)
+        }));
+    }
+
+    // 查找结束后生成结算界面上的提示
+    fn poll_puzzle_scan(&mut self) {
+        if !self
+            .puzzle_scan
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            return;
+        }
+
+        let result = self.puzzle_scan.take().unwrap().join();
+        self.puzzle_message = Some(match &result {
+            Ok(Ok(0)) => "No new practice puzzles in this game".to_string(),
+            Ok(Ok(1)) => "Saved 1 practice puzzle from this game".to_string(),
+            Ok(Ok(count)) => format!("Saved {} practice puzzles from this game", count),
+            Ok(Err(err)) => format!("Could not save practice puzzles: {}", err),
+            Err(_) => "Practice puzzle search failed".to_string(),
+        });
+        // 新题目马上出现在 Puzzles 部分
+        if matches!(result, Ok(Ok(added)) if added > 0) && self.mate_run.is_none() {
+            self.reload_mate_puzzles();
+        }
+    }
+
+    /// 和 AI 下完一局后把结果记入当前难度的统计并保存，每局只记一次
+    fn record_result(&mut self) {
+        if self.result_recorded {
+            return;
+        }
+        self.result_recorded = true;
+        let Some(outcome) = Outcome::for_player(self.game_state, self.human_color) else {
+            return;
+        };
+        // 少子开局的让子棋不记入战绩
+        if self.game_mode != GameMode::HumanVsAI
+            || self.move_history.is_empty()
+            || self.move_history.start().is_some()
+        {
+            return;
+        }
+
+        let difficulty = self.ai_difficulty;
+        let earned_before = self.profile.stats(difficulty).badges();
+        let moves = self.move_history.len().div_ceil(2) as u32;
+        self.profile.record(difficulty, outcome, moves);
+
+        let stats = self.profile.stats(difficulty);
+        let mut parts: Vec<String> = stats
+            .streak_text()
+            .map(|streak| format!("{}: {}", difficulty.to_string(), streak))
+            .into_iter()
+            .collect();
+        parts.extend(
+            stats
+                .badges()
+                .into_iter()
+                .filter(|badge| !earned_before.contains(badge))
+                .map(|badge| format!("New badge: {}", badge)),
+        );
+        if let Err(err) = self.profile.save(&profile::default_profile_path()) {
+            parts.push(format!("Could not save stats: {}", err));
+        }
+        self.profile_message = (!parts.is_empty()).then(|| parts.join(" · "));
+    }
+
+    // 右侧面板 "Stats" 部分：每个难度的战绩、连胜连败、最快胜局和徽章
+    fn show_stats(&self, ui: &mut egui::Ui) {
+        ui.collapsing("Stats", |ui| {
+            egui::Grid::new("stats_grid").striped(true).show(ui, |ui| {
+                ui.strong("AI");
+                ui.strong("W / L / D");
+                ui.strong("Best streak");
+                ui.strong("Fastest win");
+                ui.end_row();
+
+                for difficulty in AIDifficulty::ALL {
+                    let stats = self.profile.stats(difficulty);
+                    ui.label(difficulty.to_string());
+                    ui.label(format!(
+                        "{} / {} / {}",
+                        stats.wins, stats.losses, stats.draws
+                    ));
+                    ui.label(stats.best_win_streak.to_string());
+                    ui.label(
+                        stats
+                            .fastest_win
+                            .map_or_else(|| "-".to_string(), |moves| format!("{moves} moves")),
+                    );
+                    ui.end_row();
+                }
+            });
+
+            for difficulty in AIDifficulty::ALL {
+                let stats = self.profile.stats(difficulty);
+                let badges = stats.badges();
+                if stats.streak_text().is_none() && badges.is_empty() {
+                    continue;
+                }
+                ui.separator();
+                ui.strong(difficulty.to_string());
+                if let Some(streak) = stats.streak_text() {
+                    ui.label(format!("Current: {}", streak));
+                }
+                ui.horizontal_wrapped(|ui| {
+                    for badge in badges {
+                        ui.label(
+                            egui::RichText::new(format!("🏅 {}", badge))
+                                .color(Color32::from_rgb(200, 160, 40)),
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    /// AI 走完一步后，根据设置决定是否提和
+    fn consider_ai_draw_offer(&mut self) {
+        // 被拒绝后至少隔 10 个半回合再提
+        let recently_offered = self
+            .last_draw_offer
+            .is_some_and(|ply| self.move_history.len() < ply + 10);
+
+        if self.game_state == GameState::Playing
+            && !recently_offered
+            && self.ai.wants_draw(&self.board, &self.settings)
+        {
+            self.draw_offer_pending = true;
+            self.last_draw_offer = Some(self.move_history.len());
+        }
+    }
+
+    /// 玩家向 AI 提和
+    pub fn offer_draw_to_ai(&mut self) {
+        if self.game_state != GameState::Playing || self.ai_thinking {
+            return;
+        }
+
+        if self.ai.wants_draw(&self.board, &self.settings) {
+            self.agree_draw();
+        } else {
+            self.status_message = "AI declines the draw offer".to_string();
+        }
+    }
+
+    /// 正在走的一方时间用完时超时判负
+    fn check_flag_fall(&mut self) {
+        let Some(loser) = self
+            .clock
+            .as_ref()
+            .and_then(|clock| clock.flagged(Instant::now()))
+        else {
+            return;
+        };
+        self.cancel_ai_search();
+        self.ai_thinking = false;
+        self.ai_move_start = None;
+        self.ai_move_ready = None;
+        self.promotion_pending = None;
+        self.pending_move = None;
+        self.draw_offer_pending = false;
+        self.game_state = GameState::TimeForfeit(loser);
+        self.game_over_reason = format!("{:?} lost on time", loser);
+        self.status_message = format!("{:?} ran out of time!", loser);
+    }
+
+    // 状态栏下面的棋钟：正在走的一方加粗，最后 10 秒变成红色
+    fn show_clocks(&self, ui: &mut egui::Ui) {
+        let Some(clock) = &self.clock else {
+            return;
+        };
+        let now = Instant::now();
+        ui.horizontal(|ui| {
+            for color in [Color::White, Color::Black] {
+                let remaining = clock.remaining(color, now);
+                let mut text =
+                    egui::RichText::new(format!("{:?} {}", color, clock::format_time(remaining)))
+                        .monospace()
+                        .size(20.0);
+                if clock.running() == Some(color) {
+                    text = text.strong();
+                }
+                if remaining < Duration::from_secs(10) {
+                    text = text.color(Color32::RED);
+                }
+                ui.label(text);
+                ui.add_space(20.0);
+            }
+        });
+    }
+
+    fn agree_draw(&mut self) {
+        self.draw_offer_pending = false;
+        self.game_state = GameState::Draw(DrawReason::Agreement);
+        self.game_over_reason = "Draw by agreement".to_string();
+        self.status_message = "Draw by agreement!".to_string();
+    }
+
+    fn show_draw_offer_dialog(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Draw Offer")
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label("The AI offers a draw.");
+                ui.horizontal(|ui| {
+                    if ui.button("Accept").clicked() {
+                        self.agree_draw();
+                    }
+                    if ui.button("Decline").clicked() {
+                        self.draw_offer_pending = false;
+                    }
+                });
+            });
+    }
+
+    // 右侧面板 "Game" 部分：新局、提和、对弈模式和 AI 难度
+    fn show_game_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("New Game").clicked() {
+                self.new_game();
+            }
+            if self.game_mode == GameMode::HumanVsAI && ui.button("Offer Draw").clicked() {
+                self.offer_draw_to_ai();
+            }
+            if ui
+                .button("Flip Board")
+                .on_hover_text("Turn the board around")
+                .clicked()
+            {
+                self.board_flipped = !self.board_flipped;
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .button("Save Game")
+                .on_hover_text("Save this game to continue it later")
+                .clicked()
+            {
+                self.save_game();
+            }
+            if ui.button("Load Game").clicked() {
+                self.load_game();
+            }
+            if ui
+                .button("Library")
+                .on_hover_text("Browse saved and auto-saved games")
+                .clicked()
+            {
+                self.open_library();
+            }
+        });
+        if let Some(message) = &self.save_message {
+            ui.small(message);
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.can_undo(), egui::Button::new("↶ Undo"))
+                .on_hover_text("Take back your last move and the AI's reply")
+                .clicked()
+            {
+                self.undo();
+            }
+            if ui
+                .add_enabled(!self.redo_stack.is_empty(), egui::Button::new("↷ Redo"))
+                .clicked()
+            {
+                self.redo();
+            }
+            let hint_label = if self.hint_search.is_some() {
+                "Hint..."
+            } else {
+                "Hint"
+            };
+            if ui
+                .add_enabled(self.can_hint(), egui::Button::new(hint_label))
+                .on_hover_text("Ask the AI for a move and show it as an arrow")
+                .clicked()
+            {
+                self.request_hint();
+            }
+        });
+
+        let old_mode = self.game_mode;
+        egui::ComboBox::from_label("Mode")
+            .selected_text(match self.game_mode {
+                GameMode::HumanVsAI => "vs AI",
+                GameMode::HumanVsHuman => "Two players",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.game_mode, GameMode::HumanVsAI, "vs AI");
+                ui.selectable_value(&mut self.game_mode, GameMode::HumanVsHuman, "Two players");
+            });
+        // 切换模式时重新开局
+        if old_mode != self.game_mode {
+            self.new_game();
+        }
+
+        let old_variant = self.variant;
+        egui::ComboBox::from_label("Variant")
+            .selected_text(self.variant.to_string())
+            .show_ui(ui, |ui| {
+                for variant in Variant::ALL {
+                    ui.selectable_value(&mut self.variant, variant, variant.to_string());
+                }
+            })
+            .response
+            .on_hover_text("King of the Hill: bring your king to d4, e4, d5 or e5 to win");
+        // 换变体时重新开局
+        if old_variant != self.variant {
+            self.new_game();
+        }
+
+        if self.game_mode == GameMode::HumanVsAI {
+            let old_color = self.human_color;
+            ui.horizontal(|ui| {
+                ui.label("Play as");
+                ui.radio_value(&mut self.human_color, Color::White, "White");
+                ui.radio_value(&mut self.human_color, Color::Black, "Black");
+            });
+            // 换颜色时重新开局，执黑时 AI 先走
+            if old_color != self.human_color {
+                self.new_game();
+            }
+
+            let old_handicap = self.handicap;
+            egui::ComboBox::from_label("Handicap")
+                .selected_text(self.handicap.to_string())
+                .show_ui(ui, |ui| {
+                    for handicap in Handicap::ALL {
+                        // 加时间的让子要先选一个时限
+                        let enabled =
+                            handicap != Handicap::ExtraTime || self.time_control.is_some();
+                        ui.add_enabled_ui(enabled, |ui| {
+                            ui.selectable_value(&mut self.handicap, handicap, handicap.to_string())
+                        })
+                        .response
+                        .on_disabled_hover_text("Choose a clock first");
+                    }
+                });
+            // 换让子时重新开局
+            if old_handicap != self.handicap {
+                self.new_game();
+            }
+        }
+
+        let old_time_control = self.time_control;
+        egui::ComboBox::from_label("Clock")
+            .selected_text(
+                self.time_control
+                    .map_or_else(|| "No clock".to_string(), |control| control.to_string()),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.time_control, None, "No clock");
+                for control in TimeControl::PRESETS {
+                    ui.selectable_value(&mut self.time_control, Some(control), control.to_string());
+                }
+            });
+        // 换时限时重新开局
+        if old_time_control != self.time_control {
+            self.new_game();
+        }
+
+        let old_difficulty = self.ai_difficulty;
+        egui::ComboBox::from_label("AI difficulty")
+            .selected_text(format!(
+                "{} (depth:{})",
+                self.ai_difficulty.to_string(),
+                self.ai_difficulty.get_depth()
+            ))
+            .show_ui(ui, |ui| {
+                for difficulty in AIDifficulty::ALL {
+                    ui.selectable_value(
+                        &mut self.ai_difficulty,
+                        difficulty,
+                        format!(
+                            "{} (depth:{})",
+                            difficulty.to_string(),
+                            difficulty.get_depth()
+                        ),
+                    );
+                }
+            });
+
+        // 当难度改变时立即更新AI
+        if old_difficulty != self.ai_difficulty {
+            self.set_ai_difficulty(self.ai_difficulty);
         }
     }
 
-    pub fn piece_to_unicode(&self, piece: Piece) -> &str {
-        match (piece.color, piece.piece_type) {
-            (Color::White, PieceType::Pawn) => "♙ ",
-            (Color::White, PieceType::Rook) => "♖ ",
-            (Color::White, PieceType::Knight) => "♘ ",
-            (Color::White, PieceType::Bishop) => "♗ ",
-            (Color::White, PieceType::Queen) => "♕ ",
-            (Color::White, PieceType::King) => "♔ ",
-            (Color::Black, PieceType::Pawn) => "♟ ",
-            (Color::Black, PieceType::Rook) => "♜ ",
-            (Color::Black, PieceType::Knight) => "♞ ",
-            (Color::Black, PieceType::Bishop) => "♝ ",
-            (Color::Black, PieceType::Queen) => "♛ ",
-            (Color::Black, PieceType::King) => "♚ ",
+    // 右侧面板 "Moves" 部分：开局名、前后翻动、棋谱和注释编辑
+    fn show_history(&mut self, ui: &mut egui::Ui) {
+        if let Some(opening) = opening::classify(&self.move_history) {
+            ui.label(egui::RichText::new(opening.to_string()).italics());
         }
+        self.show_navigation(ui);
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .id_salt("move_list")
+            .stick_to_bottom(true)
+            .max_height(300.0)
+            .show(ui, |ui| self.show_move_list(ui));
+        self.show_annotation_editor(ui);
     }
 
-    pub fn handle_square_click(&mut self, row: usize, col: usize) {
-        if self.game_state != GameState::Playing
-            || self.current_player != Color::White
-            || self.ai_thinking
-            || self.promotion_pending.is_some()
-        // 如果正在等待升变选择，不处理点击
-        {
-            return;
+    // 右侧面板 "Evaluation" 部分：AI 最近一次搜索的评估（白方视角）和搜索节点数
+    fn show_evaluation(&mut self, ui: &mut egui::Ui) {
+        match self.ai.last_score {
+            Some(score) if score.abs() > 90000 => {
+                let winner = if score > 0 { "White" } else { "Black" };
+                ui.label(format!("{winner} mates"));
+            }
+            Some(score) => {
+                ui.label(format!("{:+.2}", score as f32 / 100.0));
+            }
+            None => {
+                ui.weak("No search yet");
+            }
+        }
+        // 显示性能信息
+        if self.ai.nodes_searched > 0 {
+            ui.label(format!("Search nodes: {}", self.ai.nodes_searched));
         }
 
-        if let Some(selected) = self.selected_square {
-            // Try to make a move
-            let mv = Move {
-                from: selected,
-                to: (row, col),
-                promotion: None,
-            };
-
-            // Check if this is pawn promotion
-            let is_promotion = if let Some(piece) = self.board.get_piece(selected) {
-                piece.piece_type == PieceType::Pawn
-                    && ((piece.color == Color::White && row == 0)
-                        || (piece.color == Color::Black && row == 7))
-            } else {
-                false
-            };
-
-            // Check if the move is in valid moves list (without considering promotion type)
-            let move_found = self
-                .valid_moves
-                .iter()
-                .find(|valid_mv| valid_mv.from == mv.from && valid_mv.to == mv.to);
-
-            if move_found.is_some() {
-                if is_promotion {
-                    // 设置待升变的走法，等待用户选择
-                    self.promotion_pending = Some(mv);
-                    self.status_message = "Choose piece for promotion".to_string();
-                } else {
-                    // 普通走法，直接执行
-                    self.board.make_move(mv);
-                    self.selected_square = None;
-                    self.valid_moves.clear();
-                    self.current_player = Color::Black;
-                    self.update_game_state();
-                    if self.game_state == GameState::Playing {
-                        self.status_message = "AI is thinking...".to_string();
-                        self.ai_thinking = true;
-                        self.ai_move_start = Some(Instant::now());
-                    }
+        ui.add_enabled(
+            !self.in_exercise(),
+            egui::Checkbox::new(&mut self.analysis_mode, "Analysis mode"),
+        )
+        .on_hover_text("Keep analysing the current position and show an evaluation bar")
+        .on_disabled_hover_text("Not available while training or solving a puzzle");
+        if self.analysis_mode {
+            match self.analysis_score() {
+                Some((score, depth)) if score.abs() > 90000 => {
+                    let winner = if score > 0 { "White" } else { "Black" };
+                    ui.label(format!("Analysis: {winner} mates (depth {depth})"));
                 }
-            } else {
-                // Select new piece or deselect
-                if let Some(piece) = self.board.get_piece((row, col)) {
-                    if piece.color == Color::White {
-                        self.selected_square = Some((row, col));
-                        self.valid_moves = self
-                            .board
-                            .generate_moves(Color::White)
-                            .into_iter()
-                            .filter(|mv| mv.from == (row, col))
-                            .collect();
-                    } else {
-                        self.selected_square = None;
-                        self.valid_moves.clear();
-                    }
-                } else {
-                    self.selected_square = None;
-                    self.valid_moves.clear();
+                Some((score, depth)) => {
+                    ui.label(format!(
+                        "Analysis: {:+.2} (depth {depth})",
+                        score as f32 / 100.0
+                    ));
                 }
-            }
-        } else {
-            // Select a piece
-            if let Some(piece) = self.board.get_piece((row, col)) {
-                if piece.color == Color::White {
-                    self.selected_square = Some((row, col));
-                    self.valid_moves = self
-                        .board
-                        .generate_moves(Color::White)
-                        .into_iter()
-                        .filter(|mv| mv.from == (row, col))
-                        .collect();
+                None => {
+                    ui.weak("Analysing...");
                 }
             }
         }
     }
 
-    pub fn update_game_state(&mut self) {
-        let moves = self.board.generate_moves(self.current_player);
+    fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Settings", |ui| {
+            ui.checkbox(
+                &mut self.settings.ai_can_resign,
+                "AI resigns hopeless positions",
+            );
+            ui.add_enabled(
+                self.settings.ai_can_resign,
+                egui::Slider::new(&mut self.settings.resign_threshold, 300..=2000)
+                    .text("Resign threshold"),
+            );
+            ui.add_enabled(
+                self.settings.ai_can_resign,
+                egui::Slider::new(&mut self.settings.resign_after_moves, 1..=10)
+                    .text("Moves below threshold"),
+            );
+            ui.checkbox(&mut self.settings.ai_draw_offers, "AI offers/accepts draws");
+            ui.add_enabled(
+                self.settings.ai_draw_offers,
+                egui::Slider::new(&mut self.settings.draw_margin, 0..=100).text("Draw margin"),
+            );
+            ui.add_enabled(
+                self.settings.ai_draw_offers,
+                egui::Slider::new(&mut self.settings.draw_after_moves, 1..=20)
+                    .text("Equal moves before offering"),
+            );
+            ui.checkbox(
+                &mut self.settings.show_square_info,
+                "Show square info on hover",
+            );
+            ui.add(
+                egui::Slider::new(&mut self.settings.ai_move_delay_ms, 0..=2000)
+                    .text("AI move delay (ms)"),
+            )
+            .on_hover_text(
+                "Minimum pause before the AI moves, thinking included; 0 plays instantly",
+            );
+            ui.checkbox(
+                &mut self.settings.auto_rotate,
+                "Two players: rotate board after each move",
+            );
+            if ui
+                .checkbox(
+                    &mut self.settings.confirm_moves,
+                    "Confirm moves (touch screens)",
+                )
+                .changed()
+                && !self.settings.confirm_moves
+            {
+                self.cancel_pending_move();
+            }
+        });
+    }
 
-        if moves.is_empty() {
-            if self.board.is_in_check(self.current_player) {
-                self.game_state = match self.current_player {
-                    Color::White => GameState::BlackWins,
-                    Color::Black => GameState::WhiteWins,
+    /// 悬停提示：格子名、棋子以及双方攻击这个格子的棋子数量
+    fn square_tooltip(&self, square: (usize, usize)) -> String {
+        let name = notation::square_name(square);
+        let white = self.board.attackers_of(square, Color::White).len();
+        let black = self.board.attackers_of(square, Color::Black).len();
+
+        match self.board.get_piece(square) {
+            Some(piece) => {
+                let (attacked, defended) = match piece.color {
+                    Color::White => (black, white),
+                    Color::Black => (white, black),
                 };
-                self.status_message = format!(
-                    "{:?} wins by checkmate!",
-                    match self.current_player {
-                        Color::White => Color::Black,
-                        Color::Black => Color::White,
-                    }
-                );
-            } else {
-                self.game_state = GameState::Draw;
-                self.status_message = "Draw by stalemate!".to_string();
+                format!(
+                    "{}: {:?} {:?}\nAttacked by {} · Defended by {}",
+                    name, piece.color, piece.piece_type, attacked, defended
+                )
             }
-        } else if self.board.is_in_check(self.current_player) {
-            self.status_message = format!("{:?} is in check!", self.current_player);
-        } else {
-            self.status_message = format!("{:?} to move", self.current_player);
+            None => format!(
+                "{}: empty\nWhite attackers: {} · Black attackers: {}",
+                name, white, black
+            ),
         }
     }
 
-    pub fn new_game(&mut self) {
-        self.board = Board::new();
-        self.current_player = Color::White;
-        self.selected_square = None;
-        self.valid_moves.clear();
-        self.game_state = GameState::Playing;
-        self.status_message = "White to move".to_string();
-        self.ai_thinking = false;
-        self.ai_move_start = None;
-        self.promotion_pending = None;
+    fn can_hint(&self) -> bool {
+        self.game_state == GameState::Playing
+            && !self.in_exercise()
+            && !self.ai_thinking
+            && self.promotion_pending.is_none()
+            && self.hint_search.is_none()
+            && self.is_human_turn()
+    }
+
+    /// "Hint" 按钮：在后台用较浅的深度替轮到的玩家搜一步，搜完后用箭头画在棋盘上
+    pub fn request_hint(&mut self) {
+        let mut ai = self.ai.clone();
+        ai.set_max_depth(HINT_DEPTH);
+        ai.time_limit = HINT_TIME_LIMIT_MS;
+        let board = self.board.clone();
+        let color = self.current_player;
+        self.hint = None;
+        self.hint_search = Some((
+            self.position_key(),
+            thread::spawn(move || ai.get_best_move(&board, color)),
+        ));
+    }
+
+    // 提示搜完后记下来；搜索期间已经走了棋的话结果作废
+    fn poll_hint_search(&mut self) {
+        if !self
+            .hint_search
+            .as_ref()
+            .is_some_and(|(_, handle)| handle.is_finished())
+        {
+            return;
+        }
+        let (key, handle) = self.hint_search.take().unwrap();
+        if let Ok(Some(mv)) = handle.join()
+            && key == self.position_key()
+        {
+            self.hint = Some((key, mv));
+        }
+    }
+
+    /// 当前局面的提示走法
+    fn current_hint(&self) -> Option<Move> {
+        let key = self.position_key();
+        self.hint
+            .filter(|(hint_key, _)| *hint_key == key)
+            .map(|(_, mv)| mv)
+    }
+
+    /// 分析模式：局面变了就停掉旧的分析，在后台重新搜索当前局面；
+    /// AI 思考、对局结束和猜步训练时不分析
+    fn update_analysis(&mut self) {
+        let wanted = self.analysis_mode
+            && self.game_state == GameState::Playing
+            && !self.ai_thinking
+            && !self.in_exercise();
+        let key = self.position_key();
+        if wanted
+            && self
+                .analysis
+                .as_ref()
+                .is_some_and(|(analysed, _, _)| *analysed == key)
+        {
+            return;
+        }
+        if let Some((_, progress, _)) = self.analysis.take() {
+            progress.stop();
+        }
+        if !wanted {
+            return;
+        }
+
+        let progress = Arc::new(SearchProgress::default());
+        let mut ai = self.ai.clone();
+        ai.set_max_depth(ANALYSIS_DEPTH);
+        ai.time_limit = ANALYSIS_TIME_LIMIT_MS;
+        ai.progress = Some(Arc::clone(&progress));
+        let board = self.board.clone();
+        let color = self.current_player;
+        let handle = thread::spawn(move || {
+            ai.get_best_move(&board, color);
+        });
+        self.analysis = Some((key, progress, handle));
+    }
+
+    /// 分析模式下当前局面的评估（白方视角）和已经搜完的深度
+    fn analysis_score(&self) -> Option<(i32, u32)> {
+        let (_, progress, _) = self.analysis.as_ref()?;
+        Some((progress.score()?, progress.depth()))
+    }
+
+    /// 棋盘左边的评估条：白方的部分在白方那一边，按胜率占比
+    fn draw_eval_bar(&self, painter: &egui::Painter, rect: Rect) {
+        painter.rect_filled(rect, 2.0, Color32::from_gray(40));
+        let Some((score, _)) = self.analysis_score() else {
+            return;
+        };
+        let white_height = rect.height() * white_share(score);
+        let white_rect = if self.board_flipped {
+            Rect::from_min_size(rect.min, Vec2::new(rect.width(), white_height))
+        } else {
+            Rect::from_min_max(Pos2::new(rect.min.x, rect.max.y - white_height), rect.max)
+        };
+        painter.rect_filled(white_rect, 2.0, Color32::from_gray(235));
+
+        // 分数写在领先一方那一端
+        let text = if score.abs() > 90000 {
+            "M".to_string()
+        } else {
+            format!("{:.1}", score.abs() as f32 / 100.0)
+        };
+        let white_at_bottom = !self.board_flipped;
+        let (at_bottom, color) = if score >= 0 {
+            (white_at_bottom, Color32::from_gray(40))
+        } else {
+            (!white_at_bottom, Color32::from_gray(235))
+        };
+        let (pos, align) = if at_bottom {
+            (
+                rect.center_bottom() - Vec2::new(0.0, 4.0),
+                egui::Align2::CENTER_BOTTOM,
+            )
+        } else {
+            (
+                rect.center_top() + Vec2::new(0.0, 4.0),
+                egui::Align2::CENTER_TOP,
+            )
+        };
+        painter.text(pos, align, text, egui::FontId::proportional(11.0), color);
     }
 
     pub fn set_ai_difficulty(&mut self, difficulty: AIDifficulty) {
+        self.cancel_ai_search();
         self.ai_difficulty = difficulty;
         self.ai = ChessAI::new(difficulty.get_depth());
         // 更新AI的时间限制
         self.ai.time_limit = difficulty.get_time_limit();
+        let _ = self.ai.load_cache(&eval_cache::default_cache_path());
+    }
+
+    /// 在后台线程上用 AI 的副本搜索当前局面，界面继续响应；
+    /// 搜索结束后由 `poll_ai_search` 换回带着新置换表的 AI
+    fn start_ai_search(&mut self) {
+        let progress = Arc::new(SearchProgress::default());
+        let mut ai = self.ai.clone();
+        ai.progress = Some(Arc::clone(&progress));
+        // 计时的对局里思考时间不超过从棋钟里分出来的这一步的时间
+        if let Some(clock) = &self.clock {
+            let budget = clock.move_budget(self.ai_color(), Instant::now());
+            ai.time_limit = ai.time_limit.min(budget.as_millis() as u64);
+        }
+        let board = self.board.clone();
+        let color = self.ai_color();
+        self.ai_progress = Some(progress);
+        self.ai_search = Some(thread::spawn(move || {
+            let best_move = ai.get_best_move(&board, color);
+            (ai, best_move)
+        }));
+    }
+
+    // 搜索结束后取回结果；没有走法可走时停止思考，而不是反复重新搜索
+    fn poll_ai_search(&mut self) {
+        if !self
+            .ai_search
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            return;
+        }
+
+        self.ai_progress = None;
+        match self.ai_search.take().unwrap().join() {
+            Ok((mut ai, best_move)) => {
+                ai.progress = None;
+                self.ai = ai;
+                self.ai_move_ready = best_move;
+            }
+            Err(_) => self.status_message = "AI search failed".to_string(),
+        }
+        if self.ai_move_ready.is_none() {
+            self.ai_thinking = false;
+            self.ai_move_start = None;
+        }
+    }
+
+    /// 中止正在进行的搜索，丢弃它的结果。搜索在下一个节点就会停下，线程随后自行结束
+    fn cancel_ai_search(&mut self) {
+        if let Some(progress) = self.ai_progress.take() {
+            progress.stop();
+        }
+        self.ai_search = None;
+    }
+
+    // 搜索进度：已经完成的深度和所用时间，哪个先到头搜索就在哪里结束
+    fn ai_search_status(&self, elapsed: Duration) -> String {
+        let Some(progress) = &self.ai_progress else {
+            return format!("{}AI thinking...", self.check_announcement());
+        };
+        let max_depth = self.ai.max_depth().max(1);
+        let by_depth = progress.depth() as f32 / max_depth as f32;
+        let by_time = elapsed.as_millis() as f32 / self.ai.time_limit.max(1) as f32;
+        format!(
+            "{}AI thinking... depth {}/{}, {} nodes ({:.0}%)",
+            self.check_announcement(),
+            progress.depth(),
+            max_depth,
+            progress.nodes(),
+            (by_depth.max(by_time) * 100.0).min(100.0)
+        )
     }
 
     fn handle_promotion_choice(&mut self, piece_type: PieceType) {
         if let Some(mut mv) = self.promotion_pending {
             mv.promotion = Some(piece_type);
-            self.board.make_move(mv);
-            self.selected_square = None;
-            self.valid_moves.clear();
             self.promotion_pending = None;
-            self.current_player = Color::Black;
-            self.update_game_state();
-            if self.game_state == GameState::Playing {
-                self.status_message = "AI is thinking...".to_string();
-                self.ai_thinking = true;
-                self.ai_move_start = Some(Instant::now());
-            }
+            self.play_human_move(mv);
         }
     }
 
@@ -266,6 +2328,15 @@ impl ChessApp {
             });
     }
 
+    // 结算界面上胜方下面的那行字：和 AI 下时 AI 获胜单独说明
+    fn victory_tagline(&self, winner: Color) -> &'static str {
+        if self.game_mode == GameMode::HumanVsAI && winner == self.ai_color() {
+            "☠ AI Victory! ☠"
+        } else {
+            "❀ Congratulations! ❀"
+        }
+    }
+
     fn show_game_over_screen(&mut self, ctx: &egui::Context) {
         // Semi-transparent background overlay
         egui::Area::new("game_over_overlay".into())
@@ -303,7 +2374,7 @@ impl ChessApp {
 
                     // Game result icon and text
                     match self.game_state {
-                        GameState::WhiteWins => {
+                        GameState::WhiteWins | GameState::TimeForfeit(Color::Black) => {
                             ui.label(egui::RichText::new("👑").size(80.0).color(Color32::GOLD));
                             ui.add_space(10.0);
                             ui.label(
@@ -314,12 +2385,12 @@ impl ChessApp {
                             );
                             ui.add_space(5.0);
                             ui.label(
-                                egui::RichText::new("❀ Congratulations! ❀")
+                                egui::RichText::new(self.victory_tagline(Color::White))
                                     .size(18.0)
                                     .color(Color32::LIGHT_GRAY),
                             );
                         }
-                        GameState::BlackWins => {
+                        GameState::BlackWins | GameState::TimeForfeit(Color::White) => {
                             ui.label(
                                 egui::RichText::new("👑")
                                     .size(80.0)
@@ -334,12 +2405,12 @@ impl ChessApp {
                             );
                             ui.add_space(5.0);
                             ui.label(
-                                egui::RichText::new("☠ AI Victory! ☠")
+                                egui::RichText::new(self.victory_tagline(Color::Black))
                                     .size(18.0)
                                     .color(Color32::LIGHT_GRAY),
                             );
                         }
-                        GameState::Draw => {
+                        GameState::Draw(_) => {
                             ui.label(
                                 egui::RichText::new("🤝")
                                     .size(80.0)
@@ -368,25 +2439,31 @@ impl ChessApp {
                     ui.separator();
                     ui.add_space(10.0);
 
-                    match self.game_state {
-                        GameState::WhiteWins | GameState::BlackWins => {
-                            ui.label(
-                                egui::RichText::new("Victory by Checkmate")
-                                    .size(16.0)
-                                    .color(Color32::WHITE),
-                            );
+                    ui.label(
+                        egui::RichText::new(&self.game_over_reason)
+                            .size(16.0)
+                            .color(Color32::WHITE),
+                    );
+
+                    ui.add_space(10.0);
+                    if let Some(message) = &self.profile_message {
+                        ui.label(
+                            egui::RichText::new(message).color(Color32::from_rgb(230, 190, 60)),
+                        );
+                    }
+                    match &self.puzzle_message {
+                        Some(message) => {
+                            ui.label(egui::RichText::new(message).color(Color32::LIGHT_GRAY));
                         }
-                        GameState::Draw => {
+                        None => {
                             ui.label(
-                                egui::RichText::new("Game ended in Stalemate")
-                                    .size(16.0)
-                                    .color(Color32::WHITE),
+                                egui::RichText::new("Looking for practice puzzles...")
+                                    .color(Color32::LIGHT_GRAY),
                             );
                         }
-                        _ => {}
                     }
 
-                    ui.add_space(20.0);
+                    ui.add_space(10.0);
 
                     // Buttons
                     ui.horizontal(|ui| {
@@ -408,6 +2485,24 @@ impl ChessApp {
 
                         ui.add_space(10.0);
 
+                        // 悔棋，从结束前的局面继续下
+                        if ui
+                            .add_sized(
+                                [120.0, 40.0],
+                                egui::Button::new(
+                                    egui::RichText::new("↶ Undo")
+                                        .size(16.0)
+                                        .color(Color32::WHITE),
+                                )
+                                .fill(Color32::from_rgb(90, 90, 90)),
+                            )
+                            .clicked()
+                        {
+                            self.undo();
+                        }
+
+                        ui.add_space(10.0);
+
                         // Exit button (you can implement this if needed)
                         if ui
                             .add_sized(
@@ -439,118 +2534,201 @@ impl Default for ChessApp {
 
 impl eframe::App for ChessApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 棋钟在走时每 100 毫秒刷新一次显示，并检查是否落旗
+        if self.game_state == GameState::Playing {
+            self.check_flag_fall();
+            if self
+                .clock
+                .as_ref()
+                .is_some_and(|clock| clock.running().is_some())
+            {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+
         // Handle AI moves
-        if self.ai_thinking && self.current_player == Color::Black {
-            if let Some(start_time) = self.ai_move_start {
-                let elapsed = start_time.elapsed().as_millis();
-                let time_limit = self.ai.time_limit as u128;
-
-                // 更新状态消息显示思考进度
-                let progress = (elapsed as f32 / time_limit as f32 * 100.0).min(100.0);
-                self.status_message = format!("AI thinking... ({:.1}%)", progress);
-
-                if elapsed > 500 {
-                    if let Some(ai_move) = self.ai.get_best_move(&self.board, Color::Black) {
-                        self.board.make_move(ai_move);
-                        self.current_player = Color::White;
-                        self.ai_thinking = false;
-                        self.ai_move_start = None;
-                        self.update_game_state();
-                    }
+        if self.ai_thinking
+            && self.current_player == self.ai_color()
+            && let Some(start_time) = self.ai_move_start
+        {
+            // 先搜索，算好的走法等停顿结束再走出：停顿包含思考时间，而不是加在思考之后
+            if self.ai_move_ready.is_none() && self.ai_search.is_none() {
+                self.start_ai_search();
+            }
+            self.poll_ai_search();
+            if self.ai_search.is_some() {
+                self.status_message = self.ai_search_status(start_time.elapsed());
+            }
+
+            let delay = Duration::from_millis(self.settings.ai_move_delay_ms);
+            if self.ai_move_ready.is_some() && start_time.elapsed() < delay {
+                ctx.request_repaint_after(delay - start_time.elapsed());
+            } else if let Some(ai_move) = self.ai_move_ready.take() {
+                self.ai_thinking = false;
+                self.ai_move_start = None;
+
+                if self.ai.should_resign(&self.settings) {
+                    self.game_state = match self.human_color {
+                        Color::White => GameState::WhiteWins,
+                        Color::Black => GameState::BlackWins,
+                    };
+                    self.game_over_reason = format!("{:?} resigned", self.ai_color());
+                    self.status_message = format!("{:?} resigns!", self.ai_color());
+                } else {
+                    self.play_move(ai_move);
+                    self.consider_ai_draw_offer();
                 }
             }
         }
 
+        self.poll_training_judge();
+        if self.training_judge.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        self.poll_hint_search();
+        self.update_analysis();
+        let analysing = self
+            .analysis
+            .as_ref()
+            .is_some_and(|(_, _, handle)| !handle.is_finished());
+        if self.hint_search.is_some() || analysing {
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+
         // Show promotion dialog if needed
         if self.promotion_pending.is_some() {
             self.show_promotion_dialog(ctx);
         }
 
+        if self.draw_offer_pending && self.game_state == GameState::Playing {
+            self.show_draw_offer_dialog(ctx);
+        }
+
+        if self.library_open {
+            self.show_library(ctx);
+        }
+
         // Show game over screen if the game is finished
         if self.game_state != GameState::Playing {
+            if let Some(clock) = &mut self.clock {
+                clock.stop(Instant::now());
+            }
+            self.finish_training();
+            self.persist_eval_cache();
+            self.record_result();
+            self.autosave_game();
+            self.collect_puzzles();
+            self.poll_puzzle_scan();
+            if self.puzzle_message.is_none() {
+                ctx.request_repaint_after(std::time::Duration::from_millis(200));
+            }
             self.show_game_over_screen(ctx);
+
             return;
         }
 
+        // 右侧面板：各部分都可以折叠，新功能加在这里而不是挤在棋盘上方的工具栏里
+        egui::SidePanel::right("side_panel")
+            .min_width(220.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::CollapsingHeader::new("Game")
+                        .default_open(true)
+                        .show(ui, |ui| self.show_game_controls(ui));
+                    egui::CollapsingHeader::new("Moves")
+                        .default_open(true)
+                        .show(ui, |ui| self.show_history(ui));
+                    egui::CollapsingHeader::new("Evaluation")
+                        .default_open(true)
+                        .show(ui, |ui| self.show_evaluation(ui));
+                    self.show_stats(ui);
+                    self.show_training(ui);
+                    self.show_mate_puzzles(ui);
+                    self.show_settings(ui);
+                    self.show_pgn_section(ui);
+                });
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Chess Game");
 
             ui.horizontal(|ui| {
                 ui.label(&self.status_message);
-                if ui.button("New Game").clicked() {
-                    self.new_game();
-                }
-
-                ui.separator();
-
-                // 显示性能信息
-                if self.ai.nodes_searched > 0 {
-                    ui.label(format!("Search nodes: {}", self.ai.nodes_searched));
-                }
-
-                ui.separator();
-
-                ui.label("AI Difficulty:");
-                let old_difficulty = self.ai_difficulty;
-                egui::ComboBox::from_label("")
-                    .selected_text(format!(
-                        "{} (depth:{})",
-                        self.ai_difficulty.to_string(),
-                        self.ai_difficulty.get_depth()
-                    ))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut self.ai_difficulty,
-                            AIDifficulty::Easy,
-                            "Easy (depth:2)",
-                        );
-                        ui.selectable_value(
-                            &mut self.ai_difficulty,
-                            AIDifficulty::Medium,
-                            "Medium (depth:4)",
-                        );
-                        ui.selectable_value(
-                            &mut self.ai_difficulty,
-                            AIDifficulty::Hard,
-                            "Hard (depth:6)",
-                        );
-                        ui.selectable_value(
-                            &mut self.ai_difficulty,
-                            AIDifficulty::Expert,
-                            "Expert (depth:8)",
-                        );
-                    });
-
-                // 当难度改变时立即更新AI
-                if old_difficulty != self.ai_difficulty {
-                    self.set_ai_difficulty(self.ai_difficulty);
+                if self.pending_move.is_some() {
+                    if ui.button("Confirm").clicked() {
+                        self.confirm_pending_move();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.cancel_pending_move();
+                    }
                 }
             });
 
+            self.show_clocks(ui);
+
             ui.add_space(20.0);
 
             // Draw the chess board
             let square_size = 100.0;
             let board_size = square_size * 8.0;
             let coordinate_size = 20.0; // 坐标标记的宽度/高度
+            // 分析模式时最左边留出评估条的位置
+            let bar_space = if self.analysis_mode {
+                EVAL_BAR_WIDTH + 8.0
+            } else {
+                0.0
+            };
 
             let (response, painter) = ui.allocate_painter(
-                Vec2::new(board_size + coordinate_size, board_size + coordinate_size),
-                Sense::click(),
+                Vec2::new(
+                    bar_space + board_size + coordinate_size,
+                    board_size + coordinate_size,
+                ),
+                Sense::click_and_drag(),
             );
 
             let board_rect = Rect::from_min_size(
-                Pos2::new(response.rect.min.x + coordinate_size, response.rect.min.y),
+                Pos2::new(
+                    response.rect.min.x + bar_space + coordinate_size,
+                    response.rect.min.y,
+                ),
                 Vec2::new(board_size, board_size),
             );
+            if self.analysis_mode {
+                self.draw_eval_bar(
+                    &painter,
+                    Rect::from_min_size(response.rect.min, Vec2::new(EVAL_BAR_WIDTH, board_size)),
+                );
+            }
 
-            // Draw board squares
-            for row in 0..8 {
-                for col in 0..8 {
+            // 拖放走棋：移动得太少时 egui 仍然把它当作点击，走下面的点击逻辑
+            let pointer_pos = ctx.input(|input| input.pointer.latest_pos());
+            if response.drag_started()
+                && self.handoff_until.is_none()
+                && let Some(origin) = ctx.input(|input| input.pointer.press_origin())
+                && let Some(square) = self.square_at(board_rect, square_size, origin)
+            {
+                self.start_drag(square);
+            }
+            if response.drag_stopped() {
+                let target =
+                    pointer_pos.and_then(|pos| self.square_at(board_rect, square_size, pos));
+                self.finish_drag(target);
+            }
+            let drag_target = self
+                .dragging
+                .and(pointer_pos)
+                .and_then(|pos| self.square_at(board_rect, square_size, pos));
+
+            // Draw board squares（row/col 是屏幕上的位置，翻转时和棋盘格子不同）
+            for view_row in 0..8 {
+                for view_col in 0..8 {
+                    let (row, col) = self.view_square(view_row, view_col);
                     let square_rect = Rect::from_min_size(
                         Pos2::new(
-                            board_rect.min.x + col as f32 * square_size,
-                            board_rect.min.y + row as f32 * square_size,
+                            board_rect.min.x + view_col as f32 * square_size,
+                            board_rect.min.y + view_row as f32 * square_size,
                         ),
                         Vec2::splat(square_size),
                     );
@@ -563,6 +2741,18 @@ impl eframe::App for ChessApp {
                         Color32::from_rgb(181, 136, 99)
                     };
 
+                    // 上一步棋改变的格子（包括易位的车、被吃的过路兵）
+                    if self
+                        .last_move
+                        .is_some_and(|delta| delta.contains((row, col)))
+                    {
+                        square_color = if is_light {
+                            Color32::from_rgb(246, 246, 130)
+                        } else {
+                            Color32::from_rgb(186, 202, 68)
+                        };
+                    }
+
                     // Highlight selected square
                     if Some((row, col)) == self.selected_square {
                         square_color = Color32::from_rgb(255, 255, 0);
@@ -573,11 +2763,27 @@ impl eframe::App for ChessApp {
                         square_color = Color32::from_rgb(0, 255, 0);
                     }
 
+                    // 等待确认的目标格
+                    if self.pending_move.is_some_and(|mv| mv.to == (row, col)) {
+                        square_color = Color32::from_rgb(70, 150, 255);
+                    }
+
                     painter.rect_filled(square_rect, 0.0, square_color);
                     painter.rect_stroke(square_rect, 0.0, egui::Stroke::new(1.0, Color32::BLACK));
 
-                    // Draw piece
-                    if let Some(piece) = self.board.get_piece((row, col)) {
+                    // 拖动时鼠标下面的格子
+                    if drag_target == Some((row, col)) {
+                        painter.rect_stroke(
+                            square_rect.shrink(2.0),
+                            0.0,
+                            egui::Stroke::new(4.0, Color32::WHITE),
+                        );
+                    }
+
+                    // Draw piece（拖动中的棋子画在鼠标处）
+                    if let Some(piece) = self.board.get_piece((row, col))
+                        && self.dragging != Some((row, col))
+                    {
                         // Check if this piece is a king in check and highlight it
                         let is_king_in_check = piece.piece_type == PieceType::King
                             && self.board.is_in_check(piece.color);
@@ -609,7 +2815,7 @@ impl eframe::App for ChessApp {
 
             // Draw file labels (a-h) at the bottom
             for col in 0..8 {
-                let file_char = (b'a' + col as u8) as char;
+                let file_char = (b'a' + self.view_square(0, col).1 as u8) as char;
                 let x = board_rect.min.x + col as f32 * square_size + square_size / 2.0;
                 let y = board_rect.max.y + coordinate_size / 2.0;
 
@@ -624,7 +2830,7 @@ impl eframe::App for ChessApp {
 
             // Draw rank labels (8-1) on the left side
             for row in 0..8 {
-                let rank_num = 8 - row;
+                let rank_num = 8 - self.view_square(row, 0).0;
                 let x = board_rect.min.x - coordinate_size / 2.0;
                 let y = board_rect.min.y + row as f32 * square_size + square_size / 2.0;
 
@@ -637,20 +2843,71 @@ impl eframe::App for ChessApp {
                 );
             }
 
-            // Handle clicks
-            if response.clicked() {
-                if let Some(pos) = response.interact_pointer_pos() {
-                    // 调整点击位置以适应新的坐标系统（减去坐标标记的偏移）
-                    let relative_pos = pos - board_rect.min;
-                    let col = (relative_pos.x / square_size) as usize;
-                    let row = (relative_pos.y / square_size) as usize;
-
-                    if row < 8 && col < 8 {
-                        self.handle_square_click(row, col);
-                    }
+            // 提示的走法：从起点格到终点格的箭头，画在棋子上面
+            if let Some(hint) = self.current_hint() {
+                let from = self.square_center(board_rect, square_size, hint.from);
+                let to = self.square_center(board_rect, square_size, hint.to);
+                painter.arrow(
+                    from,
+                    to - from,
+                    egui::Stroke::new(10.0, Color32::from_rgba_unmultiplied(30, 120, 255, 170)),
+                );
+            }
+
+            // 拖动中的棋子跟着鼠标，画在所有格子上面
+            if let Some(from) = self.dragging
+                && let Some(piece) = self.board.get_piece(from)
+                && let Some(pos) = pointer_pos
+            {
+                painter.text(
+                    pos,
+                    egui::Align2::CENTER_CENTER,
+                    self.piece_to_unicode(piece),
+                    egui::FontId::proportional(48.0),
+                    Color32::BLACK,
+                );
+                ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+            }
+
+            // 双人对弈翻转棋盘后的换人提示，时间到或者点击后消失
+            if let Some(until) = self.handoff_until {
+                if Instant::now() >= until || response.clicked() {
+                    self.handoff_until = None;
+                } else {
+                    painter.rect_filled(
+                        board_rect,
+                        0.0,
+                        Color32::from_rgba_unmultiplied(0, 0, 0, 200),
+                    );
+                    painter.text(
+                        board_rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        format!("{:?} to move\nPass the device", self.current_player),
+                        egui::FontId::proportional(36.0),
+                        Color32::WHITE,
+                    );
+                    ctx.request_repaint_after(until - Instant::now());
                 }
             }
 
+            // Handle clicks
+            if response.clicked()
+                && self.handoff_until.is_none()
+                && let Some(pos) = response.interact_pointer_pos()
+                && let Some((row, col)) = self.square_at(board_rect, square_size, pos)
+            {
+                self.handle_square_click(row, col);
+            }
+
+            // 悬停提示
+            if self.settings.show_square_info
+                && self.dragging.is_none()
+                && let Some(pos) = response.hover_pos()
+                && let Some(square) = self.square_at(board_rect, square_size, pos)
+            {
+                response.on_hover_text_at_pointer(self.square_tooltip(square));
+            }
+
             ui.add_space(10.0);
         });
 
@@ -659,4 +2916,17 @@ impl eframe::App for ChessApp {
             ctx.request_repaint();
         }
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some((_, progress, _)) = self.analysis.take() {
+            progress.stop();
+        }
+        if let Some(handle) = self.puzzle_scan.take() {
+            let _ = handle.join();
+        }
+        if let Some(handle) = self.cache_save.take() {
+            let _ = handle.join();
+        }
+        let _ = self.ai.save_cache(&eval_cache::default_cache_path());
+    }
 }
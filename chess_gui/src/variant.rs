@@ -0,0 +1,177 @@
+//! 变体规则模块
+//! 界面判断走法是否合法、走棋和判断对局结束都通过 `Rules` trait，
+//! 新的变体（例如吃子会爆炸的原子象棋）只需要另外实现一套规则，不用改 `Board`。
+//! 目前有标准规则和山丘之王（King of the Hill）：王走到中心四格之一就赢
+
+use crate::board::{Board, BoardDelta, MoveList};
+use crate::types::*;
+
+/// 棋盘中心的四个格子：d5、e5、d4、e4
+pub const HILL: [Square; 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+
+/// 对局因为规则而结束的方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ending {
+    /// 轮到走棋的一方被将死
+    Checkmate,
+    /// 轮到走棋的一方没有合法走法，但没有被将军
+    Stalemate,
+    /// 变体自己的胜利条件，`reason` 例如 `King reached the hill`
+    Win { winner: Color, reason: &'static str },
+}
+
+/// 一种变体的规则。默认实现就是标准规则，变体只需要覆盖不同的部分
+pub trait Rules: Sync {
+    /// `color` 一方的合法走法
+    fn legal_moves(&self, board: &Board, color: Color) -> MoveList {
+        board.generate_moves(color)
+    }
+
+    /// `color` 一方是否还有合法走法
+    fn has_legal_move(&self, board: &Board, color: Color) -> bool {
+        !self.legal_moves(board, color).is_empty()
+    }
+
+    /// 走一步棋，返回的差异可以交给 `Board::undo_move` 撤销
+    fn apply_move(&self, board: &mut Board, mv: Move) -> Option<BoardDelta> {
+        board.make_move_with_delta(mv)
+    }
+
+    /// 轮到 `to_move` 走棋时对局是否已经按规则结束；
+    /// 重复局面和五十步这类和棋另外由 `draw` 模块判断
+    fn ending(&self, board: &Board, to_move: Color) -> Option<Ending> {
+        if self.has_legal_move(board, to_move) {
+            None
+        } else if board.is_in_check(to_move) {
+            Some(Ending::Checkmate)
+        } else {
+            Some(Ending::Stalemate)
+        }
+    }
+
+    /// 子力不足时是否判和
+    fn insufficient_material_draws(&self) -> bool {
+        true
+    }
+}
+
+/// 标准规则
+pub struct Standard;
+
+impl Rules for Standard {}
+
+/// 山丘之王：除了标准的胜负以外，先把王走到中心四格之一的一方获胜。
+/// 只剩双王时也还能分出胜负，所以子力不足不判和
+pub struct KingOfTheHill;
+
+impl Rules for KingOfTheHill {
+    fn ending(&self, board: &Board, to_move: Color) -> Option<Ending> {
+        let mover = to_move.opposite();
+        let king = match mover {
+            Color::White => board.white_king_pos,
+            Color::Black => board.black_king_pos,
+        };
+        if HILL.contains(&king) {
+            return Some(Ending::Win {
+                winner: mover,
+                reason: "King reached the hill",
+            });
+        }
+        Standard.ending(board, to_move)
+    }
+
+    fn insufficient_material_draws(&self) -> bool {
+        false
+    }
+}
+
+/// 可以在界面上选的变体
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    KingOfTheHill,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 2] = [Variant::Standard, Variant::KingOfTheHill];
+
+    /// 这个变体的规则
+    pub fn rules(&self) -> &'static dyn Rules {
+        match self {
+            Variant::Standard => &Standard,
+            Variant::KingOfTheHill => &KingOfTheHill,
+        }
+    }
+
+    pub fn to_string(&self) -> &str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+        }
+    }
+
+    /// 按 `to_string` 的名称查找，用于读取存档
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL
+            .into_iter()
+            .find(|variant| variant.to_string() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    fn play(rules: &dyn Rules, moves: &[&str]) -> (Board, Color) {
+        let mut board = Board::new();
+        let mut color = Color::White;
+        for uci in moves {
+            let mv = notation::parse_uci_move(uci).unwrap();
+            assert!(rules.legal_moves(&board, color).contains(&mv), "{}", uci);
+            rules.apply_move(&mut board, mv).unwrap();
+            color = color.opposite();
+        }
+        (board, color)
+    }
+
+    #[test]
+    fn test_standard_checkmate() {
+        let (board, to_move) = play(&Standard, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(Standard.ending(&board, to_move), Some(Ending::Checkmate));
+        assert_eq!(
+            KingOfTheHill.ending(&board, to_move),
+            Some(Ending::Checkmate)
+        );
+    }
+
+    #[test]
+    fn test_king_reaches_hill() {
+        let moves = ["e2e3", "g8f6", "e1e2", "f6g8", "e2d3", "g8f6"];
+        let (board, to_move) = play(&KingOfTheHill, &moves);
+        assert_eq!(KingOfTheHill.ending(&board, to_move), None);
+
+        let mut board = board;
+        let mv = notation::parse_uci_move("d3d4").unwrap();
+        KingOfTheHill.apply_move(&mut board, mv).unwrap();
+        assert_eq!(
+            KingOfTheHill.ending(&board, Color::Black),
+            Some(Ending::Win {
+                winner: Color::White,
+                reason: "King reached the hill",
+            })
+        );
+        // 标准规则下走到中心不算什么
+        assert_eq!(Standard.ending(&board, Color::Black), None);
+    }
+
+    #[test]
+    fn test_variant_names() {
+        for variant in Variant::ALL {
+            assert_eq!(Variant::from_name(variant.to_string()), Some(variant));
+        }
+        assert_eq!(Variant::from_name("Atomic"), None);
+        assert!(!Variant::KingOfTheHill.rules().insufficient_material_draws());
+    }
+}
@@ -0,0 +1,58 @@
+//! Zobrist 键模块
+//! 局面的键是局面中各个特征对应的随机数的异或：每个格子上的每种棋子、走棋方、
+//! 四种王车易位的权利和过路兵所在的列。键由固定种子的 SplitMix64 在编译时生成，
+//! 在任何机器和编译器版本上都相同
+
+/// `PIECE_KEYS[颜色 * 6 + 棋子类型][row * 8 + col]`，下标与 `Board` 的位棋盘相同
+pub const PIECE_KEYS: [[u64; 64]; 12] = piece_keys();
+/// 轮到黑方走时异或进去
+pub const TURN_KEY: u64 = splitmix64(12 * 64 + 1);
+/// `[白方短易位, 白方长易位, 黑方短易位, 黑方长易位]`
+pub const CASTLING_KEYS: [u64; 4] = keys_from(12 * 64 + 2);
+/// 可以吃过路兵时，过路兵所在列的键
+pub const EN_PASSANT_KEYS: [u64; 8] = keys_from(12 * 64 + 6);
+
+/// 所有键的指纹，键改变时指纹也会改变（用来判断磁盘缓存是否由同一套键生成）
+pub fn fingerprint() -> u64 {
+    PIECE_KEYS
+        .iter()
+        .flatten()
+        .chain(&CASTLING_KEYS)
+        .chain(&EN_PASSANT_KEYS)
+        .enumerate()
+        .fold(TURN_KEY, |fingerprint, (i, key)| {
+            fingerprint ^ key.rotate_left((i % 64) as u32)
+        })
+}
+
+/// SplitMix64 的第 `index` 个输出（种子为 0）
+pub const fn splitmix64(index: u64) -> u64 {
+    let mut z = index.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0; 64]; 12];
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            keys[piece][square] = splitmix64((piece * 64 + square + 1) as u64);
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+const fn keys_from<const N: usize>(first: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut i = 0;
+    while i < N {
+        keys[i] = splitmix64(first + i as u64);
+        i += 1;
+    }
+    keys
+}
@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{match_spans, GrepError};
+
+/// 大小写规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    /// 查询串里有大写字母时区分大小写, 否则不区分 (和 vim 的 smartcase 一样)
+    Smart,
+}
+
+/// 查询串按什么方式匹配
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatcherKind {
+    #[default]
+    Literal,
+    /// 按正则匹配, 只在一行之内匹配 (跨行请用 multiline 模块)
+    Regex,
+}
+
+/// 按什么给文件排序, 用于 `SearchBuilder::sort` 和命令行的 `--sort path|modified|size`.
+/// 都是从小到大: 路径按字典序, 修改时间从早到晚, 大小从小到大; 相同时再按路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Path,
+    Modified,
+    Size,
+}
+
+impl SortBy {
+    /// 一个文件的排序键. 拿不到修改时间的文件排在最前面
+    pub fn key(self, path: &str, modified: Option<SystemTime>, size: u64) -> impl Ord {
+        let primary = match self {
+            SortBy::Path => 0,
+            SortBy::Modified => modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_nanos()),
+            SortBy::Size => u128::from(size),
+        };
+        (primary, path.to_string())
+    }
+
+    // 磁盘上的文件的排序键, 读不到元数据时修改时间和大小都当作最小
+    fn file_key(self, path: &Path) -> impl Ord {
+        let metadata = fs::metadata(path).ok();
+        self.key(
+            &path.display().to_string(),
+            metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            metadata.map_or(0, |metadata| metadata.len()),
+        )
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = GrepError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "path" => Ok(SortBy::Path),
+            "modified" => Ok(SortBy::Modified),
+            "size" => Ok(SortBy::Size),
+            _ => Err(GrepError::InvalidSort(name.to_string())),
+        }
+    }
+}
+
+/// 交给 sink 的一行结果. `spans` 是匹配部分的字节范围, 上下文行的 `spans` 为空
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLine<'a> {
+    pub path: &'a Path,
+    /// 从 1 开始的行号
+    pub line_number: usize,
+    pub line: &'a str,
+    pub spans: &'a [Range<usize>],
+    /// -A / -B 带出来的上下文行, 不是匹配行
+    pub is_context: bool,
+}
+
+/// 一次搜索的统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchSummary {
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub matched_lines: usize,
+}
+
+/// 在库里直接发起搜索, 不用拼命令行参数:
+///
+/// ```no_run
+/// use minigrep::{CaseMode, SearchBuilder};
+///
+/// SearchBuilder::new("todo")
+///     .case_mode(CaseMode::Insensitive)
+///     .context(1, 1)
+///     .path("src/lib.rs")
+///     .run(|line| println!("{}:{}:{}", line.path.display(), line.line_number, line.line))
+///     .unwrap();
+/// ```
+///
+/// 多个文件可以用多个线程搜索, 但结果总是按 `paths` 的顺序、在调用 `run` 的线程上交给 sink;
+/// 设置了 `sort` 时改为按排序后的顺序
+#[derive(Debug, Clone)]
+pub struct SearchBuilder {
+    pattern: String,
+    case_mode: CaseMode,
+    matcher: MatcherKind,
+    before_context: usize,
+    after_context: usize,
+    paths: Vec<PathBuf>,
+    threads: usize,
+    sort: Option<SortBy>,
+}
+
+impl SearchBuilder {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        SearchBuilder {
+            pattern: pattern.into(),
+            case_mode: CaseMode::default(),
+            matcher: MatcherKind::default(),
+            before_context: 0,
+            after_context: 0,
+            paths: Vec::new(),
+            threads: 1,
+            sort: None,
+        }
+    }
+
+    pub fn case_mode(mut self, case_mode: CaseMode) -> Self {
+        self.case_mode = case_mode;
+        self
+    }
+
+    pub fn matcher(mut self, matcher: MatcherKind) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// 每个匹配前后带出的行数, 和 grep 的 -B / -A 一样
+    pub fn context(mut self, before: usize, after: usize) -> Self {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// 同时搜索的文件数, 至少为 1
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// 先搜完所有文件, 再按文件排序后交给 sink, 结果和 `paths` 的顺序无关
+    pub fn sort(mut self, sort: SortBy) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    fn ignore_case(&self) -> bool {
+        match self.case_mode {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !self.pattern.chars().any(char::is_uppercase),
+        }
+    }
+
+    /// 搜索所有文件, 每一行结果调用一次 `sink`.
+    /// 读不了的文件会中止搜索, 在它之前的文件的结果已经交给了 sink; 设置了 `sort` 时什么也不交
+    pub fn run<F: FnMut(SearchLine<'_>)>(&self, mut sink: F) -> Result<SearchSummary, GrepError> {
+        let matcher = Matcher::new(&self.pattern, self.matcher, self.ignore_case())?;
+        let mut summary = SearchSummary::default();
+        let mut emit = |summary: &mut SearchSummary, path: &Path, lines: Vec<FoundLine>| {
+            summary.files_searched += 1;
+            let matched = lines.iter().filter(|line| !line.is_context).count();
+            if matched > 0 {
+                summary.files_matched += 1;
+                summary.matched_lines += matched;
+            }
+            for found in &lines {
+                sink(SearchLine {
+                    path,
+                    line_number: found.line_number,
+                    line: &found.line,
+                    spans: &found.spans,
+                    is_context: found.is_context,
+                });
+            }
+        };
+
+        match self.sort {
+            None => self.search_in_order(&matcher, |index, lines| emit(&mut summary, &self.paths[index], lines))?,
+            Some(sort) => {
+                let mut results = Vec::with_capacity(self.paths.len());
+                self.search_in_order(&matcher, |index, lines| results.push((index, lines)))?;
+                results.sort_by_cached_key(|(index, _)| sort.file_key(&self.paths[*index]));
+                for (index, lines) in results {
+                    emit(&mut summary, &self.paths[index], lines);
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    // 搜索所有文件, 按 `paths` 的顺序把每个文件的下标和结果交给 found
+    fn search_in_order<F: FnMut(usize, Vec<FoundLine>)>(&self, matcher: &Matcher, mut found: F) -> Result<(), GrepError> {
+        let threads = self.threads.min(self.paths.len());
+        if threads <= 1 {
+            for (index, path) in self.paths.iter().enumerate() {
+                found(index, self.search_file(matcher, path)?);
+            }
+            return Ok(());
+        }
+
+        // 工作线程按下标取文件, 结果带着下标送回来, 这里按顺序交给 found
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let sender = sender.clone();
+                let next = &next;
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = self.paths.get(index) else {
+                        break;
+                    };
+                    // 接收端已经因为出错而退出时没必要再搜下去
+                    if sender.send((index, self.search_file(matcher, path))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut pending = BTreeMap::new();
+            let mut expected = 0;
+            for (index, result) in &receiver {
+                pending.insert(index, result);
+                while let Some(result) = pending.remove(&expected) {
+                    let lines = match result {
+                        Ok(lines) => lines,
+                        Err(err) => {
+                            // 让工作线程在下一次 send 时停下
+                            next.store(self.paths.len(), Ordering::Relaxed);
+                            return Err(err);
+                        }
+                    };
+                    found(expected, lines);
+                    expected += 1;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn search_file(&self, matcher: &Matcher, path: &Path) -> Result<Vec<FoundLine>, GrepError> {
+        let contents = fs::read_to_string(path).map_err(|source| GrepError::ReadFile {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        let mut found = Vec::new();
+        // 还没输出的前文, 以及还要输出几行后文
+        let mut before: VecDeque<(usize, &str)> = VecDeque::with_capacity(self.before_context);
+        let mut after_left = 0;
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let spans = matcher.spans(line);
+            if !spans.is_empty() {
+                found.extend(before.drain(..).map(|(number, text)| FoundLine::context(number, text)));
+                found.push(FoundLine {
+                    line_number,
+                    line: line.to_string(),
+                    spans,
+                    is_context: false,
+                });
+                after_left = self.after_context;
+            } else if after_left > 0 {
+                found.push(FoundLine::context(line_number, line));
+                after_left -= 1;
+            } else if self.before_context > 0 {
+                if before.len() == self.before_context {
+                    before.pop_front();
+                }
+                before.push_back((line_number, line));
+            }
+        }
+        Ok(found)
+    }
+}
+
+struct FoundLine {
+    line_number: usize,
+    line: String,
+    spans: Vec<Range<usize>>,
+    is_context: bool,
+}
+
+impl FoundLine {
+    fn context(line_number: usize, line: &str) -> Self {
+        FoundLine {
+            line_number,
+            line: line.to_string(),
+            spans: Vec::new(),
+            is_context: true,
+        }
+    }
+}
+
+enum Matcher {
+    Literal { pattern: String, ignore_case: bool },
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &str, kind: MatcherKind, ignore_case: bool) -> Result<Self, GrepError> {
+        match kind {
+            MatcherKind::Literal => Ok(Matcher::Literal {
+                pattern: pattern.to_string(),
+                ignore_case,
+            }),
+            MatcherKind::Regex => RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|source| GrepError::InvalidPattern {
+                    pattern: pattern.to_string(),
+                    source,
+                }),
+        }
+    }
+
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Matcher::Literal { pattern, ignore_case } => match_spans(pattern, line, *ignore_case),
+            Matcher::Regex(re) => re
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+}
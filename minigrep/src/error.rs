@@ -0,0 +1,28 @@
+use std::io;
+use thiserror::Error;
+
+/// minigrep 的错误类型, 参数错误和文件读取错误可以分开处理
+#[derive(Debug, Error)]
+pub enum GrepError {
+    #[error("Your arguments is not enough!")]
+    NotEnoughArguments,
+    #[error("Did not get a querry string")]
+    MissingQuery,
+    #[error("Did not get a file name")]
+    MissingFilename,
+    #[error("Could not read {path}: {source}")]
+    ReadFile { path: String, source: io::Error },
+    #[error("Invalid pattern {pattern}: {source}")]
+    InvalidPattern { pattern: String, source: regex::Error },
+    #[error("--sort needs one of path, modified or size")]
+    MissingSort,
+    #[error("Invalid --sort {0}: expected path, modified or size")]
+    InvalidSort(String),
+}
+
+impl GrepError {
+    /// 文件不存在或者读不了的错误, -s/--no-messages 时不打印
+    pub fn is_file_error(&self) -> bool {
+        matches!(self, GrepError::ReadFile { .. })
+    }
+}
@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+/// 行过滤器: 搜索之前先把每一行交给过滤器, 只在它返回的部分里匹配.
+/// 过滤器按顺序看到文件的每一行, 可以保存跨行的状态(例如块注释)
+pub trait LineFilter {
+    fn filter<'a>(&mut self, line: &'a str) -> Cow<'a, str>;
+}
+
+/// 去掉 `marker` 开始到行尾的注释, 例如 Python 的 `#`.
+/// 不识别字符串, 字符串里的 `marker` 也会被当成注释
+pub struct LineComment {
+    marker: &'static str,
+}
+
+impl LineComment {
+    pub fn new(marker: &'static str) -> Self {
+        LineComment { marker }
+    }
+}
+
+impl LineFilter for LineComment {
+    fn filter<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        match line.find(self.marker) {
+            Some(index) => Cow::Borrowed(&line[..index]),
+            None => Cow::Borrowed(line),
+        }
+    }
+}
+
+/// C 风格的 `//` 行注释和 `/* */` 块注释, 块注释可以跨行.
+/// 和 LineComment 一样不识别字符串, 也不处理嵌套的块注释
+#[derive(Default)]
+pub struct CComment {
+    in_block: bool,
+}
+
+impl LineFilter for CComment {
+    fn filter<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if !self.in_block && !line.contains("/*") {
+            return LineComment::new("//").filter(line);
+        }
+
+        let mut code = String::new();
+        let mut rest = line;
+        loop {
+            if self.in_block {
+                match rest.find("*/") {
+                    Some(end) => {
+                        rest = &rest[end + 2..];
+                        self.in_block = false;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+
+            match (rest.find("//"), rest.find("/*")) {
+                (Some(comment), block) if block.is_none_or(|block| comment < block) => {
+                    code.push_str(&rest[..comment]);
+                    break;
+                }
+                (_, Some(block)) => {
+                    // 块注释两边的代码不应该连在一起
+                    code.push_str(&rest[..block]);
+                    code.push(' ');
+                    rest = &rest[block + 2..];
+                    self.in_block = true;
+                }
+                _ => {
+                    code.push_str(rest);
+                    break;
+                }
+            }
+        }
+        Cow::Owned(code)
+    }
+}
+
+/// 按文件扩展名选择去掉注释的过滤器, 不认识的文件类型返回 None
+pub fn comment_filter(path: &str) -> Option<Box<dyn LineFilter>> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    match extension {
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "js" | "ts" | "go" => {
+            Some(Box::new(CComment::default()))
+        }
+        "py" | "sh" | "rb" | "toml" | "yml" | "yaml" => Some(Box::new(LineComment::new("#"))),
+        _ => None,
+    }
+}
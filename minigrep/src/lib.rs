@@ -1,50 +1,253 @@
 use std::{env, fs};
-use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+use std::path::Path;
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let file_contents = fs::read_to_string(config.filename)?;
+pub mod builder;
+mod error;
+pub mod filter;
+pub mod multiline;
+pub mod vfs;
 
-    let result = if config.case_sensitive {
-        search_case_insentive(&config.querry, &file_contents)
+pub use builder::{CaseMode, MatcherKind, SearchBuilder, SearchLine, SearchSummary, SortBy};
+pub use error::GrepError;
+use filter::LineFilter;
+
+/// 和 grep 相同的退出码: 有匹配的行为 0, 一行都没有匹配为 1, 出错(参数不对、文件读不了、模式写错)为 2
+pub const EXIT_MATCH: i32 = 0;
+pub const EXIT_NO_MATCH: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+/// run 的结果对应的退出码
+pub fn exit_code(result: &Result<bool, GrepError>) -> i32 {
+    match result {
+        Ok(true) => EXIT_MATCH,
+        Ok(false) => EXIT_NO_MATCH,
+        Err(_) => EXIT_ERROR,
+    }
+}
+
+/// 匹配的行打印到 stdout, 返回有没有匹配; 错误交给调用者打印到 stderr
+pub fn run(config: Config) -> Result<bool, GrepError> {
+    if config.multiline {
+        return run_multiline(&config);
+    }
+    if config.search_archives {
+        return run_archives(&config);
+    }
+
+    let file_contents = fs::read_to_string(&config.filename).map_err(|source| GrepError::ReadFile {
+        path: config.filename.clone(),
+        source,
+    })?;
+
+    let search_lines: for<'a> fn(&str, &'a str) -> Vec<&'a str> = if config.case_sensitive {
+        search_case_insentive
     } else {
-        search(&config.querry, &file_contents)
+        search
     };
 
-    for line in result {
+    if config.passthrough {
+        // 和上面选择的 search_lines 用同样的大小写规则
+        let mut matched = false;
+        for line in file_contents.lines() {
+            let spans = match_spans(&config.querry, line, config.case_sensitive);
+            matched |= !spans.is_empty();
+            println!("{}", highlight(line, &spans));
+        }
+        return Ok(matched);
+    }
+
+    if config.vimgrep {
+        let mut matched = false;
+        for (index, line) in file_contents.lines().enumerate() {
+            let spans = match_spans(&config.querry, line, config.case_sensitive);
+            for entry in vimgrep_entries(&config.filename, index + 1, line, &spans) {
+                matched = true;
+                println!("{}", entry);
+            }
+        }
+        return Ok(matched);
+    }
+
+    let result = match comment_filter(&config) {
+        Some(mut filter) => search_filtered(&config.querry, &file_contents, filter.as_mut(), search_lines),
+        None => search_lines(&config.querry, &file_contents),
+    };
+
+    for line in &result {
         println!("{}", line);
     }
 
-    Ok(())
+    Ok(!result.is_empty())
+}
+
+/// -U/--multiline: 查询串作为正则, 可以跨行匹配, 每个匹配前面打印它开始的行号
+fn run_multiline(config: &Config) -> Result<bool, GrepError> {
+    let read_error = |source| GrepError::ReadFile {
+        path: config.filename.clone(),
+        source,
+    };
+
+    let re = multiline::build_regex(&config.querry, !config.case_sensitive).map_err(|source| {
+        GrepError::InvalidPattern {
+            pattern: config.querry.clone(),
+            source,
+        }
+    })?;
+    let file = fs::File::open(&config.filename).map_err(read_error)?;
+    let lines = BufReader::new(file).lines();
+    let matches = match comment_filter(config) {
+        Some(mut filter) => {
+            let code = lines.map(|line| line.map(|line| filter.filter(&line).into_owned()));
+            multiline::search_multiline(&re, code, multiline::WINDOW_LINES)
+        }
+        None => multiline::search_multiline(&re, lines, multiline::WINDOW_LINES),
+    }
+    .map_err(read_error)?;
+
+    for m in &matches {
+        println!("{}:{}", m.line, m.text);
+    }
+
+    Ok(!matches.is_empty())
+}
+
+/// --search-archives: filename 可以是目录或压缩包, 搜索其中所有的文件,
+/// 每个匹配行前面加上 `路径:行号:`, 压缩包里的文件路径写成 `archive.zip!inner/file.txt`.
+/// 有 --sort 时先按文件排序再输出
+fn run_archives(config: &Config) -> Result<bool, GrepError> {
+    let mut files = vfs::Walker::with_archives().walk(Path::new(&config.filename))?;
+    if let Some(sort) = config.sort {
+        files.sort_by_cached_key(|file| sort.key(&file.path, file.modified, file.contents.len() as u64));
+    }
+    let mut matched = false;
+    for file in &files {
+        for (index, line) in file.contents.lines().enumerate() {
+            let spans = match_spans(&config.querry, line, config.case_sensitive);
+            matched |= !spans.is_empty();
+            if config.vimgrep {
+                for entry in vimgrep_entries(&file.path, index + 1, line, &spans) {
+                    println!("{}", entry);
+                }
+            } else if !spans.is_empty() {
+                println!("{}:{}:{}", file.path, index + 1, line);
+            }
+        }
+    }
+
+    Ok(matched)
+}
+
+/// --no-comments 时按文件类型选择的过滤器, 不认识的文件类型照常搜索
+fn comment_filter(config: &Config) -> Option<Box<dyn LineFilter>> {
+    if config.no_comments {
+        filter::comment_filter(&config.filename)
+    } else {
+        None
+    }
 }
 
 pub struct Config {
     pub querry: String,
     pub filename: String,
     pub case_sensitive: bool,
+    pub multiline: bool,
+    pub no_comments: bool,
+    /// --passthrough: 打印所有行, 只给匹配的部分上色. 不和 -U / --no-comments 一起使用
+    pub passthrough: bool,
+    /// --vimgrep: 每个匹配输出一行 `path:line:col:text`, 可以直接交给编辑器的 quickfix. 不和 -U / --no-comments 一起使用
+    pub vimgrep: bool,
+    /// --search-archives: 走进目录和 .zip / .tar(.gz) 压缩包搜索里面的文件. 不和 -U / --passthrough / --no-comments 一起使用
+    pub search_archives: bool,
+    /// -s/--no-messages: 文件不存在或者读不了时不在 stderr 上报错, 退出码仍然是 2. 参数错误照常报告
+    pub no_messages: bool,
+    /// --sort path|modified|size: 递归搜索时先搜完所有文件, 按文件排序后再输出, 让脚本和测试得到稳定的结果.
+    /// 只对 --search-archives 有效
+    pub sort: Option<SortBy>,
 }
 
 impl Config {
     // pub fn new(args: &[String]) -> Result<Config, &'static str> 
-    pub fn new(mut args: std::env::Args) -> Result<Config, &'static str> {
+    pub fn new<I: Iterator<Item = String>>(args: I) -> Result<Config, GrepError> {
+
+        // 选项可以出现在任意位置, 剩下的依次是查询串和文件名
+        let mut multiline = false;
+        let mut no_comments = false;
+        let mut passthrough = false;
+        let mut vimgrep = false;
+        let mut search_archives = false;
+        let mut no_messages = false;
+        let mut sort = None;
+        let mut sort_pending = false;
+        let mut args: Vec<String> = args
+            .skip(1)
+            .filter(|arg| match arg.as_str() {
+                // --sort 后面的参数是排序方式
+                _ if sort_pending => {
+                    sort = Some(arg.clone());
+                    sort_pending = false;
+                    false
+                }
+                "--sort" => {
+                    sort_pending = true;
+                    false
+                }
+                _ if arg.starts_with("--sort=") => {
+                    sort = Some(arg["--sort=".len()..].to_string());
+                    false
+                }
+                "-U" | "--multiline" => {
+                    multiline = true;
+                    false
+                }
+                "--no-comments" => {
+                    no_comments = true;
+                    false
+                }
+                "--passthrough" => {
+                    passthrough = true;
+                    false
+                }
+                "--vimgrep" => {
+                    vimgrep = true;
+                    false
+                }
+                "--search-archives" => {
+                    search_archives = true;
+                    false
+                }
+                "-s" | "--no-messages" => {
+                    no_messages = true;
+                    false
+                }
+                _ => true,
+            })
+            .collect();
 
-        if args.len() < 3 {
-            return Err("Your arguments is not enough!")
+        if sort_pending {
+            return Err(GrepError::MissingSort);
         }
+        let sort = sort.map(|name| name.parse()).transpose()?;
 
-        args.next();
+        if args.len() < 2 {
+            return Err(GrepError::NotEnoughArguments)
+        }
+
+        let mut args = args.drain(..);
 
         // let querry = args[1].clone();
         // let filename = args[2].clone();
         let querry = match args.next() {
             Some(arg) => arg,
-            None => return Err("Did not get a querry string"),
+            None => return Err(GrepError::MissingQuery),
         };
         let filename = match args.next() {
             Some(arg) => arg,
-            None => return Err("Did not get a file name"),
+            None => return Err(GrepError::MissingFilename),
         };
         let case_sensitive = env::var("CASE_SENSITIVE").is_err();
-        Ok(Config {querry, filename, case_sensitive})
+        Ok(Config {querry, filename, case_sensitive, multiline, no_comments, passthrough, vimgrep, search_archives, no_messages, sort})
     }
 }
 
@@ -87,6 +290,81 @@ pub fn search_case_insentive<'a>(querry: &str, contents: &'a str) -> Vec<&'a str
 }
 
 
+/// 和 search_lines 一样按行搜索, 但只在 filter 留下的部分里匹配, 返回的仍然是完整的行
+pub fn search_filtered<'a>(
+    querry: &str,
+    contents: &'a str,
+    filter: &mut dyn LineFilter,
+    search_lines: for<'b> fn(&str, &'b str) -> Vec<&'b str>,
+) -> Vec<&'a str> {
+    contents.lines()
+        .filter(|line| !search_lines(querry, &filter.filter(line)).is_empty())
+        .collect()
+}
+
+/// 一行里所有匹配的字节范围, 从左到右, 互不重叠
+pub fn match_spans(querry: &str, line: &str, ignore_case: bool) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    if querry.is_empty() {
+        return spans;
+    }
+
+    let mut start = 0;
+    while let Some(ch) = line[start..].chars().next() {
+        match match_len(querry, &line[start..], ignore_case) {
+            Some(len) => {
+                spans.push(start..start + len);
+                start += len;
+            }
+            None => start += ch.len_utf8(),
+        }
+    }
+    spans
+}
+
+// text 以 querry 开头时返回匹配部分的字节长度; 忽略大小写时逐个字符比较, 长度可能和 querry 不同
+fn match_len(querry: &str, text: &str, ignore_case: bool) -> Option<usize> {
+    if !ignore_case {
+        return text.starts_with(querry).then_some(querry.len());
+    }
+
+    let mut chars = text.char_indices();
+    for q in querry.chars() {
+        let (_, ch) = chars.next()?;
+        if !ch.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(text.len(), |(index, _)| index))
+}
+
+/// ripgrep --vimgrep 格式: 一行里有几个匹配就输出几条 `path:line:col:text`,
+/// 行号和列号都从 1 开始, 列号按字节计算, text 是完整的一行
+pub fn vimgrep_entries(path: &str, line_number: usize, line: &str, spans: &[Range<usize>]) -> Vec<String> {
+    spans
+        .iter()
+        .map(|span| format!("{}:{}:{}:{}", path, line_number, span.start + 1, line))
+        .collect()
+}
+
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// 用红色粗体标出 spans 里的部分, 和 grep --color 一样
+pub fn highlight(line: &str, spans: &[Range<usize>]) -> String {
+    let mut colored = String::with_capacity(line.len() + spans.len() * 11);
+    let mut last = 0;
+    for span in spans {
+        colored.push_str(&line[last..span.start]);
+        colored.push_str(MATCH_COLOR);
+        colored.push_str(&line[span.clone()]);
+        colored.push_str(RESET_COLOR);
+        last = span.end;
+    }
+    colored.push_str(&line[last..]);
+    colored
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +393,333 @@ Trust me.";
         assert_eq!(vec!["Rust:", "Trust me."], search_case_insentive(querry, contents));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn missing_file() {
+        let config = Config {
+            querry: String::from("rust"),
+            filename: String::from("no_such_file.txt"),
+            case_sensitive: true,
+            multiline: false,
+            no_comments: false,
+            passthrough: false,
+            vimgrep: false,
+            search_archives: false,
+            no_messages: false,
+            sort: None,
+        };
+
+        match run(config) {
+            Err(GrepError::ReadFile { path, .. }) => assert_eq!(path, "no_such_file.txt"),
+            other => panic!("expected ReadFile error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn exit_code_contract() {
+        let files = temp_files("exit", &["Rust:\nsafe, fast, productive."]);
+        let config = |querry: &str, filename: &str| {
+            let args = ["minigrep", querry, filename, "--no-messages"].map(String::from);
+            Config::new(args.into_iter()).unwrap()
+        };
+        let filename = files[0].to_str().unwrap();
+
+        assert!(config("fast", filename).no_messages);
+        assert_eq!(EXIT_MATCH, exit_code(&run(config("fast", filename))));
+        assert_eq!(EXIT_NO_MATCH, exit_code(&run(config("slow", filename))));
+        let missing = run(config("fast", "no_such_file.txt"));
+        assert_eq!(EXIT_ERROR, exit_code(&missing));
+        assert!(missing.unwrap_err().is_file_error());
+        assert!(!GrepError::NotEnoughArguments.is_file_error());
+        files.iter().for_each(|path| fs::remove_file(path).unwrap());
+    }
+
+    #[test]
+    fn multiline_flag() {
+        let args = ["minigrep", "-U", "fn.*\\{", "main.rs"].map(String::from);
+        let config = Config::new(args.into_iter()).unwrap();
+
+        assert!(config.multiline);
+        assert_eq!(config.querry, "fn.*\\{");
+        assert_eq!(config.filename, "main.rs");
+    }
+
+    #[test]
+    fn multiline_match_spans_lines() {
+        let contents = "\
+Rust:
+safe, fast,
+productive.
+Pick three.";
+        let re = multiline::build_regex("fast,\\s+prod", false).unwrap();
+        let matches = multiline::search_multiline(&re, contents.as_bytes().lines(), 64).unwrap();
+
+        assert_eq!(vec![multiline::MultilineMatch { line: 2, text: String::from("fast,\nprod") }], matches);
+    }
+
+    #[test]
+    fn multiline_window_boundaries() {
+        // 窗口为 2 行时, 跨越窗口边界的匹配也只报告一次
+        let contents = "a\nstart\nend\nstart\nend\nb\nstart\nend";
+        let re = multiline::build_regex("start\\nend", false).unwrap();
+        let lines: Vec<usize> = multiline::search_multiline(&re, contents.as_bytes().lines(), 2)
+            .unwrap()
+            .iter()
+            .map(|m| m.line)
+            .collect();
+
+        assert_eq!(vec![2, 4, 7], lines);
+    }
+
+    #[test]
+    fn no_comments_flag() {
+        let args = ["minigrep", "unwrap", "--no-comments", "main.rs"].map(String::from);
+        let config = Config::new(args.into_iter()).unwrap();
+
+        assert!(config.no_comments);
+        assert!(!config.multiline);
+        assert_eq!(config.filename, "main.rs");
+    }
+
+    #[test]
+    fn skip_rust_comments() {
+        let contents = "\
+let a = x.unwrap(); // unwrap is fine here
+// TODO: remove unwrap
+/* old code:
+   y.unwrap();
+*/ let b = y.unwrap_or(0);
+let c = /* unwrap */ z;";
+        let mut filter = filter::comment_filter("main.rs").unwrap();
+
+        assert_eq!(
+            vec!["let a = x.unwrap(); // unwrap is fine here", "*/ let b = y.unwrap_or(0);"],
+            search_filtered("unwrap", contents, filter.as_mut(), search)
+        );
+    }
+
+    #[test]
+    fn skip_python_comments() {
+        let contents = "\
+import os  # os.path helpers
+# os.getcwd()
+print(os.getcwd())";
+        let mut filter = filter::comment_filter("tool.py").unwrap();
+
+        assert_eq!(vec!["import os  # os.path helpers", "print(os.getcwd())"], search_filtered("os", contents, filter.as_mut(), search));
+        assert!(filter::comment_filter("poem.txt").is_none());
+    }
+
+    #[test]
+    fn passthrough_flag() {
+        let args = ["minigrep", "--passthrough", "error", "app.log"].map(String::from);
+        let config = Config::new(args.into_iter()).unwrap();
+
+        assert!(config.passthrough);
+        assert_eq!(config.querry, "error");
+        assert_eq!(config.filename, "app.log");
+    }
+
+    #[test]
+    fn spans_of_every_match() {
+        assert_eq!(vec![0..2, 5..7], match_spans("ab", "abc, ab", false));
+        assert_eq!(vec![7..11], match_spans("rust", "Rust, trustRUST", false));
+        assert_eq!(vec![0..4, 7..11, 11..15], match_spans("rust", "Rust, trustRUST", true));
+        assert!(match_spans("", "anything", false).is_empty());
+        // 非 ASCII 字符按字符比较, 范围落在字符边界上
+        assert_eq!(vec![3..5, 6..8], match_spans("É", "Café é", true));
+    }
+
+    #[test]
+    fn highlight_matches() {
+        let line = "error: disk error";
+        assert_eq!(
+            "\x1b[1;31merror\x1b[0m: disk \x1b[1;31merror\x1b[0m",
+            highlight(line, &match_spans("error", line, false))
+        );
+        assert_eq!("no match here", highlight("no match here", &[]));
+    }
+
+    #[test]
+    fn vimgrep_flag() {
+        let args = ["minigrep", "error", "app.log", "--vimgrep"].map(String::from);
+        let config = Config::new(args.into_iter()).unwrap();
+
+        assert!(config.vimgrep);
+        assert!(!config.passthrough);
+        assert_eq!(config.querry, "error");
+        assert_eq!(config.filename, "app.log");
+    }
+
+    #[test]
+    fn vimgrep_entry_per_match() {
+        let line = "error: disk error";
+        assert_eq!(
+            vec!["app.log:3:1:error: disk error", "app.log:3:13:error: disk error"],
+            vimgrep_entries("app.log", 3, line, &match_spans("error", line, false))
+        );
+        // 列号按字节计算, 和 ripgrep 一致
+        assert_eq!(vec!["menu.txt:1:4:Café é", "menu.txt:1:7:Café é"], vimgrep_entries("menu.txt", 1, "Café é", &match_spans("é", "Café é", false)));
+        assert!(vimgrep_entries("app.log", 1, "nothing", &[]).is_empty());
+    }
+
+    // 在临时目录里写几个文件, 文件名带上测试名免得并行的测试互相干扰
+    fn temp_files(test: &str, contents: &[&str]) -> Vec<std::path::PathBuf> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let path = env::temp_dir().join(format!("minigrep-{}-{}-{}.txt", test, std::process::id(), index));
+                fs::write(&path, text).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builder_context_and_order() {
+        let files = temp_files("context", &["one\ntwo\nneedle\nfour\nfive\nsix\nneedle", "no match", "needle first\nsecond"]);
+        let mut lines = Vec::new();
+        let summary = SearchBuilder::new("needle")
+            .context(1, 1)
+            .paths(&files)
+            .threads(3)
+            .run(|line| {
+                let file = files.iter().position(|path| path == line.path).unwrap();
+                lines.push((file, line.line_number, line.is_context, line.spans.first().cloned()));
+            })
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                (0, 2, true, None),
+                (0, 3, false, Some(0..6)),
+                (0, 4, true, None),
+                (0, 6, true, None),
+                (0, 7, false, Some(0..6)),
+                (2, 1, false, Some(0..6)),
+                (2, 2, true, None),
+            ],
+            lines
+        );
+        assert_eq!(SearchSummary { files_searched: 3, files_matched: 2, matched_lines: 3 }, summary);
+        files.iter().for_each(|path| fs::remove_file(path).unwrap());
+    }
+
+    #[test]
+    fn builder_case_modes_and_regex() {
+        let files = temp_files("modes", &["Rust\nrust\nTrust 42"]);
+        let matching = |builder: SearchBuilder| {
+            let mut numbers = Vec::new();
+            builder.paths(&files).run(|line| numbers.push(line.line_number)).unwrap();
+            numbers
+        };
+
+        assert_eq!(vec![2, 3], matching(SearchBuilder::new("rust")));
+        assert_eq!(vec![1, 2, 3], matching(SearchBuilder::new("rust").case_mode(CaseMode::Smart)));
+        assert_eq!(vec![1], matching(SearchBuilder::new("Rust").case_mode(CaseMode::Smart)));
+        assert_eq!(vec![3], matching(SearchBuilder::new(r"\d+$").matcher(MatcherKind::Regex)));
+        assert!(matches!(
+            SearchBuilder::new("(").matcher(MatcherKind::Regex).run(|_| {}),
+            Err(GrepError::InvalidPattern { .. })
+        ));
+        assert!(matches!(
+            SearchBuilder::new("x").path("no_such_file.txt").run(|_| {}),
+            Err(GrepError::ReadFile { .. })
+        ));
+        files.iter().for_each(|path| fs::remove_file(path).unwrap());
+    }
+
+    #[test]
+    fn sort_flag() {
+        let parse = |args: &[&str]| Config::new(["minigrep"].iter().chain(args).map(|arg| arg.to_string()));
+
+        assert_eq!(Some(SortBy::Size), parse(&["--sort", "size", "todo", "logs"]).unwrap().sort);
+        let config = parse(&["todo", "--sort=modified", "logs"]).unwrap();
+        assert_eq!(Some(SortBy::Modified), config.sort);
+        assert_eq!(config.filename, "logs");
+        assert_eq!(None, parse(&["todo", "logs"]).unwrap().sort);
+        assert!(matches!(parse(&["todo", "logs", "--sort"]), Err(GrepError::MissingSort)));
+        assert!(matches!(parse(&["--sort", "name", "todo", "logs"]), Err(GrepError::InvalidSort(name)) if name == "name"));
+    }
+
+    #[test]
+    fn builder_sorts_results() {
+        let files = temp_files("sort", &["needle
+needle
+needle", "needle", "needle
+needle"]);
+        let order = |sort: SortBy| {
+            let mut order = Vec::new();
+            SearchBuilder::new("needle")
+                .paths(files.iter().rev())
+                .threads(3)
+                .sort(sort)
+                .run(|line| {
+                    let file = files.iter().position(|path| path == line.path).unwrap();
+                    if order.last() != Some(&file) {
+                        order.push(file);
+                    }
+                })
+                .unwrap();
+            order
+        };
+
+        assert_eq!(vec![0, 1, 2], order(SortBy::Path));
+        assert_eq!(vec![1, 2, 0], order(SortBy::Size));
+        files.iter().for_each(|path| fs::remove_file(path).unwrap());
+    }
+
+    #[test]
+    fn search_archives_flag() {
+        let args = ["minigrep", "--search-archives", "todo", "logs"].map(String::from);
+        let config = Config::new(args.into_iter()).unwrap();
+
+        assert!(config.search_archives);
+        assert_eq!(config.querry, "todo");
+        assert_eq!(config.filename, "logs");
+    }
+
+    #[test]
+    fn walk_into_archives() {
+        use std::io::Write;
+
+        let dir = env::temp_dir().join(format!("minigrep-archives-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "plain needle").unwrap();
+        fs::write(dir.join("image.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(dir.join("bundle.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("docs/", options).unwrap();
+        zip.start_file("docs/a.txt", options).unwrap();
+        zip.write_all(b"first\nzip needle").unwrap();
+        zip.finish().unwrap();
+
+        let gz = flate2::write::GzEncoder::new(fs::File::create(dir.join("code.tar.gz")).unwrap(), flate2::Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_cksum();
+        tar.append_data(&mut header, "src/b.txt", &b"tar needle"[..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let files = vfs::Walker::with_archives().walk(&dir).unwrap();
+        let found: Vec<(String, &str)> = files
+            .iter()
+            .map(|file| (file.path.strip_prefix(&dir.display().to_string()).unwrap().to_string(), file.contents.as_str()))
+            .collect();
+        // 目录按文件名排序, 二进制文件跳过
+        assert_eq!(
+            vec![
+                ("/bundle.zip!docs/a.txt".to_string(), "first\nzip needle"),
+                ("/code.tar.gz!src/b.txt".to_string(), "tar needle"),
+                ("/notes.txt".to_string(), "plain needle"),
+            ],
+            found
+        );
+
+        // 不展开压缩包时压缩包读不成文本, 也被跳过
+        assert_eq!(1, vfs::Walker::new().walk(&dir).unwrap().len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
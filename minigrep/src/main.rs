@@ -11,15 +11,20 @@ fn main() {
     //     process::exit(1);
     // });
 
+    // 退出码和 grep 一样: 0 有匹配, 1 没有匹配, 2 出错; 匹配的行在 stdout, 错误在 stderr
     let config = Config::new(env::args()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        process::exit(minigrep::EXIT_ERROR);
     });
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("{}", e);
-        process::exit(1);
+    let no_messages = config.no_messages;
+    let result = minigrep::run(config);
+    if let Err(e) = &result {
+        if !(no_messages && e.is_file_error()) {
+            eprintln!("minigrep: {}", e);
+        }
     }
+    process::exit(minigrep::exit_code(&result));
 }
 
 
@@ -0,0 +1,87 @@
+use std::io;
+
+use regex::{Regex, RegexBuilder};
+
+/// 多行模式下每次最多往前看的行数, 一个匹配最多跨越这么多行
+pub const WINDOW_LINES: usize = 64;
+
+/// 多行模式的一个匹配, `line` 是匹配开始的行号(从 1 开始)
+#[derive(Debug, PartialEq)]
+pub struct MultilineMatch {
+    pub line: usize,
+    pub text: String,
+}
+
+/// 构造多行模式用的正则: `.` 可以匹配换行(相当于 `(?s)`), `^`/`$` 匹配每一行的开头和结尾
+pub fn build_regex(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .dot_matches_new_line(true)
+        .multi_line(true)
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+/// 在一个滑动窗口上做跨行搜索, `lines` 通常是 `BufRead::lines()`, 内存中最多保存 `2 * window` 行.
+///
+/// 每次只报告从窗口前半部分开始的匹配, 然后窗口向后移动 `window` 行,
+/// 所以长度不超过 `window` 行的匹配都能被完整找到.
+pub fn search_multiline<I: Iterator<Item = io::Result<String>>>(
+    re: &Regex,
+    mut lines: I,
+    window: usize,
+) -> io::Result<Vec<MultilineMatch>> {
+    let window = window.max(1);
+    let mut buffer: Vec<String> = Vec::with_capacity(window * 2);
+    let mut first_line = 1; // buffer[0] 的行号
+    let mut consumed = (0, 0); // 上一个匹配结束的位置 (行号, 列), 避免重复报告
+    let mut eof = false;
+    let mut result = Vec::new();
+
+    loop {
+        while !eof && buffer.len() < window * 2 {
+            match lines.next() {
+                Some(line) => buffer.push(line?),
+                None => eof = true,
+            }
+        }
+        if buffer.is_empty() {
+            break;
+        }
+
+        let text = buffer.join("\n");
+        // 每一行在 text 中的起始字节位置
+        let mut starts = Vec::with_capacity(buffer.len());
+        let mut offset = 0;
+        for line in &buffer {
+            starts.push(offset);
+            offset += line.len() + 1;
+        }
+        let position = |byte: usize| {
+            let index = starts.partition_point(|&start| start <= byte) - 1;
+            (first_line + index, byte - starts[index])
+        };
+
+        for m in re.find_iter(&text) {
+            let start = position(m.start());
+            if !eof && start.0 >= first_line + window {
+                break;
+            }
+            if start < consumed || m.is_empty() {
+                continue;
+            }
+            consumed = position(m.end());
+            result.push(MultilineMatch {
+                line: start.0,
+                text: m.as_str().to_string(),
+            });
+        }
+
+        if eof {
+            break;
+        }
+        buffer.drain(..window);
+        first_line += window;
+    }
+
+    Ok(result)
+}
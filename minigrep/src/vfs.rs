@@ -0,0 +1,167 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use flate2::read::GzDecoder;
+
+use crate::GrepError;
+
+/// 要搜索的一个文件. 压缩包里的文件也当成一个文件, 路径写成 `archive.zip!inner/file.txt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualFile {
+    pub path: String,
+    pub contents: String,
+    /// 磁盘上的文件的修改时间, 压缩包里的文件用压缩包的修改时间; 拿不到时为 None
+    pub modified: Option<SystemTime>,
+}
+
+/// 一种压缩包格式. 实现这个 trait 再交给 `Walker::format`, walker 就会走进这种压缩包
+pub trait ArchiveFormat {
+    /// 按文件名判断是不是这种压缩包
+    fn matches(&self, path: &Path) -> bool;
+    /// 压缩包里所有普通文件的 (包内路径, 内容), 按它们在包里的顺序
+    fn entries(&self, path: &Path) -> io::Result<Vec<(String, Vec<u8>)>>;
+}
+
+/// `.zip`
+pub struct Zip;
+
+impl ArchiveFormat for Zip {
+    fn matches(&self, path: &Path) -> bool {
+        has_suffix(path, &[".zip"])
+    }
+
+    fn entries(&self, path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        let mut entries = Vec::new();
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            entries.push((entry.name().to_string(), contents));
+        }
+        Ok(entries)
+    }
+}
+
+/// `.tar`, 以及 gzip 压缩的 `.tar.gz` / `.tgz`
+pub struct Tar;
+
+impl ArchiveFormat for Tar {
+    fn matches(&self, path: &Path) -> bool {
+        has_suffix(path, &[".tar", ".tar.gz", ".tgz"])
+    }
+
+    fn entries(&self, path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let file = File::open(path)?;
+        if has_suffix(path, &[".tar"]) {
+            tar_entries(file)
+        } else {
+            tar_entries(GzDecoder::new(file))
+        }
+    }
+}
+
+fn tar_entries(reader: impl Read) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.display().to_string();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        entries.push((name, contents));
+    }
+    Ok(entries)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn has_suffix(path: &Path, suffixes: &[&str]) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    suffixes.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// 列出一个路径下要搜索的所有文件: 目录按文件名排序递归进去,
+/// 认识的压缩包展开成里面的文件, 其它的当普通文件读
+pub struct Walker {
+    formats: Vec<Box<dyn ArchiveFormat>>,
+}
+
+impl Walker {
+    /// 不展开任何压缩包
+    pub fn new() -> Self {
+        Walker { formats: Vec::new() }
+    }
+
+    /// 展开 zip 和 tar(.gz)
+    pub fn with_archives() -> Self {
+        Walker::new().format(Zip).format(Tar)
+    }
+
+    pub fn format(mut self, format: impl ArchiveFormat + 'static) -> Self {
+        self.formats.push(Box::new(format));
+        self
+    }
+
+    /// 命令行上直接给出的文件读不了时报错; 目录和压缩包里不是 UTF-8 的文件(多半是二进制文件)跳过
+    pub fn walk(&self, root: &Path) -> Result<Vec<VirtualFile>, GrepError> {
+        let mut files = Vec::new();
+        self.visit(root, true, &mut files)?;
+        Ok(files)
+    }
+
+    fn visit(&self, path: &Path, explicit: bool, files: &mut Vec<VirtualFile>) -> Result<(), GrepError> {
+        let read_error = |source| GrepError::ReadFile {
+            path: path.display().to_string(),
+            source,
+        };
+
+        if path.is_dir() {
+            let mut children = fs::read_dir(path)
+                .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>())
+                .map_err(read_error)?;
+            children.sort();
+            for child in children {
+                self.visit(&child, false, files)?;
+            }
+        } else if let Some(format) = self.formats.iter().find(|format| format.matches(path)) {
+            let modified = modified_time(path);
+            for (name, contents) in format.entries(path).map_err(read_error)? {
+                if let Ok(contents) = String::from_utf8(contents) {
+                    files.push(VirtualFile {
+                        path: format!("{}!{}", path.display(), name),
+                        contents,
+                        modified,
+                    });
+                }
+            }
+        } else {
+            match fs::read_to_string(path) {
+                Ok(contents) => files.push(VirtualFile {
+                    path: path.display().to_string(),
+                    contents,
+                    modified: modified_time(path),
+                }),
+                Err(err) if !explicit && err.kind() == io::ErrorKind::InvalidData => {}
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Walker {
+    fn default() -> Self {
+        Walker::new()
+    }
+}
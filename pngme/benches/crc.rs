@@ -0,0 +1,58 @@
+use std::hint::black_box;
+use std::str::FromStr;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use pngme::check::{check_png, default_threads};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+const IDAT_COUNT: usize = 64;
+const IDAT_SIZE: usize = 256 * 1024;
+
+/// 一个有很多大 IDAT 的 PNG，大小和真实的大图差不多（16 MiB）
+fn large_png() -> Vec<u8> {
+    let chunk = |ty: &str, data: Vec<u8>| Chunk::new(ChunkType::from_str(ty).unwrap(), data);
+    let mut chunks = vec![chunk("IHDR", vec![0, 0, 8, 0, 0, 0, 8, 0, 8, 2, 0, 0, 0])];
+    chunks.extend((0..IDAT_COUNT).map(|i| chunk("IDAT", vec![i as u8; IDAT_SIZE])));
+    chunks.push(chunk("IEND", Vec::new()));
+    Png::from_chunks(chunks).as_bytes()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let bytes = large_png();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("Png::try_from", |b| {
+        b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_check(c: &mut Criterion) {
+    let bytes = large_png();
+    let mut group = c.benchmark_group("check");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    let mut thread_counts = vec![1, default_threads()];
+    thread_counts.dedup();
+    for threads in thread_counts {
+        group.bench_function(format!("{} threads", threads), |b| {
+            b.iter(|| check_png(black_box(&bytes), threads).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_chunk_new(c: &mut Criterion) {
+    let chunk_type = ChunkType::from_str("IDAT").unwrap();
+    let data = vec![0xab; IDAT_SIZE];
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Bytes(IDAT_SIZE as u64));
+    group.bench_function("Chunk::new", |b| {
+        b.iter(|| Chunk::new(chunk_type, black_box(data.clone())))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_check, bench_chunk_new);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk::Chunk;
+use pngme::png::Png;
+
+// 运行方式: cargo +nightly fuzz run parse_png fuzz/corpus/parse_png
+fuzz_target!(|data: &[u8]| {
+    // 任意输入都不能让解析器 panic
+    let _ = Chunk::parse(data);
+
+    if let Ok(png) = Png::try_from(data) {
+        // 解析成功的文件重新序列化后必须与输入完全一致
+        let bytes = png.as_bytes();
+        assert_eq!(bytes, data);
+        assert!(Png::try_from(bytes.as_slice()).is_ok());
+    }
+});
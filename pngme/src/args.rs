@@ -1,5 +1,7 @@
 use clap::{Parser, Subcommand};
+use std::ffi::OsString;
 use std::path::PathBuf;
+use crate::capacity::DEFAULT_PAYLOAD_LIMIT;
 use crate::chunk_type::ChunkType;
 
 #[derive(Debug, Parser)]
@@ -23,11 +25,24 @@ pub enum Command {
         #[arg(short, long)]
         chunk_type: ChunkType,
 
+        /// 要写入的消息，`-` 表示从标准输入读取，可以是任意二进制数据
         #[arg(short, long)]
-        message: String,
+        message: OsString,
 
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// 允许写入的最大消息长度（字节）
+        #[arg(long, default_value_t = DEFAULT_PAYLOAD_LIMIT)]
+        limit: usize,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
+
+        /// 用这个密码加密消息（Argon2 + ChaCha20-Poly1305）
+        #[arg(long)]
+        password: Option<String>,
     },
     Decode {
         #[arg(short, long)]
@@ -35,6 +50,18 @@ pub enum Command {
 
         #[arg(short, long)]
         chunk_type: ChunkType,
+
+        /// 解密用 encode --password 加密的消息
+        #[arg(long)]
+        password: Option<String>,
+
+        /// 把消息的字节原样写出，不做任何转换，也不打印别的内容
+        #[arg(long)]
+        raw: bool,
+
+        /// --raw 时写到这个文件而不是标准输出
+        #[arg(short, long, requires = "raw")]
+        output: Option<PathBuf>,
     },
     Remove {
         #[arg(short, long)]
@@ -42,9 +69,163 @@ pub enum Command {
 
         #[arg(short, long)]
         chunk_type: ChunkType,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
     },
+    /// 打印所有chunk，以及IHDR、PLTE、tRNS的解码结果和结构问题
+    #[command(visible_alias = "list")]
     Print {
         #[arg(short, long)]
         file_path: PathBuf,
-    }
+    },
+    /// 在指定位置插入chunk
+    Insert {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        #[arg(short, long)]
+        chunk_type: ChunkType,
+
+        /// 要写入的消息，`-` 表示从标准输入读取，可以是任意二进制数据
+        #[arg(short, long)]
+        message: OsString,
+
+        /// 插入位置, 0 表示紧跟在PNG签名之后
+        #[arg(short, long)]
+        position: usize,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
+    },
+    /// 把chunk移动到另一个chunk之前或之后
+    MoveChunk {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        #[arg(short, long)]
+        chunk_type: ChunkType,
+
+        /// 移动到第一个该类型的chunk之前, 例如 IDAT
+        #[arg(long, conflicts_with = "after", required_unless_present = "after")]
+        before: Option<ChunkType>,
+
+        /// 移动到最后一个该类型的chunk之后
+        #[arg(long)]
+        after: Option<ChunkType>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
+    },
+    /// 以十六进制和ASCII打印chunk的数据，CRC不对的chunk也可以看
+    Dump {
+        file_path: PathBuf,
+
+        chunk_type: ChunkType,
+
+        /// 把数据原样写到标准输出，不做转储
+        #[arg(long)]
+        raw: bool,
+    },
+    /// 并排比较两个PNG文件的chunk（类型、长度、CRC）
+    Diff {
+        first: PathBuf,
+
+        second: PathBuf,
+
+        /// 同时打印有差异的文本chunk的内容
+        #[arg(short, long)]
+        text: bool,
+    },
+    /// 估计用各种方式能写入多少数据
+    Capacity {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        /// 允许写入的最大消息长度（字节）
+        #[arg(long, default_value_t = DEFAULT_PAYLOAD_LIMIT)]
+        limit: usize,
+    },
+    /// 多线程校验每个chunk的CRC，并检查IHDR、PLTE、tRNS是否一致
+    Check {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        /// 线程数，默认为CPU核数
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+    /// 打印方向、描述和版权（eXIf / XMP）
+    Metadata {
+        #[arg(short, long)]
+        file_path: PathBuf,
+    },
+    /// 设置方向、描述或版权，写入eXIf和XMP；给空字符串删除描述或版权
+    SetMetadata {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        /// EXIF方向 1-8，例如 6 表示需要顺时针旋转90度显示
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..=8))]
+        orientation: Option<u16>,
+
+        #[arg(short, long)]
+        description: Option<String>,
+
+        #[arg(long)]
+        copyright: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
+    },
+    /// 生成Ed25519密钥对，私钥和公钥分别以十六进制写入两个文件
+    Keygen {
+        #[arg(long)]
+        secret_key: PathBuf,
+
+        #[arg(long)]
+        public_key: PathBuf,
+    },
+    /// 用私钥签名IHDR和IDAT，签名写入sgNT chunk
+    Sign {
+        #[arg(short, long)]
+        file_path: PathBuf,
+
+        /// keygen 生成的私钥文件
+        #[arg(short, long)]
+        key: PathBuf,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// 覆盖前把原文件保存为 <文件名>.bak
+        #[arg(long)]
+        backup: bool,
+    },
+    /// 校验每个chunk的CRC和IHDR、IDAT、IEND的顺序，列出所有问题和偏移；给出公钥时再验证sgNT中的签名
+    Verify {
+        /// 要校验的PNG文件，也可以用 -f 给出
+        #[arg(required_unless_present = "file_path", conflicts_with = "file_path")]
+        file: Option<PathBuf>,
+
+        #[arg(short, long)]
+        file_path: Option<PathBuf>,
+
+        /// keygen 生成的公钥文件
+        #[arg(short, long)]
+        key: Option<PathBuf>,
+    },
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use thiserror::Error;
+
+use crate::chunk::MAX_CHUNK_LENGTH;
+use crate::png::Png;
+
+/// encode 默认允许写入的最大消息长度（字节），可以用 --limit 修改
+pub const DEFAULT_PAYLOAD_LIMIT: usize = 16 * 1024 * 1024;
+
+/// 消息超过可以写入的大小
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CapacityError {
+    /// 超过了 --limit 配置的上限
+    #[error("payload is {size} bytes, which exceeds the configured limit of {limit} bytes (see --limit)")]
+    OverLimit { size: usize, limit: usize },
+    /// 超过了 PNG 规范允许的单个 chunk 最大长度
+    #[error("payload is {size} bytes, which exceeds the maximum chunk length of {MAX_CHUNK_LENGTH} bytes")]
+    OverChunkMaximum { size: usize },
+}
+
+/// 检查消息能否放进一个 chunk：不能超过配置的上限，也不能超过规范规定的最大 chunk 长度
+pub fn check_payload(size: usize, limit: usize) -> Result<(), CapacityError> {
+    if size > MAX_CHUNK_LENGTH as usize {
+        return Err(CapacityError::OverChunkMaximum { size });
+    }
+    if size > limit {
+        return Err(CapacityError::OverLimit { size, limit });
+    }
+    Ok(())
+}
+
+/// IHDR 的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    /// 压缩方法，规范只定义了 0
+    pub compression_method: u8,
+    /// 过滤方法，规范只定义了 0
+    pub filter_method: u8,
+    /// 0 为逐行，1 为 Adam7 隔行扫描
+    pub interlace_method: u8,
+}
+
+impl ImageHeader {
+    /// 从 PNG 的 IHDR chunk 读取图像信息，IHDR 不存在或长度不对时返回 None
+    pub fn from_png(png: &Png) -> Option<ImageHeader> {
+        ImageHeader::parse(png.chunk_by_type("IHDR")?.data())
+    }
+
+    /// 解析 IHDR 的 13 字节内容，长度不对时返回 None；字段的取值不在这里检查
+    pub fn parse(data: &[u8]) -> Option<ImageHeader> {
+        if data.len() != 13 {
+            return None;
+        }
+        Some(ImageHeader {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+
+    /// 每个像素的采样数: 灰度 1, RGB 3, 调色板 1, 灰度+alpha 2, RGBA 4
+    pub fn channels(&self) -> Option<u64> {
+        match self.color_type {
+            0 | 3 => Some(1),
+            2 => Some(3),
+            4 => Some(2),
+            6 => Some(4),
+            _ => None,
+        }
+    }
+}
+
+/// 各种写入方式大约能写入的字节数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capacity {
+    /// 写到一个新 chunk 里（encode），受 --limit 和规范的最大 chunk 长度限制
+    pub chunk: usize,
+    /// 把数据写进像素采样的最低位（LSB，还没有实现），None 表示这种图像不适合
+    pub lsb: Option<u64>,
+}
+
+impl Capacity {
+    pub fn estimate(png: &Png, limit: usize) -> Capacity {
+        let lsb = ImageHeader::from_png(png).and_then(|header| {
+            // 只考虑每个采样 8 或 16 位的图像；调色板图像改动索引会明显改变颜色
+            if header.color_type == 3 || !matches!(header.bit_depth, 8 | 16) {
+                return None;
+            }
+            let samples = header.width as u64 * header.height as u64 * header.channels()?;
+            // 每个采样存 1 位
+            Some(samples / 8)
+        });
+
+        Capacity {
+            chunk: limit.min(MAX_CHUNK_LENGTH as usize),
+            lsb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn png_with_header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Png {
+        let mut data = Vec::new();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)])
+    }
+
+    #[test]
+    fn test_check_payload() {
+        assert_eq!(check_payload(10, 10), Ok(()));
+        assert_eq!(check_payload(11, 10), Err(CapacityError::OverLimit { size: 11, limit: 10 }));
+
+        let too_big = MAX_CHUNK_LENGTH as usize + 1;
+        assert_eq!(check_payload(too_big, usize::MAX), Err(CapacityError::OverChunkMaximum { size: too_big }));
+    }
+
+    #[test]
+    fn test_estimate_capacity() {
+        // 100x50 RGBA: 20000 个采样, 每个采样 1 位
+        let capacity = Capacity::estimate(&png_with_header(100, 50, 8, 6), DEFAULT_PAYLOAD_LIMIT);
+        assert_eq!(capacity, Capacity { chunk: DEFAULT_PAYLOAD_LIMIT, lsb: Some(2500) });
+
+        assert_eq!(Capacity::estimate(&png_with_header(100, 50, 8, 3), 1024).lsb, None);
+        assert_eq!(Capacity::estimate(&png_with_header(100, 50, 1, 0), 1024).lsb, None);
+        assert_eq!(Capacity::estimate(&Png::from_chunks(vec![]), usize::MAX).chunk, MAX_CHUNK_LENGTH as usize);
+    }
+}
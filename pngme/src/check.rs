@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::chunk::{Chunk, RawChunk};
+use crate::png::{Png, PngError};
+
+/// `check` 命令里一个 chunk 的检查结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkReport {
+    /// chunk 在文件中的字节偏移
+    pub offset: usize,
+    pub chunk_type: [u8; 4],
+    pub length: u32,
+    pub stored_crc: u32,
+    pub computed_crc: u32,
+}
+
+impl ChunkReport {
+    pub fn is_valid(&self) -> bool {
+        self.stored_crc == self.computed_crc
+    }
+}
+
+/// 检查整个文件的 CRC：先顺序找出所有 chunk 的位置（只读长度字段，很快），
+/// 再用 `threads` 个线程计算 CRC。和 `Png::try_from` 不同，CRC 不对时不会停下，
+/// 每个 chunk 都有结果；只有签名错误或者 chunk 结构损坏时才返回错误
+pub fn check_png(bytes: &[u8], threads: usize) -> Result<Vec<ChunkReport>, PngError> {
+    let raw_chunks = split_chunks(bytes)?;
+    let crcs = compute_crcs(&raw_chunks, threads);
+    Ok(raw_chunks
+        .iter()
+        .zip(crcs)
+        .map(|(&(offset, raw), computed_crc)| ChunkReport {
+            offset,
+            chunk_type: raw.chunk_type,
+            length: raw.data.len() as u32,
+            stored_crc: raw.crc,
+            computed_crc,
+        })
+        .collect())
+}
+
+/// 检查签名，按顺序切出所有 chunk 和它们在文件中的偏移，不校验 CRC
+pub fn split_chunks(bytes: &[u8]) -> Result<Vec<(usize, RawChunk<'_>)>, PngError> {
+    if bytes.len() < 8 {
+        return Err(PngError::TooShort(bytes.len()));
+    }
+    let mut signature = [0u8; 8];
+    signature.copy_from_slice(&bytes[..8]);
+    if signature != Png::STANDARD_HEADER {
+        return Err(PngError::InvalidSignature(signature));
+    }
+
+    let mut raw_chunks = Vec::new();
+    let mut offset = 8;
+    while offset < bytes.len() {
+        let (raw, consumed) = Chunk::split(&bytes[offset..])
+            .map_err(|source| PngError::Chunk { offset, source })?;
+        raw_chunks.push((offset, raw));
+        offset += consumed;
+    }
+    Ok(raw_chunks)
+}
+
+// 每个线程从共享的下标里取下一个 chunk，大小悬殊的 IDAT 也能分配均匀
+fn compute_crcs(chunks: &[(usize, RawChunk<'_>)], threads: usize) -> Vec<u32> {
+    let threads = threads.clamp(1, chunks.len().max(1));
+    if threads == 1 {
+        return chunks.iter().map(|(_, raw)| raw.computed_crc()).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut crcs = vec![0; chunks.len()];
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((_, raw)) = chunks.get(index) else {
+                            break done;
+                        };
+                        done.push((index, raw.computed_crc()));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, crc) in worker.join().expect("CRC worker panicked") {
+                crcs[index] = crc;
+            }
+        }
+    });
+    crcs
+}
+
+/// 默认的线程数：可用的 CPU 核数
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn png_bytes(idat_count: usize) -> Vec<u8> {
+        let chunk = |ty: &str, data: Vec<u8>| Chunk::new(ChunkType::from_str(ty).unwrap(), data);
+        let mut chunks = vec![chunk("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0])];
+        chunks.extend((0..idat_count).map(|i| chunk("IDAT", vec![i as u8; 1000 + i * 37])));
+        chunks.push(chunk("IEND", Vec::new()));
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    #[test]
+    fn test_all_chunks_valid() {
+        let bytes = png_bytes(20);
+        let reports = check_png(&bytes, 4).unwrap();
+        assert_eq!(reports.len(), 22);
+        assert!(reports.iter().all(ChunkReport::is_valid));
+        assert_eq!(reports[0].offset, 8);
+        assert_eq!(&reports[1].chunk_type, b"IDAT");
+    }
+
+    #[test]
+    fn test_reports_every_bad_crc() {
+        let mut bytes = png_bytes(20);
+        let reports = check_png(&bytes, 1).unwrap();
+        // 改掉第 3 个和第 10 个 chunk 的一个数据字节
+        for report in [&reports[3], &reports[10]] {
+            bytes[report.offset + 8] ^= 0xff;
+        }
+
+        let bad: Vec<usize> = check_png(&bytes, 3)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, report)| !report.is_valid())
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(bad, vec![3, 10]);
+    }
+
+    #[test]
+    fn test_threads_agree() {
+        let bytes = png_bytes(50);
+        assert_eq!(check_png(&bytes, 1).unwrap(), check_png(&bytes, 8).unwrap());
+        assert!(check_png(&bytes[..bytes.len() - 3], 2).is_err());
+    }
+}
@@ -1,7 +1,34 @@
 use std::{convert::TryFrom, fmt::Display, string::FromUtf8Error};
-use crc::CRC_32_ISO_HDLC;
+use std::io::{self, Write};
+use thiserror::Error;
 use crate::chunk_type::ChunkType;
 
+/// PNG 规范规定 chunk 数据长度最大为 2^31 - 1
+pub const MAX_CHUNK_LENGTH: u32 = (1 << 31) - 1;
+
+/// 解析 chunk 时可能出现的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChunkError {
+    /// 剩余字节不足 12 字节（长度 + 类型 + CRC）
+    #[error("chunk is too short ({0} bytes, need at least 12)")]
+    TooShort(usize),
+    /// 长度字段超过规范允许的最大值
+    #[error("chunk length {0} exceeds the maximum of {MAX_CHUNK_LENGTH}")]
+    LengthTooLarge(u32),
+    /// 长度字段指向的数据超出了输入的末尾
+    #[error("chunk declares {length} data bytes but only {available} are available")]
+    Truncated { length: u32, available: usize },
+    /// chunk 类型不是 4 个英文字母
+    #[error("invalid chunk type {0:?}")]
+    InvalidChunkType([u8; 4]),
+    /// 存储的 CRC 与计算出的 CRC 不一致
+    #[error("CRC mismatch: stored {expected:#010x}, computed {actual:#010x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+    /// chunk 之后还有多余的字节
+    #[error("{0} unexpected bytes after chunk")]
+    TrailingBytes(usize),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Chunk {
     length: u32,
@@ -12,13 +39,7 @@ pub struct Chunk {
 
 impl Chunk {
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let crc_calculator = crc::Crc::<u32>::new(&CRC_32_ISO_HDLC);
-
-        let mut crc_input: Vec<u8>= Vec::new();
-        crc_input.extend_from_slice(&chunk_type.bytes());
-        crc_input.extend_from_slice(&data);
-
-        let crc = crc_calculator.checksum(&crc_input);
+        let crc = chunk_crc(&chunk_type.bytes(), &data);
 
         let length = data.len() as u32;
 
@@ -50,6 +71,11 @@ impl Chunk {
         String::from_utf8(self.data.clone())
     }
 
+    /// 数据是合法的 UTF-8 时按文本借出，二进制数据返回 None
+    pub fn data_as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut chunk_bytes: Vec<u8> = Vec::new();
 
@@ -60,43 +86,108 @@ impl Chunk {
 
         chunk_bytes.extend_from_slice(&length_bytes);
         chunk_bytes.extend_from_slice(&chunk_type_bytes);
-        chunk_bytes.extend_from_slice(&data_bytes);
+        chunk_bytes.extend_from_slice(data_bytes);
         chunk_bytes.extend_from_slice(&crc_bytes);
 
         chunk_bytes
     }
+
+    /// 按文件中的格式写出，和 `as_bytes` 相同，但不用先拼成一个 Vec
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.length.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc.to_be_bytes())
+    }
 }
 
-impl TryFrom<&Vec<u8>> for Chunk {
-    type Error = &'static str;
+/// PNG 的 CRC 覆盖 chunk 类型和数据，不包括长度字段。
+/// crc32fast 在支持的 CPU 上使用 SIMD / CRC 指令，否则退回查表实现
+pub fn chunk_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(data);
+    hasher.finalize()
+}
 
-    fn try_from(value: &Vec<u8>) -> Result<Self, Self::Error> {
-        if value.len() < 12 {
-            return Err("Chunk data is too short");
+/// 还没有校验 CRC 的 chunk，数据借用自输入
+#[derive(Debug, Clone, Copy)]
+pub struct RawChunk<'a> {
+    pub chunk_type: [u8; 4],
+    pub data: &'a [u8],
+    /// 文件里存储的 CRC
+    pub crc: u32,
+}
+
+impl RawChunk<'_> {
+    pub fn computed_crc(&self) -> u32 {
+        chunk_crc(&self.chunk_type, self.data)
+    }
+}
+
+impl Chunk {
+    /// 从字节序列开头解析一个 chunk，返回 chunk 和消耗的字节数。
+    /// 所有长度都在分配内存之前检查，任何畸形输入都只会返回错误而不会 panic
+    pub fn parse(bytes: &[u8]) -> Result<(Chunk, usize), ChunkError> {
+        let (raw, consumed) = Chunk::split(bytes)?;
+
+        let chunk_type = ChunkType::try_from(raw.chunk_type)
+            .map_err(|_| ChunkError::InvalidChunkType(raw.chunk_type))?;
+
+        let crc_check = raw.computed_crc();
+        if raw.crc != crc_check {
+            return Err(ChunkError::CrcMismatch { expected: raw.crc, actual: crc_check });
         }
+        let chunk = Chunk {
+            length: raw.data.len() as u32,
+            chunk_type,
+            data: raw.data.to_vec(),
+            crc: raw.crc,
+        };
+        Ok((chunk, consumed))
+    }
 
-        let length = u32::from_be_bytes(value[0..4].try_into().unwrap());
-        let type_value: [u8; 4] = value[4..8].try_into().expect("unable to try_into array slice in try_from function");
-        let chunk_type = ChunkType::try_from(type_value)?;
-        let data = value[8..(8 + length as usize)].to_vec();
-        let crc = u32::from_be_bytes(value[(8 + length as usize)..].try_into().unwrap());
+    /// 和 `parse` 一样检查长度和类型，但不计算 CRC，也不复制数据；
+    /// 用于先找出所有 chunk 的位置，再并行校验 CRC
+    pub fn split(bytes: &[u8]) -> Result<(RawChunk<'_>, usize), ChunkError> {
+        if bytes.len() < 12 {
+            return Err(ChunkError::TooShort(bytes.len()));
+        }
 
-        let crc_calculator = crc::Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        
-        let mut crc_input: Vec<u8> = Vec::new();
-        crc_input.extend_from_slice(&chunk_type.bytes());
-        crc_input.extend_from_slice(&data);
-        let crc_check = crc_calculator.checksum(&crc_input);
-        if crc == crc_check {
-            Ok(Chunk {
-                length,
-                chunk_type,
-                data,
-                crc,
-            })
-        } else {
-            Err("CRC mismatch")
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if length > MAX_CHUNK_LENGTH {
+            return Err(ChunkError::LengthTooLarge(length));
         }
+
+        // 在复制数据之前确认输入里真的有这么多字节
+        let available = bytes.len() - 12;
+        if length as usize > available {
+            return Err(ChunkError::Truncated { length, available });
+        }
+
+        let type_value = [bytes[4], bytes[5], bytes[6], bytes[7]];
+        if !type_value.iter().all(|b| b.is_ascii_alphabetic()) {
+            return Err(ChunkError::InvalidChunkType(type_value));
+        }
+
+        let data_end = 8 + length as usize;
+        let data = &bytes[8..data_end];
+        let crc_bytes = &bytes[data_end..data_end + 4];
+        let crc = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+
+        Ok((RawChunk { chunk_type: type_value, data, crc }, data_end + 4))
+    }
+}
+
+impl TryFrom<&Vec<u8>> for Chunk {
+    type Error = ChunkError;
+
+    fn try_from(value: &Vec<u8>) -> Result<Self, Self::Error> {
+        let (chunk, consumed) = Chunk::parse(value)?;
+        if consumed != value.len() {
+            return Err(ChunkError::TrailingBytes(value.len() - consumed));
+        }
+        Ok(chunk)
     }
 }
 
@@ -201,6 +292,17 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_binary_data_round_trip() {
+        let data = vec![0x00, 0xff, 0xfe, b'\n', 0x80];
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), data.clone());
+        let parsed = Chunk::try_from(&chunk.as_bytes()).unwrap();
+        assert_eq!(parsed.data(), data.as_slice());
+        assert_eq!(parsed.data_as_str(), None);
+        assert!(parsed.data_as_string().is_err());
+        assert_eq!(testing_chunk().data_as_str(), Some("This is where your secret message will be!"));
+    }
+
     #[test]
     fn test_invalid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -242,4 +344,29 @@ mod tests {
         
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_malformed_chunks_return_errors() {
+        assert_eq!(Chunk::parse(&[0, 0, 0]).unwrap_err(), ChunkError::TooShort(3));
+
+        // 声明 256 字节数据, 实际没有
+        let truncated = [0, 0, 1, 0, b'R', b'u', b'S', b't', 0, 0, 0, 0];
+        assert_eq!(
+            Chunk::parse(&truncated).unwrap_err(),
+            ChunkError::Truncated { length: 256, available: 0 }
+        );
+
+        let bad_type = [0, 0, 0, 0, b'R', b'u', b'5', b't', 0, 0, 0, 0];
+        assert_eq!(
+            Chunk::parse(&bad_type).unwrap_err(),
+            ChunkError::InvalidChunkType(*b"Ru5t")
+        );
+    }
+
+    #[test]
+    fn test_trailing_bytes_rejected() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        chunk_data.push(0);
+        assert_eq!(Chunk::try_from(&chunk_data).unwrap_err(), ChunkError::TrailingBytes(1));
+    }
 }
\ No newline at end of file
@@ -1,19 +1,19 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
+use thiserror::Error;
 
 /**# 说明
-```
-位置	字符	意义	解释
-第1字节	I	是否为关键块（Critical）	大写表示关键块，必须被解析才能理解图片
-第2字节	H	是否为公开块（Public）	大写表示这是 PNG 标准公开定义的类型，小写是私有块
-第3字节	D	是否被保留（Reserved）	必须是大写，供将来标准扩展使用（当前应为大写）
-第4字节	R	是否安全复制（Safe-to-copy）	小写表示可以安全复制，即使解码器不理解这个块
+```text
+位置  字符  意义  解释
+第1字节  I  是否为关键块（Critical）  大写表示关键块，必须被解析才能理解图片
+第2字节  H  是否为公开块（Public）  大写表示这是 PNG 标准公开定义的类型，小写是私有块
+第3字节  D  是否被保留（Reserved）  必须是大写，供将来标准扩展使用（当前应为大写）
+第4字节  R  是否安全复制（Safe-to-copy）  小写表示可以安全复制，即使解码器不理解这个块
 ```
  */
-
 /**# 合法性
-```
+```text
 根据 PNG 规范，一个 chunk type 是合法的前提包括：
 正好是 4 个 ASCII 字符。
 每个字符必须是英文字母：A-Z 或 a-z。
@@ -21,7 +21,6 @@ use std::str::FromStr;
 其他位的大小写有意义，但不影响是否合法（只影响语义）。
 ```
  */
-
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ChunkType([u8; 4]); 
 
@@ -62,26 +61,35 @@ impl ChunkType {
     }
 }
 
+/// 构造 chunk type 时可能出现的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChunkTypeError {
+    /// 字节数组中有非 ASCII 字节
+    #[error("Invalid byte array {0:?}")]
+    NonAscii([u8; 4]),
+    /// 字符串不是 4 个英文字母
+    #[error("Invalid chunk type {0:?}: expected 4 alphabetic characters")]
+    InvalidString(String),
+}
+
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = &'static str;
-    // type Error = Box<dyn std::error::Error>;
+    type Error = ChunkTypeError;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
         if value.iter().all(|&x| x.is_ascii()) {
             Ok(ChunkType(value))
         } else {
-            Err("Invalid byte array")
+            Err(ChunkTypeError::NonAscii(value))
         }
     }
 }
 
 impl FromStr for ChunkType {
-    type Err = &'static str;
-    // type Err = Box<dyn std::error::Error>;
+    type Err = ChunkTypeError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() != 4 || !s.chars().all(|c| c.is_ascii_alphabetic()) {
-            return Err("Invalid string length or non-alphabetic characters");
+            return Err(ChunkTypeError::InvalidString(s.to_string()));
         }
 
         let bytes = s.as_bytes();
@@ -180,7 +188,10 @@ mod tests {
         assert!(!chunk.is_valid());
 
         let chunk = ChunkType::from_str("Ru1t");
-        assert!(chunk.is_err());
+        assert_eq!(chunk, Err(ChunkTypeError::InvalidString("Ru1t".to_string())));
+
+        let chunk = ChunkType::try_from([82, 200, 83, 116]);
+        assert_eq!(chunk, Err(ChunkTypeError::NonAscii([82, 200, 83, 116])));
     }
 
     #[test]
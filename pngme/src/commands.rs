@@ -1,4 +1,15 @@
-pub(crate) mod encode;
-pub(crate) mod decode;
-pub(crate) mod remove;
-pub(crate) mod print;
\ No newline at end of file
+pub mod encode;
+pub mod decode;
+pub mod remove;
+pub mod print;
+pub mod insert;
+pub mod message;
+pub mod move_chunk;
+pub mod diff;
+pub mod dump;
+pub mod capacity;
+pub mod check;
+pub mod metadata;
+pub mod safe_write;
+pub mod sign;
+pub mod verify;
\ No newline at end of file
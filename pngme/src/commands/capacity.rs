@@ -0,0 +1,31 @@
+use std::{fs, path::PathBuf};
+use crate::Result;
+
+use crate::capacity::{Capacity, ImageHeader};
+use crate::png::Png;
+
+/// 估计PNG文件用各种方式能写入多少数据
+pub fn capacity(
+    file_path: PathBuf,
+    limit: usize,
+) -> Result<()> {
+    // 读取PNG文件
+    let file_data = fs::read(&file_path)?;
+    let png = Png::try_from(file_data.as_slice())?;
+
+    let capacity = Capacity::estimate(&png, limit);
+
+    if let Some(header) = ImageHeader::from_png(&png) {
+        println!(
+            "Image: {}x{}, bit depth {}, color type {}",
+            header.width, header.height, header.bit_depth, header.color_type
+        );
+    }
+    println!("Chunk (encode): up to {} bytes per chunk", capacity.chunk);
+    match capacity.lsb {
+        Some(bytes) => println!("LSB (not implemented yet): about {} bytes", bytes),
+        None => println!("LSB (not implemented yet): not suitable for this image"),
+    }
+
+    Ok(())
+}
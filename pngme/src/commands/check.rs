@@ -0,0 +1,49 @@
+use std::{fs, path::PathBuf};
+use crate::{Error, Result};
+
+use crate::check::{check_png, default_threads, split_chunks};
+use crate::structure;
+
+/// 校验PNG文件中每个chunk的CRC，再检查IHDR、PLTE、tRNS是否一致，打印结果；
+/// 有损坏的chunk或者结构问题时返回错误
+pub fn check(
+    file_path: PathBuf,
+    threads: Option<usize>,
+) -> Result<()> {
+    let file_data = fs::read(&file_path)?;
+    let reports = check_png(&file_data, threads.unwrap_or_else(default_threads))?;
+
+    let mut corrupt = 0;
+    for report in &reports {
+        let status = if report.is_valid() {
+            "ok".to_string()
+        } else {
+            corrupt += 1;
+            format!("CRC mismatch (computed {:#010x})", report.computed_crc)
+        };
+        println!(
+            "{:>10}  {}  {:>10}  {:#010x}  {}",
+            report.offset,
+            String::from_utf8_lossy(&report.chunk_type),
+            report.length,
+            report.stored_crc,
+            status
+        );
+    }
+
+    let structure = structure::inspect(
+        split_chunks(&file_data)?.iter().map(|(_, raw)| (raw.chunk_type, raw.data)),
+    );
+    for line in structure.summary() {
+        println!("{}", line);
+    }
+
+    if corrupt > 0 {
+        return Err(Error::CorruptChunks(corrupt));
+    }
+    if !structure.is_healthy() {
+        return Err(Error::StructureIssues(structure.issues.len()));
+    }
+    println!("{} chunks ok", reports.len());
+    Ok(())
+}
@@ -1,25 +1,48 @@
 use std::path::PathBuf;
-use anyhow::Result;
+use crate::{Error, Result};
 use std::fs;
+use std::io::{self, Write};
 
 use crate::chunk_type::ChunkType;
+use crate::crypto;
 use crate::png::Png;
+use super::safe_write::write_atomic;
 
-/// 解码PNG文件中的指定chunk
-
+/// 解码PNG文件中的指定chunk。
+/// `raw` 时把消息的字节原样写到 `output`（没有时写到标准输出），有密码时写出的是解密后的消息
 pub fn decode(
     file_path: PathBuf,
     chunk_type: ChunkType,
+    password: Option<String>,
+    raw: bool,
+    output: Option<PathBuf>,
 ) -> Result<()> {
     // 读取PNG文件
     let file_data = fs::read(&file_path)?;
     
     // 创建Png对象
-    let png = Png::try_from(file_data.as_slice()).unwrap();
+    let png = Png::try_from(file_data.as_slice())?;
 
     // 转换chunk_type为&str
     let chunk_type_str = chunk_type.to_string();
 
+    if raw {
+        let chunk = png.chunk_by_type(&chunk_type_str).ok_or(Error::ChunkNotFound(chunk_type))?;
+        let message = match password {
+            Some(password) => crypto::decrypt(chunk.data(), &password)?,
+            None => chunk.data().to_vec(),
+        };
+        match output {
+            Some(path) => write_atomic(&path, &message, false)?,
+            None => {
+                let mut out = io::stdout().lock();
+                out.write_all(&message)?;
+                out.flush()?;
+            }
+        }
+        return Ok(());
+    }
+
     // 触发彩蛋的chunk类型
     if chunk_type_str == "bOOm" {
         println!("\n炸弹已激活！倒计时开始...");
@@ -46,9 +69,18 @@ pub fn decode(
 
     // 查找指定类型的chunk
     if let Some(chunk) = png.chunk_by_type(&chunk_type_str) {
-        // 打印chunk的内容
+        // 打印chunk的内容；加密的消息要先用密码解密，没给密码时不打印密文
         println!("Chunk Type: {:?}", chunk.chunk_type());
-        println!("Chunk Data: {:?}", String::from_utf8_lossy(chunk.data()));
+        match password {
+            Some(password) => {
+                let message = crypto::decrypt(chunk.data(), &password)?;
+                println!("Chunk Data: {}", show(std::str::from_utf8(&message).ok(), message.len()));
+            }
+            None if crypto::is_encrypted(chunk.data()) => {
+                println!("Chunk Data: <encrypted, use --password to decrypt>");
+            }
+            None => println!("Chunk Data: {}", show(chunk.data_as_str(), chunk.data().len())),
+        }
         
         // 根据chunk类型显示不同的ASCII艺术
         match chunk_type_str.as_str() {
@@ -78,4 +110,12 @@ pub fn decode(
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+// 文本消息加引号打印；二进制消息只打印长度，内容用 --raw 取出
+fn show(text: Option<&str>, len: usize) -> String {
+    match text {
+        Some(text) => format!("{:?}", text),
+        None => format!("<{} bytes of binary data, use --raw to write them out>", len),
+    }
+}
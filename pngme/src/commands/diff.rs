@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+use crate::Result;
+
+use crate::chunk::Chunk;
+use crate::diff::{diff_chunks, ChunkDiff};
+use crate::png::Png;
+
+/// 一栏的宽度: 类型 4 + 长度 10 + CRC 10 + 分隔
+const COLUMN_WIDTH: usize = 28;
+
+/// 并排比较两个PNG文件的chunk序列
+pub fn diff(
+    first_path: PathBuf,
+    second_path: PathBuf,
+    show_text: bool,
+) -> Result<()> {
+    let first = read_png(&first_path)?;
+    let second = read_png(&second_path)?;
+    let diff = diff_chunks(first.chunks(), second.chunks());
+
+    println!("  {:<COLUMN_WIDTH$}   {}", first_path.display(), second_path.display());
+    for entry in &diff {
+        let (mark, old, new) = match entry {
+            ChunkDiff::Same(a, b) => (' ', Some(*a), Some(*b)),
+            ChunkDiff::Added(b) => ('+', None, Some(*b)),
+            ChunkDiff::Removed(a) => ('-', Some(*a), None),
+            ChunkDiff::Modified(a, b) => ('~', Some(*a), Some(*b)),
+        };
+        println!("{} {:<COLUMN_WIDTH$} | {}", mark, summary(old), summary(new));
+
+        // 有差异的文本chunk打印出内容
+        if show_text && !entry.is_same() {
+            for (sign, chunk) in [('-', old), ('+', new)] {
+                if let Some(text) = chunk.and_then(text_of) {
+                    println!("    {} {:?}", sign, text);
+                }
+            }
+        }
+    }
+
+    let changed = diff.iter().filter(|entry| !entry.is_same()).count();
+    if changed == 0 {
+        println!("Chunks are identical");
+    } else {
+        println!("{} chunk(s) differ", changed);
+    }
+
+    Ok(())
+}
+
+fn read_png(path: &Path) -> Result<Png> {
+    let file_data = fs::read(path)?;
+    Ok(Png::try_from(file_data.as_slice())?)
+}
+
+/// 一个chunk在表格里的一栏: 类型、长度、CRC
+fn summary(chunk: Option<&Chunk>) -> String {
+    match chunk {
+        Some(chunk) => format!("{} {:>8} {:#010x}", chunk.chunk_type(), chunk.length(), chunk.crc()),
+        None => String::new(),
+    }
+}
+
+/// 文本chunk的内容: tEXt 的关键字和文本之间的 \0 显示为 ": ",
+/// 其它数据是合法 UTF-8 的 chunk（例如 encode 写入的消息）原样显示
+fn text_of(chunk: &Chunk) -> Option<String> {
+    if chunk.chunk_type().is_critical() {
+        return None;
+    }
+    let text = chunk.data_as_string().ok()?;
+    if chunk.chunk_type().to_string() == "tEXt" {
+        return Some(text.replacen('\0', ": ", 1));
+    }
+    (!text.contains('\0')).then_some(text)
+}
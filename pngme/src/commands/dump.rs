@@ -0,0 +1,54 @@
+use std::io::{self, Write};
+use std::{fs, path::PathBuf};
+use crate::{Error, Result};
+
+use crate::check::split_chunks;
+use crate::chunk_type::ChunkType;
+use crate::dump::hex_dump;
+
+/// 打印所有指定类型的chunk的数据：每个chunk先打印一行偏移、长度和CRC，
+/// 然后是十六进制 + ASCII 的转储，行首是数据在chunk内的偏移。
+/// 不校验CRC，损坏的chunk也能看；`raw` 时把这些chunk的数据依次原样写到标准输出
+pub fn dump(
+    file_path: PathBuf,
+    chunk_type: ChunkType,
+    raw: bool,
+) -> Result<()> {
+    let file_data = fs::read(&file_path)?;
+    let chunks: Vec<_> = split_chunks(&file_data)?
+        .into_iter()
+        .filter(|(_, chunk)| chunk.chunk_type == chunk_type.bytes())
+        .collect();
+    if chunks.is_empty() {
+        return Err(Error::ChunkNotFound(chunk_type));
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (offset, chunk) in &chunks {
+        if raw {
+            out.write_all(chunk.data)?;
+            continue;
+        }
+        let computed = chunk.computed_crc();
+        let status = if computed == chunk.crc {
+            "ok".to_string()
+        } else {
+            format!("CRC mismatch (computed {:#010x})", computed)
+        };
+        writeln!(
+            out,
+            "{} at offset {}, {} bytes, CRC {:#010x} {}",
+            chunk_type,
+            offset,
+            chunk.data.len(),
+            chunk.crc,
+            status
+        )?;
+        for line in hex_dump(chunk.data, 0) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -1,36 +1,114 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
-use std::fs;
-use std::convert::TryFrom;
-use anyhow::Result;
+use std::fs::File;
+use std::io::{self, BufReader};
+use crate::Result;
 
+use crate::capacity::check_payload;
 use crate::chunk_type::ChunkType;
 use crate::chunk::Chunk;
-use crate::png::Png;
+use crate::crypto::{self, NONCE_LEN, SALT_LEN};
+use crate::png::{ChunkReader, ChunkWriter};
+use super::message::read_message;
+use super::safe_write::write_atomic_with;
 
 pub fn encode(
     file_path: PathBuf,
     chunk_type: ChunkType,
-    message: String,
-    output_path: Option<PathBuf>
+    message: OsString,
+    output_path: Option<PathBuf>,
+    limit: usize,
+    backup: bool,
+    password: Option<String>,
 ) -> Result<()> {
-    // 读取PNG文件
-    let file_data = fs::read(&file_path)?;
-    let mut png = Png::try_from(file_data.as_slice()).unwrap();
-    
+    // 消息按字节处理，不要求是 UTF-8；有密码时先加密，写进去的是密文
+    let message = read_message(message)?;
+    let payload = match password {
+        Some(password) => {
+            let mut salt = [0u8; SALT_LEN];
+            let mut nonce = [0u8; NONCE_LEN];
+            getrandom::getrandom(&mut salt).map_err(io::Error::from)?;
+            getrandom::getrandom(&mut nonce).map_err(io::Error::from)?;
+            crypto::encrypt(&message, &password, salt, nonce)?
+        }
+        None => message,
+    };
+
+    // 消息太大时在读文件之前就报错
+    check_payload(payload.len(), limit)?;
+
     // 创建新的chunk
-    let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
-    
-    // 添加chunk到PNG
-    png.append_chunk(chunk);
-    
+    let chunk = Chunk::new(chunk_type, payload);
+
     // 确定输出路径
     let out_path = match output_path {
         Some(path) => path,
-        None => file_path
+        None => file_path.clone()
     };
-    
-    // 写回文件
-    fs::write(out_path, png.as_bytes())?;
-    
-    Ok(())
-}
\ No newline at end of file
+
+    // 逐个 chunk 从原文件复制到临时文件，新的 chunk 放在 IEND 之前（没有 IEND 时放在最后）；
+    // 文件再大，内存中也只有一个 chunk
+    let reader = ChunkReader::new(BufReader::new(File::open(&file_path)?))?;
+    write_atomic_with(&out_path, backup, |out| -> Result<()> {
+        let mut writer = ChunkWriter::new(out)?;
+        let mut inserted = false;
+        for existing in reader {
+            let existing = existing?;
+            if !inserted && existing.chunk_type().to_string() == "IEND" {
+                writer.write_chunk(&chunk)?;
+                inserted = true;
+            }
+            writer.write_chunk(&existing)?;
+        }
+        if !inserted {
+            writer.write_chunk(&chunk)?;
+        }
+        writer.finish()?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+    use std::str::FromStr;
+    use crate::capacity::DEFAULT_PAYLOAD_LIMIT;
+    use crate::png::Png;
+    use crate::verify::verify_png;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_encoded_file_passes_verify() {
+        let dir = std::env::temp_dir().join(format!("pngme-encode-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.png");
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("IDAT", b"pixels"),
+            chunk("IEND", b""),
+        ]);
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        encode(path.clone(), chunk_type, "hidden".into(), None, DEFAULT_PAYLOAD_LIMIT, false, None)
+            .unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let report = verify_png(&bytes);
+        assert!(report.is_valid(), "{:?}", report.violations);
+        let encoded = Png::try_from(bytes.as_slice()).unwrap();
+        let types: Vec<String> = encoded
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IDAT", "RuSt", "IEND"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
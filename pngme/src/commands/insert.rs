@@ -0,0 +1,36 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::fs;
+use crate::Result;
+
+use crate::chunk_type::ChunkType;
+use crate::chunk::Chunk;
+use crate::png::Png;
+use super::message::read_message;
+use super::safe_write::write_atomic;
+
+/// 在指定位置插入一个新的chunk（0 表示紧跟在签名之后）
+pub fn insert(
+    file_path: PathBuf,
+    chunk_type: ChunkType,
+    message: OsString,
+    position: usize,
+    output_path: Option<PathBuf>,
+    backup: bool,
+) -> Result<()> {
+    // 读取PNG文件
+    let file_data = fs::read(&file_path)?;
+    let mut png = Png::try_from(file_data.as_slice())?;
+
+    // 插入chunk, 顺序不合法时返回错误
+    let chunk = Chunk::new(chunk_type, read_message(message)?);
+    png.insert_chunk(position, chunk)?;
+
+    // 确定输出路径
+    let out_path = output_path.unwrap_or(file_path);
+
+    // 写回文件
+    write_atomic(&out_path, &png.as_bytes(), backup)?;
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::ffi::OsString;
+use std::io::{self, Read};
+
+/// 取得 `--message` 的字节：`-` 表示读取标准输入直到结束，可以是任意二进制数据；
+/// 其它值就是参数本身，Unix 上不要求是合法的 UTF-8
+pub fn read_message(message: OsString) -> io::Result<Vec<u8>> {
+    if message == "-" {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+    arg_bytes(message)
+}
+
+#[cfg(unix)]
+fn arg_bytes(message: OsString) -> io::Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(message.into_vec())
+}
+
+#[cfg(not(unix))]
+fn arg_bytes(message: OsString) -> io::Result<Vec<u8>> {
+    message.into_string().map(String::into_bytes).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "message is not valid Unicode, pass it on stdin with --message -")
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStringExt;
+
+    #[test]
+    fn test_argument_bytes() {
+        assert_eq!(read_message(OsString::from("héllo")).unwrap(), "héllo".as_bytes());
+        let binary = vec![0xff, 0x00, b'a', 0xfe];
+        assert_eq!(read_message(OsString::from_vec(binary.clone())).unwrap(), binary);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fs, path::PathBuf};
+use crate::Result;
+
+use crate::metadata::{Metadata, Orientation};
+use crate::png::Png;
+use super::safe_write::write_atomic;
+
+/// 打印PNG文件中的方向、描述和版权
+pub fn metadata(file_path: PathBuf) -> Result<()> {
+    let file_data = fs::read(&file_path)?;
+    let png = Png::try_from(file_data.as_slice())?;
+    let metadata = Metadata::from_png(&png)?;
+
+    match metadata.orientation {
+        Some(orientation) => println!("Orientation: {} ({})", orientation.value(), orientation.description()),
+        None => println!("Orientation: -"),
+    }
+    println!("Description: {}", metadata.description.as_deref().unwrap_or("-"));
+    println!("Copyright:   {}", metadata.copyright.as_deref().unwrap_or("-"));
+    Ok(())
+}
+
+/// 修改元数据并重新写入eXIf和XMP chunk，没有给出的字段保持不变
+pub fn set_metadata(
+    file_path: PathBuf,
+    orientation: Option<u16>,
+    description: Option<String>,
+    copyright: Option<String>,
+    output: Option<PathBuf>,
+    backup: bool,
+) -> Result<()> {
+    let update = Metadata {
+        orientation: orientation.map(Orientation::new).transpose()?,
+        description,
+        copyright,
+    };
+
+    let file_data = fs::read(&file_path)?;
+    let mut png = Png::try_from(file_data.as_slice())?;
+    Metadata::update_png(&mut png, &update)?;
+
+    write_atomic(&output.unwrap_or(file_path), &png.as_bytes(), backup)?;
+    Ok(())
+}
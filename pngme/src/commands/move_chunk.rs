@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+use std::fs;
+use crate::{Error, Result};
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use super::safe_write::write_atomic;
+
+/// 把指定类型的chunk移动到另一个chunk之前或之后
+pub fn move_chunk(
+    file_path: PathBuf,
+    chunk_type: ChunkType,
+    before: Option<ChunkType>,
+    after: Option<ChunkType>,
+    output_path: Option<PathBuf>,
+    backup: bool,
+) -> Result<()> {
+    // 读取PNG文件
+    let file_data = fs::read(&file_path)?;
+    let mut png = Png::try_from(file_data.as_slice())?;
+
+    // 移动chunk, 违反PNG顺序规则时返回错误
+    match (before, after) {
+        (Some(target), None) => png.move_chunk(chunk_type, target, true)?,
+        (None, Some(target)) => png.move_chunk(chunk_type, target, false)?,
+        _ => return Err(Error::MissingMoveTarget),
+    }
+
+    // 确定输出路径
+    let out_path = output_path.unwrap_or(file_path);
+
+    // 写回文件
+    write_atomic(&out_path, &png.as_bytes(), backup)?;
+
+    Ok(())
+}
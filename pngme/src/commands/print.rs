@@ -1,10 +1,10 @@
 use std::{fs, path::PathBuf};
-use anyhow::Result;
+use crate::Result;
 
 use crate::png::Png;
+use crate::structure::Structure;
 
-/// 打印PNG文件中的所有chunk
-
+/// 打印PNG文件中的所有chunk，最后是IHDR、PLTE、tRNS的解码结果和结构问题
 pub fn print(
     file_path: PathBuf,
 ) -> Result<()> {
@@ -12,7 +12,7 @@ pub fn print(
     let file_data = fs::read(&file_path)?;
     
     // 创建Png对象
-    let png = Png::try_from(file_data.as_slice()).unwrap();
+    let png = Png::try_from(file_data.as_slice())?;
 
     // 打印所有chunk的信息
     for chunk in png.chunks() {
@@ -20,6 +20,10 @@ pub fn print(
         println!("Chunk Data: {:?}", String::from_utf8_lossy(chunk.data()));
         println!("-----------------------------");
     }
+
+    for line in Structure::from_png(&png).summary() {
+        println!("{}", line);
+    }
     
     Ok(())
 }
\ No newline at end of file
@@ -1,30 +1,34 @@
-use anyhow::Result;
-use std::fs;
+use crate::Result;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::PathBuf;
 
 use crate::chunk_type::ChunkType;
-use crate::png::Png;
+use crate::png::{ChunkReader, ChunkWriter};
+use super::safe_write::write_atomic_with;
 
 /// 删除PNG文件中的指定chunk
-
 pub fn remove(
     file_path: PathBuf,
     chunk_type: ChunkType,
+    backup: bool,
 ) -> Result<()> {
-    // 读取PNG文件
-    let file_data = fs::read(&file_path)?;
-    
-    // 创建Png对象
-    let mut png = Png::try_from(file_data.as_slice()).unwrap();
+    // 流式读取PNG文件，内存中同时只有一个chunk
+    let reader = ChunkReader::new(BufReader::new(File::open(&file_path)?))?;
 
-    // 转换chunk_type为&str
-    let chunk_type_str = chunk_type.to_string();
-    
-    // 删除指定类型的chunk
-    let _ =png.remove_first_chunk(&chunk_type_str);
-    
-    // 写回文件
-    fs::write(file_path, png.as_bytes())?;
-    
-    Ok(())
-}
\ No newline at end of file
+    // 跳过第一个指定类型的chunk，其余的原样写回
+    write_atomic_with(&file_path, backup, |out| -> Result<()> {
+        let mut writer = ChunkWriter::new(out)?;
+        let mut removed = false;
+        for chunk in reader {
+            let chunk = chunk?;
+            if !removed && *chunk.chunk_type() == chunk_type {
+                removed = true;
+                continue;
+            }
+            writer.write_chunk(&chunk)?;
+        }
+        writer.finish()?;
+        Ok(())
+    })
+}
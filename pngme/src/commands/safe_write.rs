@@ -0,0 +1,158 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// 安全地覆盖文件：先写到同一目录下的临时文件并 fsync，再原子地 rename 到目标路径。
+/// 中途崩溃或磁盘写满时原文件保持不变，最多留下一个临时文件。
+///
+/// `backup` 为 true 且目标文件已存在时，先把它复制为 `<文件名>.bak`
+pub fn write_atomic(path: &Path, bytes: &[u8], backup: bool) -> io::Result<()> {
+    write_atomic_with(path, backup, |writer| writer.write_all(bytes))
+}
+
+/// 和 `write_atomic` 一样，但内容由 `write` 边生成边写进临时文件，不用先在内存里拼好。
+/// `write` 出错时删掉临时文件、原文件不变，错误原样返回；
+/// 目标文件也可以是 `write` 正在读的文件，rename 发生在 `write` 返回之后
+pub fn write_atomic_with<E, F>(path: &Path, backup: bool, write: F) -> Result<(), E>
+where
+    E: From<io::Error>,
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), E>,
+{
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let temp_path = temp_path(path);
+
+    if let Err(e) = write_temp(&temp_path, path, write) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    if backup && path.exists() {
+        let backup_path = backup_path(path);
+        let result = fs::copy(path, &backup_path).and_then(|_| File::open(&backup_path)?.sync_all());
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+    }
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    Ok(sync_dir(dir)?)
+}
+
+/// `image.png` 的备份文件 `image.png.bak`
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// 同一目录下的隐藏临时文件，带上进程号免得两个 pngme 同时写同一个文件时互相覆盖
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".pngme-{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+fn write_temp<E, F>(temp_path: &Path, target: &Path, write: F) -> Result<(), E>
+where
+    E: From<io::Error>,
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), E>,
+{
+    let file = OpenOptions::new().write(true).create_new(true).open(temp_path)?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+    // 覆盖已有文件时保留它的权限
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    Ok(file.sync_all()?)
+}
+
+/// rename 之后 fsync 目录，确保新的目录项也落盘
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pngme-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = temp_dir("atomic");
+        let path = dir.join("image.png");
+        fs::write(&path, b"old").unwrap();
+
+        write_atomic(&path, b"new", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert!(!backup_path(&path).exists());
+        // 不留下临时文件
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_backup() {
+        let dir = temp_dir("backup");
+        let path = dir.join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        write_atomic(&path, b"edited", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"edited");
+        assert_eq!(backup_path(&path), dir.join("image.png.bak"));
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"original");
+
+        // 目标不存在时没有东西可备份
+        let fresh = dir.join("fresh.png");
+        write_atomic(&fresh, b"data", true).unwrap();
+        assert!(!backup_path(&fresh).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_stream_leaves_original() {
+        let dir = temp_dir("stream");
+        let path = dir.join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let result = write_atomic_with(&path, false, |writer| {
+            writer.write_all(b"half of the new")?;
+            Err(io::Error::other("source ended early"))
+        });
+        assert_eq!(result.unwrap_err().to_string(), "source ended early");
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_write_leaves_original() {
+        let dir = temp_dir("missing");
+        let path = dir.join("no_such_dir").join("image.png");
+        assert!(write_atomic(&path, b"data", false).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
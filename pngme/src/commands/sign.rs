@@ -0,0 +1,55 @@
+use std::{fs, path::PathBuf};
+use std::io::{self, Write};
+use std::fs::OpenOptions;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use crate::Result;
+
+use crate::png::Png;
+use crate::signature::{parse_key, sign_png, to_hex, verify_png, SignatureError};
+use super::safe_write::write_atomic;
+
+/// 生成新的密钥对。私钥文件已存在时不覆盖，免得丢掉以前的私钥
+pub fn keygen(secret_key: PathBuf, public_key: PathBuf) -> Result<()> {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed).map_err(io::Error::from)?;
+    let key = SigningKey::from_bytes(&seed);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // 私钥只给自己读
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    writeln!(options.open(&secret_key)?, "{}", to_hex(&key.to_bytes()))?;
+    fs::write(&public_key, format!("{}\n", to_hex(key.verifying_key().as_bytes())))?;
+
+    println!("Public key: {}", to_hex(key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+/// 签名PNG文件的图像数据，已有的签名会被替换
+pub fn sign(
+    file_path: PathBuf,
+    key: PathBuf,
+    output: Option<PathBuf>,
+    backup: bool,
+) -> Result<()> {
+    let key = SigningKey::from_bytes(&parse_key(&fs::read_to_string(&key)?)?);
+    let file_data = fs::read(&file_path)?;
+    let mut png = Png::try_from(file_data.as_slice())?;
+    sign_png(&mut png, &key)?;
+
+    write_atomic(&output.unwrap_or(file_path), &png.as_bytes(), backup)?;
+    Ok(())
+}
+
+/// 验证签名，签名不对或者不是这把公钥签的时返回错误
+pub fn verify(file_path: PathBuf, key: PathBuf) -> Result<()> {
+    let key = VerifyingKey::from_bytes(&parse_key(&fs::read_to_string(&key)?)?)
+        .map_err(|_| SignatureError::InvalidKey)?;
+    let file_data = fs::read(&file_path)?;
+    let png = Png::try_from(file_data.as_slice())?;
+    verify_png(&png, &key)?;
+
+    println!("Signature OK ({})", to_hex(key.as_bytes()));
+    Ok(())
+}
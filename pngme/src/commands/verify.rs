@@ -0,0 +1,28 @@
+use std::{fs, path::PathBuf};
+use crate::{Error, Result};
+
+use crate::verify::verify_png;
+use super::sign;
+
+/// 列出文件中所有损坏的chunk和顺序问题，有问题时返回错误；
+/// 没有问题并且给出了公钥时，接着验证签名
+pub fn verify(
+    file_path: PathBuf,
+    key: Option<PathBuf>,
+) -> Result<()> {
+    let file_data = fs::read(&file_path)?;
+    let report = verify_png(&file_data);
+
+    for violation in &report.violations {
+        println!("{:>10}  {}", violation.offset(), violation);
+    }
+    if !report.is_valid() {
+        return Err(Error::Violations(report.violations.len()));
+    }
+    println!("{} chunks ok", report.chunks);
+
+    match key {
+        Some(key) => sign::verify(file_path, key),
+        None => Ok(()),
+    }
+}
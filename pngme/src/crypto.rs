@@ -0,0 +1,119 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use thiserror::Error;
+
+/// 加密消息的开头，decode 用它认出消息是加密过的
+pub const MAGIC: &[u8; 4] = b"PME1";
+
+/// Argon2 的盐的长度
+pub const SALT_LEN: usize = 16;
+
+/// ChaCha20-Poly1305 的 nonce 长度
+pub const NONCE_LEN: usize = 12;
+
+/// Poly1305 认证标签的长度
+const TAG_LEN: usize = 16;
+
+/// 加密后比原消息多出来的字节：开头、盐、nonce 和认证标签
+pub const OVERHEAD: usize = MAGIC.len() + SALT_LEN + NONCE_LEN + TAG_LEN;
+
+/// 加密和解密时的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CryptoError {
+    /// chunk 里的消息没有加密，不需要 --password
+    #[error("message is not encrypted")]
+    NotEncrypted,
+    /// 开头对，但长度不够放下盐、nonce 和认证标签
+    #[error("encrypted message is truncated")]
+    Truncated,
+    /// 密码错误，或者密文被改过；两者无法区分
+    #[error("wrong password or corrupted message")]
+    WrongPassword,
+    #[error("key derivation failed: {0}")]
+    KeyDerivation(String),
+}
+
+/// 消息是不是 `encrypt` 的结果
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// 用 Argon2id（默认参数）从密码和盐得到 32 字节的密钥
+fn derive_key(password: &str, salt: &[u8]) -> Result<Key, CryptoError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|err| CryptoError::KeyDerivation(err.to_string()))?;
+    Ok(key)
+}
+
+/// 用密码加密消息，结果是 `PME1 | 盐 | nonce | 密文和认证标签`。
+/// 盐和 nonce 由调用方随机生成，每次加密都要用新的
+pub fn encrypt(
+    message: &[u8],
+    password: &str,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+) -> Result<Vec<u8>, CryptoError> {
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), message)
+        .map_err(|_| CryptoError::WrongPassword)?;
+
+    let mut data = Vec::with_capacity(OVERHEAD + message.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// 解密 `encrypt` 的结果，密码不对时返回 `WrongPassword`
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, CryptoError> {
+    let rest = data.strip_prefix(MAGIC).ok_or(CryptoError::NotEncrypted)?;
+    if rest.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+        return Err(CryptoError::Truncated);
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::WrongPassword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: [u8; SALT_LEN] = [7; SALT_LEN];
+    const NONCE: [u8; NONCE_LEN] = [9; NONCE_LEN];
+
+    #[test]
+    fn test_round_trip() {
+        let data = encrypt(b"meet at dawn", "hunter2", SALT, NONCE).unwrap();
+        assert!(is_encrypted(&data));
+        assert_eq!(data.len(), OVERHEAD + b"meet at dawn".len());
+        assert!(!data.windows(4).any(|w| w == b"meet"));
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), b"meet at dawn");
+    }
+
+    #[test]
+    fn test_wrong_password_and_tampering() {
+        let mut data = encrypt(b"meet at dawn", "hunter2", SALT, NONCE).unwrap();
+        assert_eq!(decrypt(&data, "hunter3"), Err(CryptoError::WrongPassword));
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert_eq!(decrypt(&data, "hunter2"), Err(CryptoError::WrongPassword));
+    }
+
+    #[test]
+    fn test_not_encrypted() {
+        assert!(!is_encrypted(b"plain text"));
+        assert_eq!(decrypt(b"plain text", "x"), Err(CryptoError::NotEncrypted));
+        assert_eq!(decrypt(b"PME1short", "x"), Err(CryptoError::Truncated));
+    }
+}
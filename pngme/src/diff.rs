@@ -0,0 +1,135 @@
+use crate::chunk::Chunk;
+
+/// 两个 PNG 的 chunk 序列对齐后的一行
+#[derive(Debug, PartialEq)]
+pub enum ChunkDiff<'a> {
+    /// 类型、长度和 CRC 都相同
+    Same(&'a Chunk, &'a Chunk),
+    /// 只在第二个文件中出现
+    Added(&'a Chunk),
+    /// 只在第一个文件中出现
+    Removed(&'a Chunk),
+    /// 同一位置上类型相同但内容不同
+    Modified(&'a Chunk, &'a Chunk),
+}
+
+impl ChunkDiff<'_> {
+    pub fn is_same(&self) -> bool {
+        matches!(self, ChunkDiff::Same(..))
+    }
+}
+
+/// 按 (类型, 长度, CRC) 比较两个 chunk，不比较数据本身
+fn same_chunk(a: &Chunk, b: &Chunk) -> bool {
+    a.chunk_type() == b.chunk_type() && a.length() == b.length() && a.crc() == b.crc()
+}
+
+/// 用最长公共子序列对齐两个 chunk 序列。
+/// 两个相同 chunk 之间被删除和新增的部分里，类型相同的 chunk 按顺序配对成 Modified
+pub fn diff_chunks<'a>(old: &'a [Chunk], new: &'a [Chunk]) -> Vec<ChunkDiff<'a>> {
+    // lcs[i][j] 是 old[i..] 和 new[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if same_chunk(&old[i], &new[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut removed: Vec<&Chunk> = Vec::new();
+    let mut added: Vec<&Chunk> = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same_chunk(&old[i], &new[j]) {
+            flush_changes(&mut removed, &mut added, &mut result);
+            result.push(ChunkDiff::Same(&old[i], &new[j]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(&new[j]);
+            j += 1;
+        } else {
+            removed.push(&old[i]);
+            i += 1;
+        }
+    }
+    flush_changes(&mut removed, &mut added, &mut result);
+
+    result
+}
+
+/// 把一段连续的删除和新增写进结果，能配对的合并成 Modified
+fn flush_changes<'a>(removed: &mut Vec<&'a Chunk>, added: &mut Vec<&'a Chunk>, result: &mut Vec<ChunkDiff<'a>>) {
+    for old in removed.drain(..) {
+        match added.iter().position(|new| new.chunk_type() == old.chunk_type()) {
+            Some(index) => {
+                // 排在配对项前面的新增 chunk 先输出，保持第二个文件中的顺序
+                for new in added.drain(..index) {
+                    result.push(ChunkDiff::Added(new));
+                }
+                result.push(ChunkDiff::Modified(old, added.remove(0)));
+            }
+            None => result.push(ChunkDiff::Removed(old)),
+        }
+    }
+    result.extend(added.drain(..).map(ChunkDiff::Added));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn kinds(diff: &[ChunkDiff]) -> Vec<String> {
+        diff.iter()
+            .map(|d| match d {
+                ChunkDiff::Same(a, _) => format!("={}", a.chunk_type()),
+                ChunkDiff::Added(b) => format!("+{}", b.chunk_type()),
+                ChunkDiff::Removed(a) => format!("-{}", a.chunk_type()),
+                ChunkDiff::Modified(a, _) => format!("~{}", a.chunk_type()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let chunks = vec![chunk("IHDR", "header"), chunk("IDAT", "pixels"), chunk("IEND", "")];
+        let diff = diff_chunks(&chunks, &chunks);
+
+        assert!(diff.iter().all(ChunkDiff::is_same));
+        assert_eq!(diff.len(), 3);
+    }
+
+    #[test]
+    fn test_diff_added_removed_modified() {
+        let old = vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Comment\0old"),
+            chunk("ruSt", "secret"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ];
+        let new = vec![
+            chunk("IHDR", "header"),
+            chunk("pHYs", "dpi"),
+            chunk("tEXt", "Comment\0new"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ];
+
+        assert_eq!(
+            kinds(&diff_chunks(&old, &new)),
+            ["=IHDR", "+pHYs", "~tEXt", "-ruSt", "=IDAT", "=IEND"]
+        );
+        assert_eq!(kinds(&diff_chunks(&old, &old[..3])), ["=IHDR", "=tEXt", "=ruSt", "-IDAT", "-IEND"]);
+    }
+}
@@ -0,0 +1,58 @@
+/// 每行显示的字节数
+pub const BYTES_PER_LINE: usize = 16;
+
+/// 十六进制 + ASCII 的转储，类似 `xxd` / `hexdump -C`：
+/// 每行是偏移（从 `base` 开始计）、16 个字节的十六进制（8 个一组）和可打印字符，
+/// 不可打印的字节显示为 `.`
+pub fn hex_dump(data: &[u8], base: usize) -> Vec<String> {
+    data.chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(index, line)| {
+            let mut hex = String::new();
+            for i in 0..BYTES_PER_LINE {
+                if i == BYTES_PER_LINE / 2 {
+                    hex.push(' ');
+                }
+                match line.get(i) {
+                    Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                    None => hex.push_str("   "),
+                }
+            }
+            let ascii: String = line
+                .iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            format!("{:08x}  {} |{}|", base + index * BYTES_PER_LINE, hex, ascii)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump_lines() {
+        let data: Vec<u8> = b"Comment\0Hello, PNG!\n".to_vec();
+        let lines = hex_dump(&data, 0);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "00000000  43 6f 6d 6d 65 6e 74 00  48 65 6c 6c 6f 2c 20 50  |Comment.Hello, P|"
+        );
+        // 最后一行不足 16 个字节时用空格补齐，ASCII 列仍然对齐
+        assert_eq!(
+            lines[1],
+            "00000010  4e 47 21 0a                                       |NG!.|"
+        );
+    }
+
+    #[test]
+    fn test_hex_dump_base_and_empty() {
+        assert!(hex_dump(&[], 0).is_empty());
+        let lines = hex_dump(&[0xff; 17], 0x21);
+        assert!(lines[0].starts_with("00000021  ff ff"));
+        assert!(lines[1].starts_with("00000031  ff "));
+    }
+}
@@ -0,0 +1,51 @@
+use std::io;
+use thiserror::Error;
+
+use crate::capacity::CapacityError;
+use crate::chunk::ChunkError;
+use crate::chunk_type::{ChunkType, ChunkTypeError};
+use crate::crypto::CryptoError;
+use crate::metadata::MetadataError;
+use crate::png::{OrderError, PngError};
+use crate::signature::SignatureError;
+
+/// pngme 库的统一错误类型，各模块的错误都可以用 `?` 转换过来，
+/// 调用方既可以按变体匹配，也可以直接打印
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    ChunkType(#[from] ChunkTypeError),
+    #[error(transparent)]
+    Chunk(#[from] ChunkError),
+    #[error(transparent)]
+    Png(#[from] PngError),
+    #[error(transparent)]
+    Order(#[from] OrderError),
+    #[error(transparent)]
+    Capacity(#[from] CapacityError),
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    /// 要操作的 chunk 不存在
+    #[error("Chunk {0} not found")]
+    ChunkNotFound(ChunkType),
+    /// move-chunk 需要且只能指定 --before / --after 中的一个
+    #[error("exactly one of --before or --after is required")]
+    MissingMoveTarget,
+    /// check 发现 CRC 不对的 chunk 数量
+    #[error("{0} chunk(s) failed the CRC check")]
+    CorruptChunks(usize),
+    /// check 发现 IHDR / PLTE / tRNS 不一致的地方的数量
+    #[error("{0} structural issue(s) found")]
+    StructureIssues(usize),
+    /// verify 发现的问题数量
+    #[error("{0} problem(s) found")]
+    Violations(usize),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
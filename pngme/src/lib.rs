@@ -0,0 +1,21 @@
+#[cfg(feature = "cli")]
+pub mod args;
+pub mod capacity;
+pub mod check;
+pub mod chunk;
+pub mod chunk_type;
+#[cfg(feature = "cli")]
+pub mod commands;
+pub mod crypto;
+pub mod diff;
+pub mod dump;
+pub mod error;
+pub mod metadata;
+pub mod png;
+pub mod signature;
+pub mod structure;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::{Error, Result};
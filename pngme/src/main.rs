@@ -1,12 +1,7 @@
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
-
 use anyhow::Result;
 use clap::Parser;
-use crate::args::Args;
+use pngme::args::{self, Args};
+use pngme::commands;
 
 
 fn main() -> Result<()> {
@@ -15,18 +10,53 @@ fn main() -> Result<()> {
     
     // 执行相应的命令
     match args.command {
-        args::Command::Encode { file_path, chunk_type, message, output } => {
-            commands::encode::encode(file_path, chunk_type, message, output)?;
+        args::Command::Encode { file_path, chunk_type, message, output, limit, backup, password } => {
+            commands::encode::encode(file_path, chunk_type, message, output, limit, backup, password)?;
         }
-        args::Command::Decode { file_path, chunk_type } => {
-            commands::decode::decode(file_path, chunk_type)?;
+        args::Command::Decode { file_path, chunk_type, password, raw, output } => {
+            commands::decode::decode(file_path, chunk_type, password, raw, output)?;
         }
-        args::Command::Remove { file_path, chunk_type } => {
-            commands::remove::remove(file_path, chunk_type)?;
+        args::Command::Remove { file_path, chunk_type, backup } => {
+            commands::remove::remove(file_path, chunk_type, backup)?;
         }
         args::Command::Print { file_path } => {
             commands::print::print(file_path)?;
         }
+        args::Command::Insert { file_path, chunk_type, message, position, output, backup } => {
+            commands::insert::insert(file_path, chunk_type, message, position, output, backup)?;
+        }
+        args::Command::MoveChunk { file_path, chunk_type, before, after, output, backup } => {
+            commands::move_chunk::move_chunk(file_path, chunk_type, before, after, output, backup)?;
+        }
+        args::Command::Dump { file_path, chunk_type, raw } => {
+            commands::dump::dump(file_path, chunk_type, raw)?;
+        }
+        args::Command::Diff { first, second, text } => {
+            commands::diff::diff(first, second, text)?;
+        }
+        args::Command::Capacity { file_path, limit } => {
+            commands::capacity::capacity(file_path, limit)?;
+        }
+        args::Command::Check { file_path, threads } => {
+            commands::check::check(file_path, threads)?;
+        }
+        args::Command::Metadata { file_path } => {
+            commands::metadata::metadata(file_path)?;
+        }
+        args::Command::SetMetadata { file_path, orientation, description, copyright, output, backup } => {
+            commands::metadata::set_metadata(file_path, orientation, description, copyright, output, backup)?;
+        }
+        args::Command::Keygen { secret_key, public_key } => {
+            commands::sign::keygen(secret_key, public_key)?;
+        }
+        args::Command::Sign { file_path, key, output, backup } => {
+            commands::sign::sign(file_path, key, output, backup)?;
+        }
+        args::Command::Verify { file, file_path, key } => {
+            // clap 保证两者恰好给出一个
+            let file_path = file.or(file_path).expect("a file to verify");
+            commands::verify::verify(file_path, key)?;
+        }
     }
 
     // 返回成功
@@ -0,0 +1,398 @@
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{OrderError, Png};
+
+/// 常用的图片元数据：方向、描述和版权。
+///
+/// 写入时同时生成 eXIf chunk（TIFF 格式的 EXIF）和 iTXt chunk 里的 XMP 包，
+/// 读取时每个字段先看 eXIf，没有再看 XMP
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub orientation: Option<Orientation>,
+    pub description: Option<String>,
+    pub copyright: Option<String>,
+}
+
+/// 解析或生成元数据时的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MetadataError {
+    /// EXIF 方向只有 1 到 8
+    #[error("invalid orientation {0} (expected 1-8)")]
+    InvalidOrientation(u16),
+    /// eXIf chunk 不是合法的 TIFF 结构
+    #[error("malformed eXIf chunk: {0}")]
+    MalformedExif(&'static str),
+    #[error(transparent)]
+    Order(#[from] OrderError),
+}
+
+/// EXIF 的 Orientation 标签（0x0112），值为 1 到 8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Orientation(u16);
+
+impl Orientation {
+    pub fn new(value: u16) -> Result<Orientation, MetadataError> {
+        match value {
+            1..=8 => Ok(Orientation(value)),
+            _ => Err(MetadataError::InvalidOrientation(value)),
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+
+    /// 显示图片时要做的变换
+    pub fn description(&self) -> &'static str {
+        match self.0 {
+            1 => "normal",
+            2 => "mirror horizontal",
+            3 => "rotate 180",
+            4 => "mirror vertical",
+            5 => "mirror horizontal and rotate 270 CW",
+            6 => "rotate 90 CW",
+            7 => "mirror horizontal and rotate 90 CW",
+            _ => "rotate 270 CW",
+        }
+    }
+}
+
+/// iTXt 里 XMP 包使用的关键字
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+const TAG_DESCRIPTION: u16 = 0x010e;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_COPYRIGHT: u16 = 0x8298;
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+
+impl Metadata {
+    /// 读取 PNG 中的元数据，没有 eXIf / XMP 时各字段为 None
+    pub fn from_png(png: &Png) -> Result<Metadata, MetadataError> {
+        let exif = match png.chunk_by_type("eXIf") {
+            Some(chunk) => parse_exif(chunk.data())?,
+            None => Metadata::default(),
+        };
+        let xmp = png
+            .chunks()
+            .iter()
+            .find_map(xmp_packet)
+            .map(|packet| parse_xmp(&packet))
+            .unwrap_or_default();
+        Ok(Metadata {
+            orientation: exif.orientation.or(xmp.orientation),
+            description: exif.description.or(xmp.description),
+            copyright: exif.copyright.or(xmp.copyright),
+        })
+    }
+
+    /// 用 `update` 中给出的字段覆盖已有的元数据（空字符串表示删除该字段），
+    /// 然后替换文件中的 eXIf 和 XMP chunk；所有字段都为空时两个 chunk 都会被删除
+    pub fn update_png(png: &mut Png, update: &Metadata) -> Result<Metadata, MetadataError> {
+        let current = Metadata::from_png(png)?;
+        let merge = |new: &Option<String>, old: Option<String>| match new {
+            Some(text) if text.is_empty() => None,
+            Some(text) => Some(text.clone()),
+            None => old,
+        };
+        let metadata = Metadata {
+            orientation: update.orientation.or(current.orientation),
+            description: merge(&update.description, current.description),
+            copyright: merge(&update.copyright, current.copyright),
+        };
+
+        png.retain_chunks(|chunk| chunk.chunk_type().to_string() != "eXIf" && xmp_packet(chunk).is_none());
+        if metadata == Metadata::default() {
+            return Ok(metadata);
+        }
+
+        // eXIf 必须在 IDAT 之前，XMP 也放在一起，方便只读文件头的工具找到
+        let position = png
+            .chunks()
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .unwrap_or(png.chunks().len());
+        png.insert_chunk(position, Chunk::new(chunk_type("iTXt"), metadata.xmp_chunk_data()))?;
+        png.insert_chunk(position, Chunk::new(chunk_type("eXIf"), metadata.exif()))?;
+        Ok(metadata)
+    }
+
+    /// 生成 eXIf chunk 的内容：大端的 TIFF 头加一个 IFD。
+    /// EXIF 的文本只能是 ASCII，含有其它字符的文本只写进 XMP
+    pub fn exif(&self) -> Vec<u8> {
+        let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = Vec::new();
+        let ascii = |text: &str| {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            bytes
+        };
+        if let Some(text) = self.description.as_deref().filter(|text| text.is_ascii()) {
+            let value = ascii(text);
+            entries.push((TAG_DESCRIPTION, TYPE_ASCII, value.len() as u32, value));
+        }
+        if let Some(orientation) = self.orientation {
+            entries.push((TAG_ORIENTATION, TYPE_SHORT, 1, orientation.value().to_be_bytes().to_vec()));
+        }
+        if let Some(text) = self.copyright.as_deref().filter(|text| text.is_ascii()) {
+            let value = ascii(text);
+            entries.push((TAG_COPYRIGHT, TYPE_ASCII, value.len() as u32, value));
+        }
+
+        // TIFF 头 8 字节，IFD 为 2 字节数量 + 每项 12 字节 + 4 字节下一个 IFD 的偏移，
+        // 放不进 4 字节的值跟在 IFD 后面
+        let mut data = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        let mut extra = Vec::new();
+        let extra_start = 8 + 2 + entries.len() * 12 + 4;
+        data.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for (tag, value_type, count, value) in &entries {
+            data.extend_from_slice(&tag.to_be_bytes());
+            data.extend_from_slice(&value_type.to_be_bytes());
+            data.extend_from_slice(&count.to_be_bytes());
+            if value.len() <= 4 {
+                let mut inline = [0u8; 4];
+                inline[..value.len()].copy_from_slice(value);
+                data.extend_from_slice(&inline);
+            } else {
+                data.extend_from_slice(&((extra_start + extra.len()) as u32).to_be_bytes());
+                extra.extend_from_slice(value);
+                // 值的偏移要求是偶数
+                if extra.len() % 2 == 1 {
+                    extra.push(0);
+                }
+            }
+        }
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&extra);
+        data
+    }
+
+    /// 生成 XMP 包（UTF-8 的 XML）
+    pub fn xmp(&self) -> String {
+        let alt = |name: &str, text: &Option<String>| match text {
+            Some(text) => format!(
+                "   <{name}><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></{name}>\n",
+                escape_xml(text)
+            ),
+            None => String::new(),
+        };
+        let orientation = match self.orientation {
+            Some(orientation) => format!("   <tiff:Orientation>{}</tiff:Orientation>\n", orientation.value()),
+            None => String::new(),
+        };
+        format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+             <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:tiff=\"http://ns.adobe.com/tiff/1.0/\">\n\
+             {orientation}{}{}  \
+             </rdf:Description>\n \
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>",
+            alt("dc:description", &self.description),
+            alt("dc:rights", &self.copyright),
+        )
+    }
+
+    /// iTXt chunk 的内容：关键字、不压缩、空的语言标签和翻译关键字，然后是 XMP 包
+    fn xmp_chunk_data(&self) -> Vec<u8> {
+        let mut data = XMP_KEYWORD.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(self.xmp().as_bytes());
+        data
+    }
+}
+
+fn chunk_type(name: &str) -> ChunkType {
+    ChunkType::from_str(name).expect("standard chunk type")
+}
+
+/// chunk 是不压缩的 XMP iTXt 时返回其中的 XMP 包
+fn xmp_packet(chunk: &Chunk) -> Option<String> {
+    if chunk.chunk_type().to_string() != "iTXt" {
+        return None;
+    }
+    let data = chunk.data().strip_prefix(XMP_KEYWORD.as_bytes())?.strip_prefix(&[0])?;
+    // 压缩标志和压缩方法
+    let (&[0, _], rest) = data.split_at_checked(2)? else {
+        return None;
+    };
+    // 跳过语言标签和翻译后的关键字
+    let mut parts = rest.splitn(3, |&byte| byte == 0);
+    let (_, _, text) = (parts.next()?, parts.next()?, parts.next()?);
+    String::from_utf8(text.to_vec()).ok()
+}
+
+/// 从 eXIf chunk（TIFF 结构，"II" 小端或 "MM" 大端）的第一个 IFD 里读出关心的标签
+pub fn parse_exif(data: &[u8]) -> Result<Metadata, MetadataError> {
+    let big_endian = match data.get(..4) {
+        Some(b"MM\x00\x2a") => true,
+        Some(b"II\x2a\x00") => false,
+        _ => return Err(MetadataError::MalformedExif("missing TIFF header")),
+    };
+    const TRUNCATED: MetadataError = MetadataError::MalformedExif("truncated IFD");
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = [*data.get(offset)?, *data.get(offset + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4).ok_or(TRUNCATED)? as usize;
+    let count = u16_at(ifd).ok_or(TRUNCATED)? as usize;
+    let mut metadata = Metadata::default();
+    for index in 0..count {
+        let entry = ifd + 2 + index * 12;
+        let (Some(tag), Some(value_type), Some(value_count)) = (u16_at(entry), u16_at(entry + 2), u32_at(entry + 4))
+        else {
+            return Err(TRUNCATED);
+        };
+        let value_offset = entry + 8;
+        match (tag, value_type) {
+            (TAG_ORIENTATION, TYPE_SHORT) => {
+                let value = u16_at(value_offset).ok_or(TRUNCATED)?;
+                metadata.orientation = Some(Orientation::new(value)?);
+            }
+            (TAG_DESCRIPTION | TAG_COPYRIGHT, TYPE_ASCII) => {
+                let len = value_count as usize;
+                let start = if len <= 4 {
+                    value_offset
+                } else {
+                    u32_at(value_offset).ok_or(TRUNCATED)? as usize
+                };
+                let bytes = data
+                    .get(start..start.saturating_add(len))
+                    .ok_or(MetadataError::MalformedExif("value out of bounds"))?;
+                let text = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
+                if tag == TAG_DESCRIPTION {
+                    metadata.description = Some(text);
+                } else {
+                    metadata.copyright = Some(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+/// 从 XMP 包里找出关心的字段。只做简单的文本查找，不是完整的 XML 解析
+pub fn parse_xmp(packet: &str) -> Metadata {
+    let element = |name: &str| -> Option<&str> {
+        let open = format!("<{name}>");
+        let start = packet.find(&open)? + open.len();
+        let end = start + packet[start..].find(&format!("</{name}>"))?;
+        Some(&packet[start..end])
+    };
+    // dc:description / dc:rights 的值在 rdf:Alt 的第一个 rdf:li 里
+    let alt = |name: &str| -> Option<String> {
+        let body = element(name)?;
+        let li = body.find("<rdf:li")?;
+        let start = li + body[li..].find('>')? + 1;
+        let end = start + body[start..].find("</rdf:li>")?;
+        Some(unescape_xml(&body[start..end]))
+    };
+    Metadata {
+        orientation: element("tiff:Orientation")
+            .and_then(|text| text.trim().parse().ok())
+            .and_then(|value| Orientation::new(value).ok()),
+        description: alt("dc:description"),
+        copyright: alt("dc:rights"),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Png {
+        let chunk = |ty: &str, data: Vec<u8>| Chunk::new(chunk_type(ty), data);
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IDAT", vec![1, 2, 3]),
+            chunk("IEND", Vec::new()),
+        ])
+    }
+
+    fn metadata(orientation: u16, description: &str, copyright: &str) -> Metadata {
+        Metadata {
+            orientation: Some(Orientation::new(orientation).unwrap()),
+            description: Some(description.to_string()),
+            copyright: Some(copyright.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_exif_round_trip() {
+        let written = metadata(6, "A photo of a cat", "(c) 2024 LJB");
+        assert_eq!(parse_exif(&written.exif()).unwrap(), written);
+        // 短文本直接放在 IFD 项里
+        let short = metadata(1, "abc", "x");
+        assert_eq!(parse_exif(&short.exif()).unwrap(), short);
+    }
+
+    #[test]
+    fn test_little_endian_exif() {
+        // 手写的小端 TIFF：一个 Orientation = 3 的 IFD 项
+        let data = [
+            b'I', b'I', 0x2a, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(parse_exif(&data).unwrap().orientation, Some(Orientation(3)));
+        assert!(parse_exif(b"not exif").is_err());
+        assert_eq!(parse_exif(&data[..12]), Err(MetadataError::MalformedExif("truncated IFD")));
+    }
+
+    #[test]
+    fn test_xmp_round_trip() {
+        let written = metadata(8, "Fish & <chips> \"today\"", "版权所有");
+        assert_eq!(parse_xmp(&written.xmp()), written);
+    }
+
+    #[test]
+    fn test_update_png() {
+        let mut png = png();
+        let written = Metadata::update_png(&mut png, &metadata(6, "café", "LJB")).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "eXIf", "iTXt", "IDAT", "IEND"]);
+        // 非 ASCII 的描述只写进了 XMP，读取时从 XMP 补上
+        assert_eq!(parse_exif(png.chunk_by_type("eXIf").unwrap().data()).unwrap().description, None);
+        assert_eq!(Metadata::from_png(&png).unwrap(), written);
+
+        // 只改一个字段，其它字段保留；空字符串删除字段
+        let update = Metadata {
+            copyright: Some(String::new()),
+            orientation: Some(Orientation::new(1).unwrap()),
+            ..Metadata::default()
+        };
+        let updated = Metadata::update_png(&mut png, &update).unwrap();
+        assert_eq!(updated, Metadata { copyright: None, ..metadata(1, "café", "") });
+        assert_eq!(Metadata::from_png(&png).unwrap(), updated);
+        assert_eq!(png.chunks().len(), 5);
+    }
+
+    #[test]
+    fn test_invalid_orientation() {
+        assert_eq!(Orientation::new(9), Err(MetadataError::InvalidOrientation(9)));
+        assert_eq!(Orientation::new(6).unwrap().description(), "rotate 90 CW");
+    }
+}
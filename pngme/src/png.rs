@@ -1,12 +1,65 @@
 use std::fmt::Display;
 use std::convert::TryFrom;
+use std::io::{self, Read, Write};
 use std::str::FromStr;
+use thiserror::Error;
 
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkError, MAX_CHUNK_LENGTH};
 use crate::chunk_type::ChunkType;
+use crate::error::Error;
+
+/// 解析 PNG 文件时可能出现的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PngError {
+    /// 文件不足 8 字节，连签名都放不下
+    #[error("PNG file is too short ({0} bytes)")]
+    TooShort(usize),
+    /// 签名不是标准 PNG 签名
+    #[error("Invalid PNG signature")]
+    InvalidSignature([u8; 8]),
+    /// 某个 chunk 损坏，offset 为该 chunk 在文件中的起始位置
+    #[error("invalid chunk at offset {offset}: {source}")]
+    Chunk { offset: usize, source: ChunkError },
+}
+
+/// 调整 chunk 顺序时违反 PNG 规范的情况
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OrderError {
+    /// 找不到要移动的 chunk 或作为参照的 chunk
+    #[error("no {0} chunk found")]
+    ChunkNotFound(ChunkType),
+    /// 插入位置超出了 chunk 数量
+    #[error("position {position} is out of range (the file has {len} chunks)")]
+    PositionOutOfRange { position: usize, len: usize },
+    /// 关键块（IHDR/PLTE/IDAT/IEND）不允许移动
+    #[error("{0} is a critical chunk and cannot be moved")]
+    CriticalChunk(ChunkType),
+    /// IHDR 必须是第一个 chunk
+    #[error("IHDR must be the first chunk")]
+    HeaderNotFirst,
+    /// IEND 必须是最后一个 chunk
+    #[error("IEND must be the last chunk")]
+    EndNotLast,
+    /// 多个 IDAT 必须连续
+    #[error("IDAT chunks must be consecutive")]
+    SplitImageData,
+    /// `chunk` 必须出现在 `target` 之前
+    #[error("{chunk} must appear before {target}")]
+    MustPrecede { chunk: ChunkType, target: ChunkType },
+    /// `chunk` 必须出现在 `target` 之后
+    #[error("{chunk} must appear after {target}")]
+    MustFollow { chunk: ChunkType, target: ChunkType },
+}
+
+/// 必须出现在 PLTE 和 IDAT 之前的辅助块
+const BEFORE_PLTE: [&str; 5] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB"];
+/// 必须出现在 PLTE 之后、IDAT 之前的辅助块
+const AFTER_PLTE: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+/// 必须出现在 IDAT 之前的辅助块
+const BEFORE_IDAT: [&str; 3] = ["pHYs", "sPLT", "eXIf"];
 
 /** # 结构
-```
+```text
 [PNG签名头] 8字节
 ↓
 [IHDR] 图像头，必须，13字节内容
@@ -31,8 +84,6 @@ Type  : 49 45 4E 44 ("IEND")
 CRC   : AE 42 60 82
 ```
  */
-
-
 #[derive(Debug)]
 pub struct Png {
     signature: [u8; 8],
@@ -40,29 +91,28 @@ pub struct Png {
 }
 
 impl TryFrom<&[u8]> for Png {
-    type Error = Box<dyn std::error::Error>;
+    type Error = PngError;
 
     fn try_from(png_bytes: &[u8]) -> Result<Self, Self::Error> {
         if png_bytes.len() < 8 {
-            return Err("PNG file is too short".into());
+            return Err(PngError::TooShort(png_bytes.len()));
         }
 
-        let signature = png_bytes[0..8].try_into().unwrap();
+        let mut signature = [0u8; 8];
+        signature.copy_from_slice(&png_bytes[0..8]);
         if signature != Self::STANDARD_HEADER {
-            return Err("Invalid PNG signature".into());
+            return Err(PngError::InvalidSignature(signature));
         }
 
         let mut chunks = Vec::new();
         let mut offset = 8;
 
+        // Chunk::parse 会在分配前检查长度和 CRC，截断或损坏的文件只会得到错误
         while offset < png_bytes.len() {
-            let length = u32::from_be_bytes(png_bytes[offset..offset + 4].try_into().unwrap()) as usize;
-            let chunk_type = ChunkType::from_str(std::str::from_utf8(&png_bytes[offset + 4..offset + 8])?)?;
-            let data = png_bytes[offset + 8..offset + 8 + length].to_vec();
-            let _crc = u32::from_be_bytes(png_bytes[offset + 8 + length..offset + 12 + length].try_into().unwrap());
-
-            chunks.push(Chunk::new(chunk_type, data));
-            offset += 12 + length;
+            let (chunk, consumed) = Chunk::parse(&png_bytes[offset..])
+                .map_err(|source| PngError::Chunk { offset, source })?;
+            chunks.push(chunk);
+            offset += consumed;
         }
 
         Ok(Png { signature, chunks })
@@ -91,13 +141,119 @@ impl Png {
         self.chunks.push(chunk);
     }
 
-    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Box<dyn std::error::Error>> {
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> crate::Result<Chunk> {
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let index = self.chunks.iter().position(|x| *x.chunk_type() == chunk_type);
         match index {
             Some(i) => Ok(self.chunks.remove(i)),
-            None => Err("Chunk not found".into()),
+            None => Err(Error::ChunkNotFound(chunk_type)),
+        }
+    }
+
+    /// 只保留 `keep` 返回 true 的 chunk
+    pub fn retain_chunks<F: FnMut(&Chunk) -> bool>(&mut self, keep: F) {
+        self.chunks.retain(keep);
+    }
+
+    /// 把 chunk 插入到第 `position` 个位置（0 表示紧跟在签名之后），
+    /// 插入后的顺序不符合规范时撤销插入并返回错误
+    pub fn insert_chunk(&mut self, position: usize, chunk: Chunk) -> Result<(), OrderError> {
+        if position > self.chunks.len() {
+            return Err(OrderError::PositionOutOfRange { position, len: self.chunks.len() });
+        }
+
+        self.chunks.insert(position, chunk);
+        if let Err(e) = Self::validate_order(&self.chunks) {
+            self.chunks.remove(position);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 把第一个 `chunk_type` 类型的 chunk 移到第一个 `target` 之前（`before` 为 false 时移到最后一个 `target` 之后）
+    pub fn move_chunk(&mut self, chunk_type: ChunkType, target: ChunkType, before: bool) -> Result<(), OrderError> {
+        // 只限制标准关键块, 用户自定义的块即使首字母大写也可以移动
+        if ["IHDR", "PLTE", "IDAT", "IEND"].contains(&chunk_type.to_string().as_str()) {
+            return Err(OrderError::CriticalChunk(chunk_type));
+        }
+
+        let from = self.chunks.iter()
+            .position(|x| *x.chunk_type() == chunk_type)
+            .ok_or(OrderError::ChunkNotFound(chunk_type))?;
+        let chunk = self.chunks.remove(from);
+
+        let target_index = if before {
+            self.chunks.iter().position(|x| *x.chunk_type() == target)
+        } else {
+            self.chunks.iter().rposition(|x| *x.chunk_type() == target).map(|i| i + 1)
+        };
+        let to = match target_index {
+            Some(i) => i,
+            None => {
+                self.chunks.insert(from, chunk);
+                return Err(OrderError::ChunkNotFound(target));
+            }
+        };
+
+        self.chunks.insert(to, chunk);
+        if let Err(e) = Self::validate_order(&self.chunks) {
+            let chunk = self.chunks.remove(to);
+            self.chunks.insert(from, chunk);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 检查 chunk 顺序是否符合 PNG 规范中的排列要求，只检查文件中实际存在的 chunk
+    pub fn validate_order(chunks: &[Chunk]) -> Result<(), OrderError> {
+        let types: Vec<String> = chunks.iter().map(|x| x.chunk_type().to_string()).collect();
+        let first = |name: &str| types.iter().position(|t| t == name);
+        let last = |name: &str| types.iter().rposition(|t| t == name);
+        let chunk_type = |name: &str| ChunkType::from_str(name).unwrap();
+
+        if first("IHDR").is_some_and(|i| i != 0) {
+            return Err(OrderError::HeaderNotFirst);
+        }
+        if first("IEND").is_some_and(|i| i != types.len() - 1) {
+            return Err(OrderError::EndNotLast);
+        }
+
+        let first_idat = first("IDAT");
+        if let (Some(start), Some(end)) = (first_idat, last("IDAT"))
+            && types[start..=end].iter().any(|t| t != "IDAT")
+        {
+            return Err(OrderError::SplitImageData);
+        }
+
+        let plte = first("PLTE");
+        let precedes = |name: &str, target: Option<usize>, target_name: &str| -> Result<(), OrderError> {
+            match (last(name), target) {
+                (Some(i), Some(t)) if i > t => Err(OrderError::MustPrecede {
+                    chunk: chunk_type(name),
+                    target: chunk_type(target_name),
+                }),
+                _ => Ok(()),
+            }
+        };
+
+        precedes("PLTE", first_idat, "IDAT")?;
+        for name in BEFORE_PLTE {
+            precedes(name, plte, "PLTE")?;
+            precedes(name, first_idat, "IDAT")?;
+        }
+        for name in AFTER_PLTE {
+            if let (Some(i), Some(p)) = (first(name), plte)
+                && i < p
+            {
+                return Err(OrderError::MustFollow { chunk: chunk_type(name), target: chunk_type("PLTE") });
+            }
+            precedes(name, first_idat, "IDAT")?;
         }
+        for name in BEFORE_IDAT {
+            precedes(name, first_idat, "IDAT")?;
+        }
+
+        Ok(())
     }
 
     pub fn header(&self) -> &[u8; 8] {
@@ -109,12 +265,8 @@ impl Png {
     }
 
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        let chunks = self.chunks();
-        let chunk = chunks.iter().find(|&x| {
-            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
-            *x.chunk_type() == chunk_type
-        });
-        chunk
+        let chunk_type = ChunkType::from_str(chunk_type).ok()?;
+        self.chunks().iter().find(|&x| *x.chunk_type() == chunk_type)
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -133,13 +285,99 @@ impl Png {
     }
 }
 
+/// 流式读取 PNG：先检查签名，然后按顺序逐个读出 chunk，内存中同时只有一个 chunk，
+/// 几百 MB 的文件也不用整个读进来。每个 chunk 和 `Png::try_from` 一样检查长度、类型和 CRC，
+/// 出错时报告同样的 `PngError::Chunk`，之后迭代结束
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    offset: usize,
+    failed: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// 读取并检查文件签名。`reader` 最好是 `BufReader`
+    pub fn new(mut reader: R) -> crate::Result<Self> {
+        let mut signature = Vec::with_capacity(8);
+        (&mut reader).take(8).read_to_end(&mut signature)?;
+        let signature: [u8; 8] = signature
+            .try_into()
+            .map_err(|short: Vec<u8>| PngError::TooShort(short.len()))?;
+        if signature != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature(signature).into());
+        }
+        Ok(Self { reader, offset: 8, failed: false })
+    }
+
+    /// 下一个 chunk 在文件中的起始位置
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn read_chunk(&mut self) -> crate::Result<Option<Chunk>> {
+        // 长度和类型
+        let mut bytes = Vec::with_capacity(12);
+        (&mut self.reader).take(8).read_to_end(&mut bytes)?;
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        // 数据和 CRC。长度超过上限时只多读 CRC 的 4 个字节，让 Chunk::parse 报告 LengthTooLarge；
+        // take 按实际读到的字节扩大缓冲区，长度字段再大也不会预先分配
+        if bytes.len() == 8 {
+            let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let rest = if length <= MAX_CHUNK_LENGTH { length as u64 + 4 } else { 4 };
+            (&mut self.reader).take(rest).read_to_end(&mut bytes)?;
+        }
+
+        let (chunk, consumed) = Chunk::parse(&bytes)
+            .map_err(|source| PngError::Chunk { offset: self.offset, source })?;
+        self.offset += consumed;
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = crate::Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.read_chunk();
+        self.failed = result.is_err();
+        result.transpose()
+    }
+}
+
+/// 流式写出 PNG：创建时写签名，然后逐个写 chunk。`writer` 最好是 `BufWriter`
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> io::Result<()> {
+        chunk.write_to(&mut self.writer)
+    }
+
+    /// 刷新缓冲区，返回里面的 writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_type::ChunkType;
+    use crate::chunk_type::{ChunkType, ChunkTypeError};
     use crate::chunk::Chunk;
-    // use std::str::FromStr;
+    use std::str::FromStr;
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
@@ -265,6 +503,19 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_first_chunk_errors() {
+        let mut png = testing_png();
+        assert!(matches!(
+            png.remove_first_chunk("TeSt"),
+            Err(Error::ChunkNotFound(chunk_type)) if chunk_type.to_string() == "TeSt"
+        ));
+        assert!(matches!(
+            png.remove_first_chunk("Te5t"),
+            Err(Error::ChunkType(ChunkTypeError::InvalidString(_)))
+        ));
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -297,6 +548,153 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_truncated_file_never_panics() {
+        // 每一个前缀都只能得到 Ok 或 Err，不能 panic
+        for len in 0..PNG_FILE.len() {
+            let _ = Png::try_from(&PNG_FILE[..len]);
+        }
+
+        let png = Png::try_from(&PNG_FILE[..100]);
+        assert!(matches!(
+            png,
+            Err(PngError::Chunk { source: ChunkError::Truncated { .. }, .. })
+        ));
+    }
+
+    #[test]
+    fn test_impossible_length_rejected_before_allocation() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        bytes.extend_from_slice(b"IHDR");
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+
+        let png = Png::try_from(bytes.as_ref());
+        assert_eq!(
+            png.unwrap_err(),
+            PngError::Chunk { offset: 8, source: ChunkError::LengthTooLarge(0xFFFF_FFFF) }
+        );
+    }
+
+    #[test]
+    fn test_corrupt_crc_is_reported() {
+        let mut bytes = PNG_FILE.to_vec();
+        // 修改 IHDR 的数据, CRC 随之失效
+        bytes[20] ^= 0xFF;
+
+        let png = Png::try_from(bytes.as_ref());
+        assert!(matches!(
+            png,
+            Err(PngError::Chunk { offset: 8, source: ChunkError::CrcMismatch { .. } })
+        ));
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|x| x.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_move_chunk_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let rust = ChunkType::from_str("RuSt").unwrap();
+        let idat = ChunkType::from_str("IDAT").unwrap();
+
+        png.move_chunk(rust, idat, true).unwrap();
+        assert_eq!(types(&png), ["IHDR", "sRGB", "gAMA", "pHYs", "RuSt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_move_chunk_rejects_invalid_order() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let before = types(&png);
+        let idat = ChunkType::from_str("IDAT").unwrap();
+        let srgb = ChunkType::from_str("sRGB").unwrap();
+
+        assert_eq!(
+            png.move_chunk(srgb, idat, false),
+            Err(OrderError::MustPrecede { chunk: srgb, target: idat })
+        );
+        assert_eq!(png.move_chunk(idat, srgb, true), Err(OrderError::CriticalChunk(idat)));
+        // 失败时不改变原来的顺序
+        assert_eq!(types(&png), before);
+    }
+
+    #[test]
+    fn test_insert_chunk_at_position() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        png.insert_chunk(1, chunk_from_strings("teXt", "hi").unwrap()).unwrap();
+        assert_eq!(types(&png)[..3], ["IHDR", "teXt", "sRGB"]);
+
+        assert_eq!(
+            png.insert_chunk(0, chunk_from_strings("teXt", "hi").unwrap()),
+            Err(OrderError::HeaderNotFirst)
+        );
+        assert_eq!(
+            png.insert_chunk(100, chunk_from_strings("teXt", "hi").unwrap()),
+            Err(OrderError::PositionOutOfRange { position: 100, len: 8 })
+        );
+        assert_eq!(
+            png.insert_chunk(8, chunk_from_strings("teXt", "hi").unwrap()),
+            Err(OrderError::EndNotLast)
+        );
+    }
+
+    #[test]
+    fn test_chunk_reader_matches_try_from() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let reader = ChunkReader::new(&PNG_FILE[..]).unwrap();
+        let chunks: Vec<Chunk> = reader.map(Result::unwrap).collect();
+        assert_eq!(chunks, png.chunks());
+
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+        for chunk in &chunks {
+            writer.write_chunk(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_chunk_reader_reports_same_errors() {
+        assert!(matches!(
+            ChunkReader::new(&PNG_FILE[..5]),
+            Err(Error::Png(PngError::TooShort(5)))
+        ));
+        assert!(matches!(
+            ChunkReader::new(&b"GIF89a\0\0rest"[..]),
+            Err(Error::Png(PngError::InvalidSignature(_)))
+        ));
+
+        // 在第二个 chunk 的数据中间截断：第一个照常读出，然后报告截断，之后不再继续
+        let truncated = &PNG_FILE[..8 + 25 + 10];
+        let expected = Png::try_from(truncated).unwrap_err();
+        let mut reader = ChunkReader::new(truncated).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().chunk_type().to_string(), "IHDR");
+        match reader.next() {
+            Some(Err(Error::Png(err))) => assert_eq!(err, expected),
+            other => panic!("expected a truncation error, got {:?}", other),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_fuzz_corpus_never_panics() {
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/parse_png");
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            let parsed = Png::try_from(bytes.as_ref());
+            // 流式读取和一次读入的结果一致
+            let streamed = ChunkReader::new(bytes.as_slice())
+                .and_then(|reader| reader.collect::<crate::Result<Vec<Chunk>>>());
+            assert_eq!(parsed.is_ok(), streamed.is_ok());
+            if let Ok(png) = parsed {
+                // 能解析的输入重新序列化后必须得到相同的结果
+                assert_eq!(png.as_bytes(), bytes);
+                assert_eq!(streamed.unwrap(), png.chunks());
+            }
+        }
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
@@ -0,0 +1,195 @@
+use std::str::FromStr;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use thiserror::Error;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{OrderError, Png};
+
+/// 存放签名的私有 chunk。第四个字母大写表示不能安全复制：
+/// 编辑器改了图像数据之后应该丢掉它，而不是带着一个失效的签名
+pub const SIGNATURE_CHUNK: &str = "sgNT";
+
+/// 签名 chunk 的内容：签名者的公钥（32 字节）加上 Ed25519 签名（64 字节）
+const SIGNATURE_CHUNK_LEN: usize = 32 + 64;
+
+/// 签名和验证时的错误
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    /// 密钥文件不是 64 个十六进制字符
+    #[error("invalid key: expected 32 bytes as hex")]
+    InvalidKey,
+    /// 没有 IHDR 或 IDAT，没有可签名的图像数据
+    #[error("no IHDR/IDAT image data to sign")]
+    MissingImageData,
+    #[error("image is not signed")]
+    MissingSignature,
+    #[error("malformed {SIGNATURE_CHUNK} chunk")]
+    MalformedSignature,
+    /// 签名是另一把密钥签的
+    #[error("image was signed by a different key ({0})")]
+    KeyMismatch(String),
+    /// 签名之后 IHDR 或 IDAT 被改过
+    #[error("signature does not match the image data")]
+    InvalidSignature,
+    #[error(transparent)]
+    Order(#[from] OrderError),
+}
+
+/// 被签名的字节：IHDR 和所有 IDAT（按顺序），每个 chunk 是类型加数据。
+/// 其他 chunk（文本、元数据）可以随意增删，不影响签名
+pub fn signed_bytes(png: &Png) -> Result<Vec<u8>, SignatureError> {
+    let mut bytes = Vec::new();
+    let mut idat = false;
+    for chunk in png.chunks() {
+        match chunk.chunk_type().to_string().as_str() {
+            "IHDR" => {}
+            "IDAT" => idat = true,
+            _ => continue,
+        }
+        bytes.extend_from_slice(&chunk.chunk_type().bytes());
+        bytes.extend_from_slice(chunk.data());
+    }
+    if bytes.is_empty() || !idat {
+        return Err(SignatureError::MissingImageData);
+    }
+    Ok(bytes)
+}
+
+/// 签名图像数据，替换已有的签名 chunk；新的签名放在 IEND 之前
+pub fn sign_png(png: &mut Png, key: &SigningKey) -> Result<(), SignatureError> {
+    let signature = key.sign(&signed_bytes(png)?);
+    let mut data = key.verifying_key().to_bytes().to_vec();
+    data.extend_from_slice(&signature.to_bytes());
+
+    png.retain_chunks(|chunk| chunk.chunk_type().to_string() != SIGNATURE_CHUNK);
+    let chunk = Chunk::new(ChunkType::from_str(SIGNATURE_CHUNK).expect("valid chunk type"), data);
+    let position = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == "IEND")
+        .unwrap_or(png.chunks().len());
+    png.insert_chunk(position, chunk)?;
+    Ok(())
+}
+
+/// 用 `key` 验证签名：签名者不是 `key`，或者签名之后图像数据被改过时返回错误
+pub fn verify_png(png: &Png, key: &VerifyingKey) -> Result<(), SignatureError> {
+    let (signer, signature) = read_signature(png)?;
+    if signer != *key {
+        return Err(SignatureError::KeyMismatch(to_hex(signer.as_bytes())));
+    }
+    key.verify(&signed_bytes(png)?, &signature)
+        .map_err(|_| SignatureError::InvalidSignature)
+}
+
+/// 签名 chunk 里的公钥和签名
+pub fn read_signature(png: &Png) -> Result<(VerifyingKey, Signature), SignatureError> {
+    let data = png
+        .chunk_by_type(SIGNATURE_CHUNK)
+        .ok_or(SignatureError::MissingSignature)?
+        .data();
+    if data.len() != SIGNATURE_CHUNK_LEN {
+        return Err(SignatureError::MalformedSignature);
+    }
+    let (key, signature) = data.split_at(32);
+    let key = VerifyingKey::from_bytes(key.try_into().expect("32 bytes"))
+        .map_err(|_| SignatureError::MalformedSignature)?;
+    let signature = Signature::from_bytes(signature.try_into().expect("64 bytes"));
+    Ok((key, signature))
+}
+
+/// 密钥文件的格式：32 字节的十六进制，前后的空白忽略
+pub fn parse_key(text: &str) -> Result<[u8; 32], SignatureError> {
+    let text = text.trim();
+    if text.len() != 64 || !text.is_ascii() {
+        return Err(SignatureError::InvalidKey);
+    }
+    let mut key = [0u8; 32];
+    for (byte, pair) in key.iter_mut().zip(text.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| SignatureError::InvalidKey)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| SignatureError::InvalidKey)?;
+    }
+    Ok(key)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Png {
+        let chunk = |ty: &str, data: Vec<u8>| Chunk::new(ChunkType::from_str(ty).unwrap(), data);
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("tEXt", b"Comment\0hello".to_vec()),
+            chunk("IDAT", vec![1, 2, 3]),
+            chunk("IDAT", vec![4, 5]),
+            chunk("IEND", Vec::new()),
+        ])
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut png = png();
+        sign_png(&mut png, &key(1)).unwrap();
+        assert_eq!(png.chunks()[5].chunk_type().to_string(), "IEND");
+        assert_eq!(png.chunks()[4].length() as usize, SIGNATURE_CHUNK_LEN);
+        verify_png(&png, &key(1).verifying_key()).unwrap();
+
+        // 重新签名替换旧的签名
+        sign_png(&mut png, &key(2)).unwrap();
+        assert_eq!(png.chunks().len(), 6);
+        assert_eq!(
+            verify_png(&png, &key(1).verifying_key()),
+            Err(SignatureError::KeyMismatch(to_hex(key(2).verifying_key().as_bytes())))
+        );
+    }
+
+    #[test]
+    fn test_only_image_data_is_signed() {
+        let mut png = png();
+        sign_png(&mut png, &key(1)).unwrap();
+        png.retain_chunks(|chunk| chunk.chunk_type().to_string() != "tEXt");
+        verify_png(&png, &key(1).verifying_key()).unwrap();
+
+        // 改动 IDAT 之后签名失效
+        let chunks: Vec<Chunk> = png
+            .chunks()
+            .iter()
+            .map(|chunk| match chunk.chunk_type().to_string().as_str() {
+                "IDAT" => Chunk::new(*chunk.chunk_type(), vec![9]),
+                _ => Chunk::new(*chunk.chunk_type(), chunk.data().to_vec()),
+            })
+            .collect();
+        assert_eq!(
+            verify_png(&Png::from_chunks(chunks), &key(1).verifying_key()),
+            Err(SignatureError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_unsigned_and_malformed() {
+        let mut png = png();
+        assert_eq!(verify_png(&png, &key(1).verifying_key()), Err(SignatureError::MissingSignature));
+
+        png.append_chunk(Chunk::new(ChunkType::from_str(SIGNATURE_CHUNK).unwrap(), vec![0; 10]));
+        assert_eq!(verify_png(&png, &key(1).verifying_key()), Err(SignatureError::MalformedSignature));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let bytes = key(7).verifying_key().to_bytes();
+        assert_eq!(parse_key(&format!("{}\n", to_hex(&bytes))).unwrap(), bytes);
+        assert_eq!(parse_key("abcd"), Err(SignatureError::InvalidKey));
+        assert_eq!(parse_key(&"zz".repeat(32)), Err(SignatureError::InvalidKey));
+    }
+}
@@ -0,0 +1,395 @@
+use std::fmt;
+
+use crate::capacity::ImageHeader;
+use crate::png::Png;
+
+/// tRNS 的内容，格式取决于颜色类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// 调色板图像: 前若干个调色板项的 alpha 值
+    Palette(Vec<u8>),
+    /// 灰度图像: 这个灰度值是透明的
+    Gray(u16),
+    /// RGB 图像: 这个颜色是透明的
+    Rgb(u16, u16, u16),
+}
+
+/// 图像头、调色板和透明度之间不一致的地方
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// 没有 IHDR chunk
+    MissingHeader,
+    /// IHDR 的长度不是 13 字节
+    BadHeaderLength(usize),
+    /// 宽或高为 0
+    ZeroDimension,
+    /// 颜色类型不是 0、2、3、4、6 之一
+    UnknownColorType(u8),
+    /// 这种颜色类型不允许的位深
+    InvalidBitDepth { bit_depth: u8, color_type: u8 },
+    /// 规范没有定义的压缩、过滤或隔行方法
+    UnknownMethod { field: &'static str, value: u8 },
+    /// 同一种只能出现一次的 chunk 出现了多次
+    Duplicate(&'static str),
+    /// PLTE 或 tRNS 出现在第一个 IDAT 之后
+    AfterImageData(&'static str),
+    /// 调色板图像没有 PLTE
+    MissingPalette,
+    /// 灰度图像不能有 PLTE
+    PaletteNotAllowed(u8),
+    /// PLTE 的长度不是 3 的倍数，或者不在 1 到 256 项之间
+    BadPaletteLength(usize),
+    /// 调色板的项数超过了位深能表示的索引
+    PaletteTooLarge { entries: usize, max: usize },
+    /// 带 alpha 通道的图像不能有 tRNS
+    TransparencyNotAllowed(u8),
+    /// 调色板图像的 tRNS 出现在 PLTE 之前
+    TransparencyBeforePalette,
+    /// 调色板图像的 tRNS 比调色板还长
+    TransparencyTooLong { entries: usize, palette: usize },
+    /// 灰度 / RGB 图像的 tRNS 长度不对
+    BadTransparencyLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingHeader => write!(f, "no IHDR chunk"),
+            Issue::BadHeaderLength(length) => write!(f, "IHDR is {} bytes, expected 13", length),
+            Issue::ZeroDimension => write!(f, "image width or height is zero"),
+            Issue::UnknownColorType(color_type) => write!(f, "unknown color type {}", color_type),
+            Issue::InvalidBitDepth { bit_depth, color_type } => write!(
+                f,
+                "bit depth {} is not allowed for color type {} ({})",
+                bit_depth,
+                color_type,
+                color_type_name(*color_type)
+            ),
+            Issue::UnknownMethod { field, value } => write!(f, "unknown {} method {}", field, value),
+            Issue::Duplicate(chunk_type) => write!(f, "more than one {} chunk", chunk_type),
+            Issue::AfterImageData(chunk_type) => write!(f, "{} appears after the first IDAT", chunk_type),
+            Issue::MissingPalette => write!(f, "palette image (color type 3) has no PLTE chunk"),
+            Issue::PaletteNotAllowed(color_type) => write!(
+                f,
+                "PLTE is not allowed for color type {} ({})",
+                color_type,
+                color_type_name(*color_type)
+            ),
+            Issue::BadPaletteLength(length) => {
+                write!(f, "PLTE is {} bytes, expected a multiple of 3 between 3 and 768", length)
+            }
+            Issue::PaletteTooLarge { entries, max } => {
+                write!(f, "palette has {} entries but the bit depth allows only {}", entries, max)
+            }
+            Issue::TransparencyNotAllowed(color_type) => write!(
+                f,
+                "tRNS is not allowed for color type {} ({}), which already has alpha",
+                color_type,
+                color_type_name(*color_type)
+            ),
+            Issue::TransparencyBeforePalette => write!(f, "tRNS appears before PLTE"),
+            Issue::TransparencyTooLong { entries, palette } => {
+                write!(f, "tRNS has {} alpha values but the palette has only {} entries", entries, palette)
+            }
+            Issue::BadTransparencyLength { expected, actual } => {
+                write!(f, "tRNS is {} bytes, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+/// `list` / `check` 的结构报告: 解码后的 IHDR、PLTE 和 tRNS，以及发现的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    pub header: Option<ImageHeader>,
+    /// 调色板的项数
+    pub palette_entries: Option<usize>,
+    pub transparency: Option<Transparency>,
+    pub issues: Vec<Issue>,
+}
+
+impl Structure {
+    pub fn from_png(png: &Png) -> Structure {
+        inspect(png.chunks().iter().map(|chunk| (chunk.chunk_type().bytes(), chunk.data())))
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 给人看的报告，每项一行，例如 `Image: 16x16, 4-bit palette, non-interlaced`
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(header) = &self.header {
+            lines.push(format!(
+                "Image: {}x{}, {}-bit {}, {}",
+                header.width,
+                header.height,
+                header.bit_depth,
+                color_type_name(header.color_type),
+                if header.interlace_method == 1 { "interlaced (Adam7)" } else { "non-interlaced" }
+            ));
+        }
+        if let Some(entries) = self.palette_entries {
+            lines.push(format!("Palette: {} entries", entries));
+        }
+        match &self.transparency {
+            Some(Transparency::Palette(alpha)) => {
+                lines.push(format!("Transparency: alpha for {} palette entries", alpha.len()))
+            }
+            Some(Transparency::Gray(gray)) => lines.push(format!("Transparency: gray {}", gray)),
+            Some(Transparency::Rgb(r, g, b)) => {
+                lines.push(format!("Transparency: rgb({}, {}, {})", r, g, b))
+            }
+            None => {}
+        }
+        if self.issues.is_empty() {
+            lines.push("Structure: ok".to_string());
+        } else {
+            lines.push(format!("Structure: {} issue(s)", self.issues.len()));
+            lines.extend(self.issues.iter().map(|issue| format!("  - {}", issue)));
+        }
+        lines
+    }
+}
+
+/// 颜色类型的名字，例如 2 是 `RGB`
+pub fn color_type_name(color_type: u8) -> &'static str {
+    match color_type {
+        0 => "grayscale",
+        2 => "RGB",
+        3 => "palette",
+        4 => "grayscale+alpha",
+        6 => "RGBA",
+        _ => "unknown",
+    }
+}
+
+// 每种颜色类型允许的位深
+fn allowed_bit_depths(color_type: u8) -> &'static [u8] {
+    match color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => &[],
+    }
+}
+
+/// 按文件中的顺序检查 chunk: 解码 IHDR、PLTE、tRNS，并检查它们和彼此之间是否符合规范。
+/// 只看 chunk 类型和内容，不管 CRC，所以 CRC 不对的文件也能检查
+pub fn inspect<'a>(chunks: impl IntoIterator<Item = ([u8; 4], &'a [u8])>) -> Structure {
+    let mut header_data = None;
+    let mut palette: Option<&[u8]> = None;
+    let mut transparency: Option<&[u8]> = None;
+    let mut transparency_before_palette = false;
+    let mut seen_image_data = false;
+    let mut issues = Vec::new();
+
+    for (chunk_type, data) in chunks {
+        match &chunk_type {
+            b"IHDR" if header_data.is_some() => issues.push(Issue::Duplicate("IHDR")),
+            b"IHDR" => header_data = Some(data),
+            b"PLTE" => {
+                if palette.is_some() {
+                    issues.push(Issue::Duplicate("PLTE"));
+                    continue;
+                }
+                if seen_image_data {
+                    issues.push(Issue::AfterImageData("PLTE"));
+                }
+                palette = Some(data);
+            }
+            b"tRNS" => {
+                if transparency.is_some() {
+                    issues.push(Issue::Duplicate("tRNS"));
+                    continue;
+                }
+                if seen_image_data {
+                    issues.push(Issue::AfterImageData("tRNS"));
+                }
+                transparency_before_palette = palette.is_none();
+                transparency = Some(data);
+            }
+            b"IDAT" => seen_image_data = true,
+            _ => {}
+        }
+    }
+
+    let palette_entries = palette.and_then(|data| {
+        if data.is_empty() || data.len() % 3 != 0 || data.len() > 256 * 3 {
+            issues.push(Issue::BadPaletteLength(data.len()));
+            None
+        } else {
+            Some(data.len() / 3)
+        }
+    });
+
+    let header = match header_data {
+        None => {
+            issues.push(Issue::MissingHeader);
+            None
+        }
+        Some(data) => {
+            let header = ImageHeader::parse(data);
+            if header.is_none() {
+                issues.push(Issue::BadHeaderLength(data.len()));
+            }
+            header
+        }
+    };
+    let Some(header) = header else {
+        return Structure { header, palette_entries, transparency: None, issues };
+    };
+
+    check_header(&header, &mut issues);
+    match (header.color_type, palette.is_some()) {
+        (3, false) => issues.push(Issue::MissingPalette),
+        (0 | 4, true) => issues.push(Issue::PaletteNotAllowed(header.color_type)),
+        _ => {}
+    }
+    if let (3, Some(entries)) = (header.color_type, palette_entries) {
+        let max = 1usize << header.bit_depth.min(8);
+        if entries > max {
+            issues.push(Issue::PaletteTooLarge { entries, max });
+        }
+    }
+
+    let transparency = transparency.and_then(|data| match header.color_type {
+        0 | 2 => {
+            let expected = if header.color_type == 0 { 2 } else { 6 };
+            if data.len() != expected {
+                issues.push(Issue::BadTransparencyLength { expected, actual: data.len() });
+                return None;
+            }
+            let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+            Some(if header.color_type == 0 {
+                Transparency::Gray(sample(0))
+            } else {
+                Transparency::Rgb(sample(0), sample(2), sample(4))
+            })
+        }
+        3 => {
+            if transparency_before_palette && palette.is_some() {
+                issues.push(Issue::TransparencyBeforePalette);
+            }
+            if let Some(palette) = palette_entries
+                && data.len() > palette
+            {
+                issues.push(Issue::TransparencyTooLong { entries: data.len(), palette });
+            }
+            Some(Transparency::Palette(data.to_vec()))
+        }
+        4 | 6 => {
+            issues.push(Issue::TransparencyNotAllowed(header.color_type));
+            None
+        }
+        // 颜色类型未知时已经报过了
+        _ => None,
+    });
+
+    Structure { header: Some(header), palette_entries, transparency, issues }
+}
+
+fn check_header(header: &ImageHeader, issues: &mut Vec<Issue>) {
+    if header.width == 0 || header.height == 0 {
+        issues.push(Issue::ZeroDimension);
+    }
+    let allowed = allowed_bit_depths(header.color_type);
+    if allowed.is_empty() {
+        issues.push(Issue::UnknownColorType(header.color_type));
+    } else if !allowed.contains(&header.bit_depth) {
+        issues.push(Issue::InvalidBitDepth { bit_depth: header.bit_depth, color_type: header.color_type });
+    }
+    for (field, value, max) in [
+        ("compression", header.compression_method, 0),
+        ("filter", header.filter_method, 0),
+        ("interlace", header.interlace_method, 1),
+    ] {
+        if value > max {
+            issues.push(Issue::UnknownMethod { field, value });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr(bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&16u32.to_be_bytes());
+        data.extend_from_slice(&16u32.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        data
+    }
+
+    fn check(chunks: &[(&[u8; 4], &[u8])]) -> Structure {
+        inspect(chunks.iter().map(|&(chunk_type, data)| (*chunk_type, data)))
+    }
+
+    #[test]
+    fn test_healthy_palette_image() {
+        let header = ihdr(4, 3);
+        let palette = [0u8; 3 * 16];
+        let structure = check(&[
+            (b"IHDR", &header),
+            (b"PLTE", &palette),
+            (b"tRNS", &[0, 255, 128]),
+            (b"IDAT", &[]),
+            (b"IEND", &[]),
+        ]);
+
+        assert!(structure.is_healthy(), "{:?}", structure.issues);
+        assert_eq!(structure.palette_entries, Some(16));
+        assert_eq!(structure.transparency, Some(Transparency::Palette(vec![0, 255, 128])));
+        assert_eq!(structure.summary()[0], "Image: 16x16, 4-bit palette, non-interlaced");
+        assert_eq!(structure.summary().last().unwrap(), "Structure: ok");
+    }
+
+    #[test]
+    fn test_palette_inconsistencies() {
+        let header = ihdr(1, 3);
+        // 没有 PLTE
+        assert_eq!(check(&[(b"IHDR", &header), (b"IDAT", &[])]).issues, vec![Issue::MissingPalette]);
+
+        // 1 位的调色板图像最多 2 项；tRNS 在 PLTE 之前且比调色板还长
+        let palette = [0u8; 3 * 4];
+        let issues = check(&[(b"IHDR", &header), (b"tRNS", &[0; 5]), (b"PLTE", &palette), (b"IDAT", &[])]).issues;
+        assert_eq!(
+            issues,
+            vec![
+                Issue::PaletteTooLarge { entries: 4, max: 2 },
+                Issue::TransparencyBeforePalette,
+                Issue::TransparencyTooLong { entries: 5, palette: 4 },
+            ]
+        );
+
+        // 灰度图像不能有 PLTE，PLTE 也不能在 IDAT 之后
+        let issues = check(&[(b"IHDR", &ihdr(8, 0)), (b"IDAT", &[]), (b"PLTE", &[0, 0, 0, 1])]).issues;
+        assert_eq!(
+            issues,
+            vec![Issue::AfterImageData("PLTE"), Issue::BadPaletteLength(4), Issue::PaletteNotAllowed(0)]
+        );
+    }
+
+    #[test]
+    fn test_header_and_transparency_checks() {
+        let issues = check(&[(b"IHDR", &ihdr(4, 2)), (b"tRNS", &[0, 1, 0, 2])]).issues;
+        assert_eq!(
+            issues,
+            vec![
+                Issue::InvalidBitDepth { bit_depth: 4, color_type: 2 },
+                Issue::BadTransparencyLength { expected: 6, actual: 4 },
+            ]
+        );
+
+        let structure = check(&[(b"IHDR", &ihdr(16, 0)), (b"tRNS", &[1, 2])]);
+        assert_eq!(structure.transparency, Some(Transparency::Gray(0x0102)));
+        assert_eq!(
+            check(&[(b"IHDR", &ihdr(8, 6)), (b"tRNS", &[0, 0])]).issues,
+            vec![Issue::TransparencyNotAllowed(6)]
+        );
+        assert_eq!(check(&[(b"IHDR", &[0; 12])]).issues, vec![Issue::BadHeaderLength(12)]);
+        assert_eq!(check(&[(b"IDAT", &[])]).issues, vec![Issue::MissingHeader]);
+    }
+}
@@ -0,0 +1,232 @@
+use thiserror::Error;
+
+use crate::chunk::{Chunk, ChunkError};
+use crate::png::Png;
+
+/// `verify` 发现的一个问题，每个问题都带着它在文件中的字节偏移
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Violation {
+    /// 签名不是标准 PNG 签名，之后的 chunk 照样检查
+    #[error("invalid PNG signature")]
+    InvalidSignature,
+    /// 从 `offset` 开始读不出完整的 chunk，之后的内容无法检查
+    #[error("unreadable chunk: {source}")]
+    Unreadable { offset: usize, source: ChunkError },
+    /// 存储的 CRC 和按类型、数据算出来的不一致
+    #[error("{} CRC mismatch (stored {stored:#010x}, computed {computed:#010x})", String::from_utf8_lossy(.chunk_type))]
+    CrcMismatch { offset: usize, chunk_type: [u8; 4], stored: u32, computed: u32 },
+    /// 文件里没有 IHDR，`offset` 是它应该在的位置
+    #[error("no IHDR chunk")]
+    MissingHeader { offset: usize },
+    /// IHDR 不是第一个 chunk
+    #[error("IHDR must be the first chunk")]
+    HeaderNotFirst { offset: usize },
+    /// 第二个及以后的 IHDR
+    #[error("duplicate IHDR chunk")]
+    DuplicateHeader { offset: usize },
+    /// 文件里没有 IEND，`offset` 是文件末尾
+    #[error("no IEND chunk")]
+    MissingEnd { offset: usize },
+    /// IEND 后面还有 chunk，`offset` 是 IEND 之后的第一个 chunk
+    #[error("chunk after IEND, IEND must be the last chunk")]
+    EndNotLast { offset: usize },
+    /// 第二个及以后的 IEND
+    #[error("duplicate IEND chunk")]
+    DuplicateEnd { offset: usize },
+    /// 多个 IDAT 之间夹着别的 chunk，`offset` 是被隔开后的第一个 IDAT
+    #[error("IDAT chunks must be consecutive")]
+    SplitImageData { offset: usize },
+}
+
+impl Violation {
+    /// 问题所在的字节偏移
+    pub fn offset(&self) -> usize {
+        match *self {
+            Violation::InvalidSignature => 0,
+            Violation::Unreadable { offset, .. }
+            | Violation::CrcMismatch { offset, .. }
+            | Violation::MissingHeader { offset }
+            | Violation::HeaderNotFirst { offset }
+            | Violation::DuplicateHeader { offset }
+            | Violation::MissingEnd { offset }
+            | Violation::EndNotLast { offset }
+            | Violation::DuplicateEnd { offset }
+            | Violation::SplitImageData { offset } => offset,
+        }
+    }
+}
+
+/// `verify` 的结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// 读出来的 chunk 数量
+    pub chunks: usize,
+    /// 按偏移排序的所有问题
+    pub violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// 逐个检查文件中的 chunk：重新计算 CRC，检查 IHDR 在最前、IEND 在最后并且各只有一个、
+/// IDAT 连续。和 `Png::try_from` 不同，遇到问题不会停下，而是把所有问题都找出来；
+/// 只有读不出完整的 chunk 时，之后的内容才没法检查
+pub fn verify_png(bytes: &[u8]) -> Report {
+    let mut report = Report::default();
+    if bytes.len() < 8 {
+        report.violations.push(Violation::Unreadable {
+            offset: 0,
+            source: ChunkError::TooShort(bytes.len()),
+        });
+        return report;
+    }
+    if bytes[..8] != Png::STANDARD_HEADER {
+        report.violations.push(Violation::InvalidSignature);
+    }
+
+    let mut offset = 8;
+    let mut header_seen = false;
+    let mut end_at = None;
+    let mut after_end_reported = false;
+    // 最后一个 IDAT 的偏移，以及它后面是不是已经出现过别的 chunk
+    let mut last_idat: Option<(usize, bool)> = None;
+    while offset < bytes.len() {
+        let (raw, consumed) = match Chunk::split(&bytes[offset..]) {
+            Ok(split) => split,
+            Err(source) => {
+                report.violations.push(Violation::Unreadable { offset, source });
+                break;
+            }
+        };
+        report.chunks += 1;
+
+        let computed = raw.computed_crc();
+        if computed != raw.crc {
+            report.violations.push(Violation::CrcMismatch {
+                offset,
+                chunk_type: raw.chunk_type,
+                stored: raw.crc,
+                computed,
+            });
+        }
+
+        if end_at.is_some() && !after_end_reported && &raw.chunk_type != b"IEND" {
+            report.violations.push(Violation::EndNotLast { offset });
+            after_end_reported = true;
+        }
+        match &raw.chunk_type {
+            b"IHDR" if header_seen => report.violations.push(Violation::DuplicateHeader { offset }),
+            b"IHDR" => {
+                header_seen = true;
+                if offset != 8 {
+                    report.violations.push(Violation::HeaderNotFirst { offset });
+                }
+            }
+            b"IEND" if end_at.is_some() => report.violations.push(Violation::DuplicateEnd { offset }),
+            b"IEND" => end_at = Some(offset),
+            _ => {}
+        }
+        match (&raw.chunk_type, &mut last_idat) {
+            (b"IDAT", Some((_, true))) => {
+                report.violations.push(Violation::SplitImageData { offset });
+                last_idat = Some((offset, false));
+            }
+            (b"IDAT", _) => last_idat = Some((offset, false)),
+            (_, Some((_, interrupted))) => *interrupted = true,
+            (_, None) => {}
+        }
+        offset += consumed;
+    }
+
+    if !header_seen {
+        report.violations.push(Violation::MissingHeader { offset: 8 });
+    }
+    if end_at.is_none() {
+        report.violations.push(Violation::MissingEnd { offset: bytes.len() });
+    }
+    report.violations.sort_by_key(Violation::offset);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(types: &[&str]) -> Vec<u8> {
+        Png::from_chunks(types.iter().map(|ty| chunk(ty, b"data")).collect()).as_bytes()
+    }
+
+    #[test]
+    fn test_valid_file() {
+        let report = verify_png(&png_bytes(&["IHDR", "tEXt", "IDAT", "IDAT", "IEND"]));
+        assert!(report.is_valid());
+        assert_eq!(report.chunks, 5);
+    }
+
+    #[test]
+    fn test_reports_every_violation_with_offsets() {
+        // 每个 chunk 16 字节：长度 4 + 类型 4 + 数据 4 + CRC 4
+        let mut bytes = png_bytes(&["tEXt", "IHDR", "IDAT", "tIME", "IDAT", "IEND", "IHDR", "IEND"]);
+        bytes[8 + 16 * 3 + 8] ^= 0xff;
+        let report = verify_png(&bytes);
+        let found: Vec<(usize, &str)> = report
+            .violations
+            .iter()
+            .map(|violation| {
+                let name = match violation {
+                    Violation::HeaderNotFirst { .. } => "HeaderNotFirst",
+                    Violation::CrcMismatch { .. } => "CrcMismatch",
+                    Violation::SplitImageData { .. } => "SplitImageData",
+                    Violation::EndNotLast { .. } => "EndNotLast",
+                    Violation::DuplicateHeader { .. } => "DuplicateHeader",
+                    Violation::DuplicateEnd { .. } => "DuplicateEnd",
+                    other => panic!("unexpected {:?}", other),
+                };
+                (violation.offset(), name)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (24, "HeaderNotFirst"),
+                (56, "CrcMismatch"),
+                (72, "SplitImageData"),
+                (104, "EndNotLast"),
+                (104, "DuplicateHeader"),
+                (120, "DuplicateEnd"),
+            ]
+        );
+        assert!(report.violations[1].to_string().starts_with("tIME CRC mismatch (stored "));
+    }
+
+    #[test]
+    fn test_missing_chunks_and_truncation() {
+        let mut bytes = png_bytes(&["IDAT"]);
+        bytes[0] = 0;
+        let report = verify_png(&bytes);
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::InvalidSignature,
+                Violation::MissingHeader { offset: 8 },
+                Violation::MissingEnd { offset: 24 },
+            ]
+        );
+
+        let mut bytes = png_bytes(&["IHDR", "IEND"]);
+        bytes.truncate(30);
+        let report = verify_png(&bytes);
+        assert_eq!(report.chunks, 1);
+        assert!(matches!(report.violations[0], Violation::Unreadable { offset: 24, .. }));
+        assert_eq!(report.violations[1], Violation::MissingEnd { offset: 30 });
+    }
+}
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::capacity::{DEFAULT_PAYLOAD_LIMIT, check_payload};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// 浏览器演示页面用的接口：输入输出都是字节缓冲区，不碰文件系统。
+/// 把消息写进 PNG，返回新的 PNG 字节
+#[wasm_bindgen]
+pub fn encode(png: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>, JsError> {
+    encode_message(png, chunk_type, message).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// 读出第一个指定类型 chunk 里的消息，没有这种 chunk 时返回 `undefined`
+#[wasm_bindgen]
+pub fn decode(png: &[u8], chunk_type: &str) -> Result<Option<String>, JsError> {
+    decode_message(png, chunk_type).map_err(|e| JsError::new(&e.to_string()))
+}
+
+// JsError 只能在 wasm32 上创建，实际逻辑放在这里，原生平台也能测试
+fn encode_message(png: &[u8], chunk_type: &str, message: &str) -> crate::Result<Vec<u8>> {
+    check_payload(message.len(), DEFAULT_PAYLOAD_LIMIT)?;
+    let mut png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    png.append_chunk(Chunk::new(chunk_type, message.as_bytes().to_vec()));
+    Ok(png.as_bytes())
+}
+
+fn decode_message(png: &[u8], chunk_type: &str) -> crate::Result<Option<String>> {
+    let png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    Ok(png
+        .chunks()
+        .iter()
+        .find(|chunk| *chunk.chunk_type() == chunk_type)
+        .map(|chunk| String::from_utf8_lossy(chunk.data()).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_bytes() -> Vec<u8> {
+        let chunk = |ty: &str, data: &[u8]| Chunk::new(ChunkType::from_str(ty).unwrap(), data.to_vec());
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", &[]),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_encode_then_decode() {
+        let encoded = encode_message(&png_bytes(), "ruSt", "hello wasm").unwrap();
+        assert_eq!(decode_message(&encoded, "ruSt").unwrap().as_deref(), Some("hello wasm"));
+        assert_eq!(decode_message(&encoded, "miSs").unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(encode_message(b"not a png", "ruSt", "hi").is_err());
+        assert!(encode_message(&png_bytes(), "ru5t", "hi").is_err());
+        assert!(decode_message(&png_bytes(), "toolong").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use std::time::Duration;
+
+/// 终端程序通常关心的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermEvent {
+    Key(KeyEvent),
+    /// 新的终端大小 (列数, 行数)
+    Resize(u16, u16),
+}
+
+/// 按固定间隔轮询 crossterm 的阻塞式事件读取器
+pub struct EventReader {
+    poll_interval: Duration,
+}
+
+impl EventReader {
+    pub fn new() -> Self {
+        Self::with_poll_interval(Duration::from_millis(500))
+    }
+
+    pub fn with_poll_interval(poll_interval: Duration) -> Self {
+        Self { poll_interval }
+    }
+
+    /// 等待下一个按键或窗口大小变化, 鼠标等其他事件会被跳过
+    pub fn next_event(&self) -> crossterm::Result<TermEvent> {
+        loop {
+            if let Some(event) = self.poll_event(self.poll_interval)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// 最多等待 `timeout`, 期间没有按键或窗口大小变化时返回 `None`,
+    /// 方便调用方在空闲时做定时检查
+    pub fn poll_event(&self, timeout: Duration) -> crossterm::Result<Option<TermEvent>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        Ok(match event::read()? {
+            Event::Key(key) => Some(TermEvent::Key(normalize_key(key))),
+            Event::Resize(columns, rows) => Some(TermEvent::Resize(columns, rows)),
+            _ => None,
+        })
+    }
+
+    /// 等待下一个按键, 忽略其他所有事件
+    pub fn read_key(&self) -> crossterm::Result<KeyEvent> {
+        loop {
+            if let TermEvent::Key(key) = self.next_event()? {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+impl Default for EventReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 统一按键事件, 调用方只需要匹配字符本身
+///
+/// 有的终端把 `N` 报告为 `Char('N')` + SHIFT, 有的不带修饰键;
+/// Shift+Tab 的 `BackTab` 也可能带或不带 SHIFT。
+/// 统一之后可打印字符不再带 SHIFT, `BackTab` 总是带 SHIFT
+pub fn normalize_key(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => KeyEvent {
+            code: key.code,
+            modifiers: key.modifiers - KeyModifiers::SHIFT,
+        },
+        KeyCode::BackTab => KeyEvent {
+            code: key.code,
+            modifiers: key.modifiers | KeyModifiers::SHIFT,
+        },
+        _ => key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_is_dropped_from_characters() {
+        let shifted = KeyEvent::new(KeyCode::Char('N'), KeyModifiers::SHIFT);
+        let plain = KeyEvent::new(KeyCode::Char('N'), KeyModifiers::NONE);
+        assert_eq!(normalize_key(shifted), plain);
+        assert_eq!(normalize_key(plain), plain);
+    }
+
+    #[test]
+    fn test_other_modifiers_are_kept() {
+        let ctrl_shift = KeyEvent::new(
+            KeyCode::Char('S'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        assert_eq!(
+            normalize_key(ctrl_shift),
+            KeyEvent::new(KeyCode::Char('S'), KeyModifiers::CONTROL)
+        );
+
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::SHIFT);
+        assert_eq!(normalize_key(up), up);
+    }
+
+    #[test]
+    fn test_back_tab_always_has_shift() {
+        let back_tab = KeyEvent::new(KeyCode::BackTab, KeyModifiers::NONE);
+        assert_eq!(normalize_key(back_tab).modifiers, KeyModifiers::SHIFT);
+    }
+}
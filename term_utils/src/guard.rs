@@ -0,0 +1,20 @@
+use crossterm::terminal;
+
+/// 创建时开启 raw 模式, drop 时关闭,
+/// 程序提前返回或 panic 时终端也能恢复
+pub struct RawModeGuard {
+    _private: (),
+}
+
+impl RawModeGuard {
+    pub fn new() -> crossterm::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self { _private: () })
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        terminal::disable_raw_mode().expect("Could not turn off Raw mode");
+    }
+}
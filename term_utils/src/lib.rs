@@ -0,0 +1,11 @@
+//! 仓库里各个终端程序共用的小工具
+//!
+//! - [`RawModeGuard`]: 开启 raw 模式, 离开作用域时自动恢复终端
+//! - [`EventReader`]: 把 crossterm 事件整理成 [`TermEvent`] (按键和窗口大小变化)
+//! - [`normalize_key`]: 抹平不同终端对 Shift 组合键的差异
+
+mod event;
+mod guard;
+
+pub use event::{EventReader, TermEvent, normalize_key};
+pub use guard::RawModeGuard;
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+// 插入模式的缩写(:iabbrev)和代码片段(:snippet), 通常写在配置文件里
+//   iabbrev teh the
+//   snippet fn fn $1($2) {\n    $0\n}
+#[derive(Default)]
+pub struct Abbreviations {
+    abbrevs: HashMap<String, String>,
+    snippets: HashMap<String, Snippet>,
+}
+
+// 展开后的片段: 按行的文本和光标停留点(行, 列), 行列都相对片段开头, 列按字符计算
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub lines: Vec<String>,
+    pub stops: Vec<(usize, usize)>,
+}
+
+impl Snippet {
+    // 模板中 `\n` 换行, `\t` 制表符, `\\` 和 `\$` 是字面字符;
+    // `$1`..`$9` 是按编号依次跳到的停留点, `$0` 是最后一个, 都没有时停在片段末尾
+    pub fn parse(template: &str) -> Self {
+        let mut lines = vec![String::new()];
+        let mut numbered: Vec<(u32, (usize, usize))> = Vec::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            let row = lines.len() - 1;
+            let line = &mut lines[row];
+            match ch {
+                '\\' => match chars.next() {
+                    Some('n') => lines.push(String::new()),
+                    Some('t') => line.push('\t'),
+                    Some(other) => line.push(other),
+                    None => line.push('\\'),
+                },
+                '$' if chars.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                    let number = chars.next().unwrap().to_digit(10).unwrap();
+                    let position = (row, line.chars().count());
+                    // $0 排在所有编号之后
+                    let order = if number == 0 { 10 } else { number };
+                    numbered.push((order, position));
+                }
+                _ => line.push(ch),
+            }
+        }
+
+        // 同一个编号出现多次时只用第一个
+        numbered.sort_by_key(|(order, _)| *order);
+        numbered.dedup_by_key(|(order, _)| *order);
+        let mut stops: Vec<(usize, usize)> = numbered.into_iter().map(|(_, pos)| pos).collect();
+        if stops.is_empty() {
+            let last = lines.len() - 1;
+            stops.push((last, lines[last].chars().count()));
+        }
+        Self { lines, stops }
+    }
+}
+
+impl Abbreviations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // :iabbrev {lhs} {rhs}, lhs 必须全是关键字字符
+    pub fn add_abbrev(&mut self, lhs: &str, rhs: &str) -> Result<(), String> {
+        if lhs.is_empty() || !lhs.chars().all(is_keyword) || rhs.is_empty() {
+            return Err(format!("E474: Invalid argument: {} {}", lhs, rhs));
+        }
+        self.abbrevs.insert(lhs.to_string(), rhs.to_string());
+        Ok(())
+    }
+
+    // :iunabbrev {lhs}
+    pub fn remove_abbrev(&mut self, lhs: &str) -> Result<(), String> {
+        self.abbrevs
+            .remove(lhs)
+            .map(drop)
+            .ok_or_else(|| format!("E24: No such abbreviation: {}", lhs))
+    }
+
+    // :snippet {trigger} {template}
+    pub fn add_snippet(&mut self, trigger: &str, template: &str) -> Result<(), String> {
+        if trigger.is_empty() || !trigger.chars().all(is_keyword) || template.is_empty() {
+            return Err(format!("E474: Invalid argument: {} {}", trigger, template));
+        }
+        self.snippets
+            .insert(trigger.to_string(), Snippet::parse(template));
+        Ok(())
+    }
+
+    pub fn abbrev(&self, word: &str) -> Option<&str> {
+        self.abbrevs.get(word).map(String::as_str)
+    }
+
+    pub fn snippet(&self, trigger: &str) -> Option<&Snippet> {
+        self.snippets.get(trigger)
+    }
+
+    // 不带参数的 :iabbrev 显示的列表, 按 lhs 排序
+    pub fn list(&self) -> String {
+        let mut entries: Vec<String> = self
+            .abbrevs
+            .iter()
+            .map(|(lhs, rhs)| format!("{} {}", lhs, rhs))
+            .collect();
+        entries.sort();
+        if entries.is_empty() {
+            "No abbreviation found".to_string()
+        } else {
+            entries.join(" | ")
+        }
+    }
+}
+
+// 缩写和片段触发词只由关键字字符组成, 输入其它字符时检查前面的词
+pub fn is_keyword(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+// 光标前面的关键字(列按字符计算), 返回词开始的列和词本身
+pub fn word_before(row: &str, col: usize) -> (usize, String) {
+    let chars: Vec<char> = row.chars().take(col).collect();
+    let start = chars
+        .iter()
+        .rposition(|&ch| !is_keyword(ch))
+        .map_or(0, |i| i + 1);
+    (start, chars[start..].iter().collect())
+}
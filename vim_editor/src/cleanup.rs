@@ -1,10 +1,30 @@
-use crossterm::terminal;
+use crossterm::{execute, style::Print};
+use std::io::stdout;
+use term_utils::RawModeGuard;
 
-pub struct CleanUp;
+// xterm 的标题栈: 启动时把终端原来的标题压栈, 退出时弹出来恢复
+// 不支持标题栈的终端会忽略这两个序列
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+// 退出时先清屏、恢复终端标题, 然后由 RawModeGuard 关闭 raw 模式
+pub struct CleanUp {
+    _raw_mode: RawModeGuard,
+}
+
+impl CleanUp {
+    pub fn new() -> crossterm::Result<Self> {
+        let raw_mode = RawModeGuard::new()?;
+        execute!(stdout(), Print(PUSH_TITLE))?;
+        Ok(Self {
+            _raw_mode: raw_mode,
+        })
+    }
+}
 
 impl Drop for CleanUp {
     fn drop(&mut self) {
-        terminal::disable_raw_mode().expect("Could not turn off Raw mode");
         crate::output::Output::clear_screen().expect("error");
+        execute!(stdout(), Print(POP_TITLE)).expect("error");
     }
 }
@@ -0,0 +1,32 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// 启动时读取的配置文件, 放在用户主目录下
+pub const CONFIG_FILE: &str = ".vim_editor_rc";
+
+// 配置文件的位置: $HOME/.vim_editor_rc, 没有 HOME 时用当前目录
+pub fn config_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(CONFIG_FILE)
+}
+
+// 配置文件的每一行是一条不带 `:` 的命令, 例如 `set sw=2`、`iabbrev teh the`,
+// 空行和 `"` 开头的注释行跳过. 返回 (行号, 命令), 行号从 1 开始
+pub fn read_commands() -> io::Result<Vec<(usize, String)>> {
+    let text = match fs::read_to_string(config_path()) {
+        Ok(text) => text,
+        // 没有配置文件是正常情况
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('"'))
+        .map(|(number, line)| (number, line.trim_start_matches(':').to_string()))
+        .collect())
+}
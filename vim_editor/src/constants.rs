@@ -4,6 +4,7 @@ pub enum Mode {
     Command,
     Search,
     Insert,
+    VisualLine, // V: 按行选择
 }
 
 pub const NAME: &str = r#"
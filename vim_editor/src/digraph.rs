@@ -0,0 +1,146 @@
+// 插入模式下 Ctrl-k {char1}{char2} 使用的二合字母, 取自 vim 默认表(RFC 1345)中常用的一部分
+const DIGRAPHS: &[(&str, char)] = &[
+    // 带附加符号的拉丁字母
+    ("a:", 'ä'),
+    ("o:", 'ö'),
+    ("u:", 'ü'),
+    ("e:", 'ë'),
+    ("i:", 'ï'),
+    ("y:", 'ÿ'),
+    ("A:", 'Ä'),
+    ("O:", 'Ö'),
+    ("U:", 'Ü'),
+    ("a'", 'á'),
+    ("e'", 'é'),
+    ("i'", 'í'),
+    ("o'", 'ó'),
+    ("u'", 'ú'),
+    ("E'", 'É'),
+    ("a!", 'à'),
+    ("e!", 'è'),
+    ("i!", 'ì'),
+    ("o!", 'ò'),
+    ("u!", 'ù'),
+    ("a>", 'â'),
+    ("e>", 'ê'),
+    ("i>", 'î'),
+    ("o>", 'ô'),
+    ("u>", 'û'),
+    ("a?", 'ã'),
+    ("n?", 'ñ'),
+    ("o?", 'õ'),
+    ("N?", 'Ñ'),
+    ("c,", 'ç'),
+    ("C,", 'Ç'),
+    ("aa", 'å'),
+    ("AA", 'Å'),
+    ("ae", 'æ'),
+    ("AE", 'Æ'),
+    ("o/", 'ø'),
+    ("O/", 'Ø'),
+    ("ss", 'ß'),
+    // 货币和常用符号
+    ("Eu", '€'),
+    ("Pd", '£'),
+    ("Ye", '¥'),
+    ("Ct", '¢'),
+    ("Co", '©'),
+    ("Rg", '®'),
+    ("TM", '™'),
+    ("SE", '§'),
+    ("PI", '¶'),
+    ("DG", '°'),
+    ("+-", '±'),
+    ("*X", '×'),
+    ("-:", '÷'),
+    ("12", '½'),
+    ("14", '¼'),
+    ("34", '¾'),
+    ("1S", '¹'),
+    ("2S", '²'),
+    ("3S", '³'),
+    ("My", 'µ'),
+    ("NS", '\u{a0}'),
+    // 标点
+    ("<<", '«'),
+    (">>", '»'),
+    ("'6", '‘'),
+    ("'9", '’'),
+    ("\"6", '“'),
+    ("\"9", '”'),
+    ("-N", '–'),
+    ("-M", '—'),
+    (",.", '…'),
+    ("!I", '¡'),
+    ("?I", '¿'),
+    // 箭头和数学符号
+    ("<-", '←'),
+    ("->", '→'),
+    ("-!", '↑'),
+    ("-v", '↓'),
+    ("!=", '≠'),
+    ("=<", '≤'),
+    (">=", '≥'),
+    ("?2", '≈'),
+    ("00", '∞'),
+    ("RT", '√'),
+    ("OK", '✓'),
+    ("XX", '✗'),
+    // 希腊字母
+    ("a*", 'α'),
+    ("b*", 'β'),
+    ("g*", 'γ'),
+    ("d*", 'δ'),
+    ("e*", 'ε'),
+    ("l*", 'λ'),
+    ("m*", 'μ'),
+    ("p*", 'π'),
+    ("s*", 'σ'),
+    ("w*", 'ω'),
+    ("D*", 'Δ'),
+    ("S*", 'Σ'),
+    ("W*", 'Ω'),
+];
+
+// 查找二合字母, 和 vim 一样找不到时再试一次交换顺序
+pub fn lookup(first: char, second: char) -> Option<char> {
+    let find = |a: char, b: char| {
+        DIGRAPHS.iter().find_map(|&(keys, ch)| {
+            let mut chars = keys.chars();
+            (chars.next() == Some(a) && chars.next() == Some(b)).then_some(ch)
+        })
+    };
+    find(first, second).or_else(|| find(second, first))
+}
+
+// Ctrl-v 之后输入的编码格式, 与 vim 相同:
+// `u` 后最多 4 位十六进制, `U` 后最多 8 位十六进制, `x` 后最多 2 位十六进制, 直接输入数字为最多 3 位十进制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeFormat {
+    pub radix: u32,
+    pub max_digits: usize,
+}
+
+impl CodeFormat {
+    // Ctrl-v 后的第一个字符选择的格式; 十进制时这个字符本身就是第一位数字
+    pub fn from_prefix(prefix: char) -> Option<Self> {
+        match prefix {
+            'u' => Some(Self::new(16, 4)),
+            'U' => Some(Self::new(16, 8)),
+            'x' | 'X' => Some(Self::new(16, 2)),
+            '0'..='9' => Some(Self::new(10, 3)),
+            _ => None,
+        }
+    }
+
+    fn new(radix: u32, max_digits: usize) -> Self {
+        Self { radix, max_digits }
+    }
+
+    // 把已经输入的数字转换成字符, 不是合法的 Unicode 码点时返回 None
+    pub fn to_char(self, digits: &str) -> Option<char> {
+        u32::from_str_radix(digits, self.radix)
+            .ok()
+            .and_then(char::from_u32)
+    }
+}
@@ -1,27 +1,659 @@
-use crate::{constants::Mode, output::Output, reader::Reader};
+use crate::{
+    abbrev::{self, Abbreviations},
+    config,
+    constants::Mode,
+    digraph::{self, CodeFormat},
+    editor_rows::EditorRows,
+    filetype,
+    finder::Finder,
+    git_diff, help,
+    options::Options,
+    output::Output,
+    quickfix::{self, QuickfixList},
+    range::{self, LineRange, RangeContext},
+    reader::Reader,
+    register::Registers,
+    session::{self, Session},
+    spell::SpellChecker,
+    undo::{self, Change, UndoHistory},
+};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// 消息行上等待用户回答的提示
+enum Prompt {
+    RetryWrite(String), // 写入失败的命令, y/r 重试
+    FileChanged,        // 文件在外部被修改, r 重新读取, k 保留本地内容
+}
+
+// 插入模式下 Ctrl-v / Ctrl-k 之后等待的输入
+enum InsertInput {
+    Literal,                  // Ctrl-v 之后, 等待编码格式或要原样插入的字符
+    Code(CodeFormat, String), // Ctrl-v u 之后已经输入的数字
+    Digraph(Option<char>),    // Ctrl-k 之后已经输入的第一个字符
+}
+
+// :help 打开帮助时暂存的正在编辑的缓冲区, :q 关闭帮助时恢复
+struct HelpView {
+    rows: EditorRows,
+    cursor: (usize, usize, usize, usize), // cursor_x, cursor_y, row_offest, column_offest
+    marks: HashMap<char, usize>,
+    tag_stack: Vec<(usize, usize)>, // Ctrl-] 跳转前的位置(列, 行), Ctrl-t 依次跳回去
+}
+
+// 在只读的帮助缓冲区里修改内容时的提示
+const READ_ONLY_MESSAGE: &str = "E21: Cannot make changes, 'modifiable' is off";
+
+// 打开二进制文件时的提示
+const BINARY_MESSAGE: &str = "Binary file: showing a read-only hex dump";
 
 pub struct Editor {
     reader: Reader,
     output: Output,
     mode: Mode,
     command_buffer: String,
+    options: Options,
+    pending_count: String, // 普通模式下输入中的计数, 例如 "3>>" 中的 "3"
+    pending_operator: Option<String>, // 等待后续按键的操作符, 例如 ">"、"gc"、"]"
+    pending_register: Option<char>, // 用 "{name} 选择的寄存器, 例如 "add 中的 'a'
+    registers: Registers,
+    marks: HashMap<char, usize>, // 用 m{a-z} 设置的标记所在的行
+    message: Option<String>,     // 消息行显示的内容, 下一次按键时清除
+    prompt: Option<Prompt>,
+    insert_input: Option<InsertInput>,
+    visual_anchor: usize,               // 进入 V 模式时光标所在的行
+    finder: Option<Finder>,             // Ctrl-p 打开的模糊查找器
+    quickfix: QuickfixList,             // 最近一次 :make 解析出的错误
+    abbreviations: Abbreviations,       // :iabbrev 和 :snippet 定义的缩写和片段
+    snippet_stops: Vec<(usize, usize)>, // 展开的片段中还没跳到的停留点(行, 列), Tab 依次跳过去
+    undo: UndoHistory,                  // u / Ctrl-r 的撤销历史, :set undofile 时写入文件时一起保存
+    change_start: Option<(Vec<String>, (usize, usize))>, // 正在进行的改动开始前的内容和光标
+    help: Option<HelpView>,             // 打开帮助时被替换下来的缓冲区
 }
 
 impl Editor {
-    pub fn new() -> Self {
-        Self {
-            reader: Reader,
-            output: Output::new(),
+    pub fn new(file: Option<String>) -> Self {
+        let mut editor = Self {
+            reader: Reader::new(),
+            output: Output::new(file),
             mode: Mode::Normal,
             command_buffer: String::new(),
+            options: Options::new(),
+            pending_count: String::new(),
+            pending_operator: None,
+            pending_register: None,
+            registers: Registers::new(),
+            marks: HashMap::new(),
+            message: None,
+            prompt: None,
+            insert_input: None,
+            visual_anchor: 0,
+            finder: None,
+            quickfix: QuickfixList::new(),
+            abbreviations: Abbreviations::new(),
+            snippet_stops: Vec::new(),
+            undo: UndoHistory::new(),
+            change_start: None,
+            help: None,
+        };
+        editor.load_config();
+        editor.load_undo_file();
+        editor.refresh_markers();
+        if editor.output.editor_rows.hex_view {
+            editor.message = Some(BINARY_MESSAGE.to_string());
+        }
+        editor
+    }
+
+    // vim_editor -S <file>: 恢复会话, 读取失败时打开空缓冲区并显示错误
+    pub fn from_session(path: &str) -> Self {
+        match Session::load(Path::new(path)) {
+            Ok(session) => {
+                let file = session
+                    .file
+                    .as_ref()
+                    .map(|file| file.to_string_lossy().into_owned());
+                let mut editor = Self::new(file);
+                editor.restore_session(&session);
+                editor
+            }
+            Err(e) => {
+                let mut editor = Self::new(None);
+                editor.message = Some(e);
+                editor
+            }
+        }
+    }
+
+    // 逐行执行配置文件中的命令, 出错的行在消息行上报告第一条
+    fn load_config(&mut self) {
+        let commands = match config::read_commands() {
+            Ok(commands) => commands,
+            Err(e) => {
+                self.message = Some(format!("Can't read {}: {}", config::CONFIG_FILE, e));
+                return;
+            }
+        };
+        let mut first_error = None;
+        for (number, command) in commands {
+            self.command_buffer = command;
+            self.message = None;
+            self.execute_command();
+            if let Some(e) = self.message.take() {
+                first_error.get_or_insert(format!(
+                    "Error in {} line {}: {}",
+                    config::CONFIG_FILE,
+                    number,
+                    e
+                ));
+            }
+        }
+        self.command_buffer.clear();
+        self.message = first_error;
+    }
+
+    fn restore_session(&mut self, session: &Session) {
+        // 会话文件被手改坏时报告第一个出错的选项, 其余的照常恢复
+        for arg in &session.options {
+            if let Err(e) = self.options.set(arg) {
+                self.message.get_or_insert(e);
+            }
+        }
+        self.sync_spell_checker();
+
+        // 文件在保存会话后变短时把位置限制在文件范围内
+        let last_line = self.output.editor_rows.number_of_rows().saturating_sub(1);
+        let (x, y) = session.cursor;
+        let cursor = &mut self.output.cursor_controller;
+        cursor.cursor_y = y.min(last_line);
+        cursor.cursor_x = if self.output.editor_rows.number_of_rows() == 0 {
+            0
+        } else {
+            x.min(self.output.editor_rows.row_len(cursor.cursor_y))
+        };
+        cursor.row_offest = session.offsets.0.min(cursor.cursor_y);
+        cursor.column_offest = session.offsets.1.min(cursor.cursor_x);
+
+        self.marks = session
+            .marks
+            .iter()
+            .filter(|(_, line)| *line <= last_line)
+            .copied()
+            .collect();
+    }
+
+    // :mksession[!] [file]
+    fn make_session(&mut self, args: &[&str], force: bool) {
+        let file = match args.join(" ") {
+            name if name.is_empty() => session::DEFAULT_SESSION_FILE.to_string(),
+            name => name,
+        };
+        let cursor = &self.output.cursor_controller;
+        let session = Session::new(
+            self.output.editor_rows.filename.as_deref(),
+            (cursor.cursor_x, cursor.cursor_y),
+            (cursor.row_offest, cursor.column_offest),
+            &self.options,
+            &self.marks,
+        );
+        self.message = Some(match session.save(Path::new(&file), force) {
+            Ok(()) => format!("Session saved to \"{}\"", file),
+            Err(e) => format!("E189: Can't write session \"{}\": {}", file, e),
+        });
+    }
+
+    // 执行带范围的 ex 命令, 例如 `:10,20d`、`:.,$s/a/b/g`、`:'a,'b>`
+    // 命令不带范围且不是 :s 时返回 None, 交给其它命令处理
+    fn run_range_command(&mut self) -> Option<Result<(), String>> {
+        let ctx = RangeContext {
+            current_line: self.output.cursor_controller.cursor_y,
+            line_count: self.output.editor_rows.number_of_rows(),
+            marks: &self.marks,
+        };
+        let (range, rest) = match range::parse_range(&self.command_buffer, &ctx) {
+            Ok(parsed) => parsed,
+            Err(e) => return Some(Err(e)),
+        };
+        let rest = rest.trim().to_string();
+
+        let range = match range {
+            Some(range) => range,
+            // 不写范围的 :s 作用于当前行
+            None if parse_substitute(&rest).is_some() => LineRange {
+                start: ctx.current_line,
+                end: ctx.current_line,
+            },
+            None => return None,
+        };
+
+        Some(self.execute_range_command(range, &rest))
+    }
+
+    fn execute_range_command(&mut self, range: LineRange, command: &str) -> Result<(), String> {
+        if self.output.editor_rows.read_only && !command.is_empty() {
+            return Err(READ_ONLY_MESSAGE.to_string());
+        }
+        match command {
+            // 只有范围时跳到最后一行
+            "" => {
+                self.output.cursor_controller.cursor_y = range.end;
+                self.output.cursor_controller.cursor_x = 0;
+            }
+            "d" | "delete" => {
+                self.output.editor_rows.delete_lines(range.start, range.end);
+                self.output.cursor_controller.cursor_y = range
+                    .start
+                    .min(self.output.editor_rows.number_of_rows().saturating_sub(1));
+                self.output.cursor_controller.cursor_x = 0;
+            }
+            ">" | "<" => {
+                self.output.editor_rows.shift_lines(
+                    range.start,
+                    range.end,
+                    command == ">",
+                    &self.options,
+                );
+            }
+            _ => {
+                let (pattern, replacement, global) =
+                    parse_substitute(command).ok_or("Not an editor command")?;
+                let changed = self.output.editor_rows.substitute(
+                    range.start,
+                    range.end,
+                    &pattern,
+                    &replacement,
+                    global,
+                );
+                if changed == 0 {
+                    return Err(format!("Pattern not found: {}", pattern));
+                }
+                // 与 vim 一样, 光标停在范围的最后一行
+                self.output.cursor_controller.cursor_y = range.end;
+            }
+        }
+        Ok(())
+    }
+
+    // 普通模式下会修改内容的按键, 在只读缓冲区里被拒绝, 也决定什么时候为撤销保存内容
+    // m{a-z} 和 "{name} 后面的字符是名字, 不是命令
+    fn is_change_key(&self, key: KeyEvent) -> bool {
+        match (key.code, key.modifiers) {
+            _ if matches!(self.pending_operator.as_deref(), Some("m" | "\"")) => false,
+            (
+                KeyCode::Char('i' | 'a' | 'p' | 'P' | 'u' | 'V' | 'd' | '>' | '<'),
+                KeyModifiers::NONE,
+            ) => true,
+            (KeyCode::Char('c'), KeyModifiers::NONE) => {
+                matches!(self.pending_operator.as_deref(), Some("g" | "gc"))
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => true,
+            _ => false,
+        }
+    }
+
+    // 普通模式命令前面输入的计数, 没有输入时为 1
+    fn count(&self) -> usize {
+        self.pending_count.parse::<usize>().unwrap_or(1).max(1)
+    }
+
+    // 输入到一半的命令, 按输入顺序: 寄存器、计数、操作符, 例如 "\"a2d"
+    fn pending_keys(&self) -> String {
+        let mut keys = String::new();
+        if let Some(name) = self.pending_register {
+            keys.push('"');
+            keys.push(name);
+        }
+        keys.push_str(&self.pending_count);
+        if let Some(operator) = &self.pending_operator {
+            keys.push_str(operator);
+        }
+        keys
+    }
+
+    fn clear_pending(&mut self) {
+        self.pending_count.clear();
+        self.pending_operator = None;
+        self.pending_register = None;
+    }
+
+    // 光标移到当前行第一个非空白字符
+    fn cursor_to_first_non_blank(&mut self) {
+        let row = self
+            .output
+            .editor_rows
+            .get_row(self.output.cursor_controller.cursor_y);
+        self.output.cursor_controller.cursor_x =
+            row.chars().take_while(|ch| ch.is_whitespace()).count();
+    }
+
+    // 执行 >> / <<: 从光标所在行开始处理 count 行, 然后光标移到行首第一个非空白字符
+    fn shift_command(&mut self, indent: bool) {
+        let count = self.count();
+        let start = self.output.cursor_controller.cursor_y;
+        self.output
+            .editor_rows
+            .shift_lines(start, start + count - 1, indent, &self.options);
+        self.cursor_to_first_non_blank();
+    }
+
+    // V 模式选中的行, 两端都包含
+    fn visual_range(&self) -> (usize, usize) {
+        let cursor_y = self.output.cursor_controller.cursor_y;
+        (
+            self.visual_anchor.min(cursor_y),
+            self.visual_anchor.max(cursor_y),
+        )
+    }
+
+    // 打开 spell 选项时才读入词表, 关闭时丢掉
+    fn sync_spell_checker(&mut self) {
+        if !self.options.spell {
+            self.output.spell_checker = None;
+        } else if self.output.spell_checker.is_none() {
+            self.output.spell_checker = Some(SpellChecker::new());
+        }
+    }
+
+    // ]s / [s 跳过 count 个拼错的单词, 到文件末尾(开头)后从另一头继续
+    fn jump_to_misspelling(&mut self, forward: bool, count: usize) {
+        let Some(checker) = &self.output.spell_checker else {
+            self.message = Some("E756: Spell checking is not enabled".to_string());
+            return;
+        };
+        let rows = &self.output.editor_rows;
+        let filetype = rows.filename.as_deref().and_then(filetype::detect);
+        let errors: Vec<(usize, usize)> = (0..rows.number_of_rows())
+            .flat_map(|row| {
+                checker
+                    .misspellings(rows.get_row(row), filetype)
+                    .into_iter()
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect();
+        if errors.is_empty() {
+            self.message = Some("No misspelled words".to_string());
+            return;
+        }
+
+        let cursor = &mut self.output.cursor_controller;
+        let mut position = (cursor.cursor_y, cursor.cursor_x);
+        for _ in 0..count {
+            position = if forward {
+                errors
+                    .iter()
+                    .find(|&&error| error > position)
+                    .unwrap_or(&errors[0])
+            } else {
+                errors
+                    .iter()
+                    .rfind(|&&error| error < position)
+                    .unwrap_or(&errors[errors.len() - 1])
+            }
+            .to_owned();
+        }
+        (cursor.cursor_y, cursor.cursor_x) = position;
+    }
+
+    // 按文件类型的注释格式切换 [start, end] 行的注释
+    fn toggle_comment(&mut self, start: usize, end: usize) {
+        let comment_string = self
+            .output
+            .editor_rows
+            .filename
+            .as_deref()
+            .and_then(filetype::detect)
+            .and_then(filetype::comment_string);
+        match comment_string {
+            Some(comment_string) => {
+                self.output
+                    .editor_rows
+                    .toggle_comment(start, end, comment_string);
+                self.cursor_to_first_non_blank();
+            }
+            None => self.message = Some("No comment string for this file type".to_string()),
+        }
+    }
+
+    // 执行 dd / yy: 从光标所在行开始的 count 行存入寄存器, delete 为 true 时同时删除
+    fn yank_lines(&mut self, delete: bool) {
+        let rows = self.output.editor_rows.number_of_rows();
+        let start = self.output.cursor_controller.cursor_y;
+        if start >= rows {
+            return;
+        }
+        let end = (start + self.count() - 1).min(rows - 1);
+        let lines = self.output.editor_rows.lines(start, end);
+        let affected = lines.len();
+        let warning = self.registers.set(self.pending_register, lines);
+
+        if delete {
+            self.output.editor_rows.delete_lines(start, end);
+            self.output.cursor_controller.cursor_y =
+                start.min(self.output.editor_rows.number_of_rows().saturating_sub(1));
+            self.cursor_to_first_non_blank();
+        }
+
+        // 和 vim 一样, 超过两行时才在消息行报告
+        if affected > 2 {
+            self.message = Some(if delete {
+                format!("{} fewer lines", affected)
+            } else {
+                format!("{} lines yanked", affected)
+            });
+        }
+        if warning.is_some() {
+            self.message = warning;
+        }
+    }
+
+    // 执行 p / P: 把寄存器里的行粘贴 count 次到当前行的下面(below)或上面
+    fn paste_lines(&mut self, count: usize, register: Option<char>, below: bool) {
+        let Some(lines) = self.registers.get(register) else {
+            self.message = Some(format!(
+                "E353: Nothing in register {}",
+                register.unwrap_or('"')
+            ));
+            return;
+        };
+        let lines: Vec<String> = lines
+            .iter()
+            .cycle()
+            .take(lines.len() * count)
+            .cloned()
+            .collect();
+        let added = lines.len();
+
+        let at = if below && self.output.editor_rows.number_of_rows() > 0 {
+            self.output.cursor_controller.cursor_y + 1
+        } else {
+            self.output.cursor_controller.cursor_y
+        };
+        self.output.editor_rows.insert_lines(at, lines);
+        self.output.cursor_controller.cursor_y =
+            at.min(self.output.editor_rows.number_of_rows() - 1);
+        self.cursor_to_first_non_blank();
+
+        if added > 2 {
+            self.message = Some(format!("{} more lines", added));
         }
     }
 
     pub fn process_keypress(&mut self) -> crossterm::Result<bool> {
+        let key = self.reader.read_key()?;
+        // 终端大小变化产生的空按键不影响消息和提示
+        if key.code == KeyCode::Null {
+            return Ok(true);
+        }
+        self.message = None;
+        // 只在可能修改内容的按键前保存内容, 移动光标和空闲时不复制整个文件
+        if self.starts_change(key) {
+            self.begin_change();
+        }
+
+        if let Some(prompt) = self.prompt.take() {
+            return Ok(self.answer_prompt(prompt, key));
+        }
+
+        if let Some(finder) = self.finder.take() {
+            self.handle_finder_key(finder, key);
+            return Ok(true);
+        }
+
+        // Ctrl-s 在任何模式下保存, 不改变当前模式
+        if key.code == KeyCode::Char('s') && key.modifiers == KeyModifiers::CONTROL {
+            let result = self.write_command(&[], false, false);
+            self.report_write("w", result);
+            return Ok(true);
+        }
+
         match self.mode {
             Mode::Normal => {
-                match self.reader.read_key()? {
+                // 计数、寄存器和操作符之外的按键都会取消未完成的命令
+                // p / P 这类一个键的命令在取消之前先取出计数和寄存器
+                let is_pending_key = match key.code {
+                    KeyCode::Char(_)
+                        if matches!(self.pending_operator.as_deref(), Some("m" | "\"")) =>
+                    {
+                        true
+                    }
+                    KeyCode::Char('c')
+                        if matches!(self.pending_operator.as_deref(), Some("g" | "gc")) =>
+                    {
+                        true
+                    }
+                    KeyCode::Char('s')
+                        if matches!(self.pending_operator.as_deref(), Some("]" | "[")) =>
+                    {
+                        true
+                    }
+                    KeyCode::Char('>' | '<' | 'd' | 'y' | 'm' | '"' | 'g' | ']' | '[') => true,
+                    KeyCode::Char('0') => !self.pending_count.is_empty(),
+                    KeyCode::Char(ch) => ch.is_ascii_digit() && self.pending_operator.is_none(),
+                    _ => false,
+                };
+                let (count, register) = (self.count(), self.pending_register);
+                if !is_pending_key {
+                    self.clear_pending();
+                }
+                if self.output.editor_rows.read_only && self.is_change_key(key) {
+                    self.message = Some(READ_ONLY_MESSAGE.to_string());
+                    self.clear_pending();
+                    return Ok(true);
+                }
+
+                match key {
+                    // m{a-z}: 在当前行设置标记
+                    KeyEvent {
+                        code: KeyCode::Char(mark),
+                        ..
+                    } if self.pending_operator.as_deref() == Some("m") => {
+                        if mark.is_ascii_lowercase() {
+                            self.marks
+                                .insert(mark, self.output.cursor_controller.cursor_y);
+                        }
+                        self.clear_pending();
+                    }
+                    // "{name}: 下一个 d / y / p 使用的寄存器
+                    KeyEvent {
+                        code: KeyCode::Char(name),
+                        ..
+                    } if self.pending_operator.as_deref() == Some("\"") => {
+                        if Registers::is_valid_name(name) {
+                            self.pending_register = Some(name);
+                            self.pending_operator = None;
+                        } else {
+                            self.clear_pending();
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('"'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.pending_operator = Some("\"".to_string());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('m'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.pending_operator = Some("m".to_string());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char(digit),
+                        modifiers: KeyModifiers::NONE,
+                    } if is_pending_key && digit.is_ascii_digit() => {
+                        self.pending_count.push(digit);
+                    }
+                    // gcc: 注释或取消注释从当前行开始的 count 行
+                    KeyEvent {
+                        code: KeyCode::Char('c'),
+                        modifiers: KeyModifiers::NONE,
+                    } if is_pending_key => {
+                        if self.pending_operator.as_deref() == Some("gc") {
+                            let start = self.output.cursor_controller.cursor_y;
+                            self.toggle_comment(start, start + count - 1);
+                            self.clear_pending();
+                        } else {
+                            self.pending_operator = Some("gc".to_string());
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('g'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.pending_operator = Some("g".to_string());
+                    }
+                    // ]s / [s: 跳到下一个 / 上一个拼错的单词
+                    KeyEvent {
+                        code: KeyCode::Char('s'),
+                        modifiers: KeyModifiers::NONE,
+                    } if matches!(self.pending_operator.as_deref(), Some("]" | "[")) => {
+                        let forward = self.pending_operator.as_deref() == Some("]");
+                        self.jump_to_misspelling(forward, count);
+                        self.clear_pending();
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char(bracket @ (']' | '[')),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.pending_operator = Some(bracket.to_string());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('V'),
+                        modifiers: KeyModifiers::NONE,
+                    } if self.output.editor_rows.number_of_rows() > 0 => {
+                        self.visual_anchor = self.output.cursor_controller.cursor_y;
+                        self.mode = Mode::VisualLine;
+                    }
+                    // 按两次的整行命令: >> << dd yy
+                    KeyEvent {
+                        code: KeyCode::Char(op @ ('>' | '<' | 'd' | 'y')),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        if self.pending_operator == Some(op.to_string()) {
+                            match op {
+                                'd' | 'y' => self.yank_lines(op == 'd'),
+                                _ => self.shift_command(op == '>'),
+                            }
+                            self.clear_pending();
+                        } else {
+                            self.pending_operator = Some(op.to_string());
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char(paste @ ('p' | 'P')),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.paste_lines(count, register, paste == 'p');
+                    }
+                    // Ctrl-p: 模糊查找文件
+                    KeyEvent {
+                        code: KeyCode::Char('p'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        self.open_finder();
+                    }
                     KeyEvent {
                         code: KeyCode::Char(':'),
                         modifiers: KeyModifiers::NONE,
@@ -105,7 +737,7 @@ impl Editor {
                     }
                     KeyEvent {
                         code: KeyCode::Char('N'),
-                        modifiers: KeyModifiers::SHIFT,
+                        modifiers: KeyModifiers::NONE,
                     } => {
                         // 搜索下一个匹配项
                         if let Some((row, col)) = self.output.editor_rows.prev_match(
@@ -116,6 +748,28 @@ impl Editor {
                             self.output.cursor_controller.cursor_x = col;
                         }
                     }
+                    KeyEvent {
+                        code: KeyCode::Char('u'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.undo_changes(count, false);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('r'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        self.undo_changes(count, true);
+                    }
+                    // 帮助中的 Ctrl-] 跳到主题, Ctrl-t / Ctrl-o 跳回去
+                    // 终端把 Ctrl-] 报告成 Ctrl-5
+                    KeyEvent {
+                        code: KeyCode::Char(']' | '5'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => self.follow_help_tag(),
+                    KeyEvent {
+                        code: KeyCode::Char('t' | 'o'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => self.pop_help_tag(),
                     KeyEvent {
                         code: KeyCode::Char('q'),
                         modifiers: KeyModifiers::CONTROL,
@@ -123,10 +777,59 @@ impl Editor {
                     _ => {}
                 }
             }
-            Mode::Command => match self.reader.read_key()? {
+            Mode::VisualLine => {
+                let after_g = self.pending_operator.take().is_some_and(|op| op == "g");
+                match key {
+                    // gc: 注释或取消注释选中的行, 然后回到普通模式
+                    KeyEvent {
+                        code: KeyCode::Char('c'),
+                        modifiers: KeyModifiers::NONE,
+                    } if after_g => {
+                        let (start, end) = self.visual_range();
+                        self.toggle_comment(start, end);
+                        self.output.cursor_controller.cursor_y = start;
+                        self.mode = Mode::Normal;
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('g'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.pending_operator = Some("g".to_string());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char(val @ ('h' | 'j' | 'k' | 'l' | '0' | '$')),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.output
+                            .move_cursor(val, self.output.editor_rows.number_of_rows());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Up,
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.output
+                            .move_cursor('k', self.output.editor_rows.number_of_rows());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Down,
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.output
+                            .move_cursor('j', self.output.editor_rows.number_of_rows());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Esc | KeyCode::Char('V'),
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        self.mode = Mode::Normal;
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Command => match key {
                 KeyEvent {
                     code: KeyCode::Char(ch),
-                    modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                    modifiers: KeyModifiers::NONE,
                 } => {
                     self.command_buffer.push(ch);
                 }
@@ -134,80 +837,18 @@ impl Editor {
                     code: KeyCode::Enter,
                     modifiers: KeyModifiers::NONE,
                 } => {
-                    if self.command_buffer == "q" {
-                        return Ok(false);
-                    }
-                    if self.command_buffer == "gg" {
-                        self.output.cursor_controller.cursor_x = 0;
-                        self.output.cursor_controller.cursor_y = 0;
-                    }
-                    if self.command_buffer == "G" {
-                        self.output.cursor_controller.cursor_y =
-                            if self.output.editor_rows.number_of_rows() == 0 {
-                                self.output.win_size.1.saturating_sub(1)
-                            } else {
-                                self.output.editor_rows.number_of_rows().saturating_sub(1)
-                            };
-                        self.output.cursor_controller.cursor_x = 0;
-                    }
-                    if self.command_buffer.parse::<usize>().is_ok() {
-                        let line = self.command_buffer.parse::<usize>().unwrap();
-                        self.output.cursor_controller.cursor_y =
-                            if line != 0 && line <= self.output.editor_rows.number_of_rows() {
-                                line.saturating_sub(1)
-                            } else {
-                                0
-                            };
-                        self.output.cursor_controller.cursor_x = 0;
-                    }
-                    if self.command_buffer == "w" {
-                        match self.output.editor_rows.save_file() {
-                            Ok(_) => {
-                                self.command_buffer.clear();
-                                self.mode = Mode::Normal;
-                            }
-                            Err(e) => {
-                                self.command_buffer = format!("Error: {}", e);
-                                self.mode = Mode::Normal;
-                            }
-                        }
-                        self.command_buffer.clear();
-                        self.mode = Mode::Normal;
-                    }
-                    if self.command_buffer == "wq" {
-                        match self.output.editor_rows.save_file() {
-                            Ok(_) => {
-                                self.command_buffer.clear();
-                                return Ok(false);
-                            }
-                            Err(e) => {
-                                self.command_buffer = format!("Error: {}", e);
-                                self.mode = Mode::Normal;
-                            }
-                        }
-                        self.command_buffer.clear();
-                        self.mode = Mode::Normal;
-                    }
-                    if self.command_buffer == "q!" {
-                        self.command_buffer.clear();
-                        return Ok(false);
-                    }
-                    if self.command_buffer == "dd" {
-                        self.output
-                            .editor_rows
-                            .delete_line(self.output.cursor_controller.cursor_y);
-                    }
-
+                    let continue_running = self.execute_command();
                     self.command_buffer.clear();
                     self.mode = Mode::Normal;
+                    if !continue_running {
+                        return Ok(false);
+                    }
                 }
                 KeyEvent {
                     code: KeyCode::Backspace,
                     modifiers: KeyModifiers::NONE,
-                } => {
-                    if !self.command_buffer.is_empty() {
-                        self.command_buffer.pop();
-                    }
+                } if !self.command_buffer.is_empty() => {
+                    self.command_buffer.pop();
                 }
                 KeyEvent {
                     code: KeyCode::Esc,
@@ -219,10 +860,10 @@ impl Editor {
                 _ => {}
             },
             Mode::Search => {
-                match self.reader.read_key()? {
+                match key {
                     KeyEvent {
                         code: KeyCode::Char(ch),
-                        modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                        modifiers: KeyModifiers::NONE,
                     } => {
                         self.command_buffer.push(ch);
 
@@ -245,20 +886,18 @@ impl Editor {
                     KeyEvent {
                         code: KeyCode::Backspace,
                         modifiers: KeyModifiers::NONE,
-                    } => {
-                        if !self.command_buffer.is_empty() {
-                            self.command_buffer.pop();
-                            // 更新搜索结果
-                            if self.command_buffer.is_empty() {
-                                self.output.editor_rows.search_term = None;
-                                self.output.editor_rows.search_matches.clear();
-                            } else if let Some((row, col)) =
-                                self.output.editor_rows.search(&self.command_buffer)
-                            {
-                                // 光标跳到第一个匹配项
-                                self.output.cursor_controller.cursor_y = row;
-                                self.output.cursor_controller.cursor_x = col;
-                            }
+                    } if !self.command_buffer.is_empty() => {
+                        self.command_buffer.pop();
+                        // 更新搜索结果
+                        if self.command_buffer.is_empty() {
+                            self.output.editor_rows.search_term = None;
+                            self.output.editor_rows.search_matches.clear();
+                        } else if let Some((row, col)) =
+                            self.output.editor_rows.search(&self.command_buffer)
+                        {
+                            // 光标跳到第一个匹配项
+                            self.output.cursor_controller.cursor_y = row;
+                            self.output.cursor_controller.cursor_x = col;
                         }
                     }
                     KeyEvent {
@@ -274,24 +913,46 @@ impl Editor {
                 }
             }
             Mode::Insert => {
-                match self.reader.read_key()? {
+                if let Some(input) = self.insert_input.take()
+                    && self.handle_insert_input(input, key)
+                {
+                    return Ok(true);
+                }
+                match key {
                     KeyEvent {
                         code: KeyCode::Char(ch),
-                        modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                        modifiers: KeyModifiers::NONE,
                     } => {
-                        // 在光标位置插入字符
-                        self.output.editor_rows.insert_char(
-                            self.output.cursor_controller.cursor_y,
-                            self.output.cursor_controller.cursor_x,
-                            ch,
-                        );
-                        // 光标右移
-                        self.output.cursor_controller.cursor_x += 1;
+                        // 输入非关键字字符时展开前面的缩写
+                        if !abbrev::is_keyword(ch) {
+                            self.expand_abbreviation();
+                        }
+                        self.insert_char(ch);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Tab,
+                        modifiers: KeyModifiers::NONE,
+                    } => self.insert_tab(),
+                    KeyEvent {
+                        code: KeyCode::Char('v'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        self.insert_input = Some(InsertInput::Literal);
+                        self.message = Some("^V".to_string());
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('k'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        self.insert_input = Some(InsertInput::Digraph(None));
+                        self.message = Some("^K".to_string());
                     }
                     KeyEvent {
                         code: KeyCode::Enter,
                         modifiers: KeyModifiers::NONE,
                     } => {
+                        self.expand_abbreviation();
+                        self.snippet_stops.clear();
                         // 插入新行
                         self.output.editor_rows.insert_newline(
                             self.output.cursor_controller.cursor_y,
@@ -308,17 +969,18 @@ impl Editor {
                         if self.output.cursor_controller.cursor_x > 0 {
                             // 删除光标前的字符
                             self.output.cursor_controller.cursor_x -= 1;
+                            self.shift_snippet_stops(-1);
                             self.output.editor_rows.delete_char(
                                 self.output.cursor_controller.cursor_y,
                                 self.output.cursor_controller.cursor_x,
                             );
                         } else if self.output.cursor_controller.cursor_y > 0 {
+                            self.snippet_stops.clear();
                             // 在行首删除，需要将光标移到上一行末尾
                             let prev_row_len = self
                                 .output
                                 .editor_rows
-                                .get_row(self.output.cursor_controller.cursor_y - 1)
-                                .len();
+                                .row_len(self.output.cursor_controller.cursor_y - 1);
                             self.output.cursor_controller.cursor_y -= 1;
                             self.output.cursor_controller.cursor_x = prev_row_len;
                             // 合并行
@@ -332,6 +994,7 @@ impl Editor {
                         code: KeyCode::Delete,
                         modifiers: KeyModifiers::NONE,
                     } => {
+                        self.shift_snippet_stops(-1);
                         // 删除光标处的字符
                         self.output.editor_rows.delete_char(
                             self.output.cursor_controller.cursor_y,
@@ -342,6 +1005,8 @@ impl Editor {
                         code: KeyCode::Esc,
                         modifiers: KeyModifiers::NONE,
                     } => {
+                        self.expand_abbreviation();
+                        self.snippet_stops.clear();
                         // 返回普通模式
                         self.mode = Mode::Normal;
                     }
@@ -352,19 +1017,888 @@ impl Editor {
         Ok(true)
     }
 
+    // Ctrl-p: 在当前目录下打开模糊查找器
+    fn open_finder(&mut self) {
+        match Finder::new(Path::new(".")) {
+            Ok(finder) => self.finder = Some(finder),
+            Err(e) => self.message = Some(format!("Can't list files: {}", e)),
+        }
+    }
+
+    // 查找器打开时所有按键都交给它; Esc / Ctrl-c 关闭, Enter 打开选中的文件
+    fn handle_finder_key(&mut self, mut finder: Finder, key: KeyEvent) {
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => return,
+            (KeyCode::Enter, _) => {
+                if let Some(file) = finder.selected() {
+                    let file = PathBuf::from(file);
+                    self.open_file(file);
+                }
+                return;
+            }
+            (KeyCode::Down, _) | (KeyCode::Char('n' | 'j'), KeyModifiers::CONTROL) => {
+                finder.select_next()
+            }
+            (KeyCode::Up, _) | (KeyCode::Char('p' | 'k'), KeyModifiers::CONTROL) => {
+                finder.select_prev()
+            }
+            (KeyCode::Backspace, _) => finder.pop(),
+            (KeyCode::Char(ch), KeyModifiers::NONE) => finder.push(ch),
+            _ => {}
+        }
+        self.finder = Some(finder);
+    }
+
+    // 在当前窗口打开文件; 当前缓冲区有未保存的修改时拒绝, 和 vim 的 E37 一样
+    // 返回文件是否已经在当前窗口中打开
+    fn open_file(&mut self, path: PathBuf) -> bool {
+        self.close_help();
+        if self.output.editor_rows.filename.as_ref() == Some(&path) {
+            return true;
+        }
+        if self.output.editor_rows.is_modified() {
+            self.message = Some("E37: No write since last change".to_string());
+            return false;
+        }
+        match EditorRows::open(path) {
+            Ok(rows) => {
+                self.output.editor_rows = rows;
+                let cursor = &mut self.output.cursor_controller;
+                cursor.cursor_x = 0;
+                cursor.cursor_y = 0;
+                cursor.row_offest = 0;
+                cursor.column_offest = 0;
+                // 标记记录的是行号, 换了文件就没有意义了
+                self.marks.clear();
+                self.load_undo_file();
+                self.refresh_markers();
+                if self.output.editor_rows.hex_view {
+                    self.message = Some(BINARY_MESSAGE.to_string());
+                }
+                true
+            }
+            Err(e) => {
+                self.message = Some(format!("Can't open file: {}", e));
+                false
+            }
+        }
+    }
+
+    // :help [topic]: 用只读缓冲区显示帮助, 已经打开时跳到主题并记下跳转前的位置
+    fn open_help(&mut self, topic: &str) {
+        let line = if topic.is_empty() {
+            0
+        } else {
+            match help::find_topic(topic) {
+                Some(line) => line,
+                None => {
+                    self.message = Some(format!("E149: Sorry, no help for {}", topic));
+                    return;
+                }
+            }
+        };
+        let cursor = &self.output.cursor_controller;
+        match &mut self.help {
+            Some(view) => view.tag_stack.push((cursor.cursor_x, cursor.cursor_y)),
+            None => {
+                let cursor = (
+                    cursor.cursor_x,
+                    cursor.cursor_y,
+                    cursor.row_offest,
+                    cursor.column_offest,
+                );
+                let rows = std::mem::replace(
+                    &mut self.output.editor_rows,
+                    EditorRows::help(help::lines()),
+                );
+                self.help = Some(HelpView {
+                    rows,
+                    cursor,
+                    marks: std::mem::take(&mut self.marks),
+                    tag_stack: Vec::new(),
+                });
+                // 换了缓冲区, 和打开前的内容比较没有意义
+                self.change_start = None;
+                self.refresh_markers();
+            }
+        }
+        self.show_help_line(line);
+    }
+
+    // 跳到帮助的第 line 行, 并把它放在屏幕顶部
+    fn show_help_line(&mut self, line: usize) {
+        let cursor = &mut self.output.cursor_controller;
+        cursor.cursor_x = 0;
+        cursor.cursor_y = line;
+        cursor.row_offest = line;
+        cursor.column_offest = 0;
+    }
+
+    // 关闭帮助, 回到打开帮助前的缓冲区和位置; 没有打开帮助时什么也不做
+    fn close_help(&mut self) {
+        let Some(view) = self.help.take() else {
+            return;
+        };
+        self.output.editor_rows = view.rows;
+        let cursor = &mut self.output.cursor_controller;
+        (
+            cursor.cursor_x,
+            cursor.cursor_y,
+            cursor.row_offest,
+            cursor.column_offest,
+        ) = view.cursor;
+        self.marks = view.marks;
+        self.change_start = None;
+        self.refresh_markers();
+    }
+
+    // 帮助中的 Ctrl-]: 跳到光标下的主题
+    fn follow_help_tag(&mut self) {
+        let cursor = &self.output.cursor_controller;
+        let (x, y) = (cursor.cursor_x, cursor.cursor_y);
+        let Some(view) = &mut self.help else {
+            return;
+        };
+        let Some(tag) = help::tag_at(self.output.editor_rows.get_row(y), x) else {
+            self.message = Some("E349: No identifier under cursor".to_string());
+            return;
+        };
+        match help::find_topic(&tag) {
+            Some(line) => {
+                view.tag_stack.push((x, y));
+                self.show_help_line(line);
+            }
+            None => self.message = Some(format!("E426: Tag not found: {}", tag)),
+        }
+    }
+
+    // 帮助中的 Ctrl-t / Ctrl-o: 回到上一次 Ctrl-] 之前的位置
+    fn pop_help_tag(&mut self) {
+        let Some(view) = &mut self.help else {
+            return;
+        };
+        match view.tag_stack.pop() {
+            Some((x, y)) => {
+                self.output.cursor_controller.cursor_x = x;
+                self.output.cursor_controller.cursor_y = y;
+            }
+            None => self.message = Some("E73: Tag stack empty".to_string()),
+        }
+    }
+
+    // 打开文件后读取它的撤销文件, 没有撤销文件或者内容对不上时从空的历史开始
+    fn load_undo_file(&mut self) {
+        self.undo = UndoHistory::new();
+        self.change_start = None;
+        let rows = &self.output.editor_rows;
+        let Some(path) = rows.filename.as_deref() else {
+            return;
+        };
+        if !self.options.undofile || rows.hex_view {
+            return;
+        }
+        match UndoHistory::load(&undo::undo_file_path(path), &rows.row_contents) {
+            Ok(history) => self.undo = history,
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    // 写入缓冲区绑定的文件后保存撤销历史, 失败时只在消息行提示
+    fn write_undo_file(&mut self) {
+        let rows = &self.output.editor_rows;
+        let Some(path) = rows.filename.as_deref() else {
+            return;
+        };
+        if !self.options.undofile || rows.hex_view {
+            return;
+        }
+        if let Err(e) = self
+            .undo
+            .save(&undo::undo_file_path(path), &rows.row_contents)
+        {
+            self.message = Some(format!("E828: Cannot write undo file: {}", e));
+        }
+    }
+
+    // 这个按键是否可能修改内容: 普通模式的编辑命令、V 模式的 gc、执行 ex 命令、
+    // 插入模式中的按键, 以及文件在外部被修改后选择重新读取
+    fn starts_change(&self, key: KeyEvent) -> bool {
+        if let Some(prompt) = &self.prompt {
+            return matches!(prompt, Prompt::FileChanged) && key.code == KeyCode::Char('r');
+        }
+        if self.finder.is_some() {
+            return false;
+        }
+        match self.mode {
+            Mode::Normal => self.is_change_key(key),
+            Mode::VisualLine => {
+                key.code == KeyCode::Char('c') && self.pending_operator.as_deref() == Some("g")
+            }
+            Mode::Command => key.code == KeyCode::Enter,
+            Mode::Insert => true,
+            Mode::Search => false,
+        }
+    }
+
+    // 记下改动前的内容和光标; 插入模式中已经记过时保留最早的那一份
+    fn begin_change(&mut self) {
+        if self.change_start.is_none() {
+            let cursor = &self.output.cursor_controller;
+            self.change_start = Some((
+                self.output.editor_rows.row_contents.clone(),
+                (cursor.cursor_x, cursor.cursor_y),
+            ));
+        }
+    }
+
+    // 一次改动结束时(回到插入模式以外的模式)和开始前的内容比较, 有变化就记一步撤销
+    fn record_change(&mut self) {
+        if self.mode == Mode::Insert {
+            return;
+        }
+        if let Some((before, cursor)) = self.change_start.take()
+            && let Some(change) =
+                Change::between(&before, &self.output.editor_rows.row_contents, cursor)
+        {
+            self.undo.push(change);
+        }
+    }
+
+    // u / Ctrl-r: 撤销或重做 count 步
+    fn undo_changes(&mut self, count: usize, redo: bool) {
+        let rows = &mut self.output.editor_rows.row_contents;
+        let mut cursor = None;
+        for _ in 0..count {
+            let step = if redo {
+                self.undo.redo(rows)
+            } else {
+                self.undo.undo(rows)
+            };
+            match step {
+                Some(position) => cursor = Some(position),
+                None => break,
+            }
+        }
+        // 撤销和重做本身不算新的改动
+        self.change_start = None;
+
+        match cursor {
+            Some((x, y)) => {
+                self.output.cursor_controller.cursor_x = x;
+                self.output.cursor_controller.cursor_y = y;
+                self.clamp_cursor();
+            }
+            None if redo => self.message = Some("Already at newest change".to_string()),
+            None => self.message = Some("Already at oldest change".to_string()),
+        }
+    }
+
+    // 更新标记栏中的 git 差异和 :make 错误标记; 在打开、重新读取和写入文件后调用
+    fn refresh_markers(&mut self) {
+        let rows = &self.output.editor_rows;
+        let (git, errors) = match rows.filename.as_deref() {
+            // 十六进制转储的行和 git 里的内容对不上
+            Some(path) if rows.hex_view => (Vec::new(), self.quickfix.markers_for(path)),
+            Some(path) => (
+                git_diff::markers(path, &rows.row_contents),
+                self.quickfix.markers_for(path),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        self.output.gutter.set_markers(git_diff::SOURCE, git);
+        self.output.gutter.set_markers(quickfix::SOURCE, errors);
+    }
+
+    // :make [args]: 执行 makeprg, 解析输出中的错误并跳到第一个错误
+    fn make(&mut self, args: &[&str]) {
+        let command = match args.join(" ") {
+            extra if extra.is_empty() => self.options.makeprg.clone(),
+            extra => format!("{} {}", self.options.makeprg, extra),
+        };
+        let (success, output) = match quickfix::run(&command) {
+            Ok(result) => result,
+            Err(e) => {
+                self.message = Some(format!("E282: Can't run \"{}\": {}", command, e));
+                return;
+            }
+        };
+        self.quickfix = QuickfixList::parse(&output);
+        self.refresh_markers();
+        if self.quickfix.is_empty() {
+            self.message = Some(if success {
+                format!("\"{}\" finished without errors", command)
+            } else {
+                format!("\"{}\" failed, no error locations found", command)
+            });
+            return;
+        }
+        let entry = self.quickfix.first_error().cloned();
+        self.jump_to_quickfix(entry);
+    }
+
+    // 打开错误所在的文件并把光标移到出错位置, 消息行显示错误内容
+    fn jump_to_quickfix(&mut self, entry: Option<quickfix::QuickfixEntry>) {
+        let Some(entry) = entry else {
+            self.message = Some(if self.quickfix.is_empty() {
+                "E42: No Errors".to_string()
+            } else {
+                "E553: No more items".to_string()
+            });
+            return;
+        };
+        let is_current = self
+            .output
+            .editor_rows
+            .filename
+            .as_deref()
+            .is_some_and(|path| quickfix::same_file(path, &entry.file));
+        if !is_current && !self.open_file(entry.file.clone()) {
+            return;
+        }
+        let cursor = &mut self.output.cursor_controller;
+        cursor.cursor_y = entry.line.saturating_sub(1);
+        cursor.cursor_x = entry.col.saturating_sub(1);
+        self.clamp_cursor();
+        self.message = self.quickfix.describe();
+    }
+
+    // 执行命令行中的命令, 返回 false 表示退出编辑器
+    fn execute_command(&mut self) -> bool {
+        if self.command_buffer == "q" || self.command_buffer == "q!" {
+            // 打开帮助时 :q 只关闭帮助
+            if self.help.is_some() {
+                self.close_help();
+                return true;
+            }
+            return false;
+        }
+        // 纯数字仍按原来的方式跳转到指定行
+        if self.command_buffer.parse::<usize>().is_err()
+            && let Some(result) = self.run_range_command()
+        {
+            if let Err(e) = result {
+                self.message = Some(e);
+            }
+            return true;
+        }
+        if self.command_buffer == "gg" {
+            self.output.cursor_controller.cursor_x = 0;
+            self.output.cursor_controller.cursor_y = 0;
+        }
+        if self.command_buffer == "G" {
+            self.output.cursor_controller.cursor_y =
+                if self.output.editor_rows.number_of_rows() == 0 {
+                    self.output.win_size.1.saturating_sub(1)
+                } else {
+                    self.output.editor_rows.number_of_rows().saturating_sub(1)
+                };
+            self.output.cursor_controller.cursor_x = 0;
+        }
+        if let Ok(line) = self.command_buffer.parse::<usize>() {
+            self.output.cursor_controller.cursor_y =
+                if line != 0 && line <= self.output.editor_rows.number_of_rows() {
+                    line.saturating_sub(1)
+                } else {
+                    0
+                };
+            self.output.cursor_controller.cursor_x = 0;
+        }
+        let command = self.command_buffer.clone();
+        let mut parts = command.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let args: Vec<&str> = parts.collect();
+
+        match name {
+            // :w / :w <file> / :w! <file> / :w ++p <file>
+            "w" | "w!" => {
+                let result = self.write_command(&args, name.ends_with('!'), false);
+                self.report_write(&command, result);
+            }
+            // :saveas <file>, 写入后缓冲区改为新文件
+            "sav" | "saveas" | "sav!" | "saveas!" => {
+                let result = self.write_command(&args, name.ends_with('!'), true);
+                self.report_write(&command, result);
+            }
+            // 写入成功才退出, 失败时留在编辑器里等待重试
+            "wq" => {
+                let result = self.write_command(&args, false, false);
+                if self.report_write(&command, result) {
+                    return false;
+                }
+            }
+            "mks" | "mksession" | "mks!" | "mksession!" => {
+                self.make_session(&args, name.ends_with('!'));
+            }
+            "dd" if self.output.editor_rows.read_only => {
+                self.message = Some(READ_ONLY_MESSAGE.to_string());
+            }
+            "dd" => {
+                self.output
+                    .editor_rows
+                    .delete_line(self.output.cursor_controller.cursor_y);
+            }
+            // :set sw=2 / :set noexpandtab / :set makeprg=make\ -j4
+            "set" | "se" => {
+                let rest = command.trim_start()[name.len()..].trim_start();
+                // 和 vim 一样报告第一个出错的参数, 其余的参数照常设置
+                for arg in Options::split_args(rest) {
+                    if let Err(e) = self.options.set(arg) {
+                        self.message.get_or_insert(e);
+                    }
+                }
+                self.sync_spell_checker();
+            }
+            "make" => self.make(&args),
+            "h" | "help" => self.open_help(&args.join(" ")),
+            // :iabbrev {lhs} {rhs}, 不带参数时列出所有缩写
+            "iab" | "iabbrev" => {
+                let rest = command.trim_start()[name.len()..].trim();
+                let result = match rest.split_once(char::is_whitespace) {
+                    Some((lhs, rhs)) => self.abbreviations.add_abbrev(lhs, rhs.trim()).err(),
+                    None if rest.is_empty() => Some(self.abbreviations.list()),
+                    None => Some(format!("E474: Invalid argument: {}", rest)),
+                };
+                if let Some(message) = result {
+                    self.message = Some(message);
+                }
+            }
+            "iuna" | "iunabbrev" => {
+                let lhs = args.first().copied().unwrap_or("");
+                if let Err(e) = self.abbreviations.remove_abbrev(lhs) {
+                    self.message = Some(e);
+                }
+            }
+            // :snippet {trigger} {template}, 模板的写法见 abbrev::Snippet::parse
+            "snip" | "snippet" => {
+                let rest = command.trim_start()[name.len()..].trim();
+                let (trigger, template) =
+                    rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if let Err(e) = self
+                    .abbreviations
+                    .add_snippet(trigger, template.trim_start())
+                {
+                    self.message = Some(e);
+                }
+            }
+            // 在错误列表中移动: :cnext / :cprev / :cc [N]
+            "cn" | "cnext" => {
+                let entry = self.quickfix.next().cloned();
+                self.jump_to_quickfix(entry);
+            }
+            "cp" | "cprev" | "cprevious" | "cN" | "cNext" => {
+                let entry = self.quickfix.prev().cloned();
+                self.jump_to_quickfix(entry);
+            }
+            "cc" => {
+                let entry = match args.first() {
+                    Some(number) => number
+                        .parse()
+                        .ok()
+                        .and_then(|number| self.quickfix.select(number))
+                        .cloned(),
+                    None => self.quickfix.current().cloned(),
+                };
+                self.jump_to_quickfix(entry);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    // 在光标位置插入字符, 光标右移
+    fn insert_char(&mut self, ch: char) {
+        self.shift_snippet_stops(1);
+        self.output.editor_rows.insert_char(
+            self.output.cursor_controller.cursor_y,
+            self.output.cursor_controller.cursor_x,
+            ch,
+        );
+        self.output.cursor_controller.cursor_x += 1;
+    }
+
+    // 光标所在行、光标之后的片段停留点随插入或删除的字符移动
+    fn shift_snippet_stops(&mut self, delta: isize) {
+        let (x, y) = (
+            self.output.cursor_controller.cursor_x,
+            self.output.cursor_controller.cursor_y,
+        );
+        for (row, col) in &mut self.snippet_stops {
+            if *row == y && *col >= x {
+                *col = col.saturating_add_signed(delta);
+            }
+        }
+    }
+
+    // 把光标前从第 start 列开始的词换成 lines(多行时依次换行), 光标停在替换的文本之后
+    fn replace_word(&mut self, start: usize, lines: &[String]) {
+        let rows = &mut self.output.editor_rows;
+        let cursor = &mut self.output.cursor_controller;
+        for _ in start..cursor.cursor_x {
+            rows.delete_char(cursor.cursor_y, start);
+        }
+        cursor.cursor_x = start;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                rows.insert_newline(cursor.cursor_y, cursor.cursor_x);
+                cursor.cursor_y += 1;
+                cursor.cursor_x = 0;
+            }
+            for ch in line.chars() {
+                rows.insert_char(cursor.cursor_y, cursor.cursor_x, ch);
+                cursor.cursor_x += 1;
+            }
+        }
+    }
+
+    // 光标前的词是缩写时展开它
+    fn expand_abbreviation(&mut self) {
+        let (x, y) = (
+            self.output.cursor_controller.cursor_x,
+            self.output.cursor_controller.cursor_y,
+        );
+        let (start, word) = abbrev::word_before(self.output.editor_rows.get_row(y), x);
+        let Some(rhs) = self.abbreviations.abbrev(&word).map(str::to_string) else {
+            return;
+        };
+        let delta = rhs.chars().count() as isize - word.chars().count() as isize;
+        self.replace_word(start, &[rhs]);
+        // 替换的文本之后的停留点跟着移动
+        for (row, col) in &mut self.snippet_stops {
+            if *row == y && *col >= x {
+                *col = col.saturating_add_signed(delta);
+            }
+        }
+    }
+
+    // 插入模式的 Tab: 光标前是片段触发词时展开片段, 片段还有停留点时跳到下一个, 否则插入缩进
+    fn insert_tab(&mut self) {
+        let (x, y) = (
+            self.output.cursor_controller.cursor_x,
+            self.output.cursor_controller.cursor_y,
+        );
+        let row = self.output.editor_rows.get_row(y);
+        let (start, word) = abbrev::word_before(row, x);
+        if let Some(snippet) = self.abbreviations.snippet(&word).cloned() {
+            // 后面的行和触发词所在的行保持同样的缩进
+            let indent: String = row.chars().take_while(|c| c.is_whitespace()).collect();
+            let indent_len = indent.chars().count();
+            let lines: Vec<String> = snippet
+                .lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    if i == 0 {
+                        line.clone()
+                    } else {
+                        indent.clone() + line
+                    }
+                })
+                .collect();
+            self.replace_word(start, &lines);
+            self.snippet_stops = snippet
+                .stops
+                .iter()
+                .map(|&(row, col)| match row {
+                    0 => (y, start + col),
+                    _ => (y + row, indent_len + col),
+                })
+                .collect();
+        }
+
+        if !self.snippet_stops.is_empty() {
+            let (row, col) = self.snippet_stops.remove(0);
+            self.output.cursor_controller.cursor_y = row;
+            self.output.cursor_controller.cursor_x = col.min(self.output.editor_rows.row_len(row));
+        } else if self.options.expandtab {
+            let width = self.options.shiftwidth - x % self.options.shiftwidth;
+            for _ in 0..width {
+                self.insert_char(' ');
+            }
+        } else {
+            self.insert_char('\t');
+        }
+    }
+
+    // 处理 Ctrl-v / Ctrl-k 之后的按键, 返回 false 表示这个键还要按普通插入模式处理
+    // 例如 `Ctrl-v u 41 <Esc>` 先插入 'A', 再由 Esc 退出插入模式
+    fn handle_insert_input(&mut self, input: InsertInput, key: KeyEvent) -> bool {
+        let typed = match key {
+            KeyEvent {
+                code: KeyCode::Char(ch),
+                modifiers: KeyModifiers::NONE,
+            } => Some(ch),
+            _ => None,
+        };
+
+        match (input, typed) {
+            (InsertInput::Literal, Some(ch)) => match CodeFormat::from_prefix(ch) {
+                Some(format) => {
+                    // 十进制时第一个字符本身就是数字
+                    let digits = if format.radix == 10 {
+                        ch.to_string()
+                    } else {
+                        String::new()
+                    };
+                    self.message = Some(format!("^V{}", ch));
+                    self.insert_input = Some(InsertInput::Code(format, digits));
+                }
+                None => self.insert_char(ch),
+            },
+            (InsertInput::Literal, None) => {
+                if key.code == KeyCode::Tab {
+                    self.insert_char('\t');
+                }
+            }
+            (InsertInput::Code(format, mut digits), Some(ch)) if ch.is_digit(format.radix) => {
+                digits.push(ch);
+                if digits.len() == format.max_digits {
+                    self.insert_code(format, &digits);
+                } else {
+                    self.message = Some(format!("^V{}", digits));
+                    self.insert_input = Some(InsertInput::Code(format, digits));
+                }
+            }
+            // 不是数字的键结束输入, 这个键再按普通方式处理
+            (InsertInput::Code(format, digits), _) => {
+                self.insert_code(format, &digits);
+                return false;
+            }
+            (InsertInput::Digraph(None), Some(first)) => {
+                self.message = Some(format!("^K{}", first));
+                self.insert_input = Some(InsertInput::Digraph(Some(first)));
+            }
+            // 没有对应的二合字母时和 vim 一样只插入第二个字符
+            (InsertInput::Digraph(Some(first)), Some(second)) => {
+                self.insert_char(digraph::lookup(first, second).unwrap_or(second));
+            }
+            // Esc 等其它键取消
+            (InsertInput::Digraph(_), None) => {}
+        }
+        true
+    }
+
+    fn insert_code(&mut self, format: CodeFormat, digits: &str) {
+        if digits.is_empty() {
+            return;
+        }
+        match format.to_char(digits) {
+            Some(ch) => self.insert_char(ch),
+            None => self.message = Some(format!("Invalid character code: {}", digits)),
+        }
+    }
+
+    // 处理提示的回答, 返回 false 表示退出编辑器
+    fn answer_prompt(&mut self, prompt: Prompt, key: KeyEvent) -> bool {
+        match (prompt, key.code) {
+            // 写入失败后: y/r 重试, 其它键放弃
+            (Prompt::RetryWrite(command), KeyCode::Char('y' | 'r')) => {
+                self.command_buffer = command;
+                let continue_running = self.execute_command();
+                self.command_buffer.clear();
+                continue_running
+            }
+            (Prompt::RetryWrite(_), _) => true,
+            (Prompt::FileChanged, KeyCode::Char('r')) => {
+                match self.output.editor_rows.reload() {
+                    Ok(()) => {
+                        self.clamp_cursor();
+                        self.refresh_markers();
+                        self.message = Some("File reloaded".to_string());
+                    }
+                    Err(e) => self.message = Some(format!("Can't reload file: {}", e)),
+                }
+                true
+            }
+            (Prompt::FileChanged, KeyCode::Char('k' | 'n') | KeyCode::Esc) => {
+                self.output.editor_rows.accept_disk_version();
+                true
+            }
+            // 其它键不算回答, 继续提示
+            (prompt @ Prompt::FileChanged, _) => {
+                self.prompt = Some(prompt);
+                self.show_file_changed();
+                true
+            }
+        }
+    }
+
+    // 文件在外部被修改时提示重新读取或保留本地内容; 输入命令和搜索时不打扰
+    fn check_file_changed(&mut self) {
+        if self.prompt.is_none()
+            && matches!(self.mode, Mode::Normal | Mode::Insert)
+            && self.output.editor_rows.changed_on_disk()
+        {
+            self.prompt = Some(Prompt::FileChanged);
+            self.show_file_changed();
+        }
+    }
+
+    fn show_file_changed(&mut self) {
+        self.message = Some(
+            "W11: File changed on disk since reading it -- (r)eload / (k)eep local edits"
+                .to_string(),
+        );
+    }
+
+    // 文件变短后把光标限制在文件范围内
+    fn clamp_cursor(&mut self) {
+        let rows = &self.output.editor_rows;
+        let cursor = &mut self.output.cursor_controller;
+        cursor.cursor_y = cursor.cursor_y.min(rows.number_of_rows().saturating_sub(1));
+        cursor.cursor_x = cursor.cursor_x.min(rows.row_len(cursor.cursor_y));
+    }
+
+    // 在消息行报告写入结果; 失败时显示错误并提示重试, 返回是否写入成功
+    fn report_write(&mut self, command: &str, result: io::Result<()>) -> bool {
+        match result {
+            Ok(()) => {
+                self.refresh_markers();
+                let name = self
+                    .output
+                    .editor_rows
+                    .filename
+                    .as_ref()
+                    .map_or("[No Name]".to_string(), |path| path.display().to_string());
+                let written = format!(
+                    "\"{}\" {}L written",
+                    name,
+                    self.output.editor_rows.number_of_rows()
+                );
+                // 写入撤销文件失败的提示接在后面
+                self.message = Some(match self.message.take() {
+                    Some(warning) => format!("{}; {}", written, warning),
+                    None => written,
+                });
+                true
+            }
+            Err(e) => {
+                self.message = Some(format!("E212: Can't write file: {} -- retry? (y/n)", e));
+                self.prompt = Some(Prompt::RetryWrite(command.to_string()));
+                false
+            }
+        }
+    }
+
+    // 处理 :w <file> 和 :saveas <file> 的参数
+    // `++p` 表示自动创建缺失的父目录; 没有 `!` 时不覆盖已存在的其它文件
+    fn write_command(&mut self, args: &[&str], force: bool, rebind: bool) -> io::Result<()> {
+        if self.output.editor_rows.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "E45: 'readonly' option is set",
+            ));
+        }
+        let create_dirs = args.contains(&"++p");
+        let file = args
+            .iter()
+            .filter(|arg| **arg != "++p")
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if file.is_empty() {
+            return if rebind {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Argument required",
+                ))
+            } else if !force && self.output.editor_rows.changed_on_disk() {
+                // 避免悄悄覆盖别人在外部做的修改
+                Err(io::Error::other(
+                    "file changed on disk since reading it (add ! to override)",
+                ))
+            } else {
+                self.output.editor_rows.save_file()?;
+                self.write_undo_file();
+                Ok(())
+            };
+        }
+
+        let path = PathBuf::from(file);
+        let is_current = self.output.editor_rows.filename.as_ref() == Some(&path);
+        if !force && !is_current && path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "File exists (add ! to override)",
+            ));
+        }
+
+        // 没有文件名的缓冲区第一次 :w <file> 时也绑定到该文件, 和 vim 一致
+        if rebind || self.output.editor_rows.filename.is_none() {
+            self.output.editor_rows.save_as(path, create_dirs)?;
+        } else {
+            self.output.editor_rows.write_to(&path, create_dirs)?;
+            // 写到别的文件时撤销历史不属于那个文件
+            if !is_current {
+                return Ok(());
+            }
+        }
+        self.write_undo_file();
+        Ok(())
+    }
+
+    // 终端标题: 文件名, 有未保存的修改时加 `+`, 括号里是所在目录, 例如 `main.rs + (src) - vim_editor`
+    // 同时打开几个编辑器时可以在终端的标签页上分清楚
+    fn title(&self) -> String {
+        let rows = &self.output.editor_rows;
+        let name = match rows.filename.as_deref().and_then(Path::file_name) {
+            _ if rows.read_only => "[Help]".to_string(),
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => "[No Name]".to_string(),
+        };
+        let modified = if rows.is_modified() { " +" } else { "" };
+        let dir = match rows.filename.as_deref().and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => format!(" ({})", dir.display()),
+            _ => String::new(),
+        };
+        format!("{}{}{} - vim_editor", name, modified, dir)
+    }
+
     pub fn run(&mut self) -> crossterm::Result<bool> {
+        self.output.selection = (self.mode == Mode::VisualLine).then(|| self.visual_range());
+        self.output.pending_keys = self.pending_keys();
+        let rows = (self.output.win_size.1 / 2).clamp(1, 10);
+        self.output.overlay = self.finder.as_ref().map(|finder| finder.overlay(rows));
+        // 终端大小变化后先更新布局再重绘
+        if let Some(size) = self.reader.take_resize() {
+            self.output.resize(size);
+        }
+        self.check_file_changed();
+        let title = self.title();
+        self.output.set_title(title);
         // 首先刷新屏幕,显示当前状态
         self.output
-            .refresh_screen(&self.mode, &self.command_buffer)?;
-        // 处理按键输入
+            .refresh_screen(&self.mode, &self.command_buffer, self.message.as_deref())?;
+        // 处理按键输入; 插入模式中的按键连在一起算一次改动
         let continue_running = self.process_keypress()?;
+        self.record_change();
 
         // 在Insert模式下, 立即刷新屏幕以显示更改
         if self.mode == Mode::Insert {
-            self.output
-                .refresh_screen(&self.mode, &self.command_buffer)?;
+            self.output.refresh_screen(
+                &self.mode,
+                &self.command_buffer,
+                self.message.as_deref(),
+            )?;
         }
 
         Ok(continue_running)
     }
 }
+
+// 解析 `s/pattern/replacement/flags`, 分隔符可以是任意非字母数字字符
+fn parse_substitute(command: &str) -> Option<(String, String, bool)> {
+    let rest = command
+        .strip_prefix("substitute")
+        .or_else(|| command.strip_prefix('s'))?;
+    let delimiter = rest.chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() {
+        return None;
+    }
+
+    let mut parts = rest[delimiter.len_utf8()..].splitn(3, delimiter);
+    let pattern = parts.next()?.to_string();
+    let replacement = parts.next().unwrap_or("").to_string();
+    let flags = parts.next().unwrap_or("");
+    Some((pattern, replacement, flags.contains('g')))
+}
@@ -1,24 +1,31 @@
+use crate::options::Options;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 pub struct EditorRows {
-    pub row_contents: Vec<Box<String>>,
+    pub row_contents: Vec<String>,
     pub filename: Option<PathBuf>,
+    disk_mtime: Option<SystemTime>, // 最近一次读取或写入时文件的修改时间
+    pub hex_view: bool,             // 二进制文件: 内容是只读的十六进制转储, 不能写回原文件
+    pub read_only: bool,            // :help 打开的帮助文档, 不能修改也不能写入
 
     pub search_term: Option<String>,
     pub search_matches: Vec<(usize, usize, usize)>, // (行号, 起始列, 长度)
 }
 
 impl EditorRows {
-    pub fn new() -> Self {
-        let mut arg = std::env::args().skip(1);
-
-        match arg.next() {
+    // 打开命令行指定的文件, 文件不存在时得到空缓冲区
+    pub fn new(file: Option<String>) -> Self {
+        match file {
             None => {
                 eprintln!("No file provided.");
                 Self {
                     row_contents: Vec::new(),
                     filename: None,
+                    disk_mtime: None,
+                    hex_view: false,
+                    read_only: false,
                     search_term: None,
                     search_matches: Vec::new(),
                 }
@@ -29,6 +36,9 @@ impl EditorRows {
                     Self {
                         row_contents: Vec::new(),
                         filename: None,
+                        disk_mtime: None,
+                        hex_view: false,
+                        read_only: false,
                         search_term: None,
                         search_matches: Vec::new(),
                     }
@@ -40,15 +50,50 @@ impl EditorRows {
     }
 
     pub fn from_file(file: PathBuf) -> Self {
-        let file_content = fs::read_to_string(&file).expect("Unable to read file");
-        Self {
+        Self::open(file).expect("Unable to read file")
+    }
+
+    // 读取文件, 读取失败时返回错误而不是退出, 用于在编辑器里打开其它文件
+    // 含有 NUL 字节或者不是 UTF-8 的文件当作二进制, 以十六进制转储只读显示
+    pub fn open(file: PathBuf) -> std::io::Result<Self> {
+        let (row_contents, hex_view) = read_rows(&file)?;
+        Ok(Self {
+            disk_mtime: file_mtime(&file),
             filename: Some(file),
-            row_contents: file_content
-                .lines()
-                .map(|it| Box::new(it.to_string()))
-                .collect(),
+            row_contents,
+            hex_view,
+            read_only: false,
             search_term: None,
             search_matches: Vec::new(),
+        })
+    }
+
+    // :help 的只读缓冲区, 不绑定文件
+    pub fn help(lines: Vec<String>) -> Self {
+        Self {
+            row_contents: lines,
+            filename: None,
+            disk_mtime: None,
+            hex_view: false,
+            read_only: true,
+            search_term: None,
+            search_matches: Vec::new(),
+        }
+    }
+
+    // 内容和磁盘上的文件不同(没有文件名时只要有内容就算修改过)
+    // 十六进制转储反正写不回去, 帮助文档不能修改, 都不算修改
+    pub fn is_modified(&self) -> bool {
+        if self.hex_view || self.read_only {
+            return false;
+        }
+        match &self.filename {
+            Some(path) => fs::read_to_string(path).map_or(true, |content| {
+                !content
+                    .lines()
+                    .eq(self.row_contents.iter().map(String::as_str))
+            }),
+            None => !self.row_contents.is_empty(),
         }
     }
 
@@ -80,19 +125,20 @@ impl EditorRows {
             let mut col_idx = 0;
 
             // 安全地查找所有匹配项
-            while let Some(pos) = match row[col_idx..].find(query) {
-                Some(p) => Some(p),
-                None => None, // 处理可能的None值 (同下面一样的道理)
-            } {
+            while let Some(pos) = row[col_idx..].find(query) {
                 let match_pos = col_idx + pos;
-                // 保存匹配项的位置和长度
-                self.search_matches.push((row_idx, match_pos, query.len()));
+                // 保存匹配项的位置和长度(按字符计算)
+                self.search_matches.push((
+                    row_idx,
+                    row[..match_pos].chars().count(),
+                    query.chars().count(),
+                ));
 
                 // 防止无限循环，确保col_idx会前进(问题出自这里, 举个例子:如果你跳转到最后一行,只有一个不匹配的字符,就会陷入无限循环)
-                if match_pos + 1 <= row.len() {
-                    col_idx = match_pos + 1;
-                } else {
-                    break;
+                // 按整个字符前进, 避免落在多字节字符中间
+                match row[match_pos..].chars().next() {
+                    Some(ch) => col_idx = match_pos + ch.len_utf8(),
+                    None => break,
                 }
             }
         }
@@ -151,22 +197,28 @@ impl EditorRows {
         }
     }
 
+    // 行的长度(字符数); 光标列和下面各个方法的列号都按字符计算, 不是字节
+    pub fn row_len(&self, at: usize) -> usize {
+        self.get_row(at).chars().count()
+    }
+
     // 在指定位置插入字符
     pub fn insert_char(&mut self, at_row: usize, at_col: usize, ch: char) {
         // 如果行号超出范围，添加新行直到达到要求的行
         while at_row >= self.row_contents.len() {
-            self.row_contents.push(Box::new(String::new()));
+            self.row_contents.push(String::new());
         }
 
         // 获取指定行并插入字符
         let row = &mut self.row_contents[at_row];
-        if at_col > row.len() {
+        let len = row.chars().count();
+        if at_col > len {
             // 如果列号超出范围，填充空格
-            row.push_str(&" ".repeat(at_col - row.len()));
+            row.push_str(&" ".repeat(at_col - len));
             row.push(ch);
         } else {
             // 否则在指定位置插入
-            row.insert(at_col, ch);
+            row.insert(byte_index(row, at_col), ch);
         }
     }
 
@@ -178,7 +230,7 @@ impl EditorRows {
         }
 
         // 直接在原始数据上操作，不要克隆
-        if at_col >= self.row_contents[at_row].len() {
+        if at_col >= self.row_len(at_row) {
             // 在行尾删除，需要与下一行合并
             if at_row < self.row_contents.len() - 1 {
                 // 获取下一行内容并移除
@@ -187,11 +239,12 @@ impl EditorRows {
                 self.row_contents[at_row].push_str(&next_row);
                 return true;
             }
-            return false;
+            false
         } else {
             // 删除指定位置的字符
-            self.row_contents[at_row].remove(at_col);
-            return true;
+            let row = &mut self.row_contents[at_row];
+            row.remove(byte_index(row, at_col));
+            true
         }
     }
 
@@ -204,53 +257,302 @@ impl EditorRows {
 
         // 直接在原始数据上操作，不要克隆
         self.row_contents.remove(at_row);
-        return true;
+        true
+    }
+
+    // 删除 [start, end] 范围内的行
+    pub fn delete_lines(&mut self, start: usize, end: usize) {
+        if start >= self.row_contents.len() {
+            return;
+        }
+        let end = end.min(self.row_contents.len() - 1);
+        self.row_contents.drain(start..=end);
+    }
+
+    // 复制 [start, end] 范围内的行
+    pub fn lines(&self, start: usize, end: usize) -> Vec<String> {
+        let end = end.min(self.row_contents.len().saturating_sub(1));
+        self.row_contents
+            .get(start..=end)
+            .map_or_else(Vec::new, <[String]>::to_vec)
+    }
+
+    // 在 at 行之前插入多行, at 等于行数时追加到末尾
+    pub fn insert_lines(&mut self, at: usize, lines: Vec<String>) {
+        let at = at.min(self.row_contents.len());
+        self.row_contents.splice(at..at, lines);
+    }
+
+    // 注释或取消注释 [start, end] 行, comment_string 形如 "// %s"
+    // 所有非空行都已经注释时取消注释, 否则给每个非空行加上注释, 注释符号按最小缩进对齐
+    pub fn toggle_comment(&mut self, start: usize, end: usize, comment_string: &str) {
+        let (prefix, suffix) = comment_string
+            .split_once("%s")
+            .unwrap_or((comment_string, ""));
+        let (prefix, suffix) = (prefix.trim_end(), suffix.trim_start());
+        let end = end.min(self.row_contents.len().saturating_sub(1));
+        let Some(rows) = self.row_contents.get_mut(start..=end) else {
+            return;
+        };
+
+        let is_commented = |row: &str| {
+            let text = row.trim();
+            text.len() >= prefix.len() + suffix.len()
+                && text.starts_with(prefix)
+                && text.ends_with(suffix)
+        };
+        let non_blank = |row: &&mut String| !row.trim().is_empty();
+
+        if rows.iter().any(|row| !row.trim().is_empty())
+            && rows
+                .iter()
+                .filter(|row| !row.trim().is_empty())
+                .all(|row| is_commented(row))
+        {
+            for row in rows.iter_mut().filter(non_blank) {
+                let indent = row.len() - row.trim_start().len();
+                let text = row.trim();
+                let inner = &text[prefix.len()..text.len() - suffix.len()];
+                let inner = inner.strip_prefix(' ').unwrap_or(inner);
+                let inner = if suffix.is_empty() {
+                    inner
+                } else {
+                    inner.strip_suffix(' ').unwrap_or(inner)
+                };
+                *row = format!("{}{}", &row[..indent], inner);
+            }
+        } else {
+            let indent = rows
+                .iter()
+                .filter(|row| !row.trim().is_empty())
+                .map(|row| row.len() - row.trim_start().len())
+                .min()
+                .unwrap_or(0);
+            for row in rows.iter_mut().filter(non_blank) {
+                let (head, text) = row.split_at(indent);
+                *row = if suffix.is_empty() {
+                    format!("{}{} {}", head, prefix, text)
+                } else {
+                    format!("{}{} {} {}", head, prefix, text, suffix)
+                };
+            }
+        }
+    }
+
+    // 在 [start, end] 范围内把 pattern 替换为 replacement, global 为 false 时每行只替换第一个
+    // 返回发生替换的行数
+    pub fn substitute(
+        &mut self,
+        start: usize,
+        end: usize,
+        pattern: &str,
+        replacement: &str,
+        global: bool,
+    ) -> usize {
+        if pattern.is_empty() {
+            return 0;
+        }
+
+        let mut changed = 0;
+        for row in self.row_contents.iter_mut().take(end + 1).skip(start) {
+            if !row.contains(pattern) {
+                continue;
+            }
+            *row = if global {
+                row.replace(pattern, replacement)
+            } else {
+                row.replacen(pattern, replacement, 1)
+            };
+            changed += 1;
+        }
+        changed
     }
 
     // 处理回车键，分割行
     pub fn insert_newline(&mut self, at_row: usize, at_col: usize) {
         // 如果行号超出范围，添加新行
         while at_row >= self.row_contents.len() {
-            self.row_contents.push(Box::new(String::new()));
+            self.row_contents.push(String::new());
         }
 
         // 获取当前行
         let current_row = &mut self.row_contents[at_row];
 
         // 创建新行
-        let new_row = if at_col >= current_row.len() {
+        let at = byte_index(current_row, at_col);
+        let new_row = if at >= current_row.len() {
             // 如果在行尾，创建空行
-            Box::new(String::new())
+            String::new()
         } else {
             // 否则分割当前行
-            let remainder = current_row[at_col..].to_string();
-            current_row.truncate(at_col);
-            Box::new(remainder)
+            let remainder = current_row[at..].to_string();
+            current_row.truncate(at);
+            remainder
         };
 
         // 插入新行
         self.row_contents.insert(at_row + 1, new_row);
     }
 
+    // 行首空白占的列数(制表符按 tabstop 计算)
+    pub fn indent_width(&self, at_row: usize, tabstop: usize) -> usize {
+        let mut width = 0;
+        for ch in self.get_row(at_row).chars() {
+            match ch {
+                ' ' => width += 1,
+                '\t' => width += tabstop - width % tabstop,
+                _ => break,
+            }
+        }
+        width
+    }
+
+    // indent 为 true 时把 [start, end] 行的缩进增加 shiftwidth, 否则减少; 空行保持不变
+    pub fn shift_lines(&mut self, start: usize, end: usize, indent: bool, options: &Options) {
+        let end = end.min(self.row_contents.len().saturating_sub(1));
+        for at_row in start..=end {
+            if at_row >= self.row_contents.len() || self.row_contents[at_row].trim().is_empty() {
+                continue;
+            }
+
+            let width = self.indent_width(at_row, options.tabstop);
+            let new_width = if indent {
+                width + options.shiftwidth
+            } else {
+                width.saturating_sub(options.shiftwidth)
+            };
+
+            let row = &mut self.row_contents[at_row];
+            let text = row.trim_start_matches([' ', '\t']).to_string();
+            *row = options.indent_string(new_width) + &text;
+        }
+    }
+
+    // 文件在外部被修改过(修改时间和最近一次读写时不同)
+    // 文件被删除或者读不到修改时间时不算修改
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.filename, self.disk_mtime) {
+            (Some(path), Some(known)) => file_mtime(path).is_some_and(|mtime| mtime != known),
+            _ => false,
+        }
+    }
+
+    // 保留本地内容, 不再为这次外部修改提示
+    pub fn accept_disk_version(&mut self) {
+        if let Some(path) = &self.filename {
+            self.disk_mtime = file_mtime(path);
+        }
+    }
+
+    // 丢弃本地修改, 重新读取文件
+    pub fn reload(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.filename else {
+            return Ok(());
+        };
+        (self.row_contents, self.hex_view) = read_rows(path)?;
+        self.disk_mtime = file_mtime(path);
+        if let Some(term) = self.search_term.clone() {
+            self.search(&term);
+        }
+        Ok(())
+    }
+
     // 保存文件
-    pub fn save_file(&self) -> std::io::Result<()> {
+    pub fn save_file(&mut self) -> std::io::Result<()> {
         match &self.filename {
-            Some(path) => {
-                // 将所有行连接成一个字符串，使用换行符分隔
-                let content = self
-                    .row_contents
-                    .iter()
-                    .map(|row| row.as_str())
-                    .collect::<Vec<&str>>()
-                    .join("\n");
-
-                // 写入文件
-                std::fs::write(path, content)
-            }
+            Some(path) => self.write_to(&path.clone(), false),
             None => Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 "No filename specified",
             )),
         }
     }
+
+    // 把内容写到指定路径(:w <file>), 不改变当前缓冲区绑定的文件
+    // create_dirs 为 true 时先创建不存在的父目录
+    pub fn write_to(&mut self, path: &Path, create_dirs: bool) -> std::io::Result<()> {
+        // 把转储写回去会毁掉原来的二进制内容; 写到别的文件(保存转储)可以
+        if self.hex_view && self.filename.as_deref() == Some(path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "E45: binary file is shown as a read-only hex dump",
+            ));
+        }
+        if create_dirs && let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // 将所有行连接成一个字符串，使用换行符分隔
+        let content = self
+            .row_contents
+            .iter()
+            .map(|row| row.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        // 写入文件
+        fs::write(path, content)?;
+        if self.filename.as_deref() == Some(path) {
+            self.disk_mtime = file_mtime(path);
+        }
+        Ok(())
+    }
+
+    // 另存为(:saveas <file>), 写入成功后缓冲区改为绑定新文件
+    pub fn save_as(&mut self, path: PathBuf, create_dirs: bool) -> std::io::Result<()> {
+        self.write_to(&path, create_dirs)?;
+        self.disk_mtime = file_mtime(&path);
+        self.filename = Some(path);
+        Ok(())
+    }
+}
+
+// 读取文件的行, 二进制文件返回十六进制转储, 第二项表示是否是二进制
+fn read_rows(path: &Path) -> std::io::Result<(Vec<String>, bool)> {
+    let bytes = fs::read(path)?;
+    match String::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => {
+            Ok((text.lines().map(|it| it.to_string()).collect(), false))
+        }
+        Ok(text) => Ok((hex_dump(text.as_bytes()), true)),
+        Err(err) => Ok((hex_dump(err.as_bytes()), true)),
+    }
+}
+
+// 和 xxd 一样每行 16 字节: `00000010: 0200 3e00 0100 0000  ..>.....`
+fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut line = format!("{:08x}:", i * 16);
+            for (j, byte) in chunk.iter().enumerate() {
+                if j % 2 == 0 {
+                    line.push(' ');
+                }
+                line.push_str(&format!("{:02x}", byte));
+            }
+            // 最后一行不满 16 字节时补齐, 让右边的字符列对齐
+            let missing = 16 - chunk.len();
+            line.push_str(&" ".repeat(missing * 2 + missing / 2));
+            line.push_str("  ");
+            line.extend(chunk.iter().map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            }));
+            line
+        })
+        .collect()
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// 把字符列号转换成字节下标, 超出行尾时返回行的字节长度
+pub fn byte_index(row: &str, col: usize) -> usize {
+    row.char_indices().nth(col).map_or(row.len(), |(i, _)| i)
 }
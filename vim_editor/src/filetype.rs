@@ -0,0 +1,112 @@
+use std::path::Path;
+
+// 按文件名识别文件类型, 以及每种类型的注释格式
+// 注释格式和 vim 的 'commentstring' 一样, `%s` 的位置是被注释的内容
+struct FileType {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str], // 没有扩展名的特殊文件, 例如 Makefile
+    comment_string: &'static str,
+}
+
+const FILETYPES: &[FileType] = &[
+    FileType {
+        name: "rust",
+        extensions: &["rs"],
+        file_names: &[],
+        comment_string: "// %s",
+    },
+    FileType {
+        name: "c",
+        extensions: &["c", "h", "cpp", "hpp", "cc", "java", "js", "ts", "go"],
+        file_names: &[],
+        comment_string: "// %s",
+    },
+    FileType {
+        name: "python",
+        extensions: &["py"],
+        file_names: &[],
+        comment_string: "# %s",
+    },
+    FileType {
+        name: "sh",
+        extensions: &["sh", "bash", "zsh"],
+        file_names: &[".bashrc", ".zshrc", ".profile"],
+        comment_string: "# %s",
+    },
+    FileType {
+        name: "toml",
+        extensions: &["toml"],
+        file_names: &[],
+        comment_string: "# %s",
+    },
+    FileType {
+        name: "yaml",
+        extensions: &["yml", "yaml"],
+        file_names: &[],
+        comment_string: "# %s",
+    },
+    FileType {
+        name: "make",
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        comment_string: "# %s",
+    },
+    FileType {
+        name: "lua",
+        extensions: &["lua"],
+        file_names: &[],
+        comment_string: "-- %s",
+    },
+    FileType {
+        name: "sql",
+        extensions: &["sql"],
+        file_names: &[],
+        comment_string: "-- %s",
+    },
+    FileType {
+        name: "vim",
+        extensions: &["vim"],
+        file_names: &[".vimrc"],
+        comment_string: "\" %s",
+    },
+    FileType {
+        name: "css",
+        extensions: &["css"],
+        file_names: &[],
+        comment_string: "/* %s */",
+    },
+    FileType {
+        name: "html",
+        extensions: &["html", "htm", "xml"],
+        file_names: &[],
+        comment_string: "<!-- %s -->",
+    },
+    FileType {
+        name: "markdown",
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        comment_string: "<!-- %s -->",
+    },
+];
+
+// 识别文件类型, 不认识时返回 None
+pub fn detect(path: &Path) -> Option<&'static str> {
+    let file_name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    FILETYPES
+        .iter()
+        .find(|ft| {
+            ft.file_names.contains(&file_name)
+                || extension.is_some_and(|ext| ft.extensions.contains(&ext))
+        })
+        .map(|ft| ft.name)
+}
+
+// 文件类型的注释格式, 例如 rust 的 "// %s"
+pub fn comment_string(filetype: &str) -> Option<&'static str> {
+    FILETYPES
+        .iter()
+        .find(|ft| ft.name == filetype)
+        .map(|ft| ft.comment_string)
+}
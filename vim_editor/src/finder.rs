@@ -0,0 +1,235 @@
+use crate::output::Overlay;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Ctrl-p 模糊查找器: 列出项目中的文件, 随输入按模糊匹配的得分排序
+
+// 最多收集这么多文件, 避免在很大的目录里卡住
+const MAX_FILES: usize = 20_000;
+// 总是跳过的目录, 即使没有写进 .gitignore
+const ALWAYS_IGNORED: &[&str] = &["target", "node_modules"];
+
+pub struct Finder {
+    files: Vec<String>, // 相对于根目录的路径, 用 '/' 分隔
+    query: String,
+    matches: Vec<usize>, // 匹配的文件在 files 中的下标, 得分高的在前
+    selected: usize,
+}
+
+impl Finder {
+    // 遍历 root 下的文件, 跳过隐藏文件和 .gitignore 忽略的文件
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let mut files = Vec::new();
+        walk(root, "", &mut Vec::new(), &mut files)?;
+        files.sort();
+        let mut finder = Self {
+            files,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        finder.update_matches();
+        Ok(finder)
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.query.push(ch);
+        self.update_matches();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|&index| self.files[index].as_str())
+    }
+
+    // 生成最多 rows 行的列表, 保证选中的一项可见
+    pub fn overlay(&self, rows: usize) -> Overlay {
+        let rows = rows.max(1);
+        let start = (self.selected + 1).saturating_sub(rows);
+        Overlay {
+            prompt: format!(">>> {}", self.query),
+            items: self
+                .matches
+                .iter()
+                .skip(start)
+                .take(rows)
+                .map(|&index| self.files[index].clone())
+                .collect(),
+            selected: self.selected - start,
+            status: format!("{}/{}", self.matches.len(), self.files.len()),
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<(i32, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| fuzzy_score(&self.query, file).map(|score| (score, index)))
+            .collect();
+        // 得分相同的按路径长度, 短的在前
+        scored.sort_by_key(|&(score, index)| (-score, self.files[index].len(), index));
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+}
+
+// 模糊匹配: query 的字符按顺序出现在 candidate 中即为匹配
+// 连续匹配、单词开头和文件名部分的匹配加分, 中间跳过的字符扣分
+// query 全是小写时忽略大小写(smartcase)
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let normalize = |ch: char| {
+        if ignore_case {
+            ch.to_ascii_lowercase()
+        } else {
+            ch
+        }
+    };
+    let chars: Vec<char> = candidate.chars().collect();
+    let name_start = chars
+        .iter()
+        .rposition(|&ch| ch == '/')
+        .map_or(0, |slash| slash + 1);
+
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().map(normalize) {
+        let found = (position..chars.len()).find(|&i| normalize(chars[i]) == wanted)?;
+        score += 10;
+        match previous {
+            Some(prev) if prev + 1 == found => score += 15,
+            Some(prev) => score -= (found - prev - 1).min(10) as i32,
+            None => score -= found.min(10) as i32 / 2,
+        }
+        if found == 0 || matches!(chars[found - 1], '/' | '_' | '-' | '.' | ' ') {
+            score += 10;
+        }
+        if found >= name_start {
+            score += 5;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+// .gitignore 中的一条规则
+struct IgnoreRule {
+    base: String, // 规则所在目录(相对路径), 带 '/' 的规则相对它匹配
+    pattern: String,
+    anchored: bool, // 规则中间或开头有 '/', 只匹配相对 base 的完整路径
+    dir_only: bool, // 以 '/' 结尾, 只匹配目录
+}
+
+impl IgnoreRule {
+    // 解析一行; 空行、注释和 `!` 开头的反向规则不处理
+    fn parse(base: &str, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            return None;
+        }
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        let anchored = line.contains('/');
+        Some(Self {
+            base: base.to_string(),
+            pattern: line.trim_start_matches('/').to_string(),
+            anchored,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if !self.anchored {
+            return glob_match(&self.pattern, name);
+        }
+        path.strip_prefix(&self.base)
+            .is_some_and(|relative| glob_match(&self.pattern, relative))
+    }
+}
+
+// 递归收集文件, prefix 是 dir 相对根目录的路径(空或以 '/' 结尾)
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    rules: &mut Vec<IgnoreRule>,
+    files: &mut Vec<String>,
+) -> io::Result<()> {
+    let inherited = rules.len();
+    if let Ok(content) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(
+            content
+                .lines()
+                .filter_map(|line| IgnoreRule::parse(prefix, line)),
+        );
+    }
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+        let path = format!("{}{}", prefix, name);
+        if name.starts_with('.')
+            || (is_dir && ALWAYS_IGNORED.contains(&name.as_str()))
+            || rules.iter().any(|rule| rule.matches(&path, &name, is_dir))
+        {
+            continue;
+        }
+        if is_dir {
+            // 读不了的子目录直接跳过
+            let _ = walk(&entry.path(), &format!("{}/", path), rules, files);
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+
+    rules.truncate(inherited);
+    Ok(())
+}
+
+// 简单的 glob: `*` 匹配除 '/' 外的任意字符, `**` 也匹配 '/', `?` 匹配一个字符
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            ['*', '*', rest @ ..] => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            ['*', rest @ ..] => (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != '/')
+                .any(|i| matches(rest, &text[i..])),
+            ['?', rest @ ..] => {
+                text.first().is_some_and(|&ch| ch != '/') && matches(rest, &text[1..])
+            }
+            [ch, rest @ ..] => text.first() == Some(ch) && matches(rest, &text[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
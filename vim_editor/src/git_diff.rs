@@ -0,0 +1,207 @@
+use crate::gutter::GutterMarker;
+use crossterm::style::Color;
+use std::path::Path;
+use std::process::Command;
+
+// 和 HEAD 中的版本比较, 在标记栏显示新增、修改和删除的行(类似 vim-gitgutter)
+
+// 标记栏中这些标记的来源名, 每次刷新整体替换
+pub const SOURCE: &str = "git";
+
+// 一行在差异中的状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    Removed,      // 这一行之后有行被删除
+    RemovedAbove, // 文件开头有行被删除, 标在第一行
+}
+
+impl LineChange {
+    pub fn marker(self) -> GutterMarker {
+        match self {
+            LineChange::Added => GutterMarker::new('+', Color::Green),
+            LineChange::Modified => GutterMarker::new('~', Color::Yellow),
+            LineChange::Removed => GutterMarker::new('_', Color::Red),
+            LineChange::RemovedAbove => GutterMarker::new('‾', Color::Red),
+        }
+    }
+}
+
+// 读取文件在 HEAD 中的内容; 不在 git 仓库中、文件未被跟踪或者没有 git 时返回 None
+pub fn head_version(path: &Path) -> Option<String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name()?.to_str()?;
+    // `HEAD:./name` 中的路径相对于 -C 指定的目录
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["show", &format!("HEAD:./{}", name)])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+// 当前缓冲区相对 HEAD 的标记; 文件不在 git 中时没有标记
+pub fn markers(path: &Path, lines: &[String]) -> Vec<(usize, GutterMarker)> {
+    let Some(head) = head_version(path) else {
+        return Vec::new();
+    };
+    let old: Vec<&str> = head.lines().collect();
+    let new: Vec<&str> = lines.iter().map(String::as_str).collect();
+    line_changes(&old, &new)
+        .into_iter()
+        .map(|(line, change)| (line, change.marker()))
+        .collect()
+}
+
+// 比较两个版本, 返回新版本中每个有变化的行
+// 一段连续的删除后面紧跟插入时, 前面对应的行算修改, 多出的行算新增
+pub fn line_changes(old: &[&str], new: &[&str]) -> Vec<(usize, LineChange)> {
+    let mut changes = Vec::new();
+    let (mut removed, mut added) = (0, Vec::new());
+    let mut flush = |removed: &mut usize, added: &mut Vec<usize>, next_line: usize| {
+        for (i, &line) in added.iter().enumerate() {
+            let change = if i < *removed {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            };
+            changes.push((line, change));
+        }
+        // 只有删除没有插入时, 标在删除位置的上一行
+        if *removed > 0 && added.is_empty() {
+            changes.push(match next_line {
+                0 => (0, LineChange::RemovedAbove),
+                line => (line - 1, LineChange::Removed),
+            });
+        }
+        *removed = 0;
+        added.clear();
+    };
+
+    let mut line = 0;
+    for edit in diff(old, new) {
+        match edit {
+            Edit::Keep => {
+                flush(&mut removed, &mut added, line);
+                line += 1;
+            }
+            Edit::Remove => removed += 1,
+            Edit::Insert => {
+                added.push(line);
+                line += 1;
+            }
+        }
+    }
+    flush(&mut removed, &mut added, line);
+    changes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Keep,
+    Remove,
+    Insert,
+}
+
+// 编辑距离超过这个值时不再细分, 把中间整段当作替换, 避免大文件差异过多时卡住
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+// 得到把 old 变成 new 的编辑序列; 先去掉相同的开头和结尾, 中间用 Myers 算法
+fn diff(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut edits = vec![Edit::Keep; prefix];
+    match myers(old_middle, new_middle) {
+        Some(middle) => edits.extend(middle),
+        None => {
+            edits.extend(old_middle.iter().map(|_| Edit::Remove));
+            edits.extend(new_middle.iter().map(|_| Edit::Insert));
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    edits
+}
+
+// Myers 差分算法, 得到最短编辑序列; 编辑距离超过 MAX_EDIT_DISTANCE 时返回 None
+// 每一轮只保存用得到的对角线 [-d-1, d+1], 内存随编辑距离而不是文件长度增长
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDIT_DISTANCE) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d as usize == MAX_EDIT_DISTANCE {
+            return None;
+        }
+    }
+
+    // 从终点沿着记录的路径往回走
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, window) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| window[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Remove
+            });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    Some(edits)
+}
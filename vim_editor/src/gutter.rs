@@ -0,0 +1,115 @@
+use crate::editor_rows::EditorRows;
+use crossterm::style::{self, Color};
+use std::collections::BTreeMap;
+
+// 左侧标记栏的宽度: 一个标记字符加一个空格
+pub const GUTTER_WIDTH: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GutterMarker {
+    pub symbol: char,
+    pub color: Color,
+}
+
+impl GutterMarker {
+    pub fn new(symbol: char, color: Color) -> Self {
+        Self { symbol, color }
+    }
+}
+
+// 按行提供标记的功能(搜索、TODO、以后的 linter 等)实现这个 trait,
+// 注册到 Output 之后渲染时会自动调用, 不需要改动绘制代码
+pub trait GutterProvider {
+    fn marker(&self, rows: &EditorRows, line: usize) -> Option<GutterMarker>;
+}
+
+// 显示当前行的搜索匹配数量, 超过 9 个显示 '+'
+pub struct SearchMatchCount;
+
+impl GutterProvider for SearchMatchCount {
+    fn marker(&self, rows: &EditorRows, line: usize) -> Option<GutterMarker> {
+        let count = rows
+            .search_matches
+            .iter()
+            .filter(|&&(row, _, _)| row == line)
+            .count();
+        match count {
+            0 => None,
+            1..=9 => Some(GutterMarker::new(
+                char::from_digit(count as u32, 10).unwrap(),
+                Color::Yellow,
+            )),
+            _ => Some(GutterMarker::new('+', Color::Yellow)),
+        }
+    }
+}
+
+// 标出包含 TODO / FIXME 的行
+pub struct TodoMarker;
+
+impl GutterProvider for TodoMarker {
+    fn marker(&self, rows: &EditorRows, line: usize) -> Option<GutterMarker> {
+        let row = rows.get_row(line);
+        if row.contains("TODO") || row.contains("FIXME") {
+            Some(GutterMarker::new('!', Color::Cyan))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Gutter {
+    providers: Vec<Box<dyn GutterProvider>>,
+    // 外部直接设置的标记, 按来源分组(例如 "lint"), 方便整体替换
+    markers: BTreeMap<String, BTreeMap<usize, GutterMarker>>,
+}
+
+impl Gutter {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            markers: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_provider(&mut self, provider: Box<dyn GutterProvider>) {
+        self.providers.push(provider);
+    }
+
+    // 替换某个来源的全部标记, 例如 git 差异
+    pub fn set_markers(&mut self, source: &str, markers: Vec<(usize, GutterMarker)>) {
+        self.markers
+            .insert(source.to_string(), markers.into_iter().collect());
+    }
+
+    // 某一行的标记: 直接设置的标记优先, 其次按注册顺序询问 provider
+    pub fn marker_for(&self, rows: &EditorRows, line: usize) -> Option<GutterMarker> {
+        self.markers
+            .values()
+            .find_map(|markers| markers.get(&line).copied())
+            .or_else(|| {
+                self.providers
+                    .iter()
+                    .find_map(|provider| provider.marker(rows, line))
+            })
+    }
+
+    // 只有缓冲区中至少有一行带标记时才显示标记栏
+    pub fn width(&self, rows: &EditorRows) -> usize {
+        let visible = (0..rows.number_of_rows()).any(|line| self.marker_for(rows, line).is_some());
+        if visible { GUTTER_WIDTH } else { 0 }
+    }
+
+    // 渲染一行的标记栏内容(含颜色控制符)
+    pub fn render(&self, rows: &EditorRows, line: usize) -> String {
+        match self.marker_for(rows, line) {
+            Some(marker) => format!(
+                "{}{}{} ",
+                style::SetForegroundColor(marker.color),
+                marker.symbol,
+                style::ResetColor
+            ),
+            None => " ".repeat(GUTTER_WIDTH),
+        }
+    }
+}
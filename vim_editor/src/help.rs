@@ -0,0 +1,86 @@
+// :help 显示的内置文档, 写法和 vim 的帮助文件一样:
+// `*name*` 定义一个主题, `|name|` 链接到主题, 在链接上按 Ctrl-] 跳过去
+const HELP_TEXT: &str = include_str!("help.txt");
+
+// 帮助文档的所有行, 用来填充只读的帮助缓冲区
+pub fn lines() -> Vec<String> {
+    HELP_TEXT.lines().map(String::from).collect()
+}
+
+// 主题所在的行. 依次尝试: 同名主题、同名命令(:w)、同名选项('sw')、名字里含有它的第一个主题
+pub fn find_topic(topic: &str) -> Option<usize> {
+    let tags = tags();
+    [
+        topic.to_string(),
+        format!(":{}", topic),
+        format!("'{}'", topic),
+    ]
+    .iter()
+    .find_map(|name| tags.iter().find(|(tag, _)| tag == name))
+    .or_else(|| tags.iter().find(|(tag, _)| tag.contains(topic)))
+    .map(|&(_, line)| line)
+}
+
+// 光标(第 col 个字符)下的主题名: 在 |name| 或 *name* 上时取其中的名字, 否则取光标下的词
+pub fn tag_at(line: &str, col: usize) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if col >= chars.len() {
+        return None;
+    }
+    for delimiter in ['|', '*'] {
+        if let Some((start, end)) = spans(&chars, delimiter)
+            .into_iter()
+            .find(|&(start, end)| start <= col && col <= end)
+        {
+            return Some(chars[start + 1..end].iter().collect());
+        }
+    }
+
+    if chars[col].is_whitespace() {
+        return None;
+    }
+    let start = chars[..col]
+        .iter()
+        .rposition(|ch| ch.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let end = chars[col..]
+        .iter()
+        .position(|ch| ch.is_whitespace())
+        .map_or(chars.len(), |i| col + i);
+    let word: String = chars[start..end].iter().collect();
+    let word = word.trim_matches(|ch| matches!(ch, ',' | '.' | '(' | ')' | '"'));
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+// 所有 *name* 定义的主题和所在的行
+fn tags() -> Vec<(String, usize)> {
+    let mut tags = Vec::new();
+    for (number, line) in HELP_TEXT.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        for (start, end) in spans(&chars, '*') {
+            tags.push((chars[start + 1..end].iter().collect(), number));
+        }
+    }
+    tags
+}
+
+// 一行中用 delimiter 括起来的名字的位置(两个分隔符的下标), 名字不能为空也不能含有空白
+fn spans(chars: &[char], delimiter: char) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == delimiter
+            && let Some(len) = chars[i + 1..].iter().position(|&ch| ch == delimiter)
+            && len > 0
+            && !chars[i + 1..i + 1 + len]
+                .iter()
+                .any(|ch| ch.is_whitespace())
+        {
+            spans.push((i, i + 1 + len));
+            i += len + 2;
+        } else {
+            i += 1;
+        }
+    }
+    spans
+}
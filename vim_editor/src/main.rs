@@ -1,20 +1,42 @@
+mod abbrev;
 mod cleanup;
+mod config;
 mod constants;
 mod cursor;
+mod digraph;
 mod editor;
 mod editor_contents;
 mod editor_rows;
+mod filetype;
+mod finder;
+mod git_diff;
+mod gutter;
+mod help;
+mod options;
 mod output;
+mod quickfix;
+mod range;
 mod reader;
+mod register;
+mod session;
+mod spell;
+mod undo;
 
-use crossterm::terminal;
 use editor::Editor;
 
 fn main() -> crossterm::Result<()> {
-    let _clean = cleanup::CleanUp;
-    terminal::enable_raw_mode()?;
+    let _clean = cleanup::CleanUp::new()?;
 
-    let mut editor = Editor::new();
+    // vim_editor [file] 或 vim_editor -S [session]
+    let mut args = std::env::args().skip(1);
+    let mut editor = match args.next() {
+        Some(flag) if flag == "-S" => Editor::from_session(
+            &args
+                .next()
+                .unwrap_or_else(|| session::DEFAULT_SESSION_FILE.to_string()),
+        ),
+        file => Editor::new(file),
+    };
     while editor.run()? {}
 
     Ok(())
@@ -0,0 +1,111 @@
+// 编辑器选项, 通过 :set 修改
+pub struct Options {
+    pub shiftwidth: usize, // >> / << 每次移动的宽度
+    pub tabstop: usize,    // 一个制表符占几列
+    pub expandtab: bool,   // 缩进时用空格代替制表符
+    pub makeprg: String,   // :make 执行的命令
+    pub spell: bool,       // 给拼错的单词加下划线
+    pub undofile: bool,    // 写入文件时保存撤销历史, 再打开时读回来
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            shiftwidth: 4,
+            tabstop: 8,
+            expandtab: true,
+            makeprg: "cargo build".to_string(),
+            spell: false,
+            undofile: true,
+        }
+    }
+
+    // 处理 :set 的参数, 例如 `sw=2`、`expandtab`、`noet`、`makeprg=make\ -j4`
+    pub fn set(&mut self, arg: &str) -> Result<(), String> {
+        match arg.split_once('=') {
+            // 字符串选项中的空格写成 `\ `, 和 vim 一样
+            Some(("makeprg" | "mp", value)) => self.makeprg = value.replace("\\ ", " "),
+            Some((name, value)) => {
+                let value = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| format!("E521: Number required after =: {}", arg))?;
+                match name {
+                    "shiftwidth" | "sw" => self.shiftwidth = value,
+                    "tabstop" | "ts" => self.tabstop = value,
+                    _ => return Err(format!("E518: Unknown option: {}", name)),
+                }
+            }
+            None => match arg {
+                "expandtab" | "et" => self.expandtab = true,
+                "noexpandtab" | "noet" => self.expandtab = false,
+                "spell" => self.spell = true,
+                "nospell" => self.spell = false,
+                "undofile" | "udf" => self.undofile = true,
+                "noundofile" | "noudf" => self.undofile = false,
+                _ => return Err(format!("E518: Unknown option: {}", arg)),
+            },
+        }
+        Ok(())
+    }
+
+    // 当前选项对应的 :set 参数, 用于保存会话
+    pub fn to_args(&self) -> Vec<String> {
+        vec![
+            format!("shiftwidth={}", self.shiftwidth),
+            format!("tabstop={}", self.tabstop),
+            if self.expandtab {
+                "expandtab"
+            } else {
+                "noexpandtab"
+            }
+            .to_string(),
+            format!("makeprg={}", self.makeprg.replace(' ', "\\ ")),
+            if self.spell { "spell" } else { "nospell" }.to_string(),
+            if self.undofile {
+                "undofile"
+            } else {
+                "noundofile"
+            }
+            .to_string(),
+        ]
+    }
+
+    // 把 :set 后面的内容按空格拆成参数, `\ ` 转义的空格不拆开
+    pub fn split_args(text: &str) -> Vec<&str> {
+        let mut args = Vec::new();
+        let mut start = None;
+        let mut escaped = false;
+        for (i, ch) in text.char_indices() {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => {
+                    escaped = true;
+                    start.get_or_insert(i);
+                }
+                ch if ch.is_whitespace() => {
+                    if let Some(start) = start.take() {
+                        args.push(&text[start..i]);
+                    }
+                }
+                _ => {
+                    start.get_or_insert(i);
+                }
+            }
+        }
+        if let Some(start) = start {
+            args.push(&text[start..]);
+        }
+        args
+    }
+
+    // 生成指定宽度的缩进, 不展开制表符时尽量用制表符填充
+    pub fn indent_string(&self, width: usize) -> String {
+        if self.expandtab {
+            " ".repeat(width)
+        } else {
+            "\t".repeat(width / self.tabstop) + &" ".repeat(width % self.tabstop)
+        }
+    }
+}
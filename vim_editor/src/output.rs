@@ -1,6 +1,11 @@
 use crate::{
-    constants::Mode, cursor::CursorController, editor_contents::EditorContents,
+    constants::Mode,
+    cursor::CursorController,
+    editor_contents::EditorContents,
     editor_rows::EditorRows,
+    filetype,
+    gutter::{Gutter, GutterProvider, SearchMatchCount, TodoMarker},
+    spell::SpellChecker,
 };
 use crossterm::{cursor, execute, queue, style, terminal};
 use std::cmp;
@@ -11,21 +16,66 @@ pub struct Output {
     pub editor_contents: EditorContents,
     pub editor_rows: EditorRows,
     pub cursor_controller: CursorController,
+    pub gutter: Gutter,
+    gutter_width: usize,                     // 本次刷新时标记栏的宽度
+    pub selection: Option<(usize, usize)>,   // V 模式选中的行, 反色显示
+    pub overlay: Option<Overlay>,            // 盖在正文底部的列表, 例如 Ctrl-p 查找器
+    pub pending_keys: String, // 输入到一半的普通模式命令, 例如 "2d", 显示在状态栏右侧
+    pub spell_checker: Option<SpellChecker>, // :set spell 时给拼错的单词加红色下划线
+    title: String,            // 最近一次设置的终端标题
+}
+
+// 浮动列表: 若干行候选项加一行输入提示, 画在正文区域的底部
+pub struct Overlay {
+    pub prompt: String,
+    pub items: Vec<String>,
+    pub selected: usize, // 反色显示的一项在 items 中的下标
+    pub status: String,  // 显示在提示行右侧, 例如匹配数量
 }
 
 impl Output {
-    pub fn new() -> Self {
+    pub fn new(file: Option<String>) -> Self {
         let win_size = terminal::size()
             .map(|(x, y)| (x as usize, y as usize - 1))
             .unwrap(); // terminal::size() return Result<(u16: column, u16: row)> 类型
-        Self {
+        let mut output = Self {
             win_size,
             editor_contents: EditorContents::new(),
-            editor_rows: EditorRows::new(),
+            editor_rows: EditorRows::new(file),
             cursor_controller: CursorController::new(win_size),
+            gutter: Gutter::new(),
+            gutter_width: 0,
+            selection: None,
+            overlay: None,
+            pending_keys: String::new(),
+            spell_checker: None,
+            title: String::new(),
+        };
+        output.add_gutter_provider(Box::new(SearchMatchCount));
+        output.add_gutter_provider(Box::new(TodoMarker));
+        output
+    }
+
+    // 终端大小变化时更新可见区域, 最后一行留给状态栏
+    pub fn resize(&mut self, (columns, rows): (u16, u16)) {
+        self.win_size = (columns as usize, (rows as usize).saturating_sub(1));
+        self.cursor_controller.screen_columns = self.win_size.0.saturating_sub(self.gutter_width);
+        self.cursor_controller.screen_rows = self.win_size.1;
+    }
+
+    // 设置终端标题, 和上一次相同时不重复发送; 随下一次刷新屏幕一起输出
+    pub fn set_title(&mut self, title: String) {
+        if self.title != title {
+            queue!(self.editor_contents, terminal::SetTitle(&title)).unwrap();
+            self.title = title;
         }
     }
 
+    // 注册一个按行提供标记的功能
+    pub fn add_gutter_provider(&mut self, provider: Box<dyn GutterProvider>) {
+        self.gutter.add_provider(provider);
+    }
+
     fn draw_welcome(&mut self) {
         let screen_rows = self.win_size.1;
         let screen_columns = self.win_size.0;
@@ -75,13 +125,30 @@ impl Output {
 
     fn draw_contents(&mut self) {
         let screen_rows = self.win_size.1;
-        let screen_columns = self.win_size.0;
+        let screen_columns = self.win_size.0.saturating_sub(self.gutter_width);
+        let filetype = self
+            .editor_rows
+            .filename
+            .as_deref()
+            .and_then(filetype::detect);
         for i in 0..screen_rows {
             let file_row = i + self.cursor_controller.row_offest; // row_offest 为一个偏移量(使得文件内容随着光标偏移)
             if file_row >= self.editor_rows.number_of_rows() {
                 self.editor_contents.push('~');
             } else {
-                let row = self.editor_rows.get_row(file_row);
+                if self.gutter_width > 0 {
+                    let marker = self.gutter.render(&self.editor_rows, file_row);
+                    self.editor_contents.push_str(&marker);
+                }
+                let selected = self
+                    .selection
+                    .is_some_and(|(start, end)| (start..=end).contains(&file_row));
+                if selected {
+                    self.editor_contents
+                        .push_str(&style::Attribute::Reverse.to_string());
+                }
+                // 按字符切分, 列号和光标一样按字符计算
+                let row: Vec<char> = self.editor_rows.get_row(file_row).chars().collect();
                 if row.is_empty() {
                     // 处理空行的情况
                     // 不需要添加内容
@@ -99,25 +166,43 @@ impl Output {
                         let adjusted_row = &row[start..end];
                         let display_length = cmp::min(adjusted_row.len(), screen_columns); // 限制屏幕内显示行的长度
 
-                        // 检查当前行是否有搜索匹配项,高亮显示
-                        let matches_in_line: Vec<_> = self
+                        // 检查当前行是否有搜索匹配项和拼写错误, 分别高亮显示
+                        let mut matches_in_line: Vec<(usize, usize, String)> = self
                             .editor_rows
                             .search_matches
                             .iter()
-                            .filter(|&&(row, col, _)| {
-                                row == file_row && col >= start && col < start + display_length
+                            .filter(|&&(row, _, _)| row == file_row)
+                            .map(|&(_, col, len)| {
+                                (col, len, style::Attribute::Underlined.to_string())
                             })
                             .collect();
+                        if let Some(checker) = &self.spell_checker {
+                            let misspelled = format!(
+                                "{}{}",
+                                style::SetForegroundColor(style::Color::Red),
+                                style::Attribute::Underlined
+                            );
+                            matches_in_line.extend(
+                                checker
+                                    .misspellings(self.editor_rows.get_row(file_row), filetype)
+                                    .into_iter()
+                                    .map(|(col, len)| (col, len, misspelled.clone())),
+                            );
+                        }
+                        matches_in_line
+                            .retain(|&(col, _, _)| col >= start && col < start + display_length);
+                        matches_in_line.sort_by_key(|&(col, _, _)| col);
 
                         if matches_in_line.is_empty() {
                             // 没有匹配项, 正常显示
                             self.editor_contents
-                                .push_str(&adjusted_row[..display_length]);
+                                .push_str(&collect(&adjusted_row[..display_length]));
                         } else {
                             // 有匹配项, 高亮显示
                             let mut last_pos = 0;
-                            for &(_, col, len) in &matches_in_line {
-                                let rel_col = col.saturating_sub(start); // 相对于当前显示窗口的列位置
+                            for (col, len, highlight) in &matches_in_line {
+                                // 相对于当前显示窗口的列位置, 和前一处重叠时从重叠之后开始
+                                let rel_col = (col - start).max(last_pos);
 
                                 // 先显示匹配前的正常文本
                                 if rel_col > last_pos {
@@ -125,20 +210,20 @@ impl Output {
                                     let end_pos = std::cmp::min(rel_col, adjusted_row.len());
                                     if last_pos < end_pos {
                                         self.editor_contents
-                                            .push_str(&adjusted_row[last_pos..end_pos]);
+                                            .push_str(&collect(&adjusted_row[last_pos..end_pos]));
                                     }
                                 }
 
                                 // 高亮显示匹配部分
-                                let match_end = cmp::min(rel_col + len, display_length);
+                                let match_end =
+                                    cmp::min((col - start + len).max(rel_col), display_length);
 
                                 if rel_col < match_end && rel_col < adjusted_row.len() {
                                     let actual_end = std::cmp::min(match_end, adjusted_row.len());
 
+                                    self.editor_contents.push_str(highlight);
                                     self.editor_contents
-                                        .push_str(&style::Attribute::Underlined.to_string());
-                                    self.editor_contents
-                                        .push_str(&adjusted_row[rel_col..actual_end]);
+                                        .push_str(&collect(&adjusted_row[rel_col..actual_end]));
                                     self.editor_contents
                                         .push_str(&style::Attribute::Reset.to_string());
                                 }
@@ -153,11 +238,15 @@ impl Output {
                             // 显示匹配后的剩余文本
                             if last_pos < display_length {
                                 self.editor_contents
-                                    .push_str(&adjusted_row[last_pos..display_length]);
+                                    .push_str(&collect(&adjusted_row[last_pos..display_length]));
                             }
                         }
                     }
                 }
+                if selected {
+                    self.editor_contents
+                        .push_str(&style::Attribute::Reset.to_string());
+                }
             }
             queue!(
                 self.editor_contents,
@@ -168,56 +257,132 @@ impl Output {
         }
     }
 
+    // 状态栏: 左边是带颜色的模式, 然后是文件名和行数, 右边是输入中的命令和光标所在行
     pub fn draw_status_bar(&mut self, mode: &Mode) {
-        self.editor_contents
-            .push_str(&style::Attribute::Reverse.to_string());
+        let (mode_str, mode_color) = match mode {
+            Mode::Normal => ("NORMAL", style::Color::Blue),
+            Mode::Command => ("COMMAND", style::Color::Yellow),
+            Mode::Search => ("SEARCH", style::Color::Cyan),
+            Mode::Insert => ("INSERT", style::Color::Green),
+            Mode::VisualLine => ("VISUAL LINE", style::Color::Magenta),
+        };
+        let mode_info = format!(" {} ", mode_str);
         let info = format!(
-            "{} -- {} lines",
-            self.editor_rows
-                .filename
-                .as_ref()
-                .and_then(|path| path.file_name())
-                .and_then(|name| name.to_str())
-                .unwrap_or("[No Name]"),
+            " {}{} -- {} lines",
+            if self.editor_rows.read_only {
+                "[Help]"
+            } else {
+                self.editor_rows
+                    .filename
+                    .as_ref()
+                    .and_then(|path| path.file_name())
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("[No Name]")
+            },
+            if self.editor_rows.hex_view {
+                " [hex, RO]"
+            } else if self.editor_rows.read_only {
+                " [RO]"
+            } else {
+                ""
+            },
             self.editor_rows.number_of_rows()
         );
-
-        let mode_str = match mode {
-            Mode::Normal => "NORMAL",
-            Mode::Command => "COMMAND",
-            Mode::Search => "SEARCH",
-            Mode::Insert => "INSERT",
-        };
-
-        let mode_info = format!(" - {} - ", mode_str);
-        let info_len = cmp::min(info.len(), self.win_size.0);
         let line_info = format!(
-            "{}/{}",
+            "{}{}/{}",
+            if self.pending_keys.is_empty() {
+                String::new()
+            } else {
+                format!("{}   ", self.pending_keys)
+            },
             self.cursor_controller.cursor_y + 1,
             self.editor_rows.number_of_rows()
         );
 
-        let total_len = info_len + mode_info.len() + line_info.len();
-        let padding = if total_len < self.win_size.0 {
-            (self.win_size.0 - total_len) / 2
-        } else {
-            0
-        };
+        let columns = self.win_size.0;
+        let mode_len = cmp::min(mode_info.chars().count(), columns);
+        let info_len = cmp::min(info.chars().count(), columns - mode_len);
+        let line_len = line_info.chars().count();
+        let used = mode_len + info_len;
+        let padding = columns.saturating_sub(used + line_len);
 
-        self.editor_contents.push_str(&info[..info_len]);
+        queue!(
+            self.editor_contents,
+            style::SetBackgroundColor(mode_color),
+            style::SetForegroundColor(style::Color::Black),
+            style::SetAttribute(style::Attribute::Bold),
+            style::Print(mode_info.chars().take(mode_len).collect::<String>()),
+            style::SetAttribute(style::Attribute::Reset),
+            style::SetAttribute(style::Attribute::Reverse),
+            style::Print(info.chars().take(info_len).collect::<String>()),
+        )
+        .unwrap();
         for _ in 0..padding {
             self.editor_contents.push(' ');
         }
-        self.editor_contents.push_str(&mode_info);
-        for _ in 0..padding {
-            self.editor_contents.push(' ');
+        // 放不下时宁可不显示右侧的信息
+        if used + line_len <= columns {
+            self.editor_contents.push_str(&line_info);
         }
-        self.editor_contents.push_str(&line_info);
 
         self.editor_contents
             .push_str(&style::Attribute::Reset.to_string());
     }
 
+    // 从正文区域底部往上画: 最后一行是提示, 上面是候选项
+    // 返回提示行的行号和输入结束的列, 用来放置光标
+    fn draw_overlay(&mut self) -> Option<(usize, usize)> {
+        let overlay = self.overlay.as_ref()?;
+        let columns = self.win_size.0;
+        let prompt_y = self.win_size.1.checked_sub(1)?;
+        let items = overlay.items.len().min(prompt_y);
+        let first_y = prompt_y - items;
+
+        for (i, item) in overlay.items.iter().take(items).enumerate() {
+            let selected = i == overlay.selected;
+            let marker = if selected { "> " } else { "  " };
+            let text: String = format!("{}{}", marker, item)
+                .chars()
+                .take(columns)
+                .collect();
+            queue!(
+                self.editor_contents,
+                cursor::MoveTo(0, (first_y + i) as u16),
+                terminal::Clear(terminal::ClearType::UntilNewLine)
+            )
+            .unwrap();
+            if selected {
+                self.editor_contents
+                    .push_str(&style::Attribute::Reverse.to_string());
+            }
+            self.editor_contents.push_str(&text);
+            if selected {
+                self.editor_contents
+                    .push_str(&style::Attribute::Reset.to_string());
+            }
+        }
+
+        let prompt: String = overlay.prompt.chars().take(columns).collect();
+        let prompt_len = prompt.chars().count();
+        queue!(
+            self.editor_contents,
+            cursor::MoveTo(0, prompt_y as u16),
+            terminal::Clear(terminal::ClearType::UntilNewLine),
+            style::Print(&prompt)
+        )
+        .unwrap();
+        let status_len = overlay.status.chars().count();
+        if prompt_len + status_len < columns {
+            queue!(
+                self.editor_contents,
+                cursor::MoveTo((columns - status_len) as u16, prompt_y as u16),
+                style::Print(&overlay.status)
+            )
+            .unwrap();
+        }
+        Some((prompt_y, prompt_len))
+    }
+
     pub fn draw_rows(&mut self) {
         if self.editor_rows.number_of_rows() == 0 {
             self.draw_welcome();
@@ -226,10 +391,19 @@ impl Output {
         }
     }
 
-    pub fn refresh_screen(&mut self, mode: &Mode, command_buffer: &str) -> crossterm::Result<()> {
+    pub fn refresh_screen(
+        &mut self,
+        mode: &Mode,
+        command_buffer: &str,
+        message: Option<&str>,
+    ) -> crossterm::Result<()> {
+        // 标记栏占用左侧几列, 正文可用宽度相应减少
+        self.gutter_width = self.gutter.width(&self.editor_rows);
+        self.cursor_controller.screen_columns = self.win_size.0.saturating_sub(self.gutter_width);
         self.cursor_controller.scroll();
         queue!(self.editor_contents, cursor::Hide, cursor::MoveTo(0, 0))?;
         self.draw_rows();
+        let overlay_cursor = self.draw_overlay();
         let status_line_y = self.win_size.1;
         queue!(
             self.editor_contents,
@@ -254,6 +428,15 @@ impl Output {
                 style::Print(":"),
                 style::Print(command_buffer)
             )?;
+        } else if let Some(message) = message {
+            // 命令行不用时显示消息, 例如写入结果或错误
+            let message: String = message.chars().take(self.win_size.0).collect();
+            queue!(
+                self.editor_contents,
+                cursor::MoveTo(0, (status_line_y + 1) as u16),
+                terminal::Clear(terminal::ClearType::UntilNewLine),
+                style::Print(message)
+            )?;
         }
 
         let cursor_y = self
@@ -263,7 +446,14 @@ impl Output {
         let cursor_x = self
             .cursor_controller
             .cursor_x
-            .saturating_sub(self.cursor_controller.column_offest);
+            .saturating_sub(self.cursor_controller.column_offest)
+            + self.gutter_width;
+
+        // 查找器打开时光标停在输入提示的末尾
+        let (cursor_x, cursor_y) = match overlay_cursor {
+            Some((y, x)) => (x, y),
+            None => (cursor_x, cursor_y),
+        };
 
         // 添加额外检查确保不会溢出u16
         let cursor_x = std::cmp::min(cursor_x, u16::MAX as usize) as u16;
@@ -283,30 +473,21 @@ impl Output {
 
     pub fn move_cursor(&mut self, direction: char, number_of_rows: usize) {
         match direction {
-            'h' => {
-                if self.cursor_controller.cursor_x > 0 {
-                    self.cursor_controller.cursor_x -= 1;
-                }
+            'h' if self.cursor_controller.cursor_x > 0 => {
+                self.cursor_controller.cursor_x -= 1;
             }
-            'j' => {
-                if self.cursor_controller.cursor_y < number_of_rows.saturating_sub(1) {
-                    self.cursor_controller.cursor_y += 1;
-                }
+            'j' if self.cursor_controller.cursor_y < number_of_rows.saturating_sub(1) => {
+                self.cursor_controller.cursor_y += 1;
             }
-            'k' => {
-                if self.cursor_controller.cursor_y > 0 {
-                    self.cursor_controller.cursor_y -= 1;
-                }
+            'k' if self.cursor_controller.cursor_y > 0 => {
+                self.cursor_controller.cursor_y -= 1;
             }
             'l' => {
                 // 允许光标在文件内容的情况下根据行长度限制
                 if self.editor_rows.number_of_rows() > 0
                     && self.cursor_controller.cursor_y < self.editor_rows.number_of_rows()
                 {
-                    let row_len = self
-                        .editor_rows
-                        .get_row(self.cursor_controller.cursor_y)
-                        .len();
+                    let row_len = self.editor_rows.row_len(self.cursor_controller.cursor_y);
                     if self.cursor_controller.cursor_x < row_len {
                         self.cursor_controller.cursor_x += 1;
                     }
@@ -322,10 +503,7 @@ impl Output {
                 if self.editor_rows.number_of_rows() > 0
                     && self.cursor_controller.cursor_y < self.editor_rows.number_of_rows()
                 {
-                    let row_len = self
-                        .editor_rows
-                        .get_row(self.cursor_controller.cursor_y)
-                        .len();
+                    let row_len = self.editor_rows.row_len(self.cursor_controller.cursor_y);
                     // 检查行长度，避免在空行上出现问题
                     if row_len > 0 {
                         self.cursor_controller.cursor_x = row_len - 1; // 移动到行的最后一个字符
@@ -346,3 +524,7 @@ impl Output {
         }
     }
 }
+
+fn collect(chars: &[char]) -> String {
+    chars.iter().collect()
+}
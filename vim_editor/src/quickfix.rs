@@ -0,0 +1,206 @@
+use crate::gutter::GutterMarker;
+use crossterm::style::Color;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// :make 的错误列表(quickfix), 从编译输出中解析 `file:line:col` 位置
+
+// 标记栏中错误标记的来源名
+pub const SOURCE: &str = "quickfix";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuickfixEntry {
+    pub file: PathBuf,
+    pub line: usize, // 从 1 开始, 和编译器输出一致
+    pub col: usize,  // 从 1 开始
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub struct QuickfixList {
+    entries: Vec<QuickfixEntry>,
+    current: usize,
+}
+
+impl QuickfixList {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            current: 0,
+        }
+    }
+
+    // 解析两种格式:
+    // rustc/cargo 的 `error[E0425]: message` 加下一行的 `--> file:line:col`,
+    // 以及 gcc 等常见的 `file:line:col: error: message`
+    pub fn parse(output: &str) -> Self {
+        let mut entries = Vec::new();
+        // 最近一行 rustc 诊断的级别和内容, 等待后面的 `-->` 行
+        let mut pending: Option<(Severity, String)> = None;
+        for line in output.lines() {
+            let trimmed = line.trim_start();
+            if let Some(location) = trimmed.strip_prefix("--> ") {
+                if let (Some((severity, message)), Some((file, line, col))) =
+                    (pending.take(), parse_location(location))
+                {
+                    entries.push(QuickfixEntry {
+                        file,
+                        line,
+                        col,
+                        severity,
+                        message,
+                    });
+                }
+            } else if let Some(diagnostic) = parse_rustc_header(trimmed) {
+                pending = Some(diagnostic);
+            } else if let Some(entry) = parse_gcc_line(line) {
+                entries.push(entry);
+            }
+        }
+        Self {
+            entries,
+            current: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn current(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.current)
+    }
+
+    // 第一个错误; 只有警告时是第一个警告
+    pub fn first_error(&mut self) -> Option<&QuickfixEntry> {
+        self.current = self
+            .entries
+            .iter()
+            .position(|entry| entry.severity == Severity::Error)
+            .unwrap_or(0);
+        self.current()
+    }
+
+    // :cnext / :cprev, 已经在两端时返回 None
+    pub fn next(&mut self) -> Option<&QuickfixEntry> {
+        if self.current + 1 >= self.entries.len() {
+            return None;
+        }
+        self.current += 1;
+        self.current()
+    }
+
+    pub fn prev(&mut self) -> Option<&QuickfixEntry> {
+        if self.current == 0 || self.entries.is_empty() {
+            return None;
+        }
+        self.current -= 1;
+        self.current()
+    }
+
+    // :cc N, N 从 1 开始
+    pub fn select(&mut self, number: usize) -> Option<&QuickfixEntry> {
+        if number == 0 || number > self.entries.len() {
+            return None;
+        }
+        self.current = number - 1;
+        self.current()
+    }
+
+    // 消息行显示的当前项, 例如 `(2 of 5) error: mismatched types`
+    pub fn describe(&self) -> Option<String> {
+        let entry = self.current()?;
+        let severity = match entry.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        Some(format!(
+            "({} of {}) {}: {}",
+            self.current + 1,
+            self.entries.len(),
+            severity,
+            entry.message
+        ))
+    }
+
+    // path 对应的文件中出错的行, 用于标记栏
+    pub fn markers_for(&self, path: &Path) -> Vec<(usize, GutterMarker)> {
+        self.entries
+            .iter()
+            .filter(|entry| same_file(&entry.file, path))
+            .map(|entry| {
+                let marker = match entry.severity {
+                    Severity::Error => GutterMarker::new('E', Color::Red),
+                    Severity::Warning => GutterMarker::new('W', Color::Magenta),
+                };
+                (entry.line.saturating_sub(1), marker)
+            })
+            .collect()
+    }
+}
+
+// 用 shell 执行命令, 返回是否成功和合并后的 stdout / stderr
+pub fn run(command: &str) -> io::Result<(bool, String)> {
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// `error[E0425]: cannot find value` / `warning: unused variable`
+fn parse_rustc_header(line: &str) -> Option<(Severity, String)> {
+    let (head, message) = line.split_once(": ")?;
+    let severity = match head.split('[').next()? {
+        "error" => Severity::Error,
+        "warning" => Severity::Warning,
+        _ => return None,
+    };
+    Some((severity, message.to_string()))
+}
+
+// `src/main.rs:3:5: error: expected ';'`, 没有级别时算错误
+fn parse_gcc_line(line: &str) -> Option<QuickfixEntry> {
+    let mut parts = line.splitn(4, ':');
+    let file = parts.next()?;
+    let line_number = parts.next()?.parse().ok()?;
+    let col = parts.next()?.trim().parse().ok()?;
+    let rest = parts.next()?.trim();
+    if file.is_empty() || file.contains(' ') {
+        return None;
+    }
+    let (severity, message) = match rest.split_once(": ") {
+        Some(("warning", message)) => (Severity::Warning, message),
+        Some(("error" | "fatal error", message)) => (Severity::Error, message),
+        _ => (Severity::Error, rest),
+    };
+    Some(QuickfixEntry {
+        file: PathBuf::from(file),
+        line: line_number,
+        col,
+        severity,
+        message: message.to_string(),
+    })
+}
+
+// `src/main.rs:3:5`, 文件名里可能有 ':' 所以从右边拆
+fn parse_location(location: &str) -> Option<(PathBuf, usize, usize)> {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let col = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?;
+    Some((PathBuf::from(file), line, col))
+}
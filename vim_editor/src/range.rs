@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+// ex 命令的行范围, 行号从 0 开始, 两端都包含
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+// 解析地址时需要的编辑器状态
+pub struct RangeContext<'a> {
+    pub current_line: usize, // 光标所在行(从 0 开始)
+    pub line_count: usize,
+    pub marks: &'a HashMap<char, usize>,
+}
+
+impl RangeContext<'_> {
+    fn last_line(&self) -> usize {
+        self.line_count.saturating_sub(1)
+    }
+}
+
+// 从命令开头解析范围, 返回范围(没有写范围时为 None)和剩下的命令部分
+// 支持: 行号、`.`、`$`、`%`、`'a` 标记、`+N`/`-N` 偏移, 以及用 `,` 或 `;` 分隔的两个地址
+pub fn parse_range<'c>(
+    command: &'c str,
+    ctx: &RangeContext,
+) -> Result<(Option<LineRange>, &'c str), String> {
+    let command = command.trim_start();
+
+    if let Some(rest) = command.strip_prefix('%') {
+        let range = LineRange {
+            start: 0,
+            end: ctx.last_line(),
+        };
+        return Ok((Some(range), rest));
+    }
+
+    let (start, rest) = match parse_address(command, ctx)? {
+        Some(parsed) => parsed,
+        None => return Ok((None, command)),
+    };
+
+    let (end, rest) = match rest.strip_prefix([',', ';']) {
+        Some(after_separator) => match parse_address(after_separator, ctx)? {
+            Some(parsed) => parsed,
+            None => return Err("Invalid range".to_string()),
+        },
+        None => (start, rest),
+    };
+
+    let last = ctx.last_line() as isize;
+    if start < 0 || end < 0 || start > last || end > last {
+        return Err("Invalid range".to_string());
+    }
+
+    // vim 会询问是否交换倒序的范围, 这里直接交换
+    let (start, end) = (start.min(end) as usize, start.max(end) as usize);
+    Ok((Some(LineRange { start, end }), rest))
+}
+
+// 解析单个地址和后面的偏移, 返回行号(从 0 开始, 可能越界, 由调用者检查)
+fn parse_address<'c>(
+    input: &'c str,
+    ctx: &RangeContext,
+) -> Result<Option<(isize, &'c str)>, String> {
+    let (mut line, mut rest) = if let Some(rest) = input.strip_prefix('.') {
+        (ctx.current_line as isize, rest)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        (ctx.last_line() as isize, rest)
+    } else if let Some(rest) = input.strip_prefix('\'') {
+        let mut chars = rest.chars();
+        let mark = chars.next().ok_or("Mark not set")?;
+        let line = ctx.marks.get(&mark).ok_or("Mark not set")?;
+        (*line as isize, chars.as_str())
+    } else if input.starts_with(|ch: char| ch.is_ascii_digit()) {
+        let (number, rest) = split_number(input);
+        // 行号 0 当作第一行
+        (number.saturating_sub(1) as isize, rest)
+    } else if input.starts_with(['+', '-']) {
+        // 只有偏移时相对于当前行, 例如 `:+2d`
+        (ctx.current_line as isize, input)
+    } else {
+        return Ok(None);
+    };
+
+    // 偏移可以连写, 例如 `.+1-2`; 只写符号相当于 1
+    while let Some(sign) = rest.chars().next().filter(|ch| *ch == '+' || *ch == '-') {
+        let (number, after) = split_number(&rest[1..]);
+        let offset = if after.len() == rest.len() - 1 {
+            1
+        } else {
+            number as isize
+        };
+        line += if sign == '+' { offset } else { -offset };
+        rest = after;
+    }
+
+    Ok(Some((line, rest)))
+}
+
+fn split_number(input: &str) -> (usize, &str) {
+    let digits = input
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(input.len());
+    let number = input[..digits].parse().unwrap_or(0);
+    (number, &input[digits..])
+}
@@ -1,16 +1,38 @@
-use crossterm::event::{self, Event, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::Cell;
 use std::time::Duration;
+use term_utils::{EventReader, TermEvent};
 
-pub struct Reader;
+// 空闲时多久唤醒一次主循环, 用于检查文件是否在外部被修改
+const IDLE_TICK: Duration = Duration::from_millis(500);
+
+pub struct Reader {
+    events: EventReader,
+    resized: Cell<Option<(u16, u16)>>, // 尚未处理的终端大小变化
+}
 
 impl Reader {
+    pub fn new() -> Self {
+        Self {
+            events: EventReader::new(),
+            resized: Cell::new(None),
+        }
+    }
+
+    // 按键已经过 term_utils 统一处理, 字符键不会带 SHIFT
+    // 终端大小变化或空闲超时时返回 KeyCode::Null, 让主循环有机会重绘和做定时检查
     pub fn read_key(&self) -> crossterm::Result<KeyEvent> {
-        loop {
-            if event::poll(Duration::from_millis(500))? {
-                if let Event::Key(event) = event::read()? {
-                    return Ok(event);
-                }
+        match self.events.poll_event(IDLE_TICK)? {
+            Some(TermEvent::Key(key)) => Ok(key),
+            Some(TermEvent::Resize(columns, rows)) => {
+                self.resized.set(Some((columns, rows)));
+                Ok(KeyEvent::new(KeyCode::Null, KeyModifiers::NONE))
             }
+            None => Ok(KeyEvent::new(KeyCode::Null, KeyModifiers::NONE)),
         }
     }
+
+    pub fn take_resize(&self) -> Option<(u16, u16)> {
+        self.resized.take()
+    }
 }
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+// 寄存器: dd / yy 把整行保存在这里, p / P 从这里粘贴
+// 不指定寄存器时使用无名寄存器 `"`; `"a`-`"z` 是命名寄存器, 写入时同时更新无名寄存器
+// `"+` 和 `"*` 是系统剪贴板(编译时启用 clipboard 特性), 这里不区分 X11 的 PRIMARY 和 CLIPBOARD;
+// 剪贴板不可用时(没有图形环境、没有启用特性)退化为普通寄存器
+pub struct Registers {
+    unnamed: Vec<String>,
+    named: HashMap<char, Vec<String>>,
+    #[cfg(feature = "clipboard")]
+    clipboard: Option<arboard::Clipboard>, // X11 下剪贴板内容由这个对象提供, 要一直保留
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            unnamed: Vec::new(),
+            named: HashMap::new(),
+            #[cfg(feature = "clipboard")]
+            clipboard: None,
+        }
+    }
+
+    // `"` 后面可以跟的寄存器名
+    pub fn is_valid_name(name: char) -> bool {
+        matches!(name, '"' | '+' | '*') || name.is_ascii_lowercase()
+    }
+
+    // 写入剪贴板失败时返回提示, 内容仍然保存在编辑器里
+    pub fn set(&mut self, name: Option<char>, lines: Vec<String>) -> Option<String> {
+        let mut warning = None;
+        if let Some(name) = name.filter(|&name| name != '"') {
+            if is_clipboard(name) {
+                warning = self.write_clipboard(&lines).err();
+            }
+            self.named.insert(name, lines.clone());
+        }
+        self.unnamed = lines;
+        warning
+    }
+
+    // 寄存器为空时返回 None; 剪贴板读不到时使用上一次写入的内容
+    pub fn get(&mut self, name: Option<char>) -> Option<Vec<String>> {
+        let lines = match name.filter(|&name| name != '"') {
+            Some(name) if is_clipboard(name) => match self.read_clipboard() {
+                Ok(text) => text.lines().map(str::to_string).collect(),
+                Err(_) => self.named.get(&name).cloned()?,
+            },
+            Some(name) => self.named.get(&name).cloned()?,
+            None => self.unnamed.clone(),
+        };
+        (!lines.is_empty()).then_some(lines)
+    }
+
+    #[cfg(feature = "clipboard")]
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.clipboard.is_none() {
+            let clipboard =
+                arboard::Clipboard::new().map_err(|e| format!("Clipboard unavailable: {}", e))?;
+            self.clipboard = Some(clipboard);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    // 整行内容以换行结尾, 和 vim 复制整行时一样
+    #[cfg(feature = "clipboard")]
+    fn write_clipboard(&mut self, lines: &[String]) -> Result<(), String> {
+        let text = lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        self.clipboard()?
+            .set_text(text)
+            .map_err(|e| format!("Can't write clipboard: {}", e))
+    }
+
+    #[cfg(feature = "clipboard")]
+    fn read_clipboard(&mut self) -> Result<String, String> {
+        self.clipboard()?
+            .get_text()
+            .map_err(|e| format!("Can't read clipboard: {}", e))
+    }
+
+    // 没有启用 clipboard 特性时 "+ 和 "* 只在编辑器内部有效, 不提示
+    #[cfg(not(feature = "clipboard"))]
+    fn write_clipboard(&mut self, _lines: &[String]) -> Result<(), String> {
+        Ok(())
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    fn read_clipboard(&mut self) -> Result<String, String> {
+        Err("clipboard support not compiled in".to_string())
+    }
+}
+
+fn is_clipboard(name: char) -> bool {
+    matches!(name, '+' | '*')
+}
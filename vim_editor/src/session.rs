@@ -0,0 +1,126 @@
+use crate::options::Options;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// :mksession 不写文件名时使用的默认文件
+pub const DEFAULT_SESSION_FILE: &str = "Session.vim-rs";
+
+const HEADER: &str = "\" vim_editor session";
+
+// 会话: 打开的文件、光标和窗口滚动位置、选项和标记
+// 文件格式是按行的文本, 例如:
+//   set shiftwidth=4
+//   edit /home/me/project/main.rs
+//   cursor 12 4      (行 列, 从 0 开始)
+//   offset 0 0       (窗口滚动的行、列偏移)
+//   mark a 30
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    pub file: Option<PathBuf>,
+    pub cursor: (usize, usize),  // (cursor_x, cursor_y)
+    pub offsets: (usize, usize), // (row_offest, column_offest)
+    pub options: Vec<String>,    // :set 的参数
+    pub marks: Vec<(char, usize)>,
+}
+
+impl Session {
+    pub fn new(
+        file: Option<&Path>,
+        cursor: (usize, usize),
+        offsets: (usize, usize),
+        options: &Options,
+        marks: &HashMap<char, usize>,
+    ) -> Self {
+        // 保存绝对路径, 在其它目录下恢复会话也能找到文件
+        let file = file.map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        let mut marks: Vec<(char, usize)> = marks.iter().map(|(&c, &line)| (c, line)).collect();
+        marks.sort();
+
+        Self {
+            file,
+            cursor,
+            offsets,
+            options: options.to_args(),
+            marks,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![HEADER.to_string()];
+        lines.extend(self.options.iter().map(|arg| format!("set {}", arg)));
+        if let Some(file) = &self.file {
+            lines.push(format!("edit {}", file.display()));
+        }
+        lines.push(format!("cursor {} {}", self.cursor.1, self.cursor.0));
+        lines.push(format!("offset {} {}", self.offsets.0, self.offsets.1));
+        lines.extend(
+            self.marks
+                .iter()
+                .map(|(mark, line)| format!("mark {} {}", mark, line)),
+        );
+        lines.join("\n") + "\n"
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut session = Session::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('"') {
+                continue;
+            }
+            let invalid = || format!("Invalid session line {}: {}", number + 1, line);
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let numbers = || -> Result<(usize, usize), String> {
+                let mut parts = rest.split_whitespace().map(|n| n.parse::<usize>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(a)), Some(Ok(b))) => Ok((a, b)),
+                    _ => Err(invalid()),
+                }
+            };
+
+            match command {
+                "set" => session.options.push(rest.to_string()),
+                "edit" => session.file = Some(PathBuf::from(rest)),
+                "cursor" => {
+                    let (y, x) = numbers()?;
+                    session.cursor = (x, y);
+                }
+                "offset" => session.offsets = numbers()?,
+                "mark" => {
+                    let mut parts = rest.split_whitespace();
+                    let mark = parts
+                        .next()
+                        .and_then(|m| m.chars().next())
+                        .ok_or_else(invalid)?;
+                    let line = parts
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(invalid)?;
+                    session.marks.push((mark, line));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(session)
+    }
+
+    // :mksession[!] [file], 没有 `!` 时不覆盖已存在的文件
+    pub fn save(&self, path: &Path, force: bool) -> io::Result<()> {
+        if !force && path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "File exists (add ! to override)",
+            ));
+        }
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Can't open session {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+}
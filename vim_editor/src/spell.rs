@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use crate::filetype;
+
+// 拼写检查(:set spell)用的词表, 每行一个小写单词
+const WORD_LIST: &str = include_str!("words.txt");
+
+// 常见的词尾变化: (词尾, 换成), 去掉后是词表中的词也算拼对, 例如 files、copies、parsing
+const SUFFIXES: &[(&str, &str)] = &[
+    ("'s", ""),
+    ("s", ""),
+    ("es", ""),
+    ("ies", "y"),
+    ("ied", "y"),
+    ("ier", "y"),
+    ("iest", "y"),
+    ("ily", "y"),
+    ("ed", ""),
+    ("ing", ""),
+    ("er", ""),
+    ("est", ""),
+    ("ly", ""),
+    ("ment", ""),
+    ("ness", ""),
+    ("able", ""),
+];
+
+pub struct SpellChecker {
+    words: HashSet<&'static str>,
+}
+
+impl SpellChecker {
+    pub fn new() -> Self {
+        Self {
+            words: WORD_LIST.lines().collect(),
+        }
+    }
+
+    // 单词是否在词表中, 不区分大小写
+    pub fn is_known(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        if self.words.contains(word.as_str()) {
+            return true;
+        }
+        SUFFIXES.iter().any(|(suffix, replacement)| {
+            let Some(stem) = word.strip_suffix(suffix) else {
+                return false;
+            };
+            if stem.len() < 2 {
+                return false;
+            }
+            let stem = format!("{}{}", stem, replacement);
+            let bytes = stem.as_bytes();
+            // parsing -> parse, stopped -> stop
+            self.words.contains(stem.as_str())
+                || self.words.contains(format!("{}e", stem).as_str())
+                || (bytes[bytes.len() - 1] == bytes[bytes.len() - 2]
+                    && self.words.contains(&stem[..stem.len() - 1]))
+        })
+    }
+
+    // 一行中拼错的单词, 返回 (起始列, 长度), 列按字符计算
+    // 纯文本和 Markdown 检查整行, 其它认识的文件类型只检查注释开头之后的部分
+    pub fn misspellings(&self, line: &str, filetype: Option<&str>) -> Vec<(usize, usize)> {
+        let comment_string = filetype
+            .filter(|name| *name != "markdown")
+            .and_then(filetype::comment_string);
+        let start = match comment_string {
+            None => 0,
+            Some(comment_string) => {
+                let leader = comment_string.split("%s").next().unwrap_or("").trim();
+                match line.find(leader) {
+                    Some(i) if !leader.is_empty() => line[..i].chars().count(),
+                    _ => return Vec::new(),
+                }
+            }
+        };
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut errors = Vec::new();
+        let mut col = start;
+        while col < chars.len() {
+            if chars[col].is_whitespace() {
+                col += 1;
+                continue;
+            }
+            let end = (col..chars.len())
+                .find(|&i| chars[i].is_whitespace())
+                .unwrap_or(chars.len());
+            self.check_chunk(&chars, col, end, &mut errors);
+            col = end;
+        }
+        errors
+    }
+
+    // 检查一段不含空白的文本: 去掉两边的标点后只剩字母、撇号和连字符时按连字符拆开检查,
+    // 含有数字、点、斜杠、下划线等字符的多半是代码、路径或网址, 不检查
+    fn check_chunk(
+        &self,
+        chars: &[char],
+        start: usize,
+        end: usize,
+        errors: &mut Vec<(usize, usize)>,
+    ) {
+        let Some(start) = (start..end).find(|&i| chars[i].is_alphanumeric()) else {
+            return;
+        };
+        let end = (start..end)
+            .rfind(|&i| chars[i].is_alphanumeric())
+            .map_or(start, |i| i + 1);
+        let chunk = &chars[start..end];
+        if !chunk
+            .iter()
+            .all(|&ch| ch.is_ascii_alphabetic() || ch == '\'' || ch == '-')
+        {
+            return;
+        }
+
+        let mut word_start = start;
+        for part in chunk.split(|&ch| ch == '-') {
+            // 单个字母、全大写的缩写和驼峰命名都不检查
+            let checked = part.len() >= 2 && !part[1..].iter().any(|ch| ch.is_ascii_uppercase());
+            if checked && !self.is_known(&part.iter().collect::<String>()) {
+                errors.push((word_start, part.len()));
+            }
+            word_start += part.len() + 1;
+        }
+    }
+}
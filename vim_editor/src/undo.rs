@@ -0,0 +1,229 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// 最多保留的撤销步数, 更早的改动丢掉
+const UNDO_LEVELS: usize = 1000;
+
+const HEADER: &str = "\" vim_editor undo file";
+
+// 一次改动: 从 start 行开始的 old 这几行被换成了 new
+// 只记录前后不同的那一段, 不保存整个缓冲区
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub start: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    pub cursor: (usize, usize), // 改动之前的光标 (cursor_x, cursor_y), 撤销后回到这里
+}
+
+impl Change {
+    // 比较改动前后的内容, 去掉开头和结尾相同的行; 内容没变时返回 None
+    pub fn between(before: &[String], after: &[String], cursor: (usize, usize)) -> Option<Self> {
+        let prefix = before
+            .iter()
+            .zip(after)
+            .take_while(|(old, new)| old == new)
+            .count();
+        if prefix == before.len() && prefix == after.len() {
+            return None;
+        }
+        let suffix = before[prefix..]
+            .iter()
+            .rev()
+            .zip(after[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        Some(Self {
+            start: prefix,
+            old: before[prefix..before.len() - suffix].to_vec(),
+            new: after[prefix..after.len() - suffix].to_vec(),
+            cursor,
+        })
+    }
+
+    fn undo(&self, rows: &mut Vec<String>) {
+        let end = (self.start + self.new.len()).min(rows.len());
+        rows.splice(self.start.min(end)..end, self.old.iter().cloned());
+    }
+
+    fn redo(&self, rows: &mut Vec<String>) {
+        let end = (self.start + self.old.len()).min(rows.len());
+        rows.splice(self.start.min(end)..end, self.new.iter().cloned());
+    }
+}
+
+// u / Ctrl-r 用的撤销和重做栈, 栈顶是最近的一步
+#[derive(Debug, Default, PartialEq)]
+pub struct UndoHistory {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 记录一次新的改动, 之前撤销掉的改动不能再重做
+    pub fn push(&mut self, change: Change) {
+        self.redo.clear();
+        self.undo.push(change);
+        if self.undo.len() > UNDO_LEVELS {
+            self.undo.remove(0);
+        }
+    }
+
+    // 撤销最近一步, 返回光标应该回到的位置; 已经是最早的状态时返回 None
+    pub fn undo(&mut self, rows: &mut Vec<String>) -> Option<(usize, usize)> {
+        let change = self.undo.pop()?;
+        change.undo(rows);
+        let cursor = change.cursor;
+        self.redo.push(change);
+        Some(cursor)
+    }
+
+    // 重做最近撤销的一步, 光标放在改动的第一行
+    pub fn redo(&mut self, rows: &mut Vec<String>) -> Option<(usize, usize)> {
+        let change = self.redo.pop()?;
+        change.redo(rows);
+        let cursor = (0, change.start);
+        self.undo.push(change);
+        Some(cursor)
+    }
+
+    // 撤销文件的内容, 和 vim 的 undofile 类似, 按行的文本, 例如:
+    //   text 9a3c0f1e2b4d5a67      (写入时文件内容的散列, 读取时内容不同就不用这份历史)
+    //   undo 3 12 4 1 2            (起始行 光标行 光标列 旧行数 新行数)
+    //   -旧的一行
+    //   +新的一行
+    //   +新的另一行
+    //   redo ...
+    // 先是撤销栈从底到顶, 然后是重做栈从底到顶
+    pub fn to_text(&self, rows: &[String]) -> String {
+        let mut lines = vec![HEADER.to_string(), format!("text {:016x}", hash_rows(rows))];
+        let stacks = [("undo", &self.undo), ("redo", &self.redo)];
+        for (kind, changes) in stacks {
+            for change in changes.iter() {
+                lines.push(format!(
+                    "{} {} {} {} {} {}",
+                    kind,
+                    change.start,
+                    change.cursor.1,
+                    change.cursor.0,
+                    change.old.len(),
+                    change.new.len()
+                ));
+                lines.extend(change.old.iter().map(|line| format!("-{}", line)));
+                lines.extend(change.new.iter().map(|line| format!("+{}", line)));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    // 读取撤销文件; 文件是为别的内容写的(文件在外部被改过)时返回 Ok(None)
+    pub fn parse(text: &str, rows: &[String]) -> Result<Option<Self>, String> {
+        let mut lines = text.lines().enumerate();
+        let mut history = Self::new();
+        let invalid =
+            |number: usize, line: &str| format!("Invalid undo file line {}: {}", number + 1, line);
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((number, line)) => return Err(invalid(number, line)),
+            None => return Err("Empty undo file".to_string()),
+        }
+        let expected = format!("{:016x}", hash_rows(rows));
+        match lines.next() {
+            Some((_, line)) if line.strip_prefix("text ") == Some(expected.as_str()) => {}
+            Some((_, line)) if line.starts_with("text ") => return Ok(None),
+            Some((number, line)) => return Err(invalid(number, line)),
+            None => return Err("Truncated undo file".to_string()),
+        }
+
+        while let Some((number, line)) = lines.next() {
+            let mut parts = line.split(' ');
+            let kind = parts.next().unwrap_or("");
+            let numbers: Vec<usize> = parts.filter_map(|n| n.parse().ok()).collect();
+            let &[start, cursor_y, cursor_x, old, new] = numbers.as_slice() else {
+                return Err(invalid(number, line));
+            };
+            let mut take = |count: usize, prefix: char| -> Result<Vec<String>, String> {
+                (0..count)
+                    .map(|_| match lines.next() {
+                        Some((number, line)) => line
+                            .strip_prefix(prefix)
+                            .map(str::to_string)
+                            .ok_or_else(|| invalid(number, line)),
+                        None => Err("Truncated undo file".to_string()),
+                    })
+                    .collect()
+            };
+            let change = Change {
+                start,
+                old: take(old, '-')?,
+                new: take(new, '+')?,
+                cursor: (cursor_x, cursor_y),
+            };
+            match kind {
+                "undo" => history.undo.push(change),
+                "redo" => history.redo.push(change),
+                _ => return Err(invalid(number, line)),
+            }
+        }
+        Ok(Some(history))
+    }
+
+    // 写入时只保存有内容的历史; 没有历史时删掉旧的撤销文件
+    pub fn save(&self, path: &Path, rows: &[String]) -> io::Result<()> {
+        if self.undo.is_empty() && self.redo.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text(rows))
+    }
+
+    // 没有撤销文件或者文件内容对不上时得到空的历史
+    pub fn load(path: &Path, rows: &[String]) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text, rows)?.unwrap_or_default()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(format!(
+                "E822: Cannot open undo file {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+}
+
+// 文件对应的撤销文件: ~/.vim_editor/undo/ 下, 文件名是绝对路径把 `/` 换成 `%`, 和 vim 一样
+pub fn undo_file_path(file: &Path) -> PathBuf {
+    let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    let name = file.to_string_lossy().replace(['/', '\\'], "%");
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".vim_editor")
+        .join("undo")
+        .join(name)
+}
+
+// FNV-1a, 写入文件时的内容(各行用换行连接)的散列, 每次运行结果都一样
+fn hash_rows(rows: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (i, row) in rows.iter().enumerate() {
+        let separator: &[u8] = if i == 0 { b"" } else { b"\n" };
+        for &byte in separator.iter().chain(row.as_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}